@@ -0,0 +1,565 @@
+//! Read-only analysis of templates, used to judge whether a migration is feasible before anything
+//! is modified.
+
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use crate::reference_updater::{Reference, ReferenceKind};
+use crate::{composer, diff, reference_updater};
+
+/// A reference from a template entry to a resource which is no longer part of the template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DanglingReference {
+    /// Location of the referencing entry, e.g. `Resources.BucketPolicy` or `Outputs.BucketArn`
+    pub location: String,
+
+    /// Logical ID of the referenced resource
+    pub logical_id: String,
+
+    /// JSON pointer of the referencing value, e.g. `/Resources/BucketPolicy/Properties/Bucket`
+    pub pointer: String,
+    pub kind: ReferenceKind,
+}
+
+impl DanglingReference {
+    fn new(reference: &Reference) -> Option<Self> {
+        let (section, id) = reference.entry()?;
+        Some(DanglingReference {
+            location: format!("{}.{}", section, id),
+            logical_id: reference.id.clone(),
+            pointer: reference.pointer(),
+            kind: reference.kind,
+        })
+    }
+}
+
+impl fmt::Display for DanglingReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ► {} ({} at {})",
+            self.location, self.logical_id, self.kind, self.pointer
+        )
+    }
+}
+
+/// All references of the `Resources` and `Outputs` sections, the references of the resources first
+fn entry_references(template: &Value) -> Vec<Reference> {
+    let mut references = reference_updater::find_references(template)
+        .into_iter()
+        .filter(|reference| {
+            matches!(
+                reference.entry(),
+                Some(("Resources", _)) | Some(("Outputs", _))
+            )
+        })
+        .collect::<Vec<_>>();
+    references
+        .sort_by_key(|reference| reference.entry().map(|(section, _)| section != "Resources"));
+    references
+}
+
+/// Finds all references in the `Resources` and `Outputs` sections to any of the given logical IDs,
+/// which are not part of one of them. Outputs which only reference removed resources are moved
+/// along with them and not reported.
+pub fn dangling_references(template: &Value, removed_ids: &[String]) -> Vec<DanglingReference> {
+    let moved_outputs = composer::dependent_outputs(template, removed_ids);
+
+    entry_references(template)
+        .iter()
+        .filter(|reference| match reference.entry() {
+            Some(("Resources", id)) => !removed_ids.iter().any(|removed| removed == id),
+            Some((_, id)) => !moved_outputs.iter().any(|moved| moved == id),
+            None => false,
+        })
+        .filter(|reference| removed_ids.contains(&reference.id))
+        .filter_map(DanglingReference::new)
+        .collect()
+}
+
+/// Finds references in the `Resources` and `Outputs` sections to logical IDs which are neither
+/// defined as resource nor as parameter of the template
+pub fn unresolved_references(template: &Value) -> Vec<DanglingReference> {
+    let defined = |id: &str| {
+        ["Resources", "Parameters"]
+            .iter()
+            .any(|section| template.get(section).and_then(|s| s.get(id)).is_some())
+    };
+
+    entry_references(template)
+        .iter()
+        .filter(|reference| !defined(&reference.id))
+        .filter_map(DanglingReference::new)
+        .collect()
+}
+
+/// The only template format version CloudFormation knows
+pub const FORMAT_VERSION: &str = "2010-09-09";
+
+/// Top-level sections of a template
+pub static SECTIONS: [&str; 11] = [
+    "AWSTemplateFormatVersion",
+    "Description",
+    "Metadata",
+    "Parameters",
+    "Rules",
+    "Mappings",
+    "Conditions",
+    "Transform",
+    "Resources",
+    "Hooks",
+    "Outputs",
+];
+
+/// Checks the format version and the shape of the top-level sections of a template. Composing
+/// templates relies on this shape, exotic templates, e.g. with sections added by third-party
+/// tooling, are reported before anything is changed.
+pub fn format_errors(template: &Value) -> Vec<String> {
+    let Some(sections) = template.as_object() else {
+        return vec!["The template is not an object".to_string()];
+    };
+    let mut errors = Vec::new();
+
+    match template.get("AWSTemplateFormatVersion") {
+        None => {}
+        Some(Value::String(version)) if version == FORMAT_VERSION => {}
+        Some(version) => errors.push(format!(
+            "Unsupported AWSTemplateFormatVersion {}, expected {}",
+            version, FORMAT_VERSION
+        )),
+    }
+
+    for name in sections
+        .keys()
+        .filter(|name| !SECTIONS.contains(&name.as_str()))
+    {
+        errors.push(format!("Unknown top-level section {}", name));
+    }
+
+    if let Some(description) = template.get("Description").filter(|d| !d.is_string()) {
+        errors.push(format!("Description is not a string: {}", description));
+    }
+
+    for section in [
+        "Metadata",
+        "Parameters",
+        "Rules",
+        "Mappings",
+        "Conditions",
+        "Hooks",
+        "Outputs",
+    ] {
+        if template
+            .get(section)
+            .is_some_and(|value| !value.is_object())
+        {
+            errors.push(format!("{} section is not an object", section));
+        }
+    }
+
+    match template.get("Transform") {
+        None | Some(Value::String(_)) => {}
+        Some(Value::Array(transforms)) if transforms.iter().all(Value::is_string) => {}
+        Some(transform) => errors.push(format!(
+            "Transform is neither a name nor a list of names: {}",
+            transform
+        )),
+    }
+
+    match template.get("Resources") {
+        None => errors.push("Resources section is missing".to_string()),
+        Some(Value::Object(resources)) => {
+            for (id, resource) in resources {
+                match resource.get("Type") {
+                    _ if !resource.is_object() => {
+                        errors.push(format!("Resource {} is not an object", id))
+                    }
+                    Some(Value::String(_)) => {}
+                    Some(_) => errors.push(format!("Type of resource {} is not a string", id)),
+                    None => errors.push(format!("Resource {} has no Type", id)),
+                }
+            }
+        }
+        Some(_) => errors.push("Resources section is not an object".to_string()),
+    }
+
+    errors
+}
+
+/// Transform expanding the resource types `AWS::Serverless::*`
+pub const SERVERLESS_TRANSFORM: &str = "AWS::Serverless-2016-10-31";
+
+/// Transform adding intrinsic functions like `Fn::Length` and intrinsic functions in
+/// `DeletionPolicy` and `UpdateReplacePolicy`
+pub const LANGUAGE_EXTENSIONS_TRANSFORM: &str = "AWS::LanguageExtensions";
+
+/// Intrinsic functions only known with `AWS::LanguageExtensions`
+static LANGUAGE_EXTENSIONS_FUNCTIONS: [&str; 2] = ["Fn::Length", "Fn::ToJsonString"];
+
+/// A transform of the source template which copied resources rely on, but the target template does
+/// not declare
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingTransform {
+    pub transform: String,
+
+    /// Logical IDs of the resources relying on the transform
+    pub resources: Vec<String>,
+}
+
+/// Names of the transforms a template declares
+pub fn transforms(template: &Value) -> Vec<&str> {
+    match template.get("Transform") {
+        Some(Value::String(transform)) => vec![transform.as_str()],
+        Some(Value::Array(transforms)) => transforms.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Finds the transforms of the source template which the given resources rely on, but the target
+/// template does not declare. Copied without the transform, their definitions are invalid in the
+/// target. Only `AWS::Serverless-2016-10-31` and `AWS::LanguageExtensions` are detected, what a
+/// macro changes is unknown before it runs.
+pub fn missing_transforms(
+    template_source: &Value,
+    template_target: &Value,
+    resource_ids: &[String],
+) -> Vec<MissingTransform> {
+    let declared = transforms(template_target);
+    let mut missing: Vec<MissingTransform> = Vec::new();
+
+    for transform in transforms(template_source) {
+        if declared.contains(&transform) {
+            continue;
+        }
+        let resources = resource_ids
+            .iter()
+            .filter(|id| {
+                let resource = &template_source["Resources"][id.as_str()];
+                match transform {
+                    SERVERLESS_TRANSFORM => resource["Type"]
+                        .as_str()
+                        .is_some_and(|t| t.starts_with("AWS::Serverless::")),
+                    LANGUAGE_EXTENSIONS_TRANSFORM => uses_language_extensions(resource),
+                    _ => false,
+                }
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if !resources.is_empty() {
+            missing.push(MissingTransform {
+                transform: transform.to_string(),
+                resources,
+            });
+        }
+    }
+
+    missing
+}
+
+fn uses_language_extensions(resource: &Value) -> bool {
+    ["DeletionPolicy", "UpdateReplacePolicy"]
+        .iter()
+        .any(|policy| resource.get(policy).is_some_and(Value::is_object))
+        || uses_language_extension_function(resource)
+}
+
+fn uses_language_extension_function(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => map.iter().any(|(key, value)| {
+            LANGUAGE_EXTENSIONS_FUNCTIONS.contains(&key.as_str())
+                // the fourth argument of Fn::FindInMap is a default value
+                || (key == "Fn::FindInMap" && value.as_array().is_some_and(|args| args.len() == 4))
+                || uses_language_extension_function(value)
+        }),
+        Value::Array(items) => items.iter().any(uses_language_extension_function),
+        _ => false,
+    }
+}
+
+/// Resolves the export name of an output as it would be in the given stack. Only names built from
+/// strings and the pseudo parameters `AWS::StackName`, `AWS::Region` and `AWS::AccountId` are
+/// known before deployment, others return `None`.
+pub fn resolve_export_name(output: &Value, stack: (&str, &str, &str)) -> Option<String> {
+    resolve_string(output.get("Export")?.get("Name")?, stack)
+}
+
+fn resolve_string(value: &Value, stack: (&str, &str, &str)) -> Option<String> {
+    let (stack_name, region, account) = stack;
+    let pseudo = |name: &str| match name {
+        "AWS::StackName" => Some(stack_name.to_string()),
+        "AWS::Region" => Some(region.to_string()),
+        "AWS::AccountId" => Some(account.to_string()),
+        _ => None,
+    };
+
+    match value {
+        Value::String(string) => Some(string.clone()),
+        Value::Object(map) if map.len() == 1 => {
+            let (function, argument) = map.iter().next()?;
+            match (function.as_str(), argument) {
+                ("Ref", Value::String(name)) => pseudo(name),
+                ("Fn::Sub", Value::String(template)) => {
+                    let mut resolved = String::new();
+                    let mut rest = template.as_str();
+                    while let Some(start) = rest.find("${") {
+                        let end = start + rest[start..].find('}')?;
+                        resolved.push_str(&rest[..start]);
+                        let expression = &rest[start + 2..end];
+                        match expression.strip_prefix('!') {
+                            Some(literal) => resolved.push_str(&format!("${{{}}}", literal)),
+                            None => resolved.push_str(&pseudo(expression)?),
+                        }
+                        rest = &rest[end + 1..];
+                    }
+                    resolved.push_str(rest);
+                    Some(resolved)
+                }
+                ("Fn::Join", Value::Array(items)) => {
+                    let [Value::String(separator), Value::Array(parts)] = items.as_slice() else {
+                        return None;
+                    };
+                    let parts = parts
+                        .iter()
+                        .map(|part| resolve_string(part, stack))
+                        .collect::<Option<Vec<_>>>()?;
+                    Some(parts.join(separator))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Maximum size in bytes of a template passed in the request body, larger templates are uploaded
+/// to S3, see [`crate::template_body`]
+pub const TEMPLATE_BODY_LIMIT: usize = 51_200;
+
+/// Quotas of CloudFormation on the number of entries of a template section
+pub static SECTION_QUOTAS: [(&str, usize); 4] = [
+    ("Resources", 500),
+    ("Outputs", 200),
+    ("Parameters", 200),
+    ("Mappings", 200),
+];
+
+/// Usage of a quota of CloudFormation by a single template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaUsage {
+    /// What is limited, e.g. `Resources` or `template body bytes`
+    pub name: String,
+    pub used: usize,
+    pub limit: usize,
+}
+
+impl QuotaUsage {
+    pub fn is_exceeded(&self) -> bool {
+        self.used > self.limit
+    }
+}
+
+impl fmt::Display for QuotaUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} of {}", self.name, self.used, self.limit)
+    }
+}
+
+/// Usage of the per-stack quotas of CloudFormation by a template, with the maximum size of its
+/// body, see [`crate::template_body::body_limit`]. Exceeding any of them only shows as a failed
+/// stack update or changeset, after the resources might already have been removed from the source
+/// stack.
+pub fn quota_usage(template: &Value, body_limit: usize) -> Vec<QuotaUsage> {
+    let mut usage = SECTION_QUOTAS
+        .iter()
+        .map(|(section, limit)| QuotaUsage {
+            name: section.to_string(),
+            used: template
+                .get(section)
+                .and_then(|s| s.as_object())
+                .map_or(0, |s| s.len()),
+            limit: *limit,
+        })
+        .collect::<Vec<_>>();
+
+    usage.push(QuotaUsage {
+        name: "template body bytes".to_string(),
+        used: serde_json::to_string(template).map_or(0, |body| body.len()),
+        limit: body_limit,
+    });
+
+    usage
+}
+
+/// Change of a tag which a resource inherits from its stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagChange {
+    pub key: String,
+
+    /// Value of the tag on the source stack, `None` if the tag is added
+    pub old_value: Option<String>,
+
+    /// Value of the tag on the target stack, `None` if the tag is removed
+    pub new_value: Option<String>,
+}
+
+/// Changes of the stack tags propagated to a resource when it is moved from the source to the
+/// target stack. Tags defined on the resource itself take precedence over stack tags and are not
+/// affected.
+pub fn propagated_tag_changes(
+    resource: &Value,
+    source_tags: &HashMap<String, String>,
+    target_tags: &HashMap<String, String>,
+) -> Vec<TagChange> {
+    let own_tags = resource_tag_keys(resource);
+
+    source_tags
+        .keys()
+        .chain(target_tags.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|key| !own_tags.contains(key.as_str()))
+        .filter(|key| source_tags.get(*key) != target_tags.get(*key))
+        .map(|key| TagChange {
+            key: key.to_string(),
+            old_value: source_tags.get(key).cloned(),
+            new_value: target_tags.get(key).cloned(),
+        })
+        .collect()
+}
+
+/// Keys of the tags a resource defines in its `Tags` property, either as list of `Key`/`Value`
+/// pairs or as map
+fn resource_tag_keys(resource: &Value) -> HashSet<&str> {
+    match &resource["Properties"]["Tags"] {
+        Value::Array(tags) => tags.iter().filter_map(|tag| tag["Key"].as_str()).collect(),
+        Value::Object(tags) => tags.keys().map(String::as_str).collect(),
+        _ => HashSet::new(),
+    }
+}
+
+/// A createOnly property whose value in the template differs from the live resource. Such a
+/// property cannot change without replacing the resource, an import with this template fails.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateOnlyMismatch {
+    /// JSON pointer of the property below `Properties`, e.g. `/BucketName`
+    pub property: String,
+    pub template: Value,
+    pub live: Value,
+}
+
+/// Compares the createOnly properties of a resource type schema between the properties of a
+/// resource in a template and the properties of the live resource. Properties which are missing on
+/// either side, contain intrinsic functions or lie below lists (`*` in the schema) are skipped.
+pub fn create_only_mismatches(
+    schema: &Value,
+    properties: &Value,
+    live: &Value,
+) -> Vec<CreateOnlyMismatch> {
+    schema["createOnlyProperties"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .filter_map(|pointer| pointer.strip_prefix("/properties"))
+        .filter(|pointer| !pointer.contains('*'))
+        .filter_map(|pointer| {
+            let template = properties.pointer(pointer)?;
+            let live = live.pointer(pointer)?;
+            if uses_intrinsic_function(template) || diff::equivalent(template, live) {
+                return None;
+            }
+            Some(CreateOnlyMismatch {
+                property: pointer.to_string(),
+                template: template.clone(),
+                live: live.clone(),
+            })
+        })
+        .collect()
+}
+
+fn uses_intrinsic_function(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => map.iter().any(|(key, value)| {
+            key == "Ref" || key.starts_with("Fn::") || uses_intrinsic_function(value)
+        }),
+        Value::Array(items) => items.iter().any(uses_intrinsic_function),
+        _ => false,
+    }
+}
+
+/// A dynamic reference to a parameter or secret in the definition of a resource, e.g.
+/// `{{resolve:ssm:/app/endpoint}}`. CloudFormation resolves them when a stack operation uses the
+/// template, in the account and region of the stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynamicReference {
+    pub resource_id: String,
+
+    /// `ssm`, `ssm-secure` or `secretsmanager`
+    pub service: String,
+
+    /// Name or ARN of the parameter or secret, without version or JSON key
+    pub name: String,
+    pub reference: String,
+}
+
+impl DynamicReference {
+    /// Whether the name is only known after `Fn::Sub` has been evaluated
+    pub fn is_substituted(&self) -> bool {
+        self.name.contains("${")
+    }
+}
+
+/// Finds the dynamic references in the definitions of the given resources, in the order of the
+/// resources and of their occurrence
+pub fn dynamic_references(template: &Value, resource_ids: &[String]) -> Vec<DynamicReference> {
+    let mut references = Vec::new();
+    for resource_id in resource_ids {
+        let mut strings = Vec::new();
+        collect_strings(&template["Resources"][resource_id], &mut strings);
+        for string in strings {
+            for reference in string.split("{{resolve:").skip(1) {
+                let Some((reference, _)) = reference.split_once("}}") else {
+                    continue;
+                };
+                let Some((service, parameters)) = reference.split_once(':') else {
+                    continue;
+                };
+                // ARNs contain colons themselves, the segments after them are versions and keys
+                let arn_segments = match service {
+                    "ssm" | "ssm-secure" => 6,
+                    "secretsmanager" => 7,
+                    _ => continue,
+                };
+                let segments = match parameters.starts_with("arn:") {
+                    true => arn_segments,
+                    false => 1,
+                };
+                references.push(DynamicReference {
+                    resource_id: resource_id.clone(),
+                    service: service.to_string(),
+                    name: parameters
+                        .split(':')
+                        .take(segments)
+                        .collect::<Vec<_>>()
+                        .join(":"),
+                    reference: format!("{{{{resolve:{}}}}}", reference),
+                });
+            }
+        }
+    }
+    references
+}
+
+fn collect_strings<'a>(value: &'a Value, strings: &mut Vec<&'a str>) {
+    match value {
+        Value::String(string) => strings.push(string),
+        Value::Object(map) => map
+            .values()
+            .for_each(|value| collect_strings(value, strings)),
+        Value::Array(items) => items
+            .iter()
+            .for_each(|value| collect_strings(value, strings)),
+        _ => {}
+    }
+}
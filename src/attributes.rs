@@ -0,0 +1,89 @@
+//! Resource attributes which can be kept, stripped or confirmed one by one when resources are copied
+//! into the target stack. Organizations differ in which of them should survive a move, e.g.
+//! `Metadata` of internal tooling or a `Condition` which makes no sense in the target stack.
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+/// Resource attributes whose treatment can be configured
+pub static ATTRIBUTES: [&str; 5] = [
+    "Metadata",
+    "DependsOn",
+    "Condition",
+    "UpdatePolicy",
+    "CreationPolicy",
+];
+
+/// What happens to an attribute of a copied resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Treatment {
+    #[default]
+    Keep,
+    Strip,
+    Ask,
+}
+
+impl FromStr for Treatment {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "keep" => Ok(Treatment::Keep),
+            "strip" => Ok(Treatment::Strip),
+            "ask" => Ok(Treatment::Ask),
+            _ => Err(format!(
+                "Invalid treatment '{}', expected keep, strip or ask",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Treatment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Treatment::Keep => write!(f, "keep"),
+            Treatment::Strip => write!(f, "strip"),
+            Treatment::Ask => write!(f, "ask"),
+        }
+    }
+}
+
+/// Checks the name of an attribute and returns its canonical form
+pub fn attribute(name: &str) -> Result<&'static str, String> {
+    ATTRIBUTES
+        .iter()
+        .find(|attribute| **attribute == name)
+        .copied()
+        .ok_or_else(|| {
+            format!(
+                "Unsupported attribute '{}', expected one of {}",
+                name,
+                ATTRIBUTES.join(", ")
+            )
+        })
+}
+
+/// Splits `ATTRIBUTE=TREATMENT`, e.g. `Metadata=strip`
+pub fn split_rule(value: &str) -> Result<(&'static str, Treatment), String> {
+    let (name, treatment) = value.split_once('=').ok_or_else(|| {
+        format!(
+            "Invalid attribute rule '{}', expected ATTRIBUTE=keep|strip|ask",
+            value
+        )
+    })?;
+    Ok((attribute(name)?, treatment.parse()?))
+}
+
+/// Removes an attribute from a resource of a template. Returns whether the resource had it.
+pub fn strip(template: &mut Value, logical_id: &str, attribute: &str) -> bool {
+    template
+        .get_mut("Resources")
+        .and_then(|resources| resources.get_mut(logical_id))
+        .and_then(|resource| resource.as_object_mut())
+        .and_then(|resource| resource.shift_remove(attribute))
+        .is_some()
+}
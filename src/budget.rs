@@ -0,0 +1,156 @@
+use aws_sdk_cloudformation::config::SharedHttpClient;
+use aws_smithy_runtime_api::client::http::{
+    HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpConnector,
+};
+use aws_smithy_runtime_api::client::orchestrator::HttpRequest;
+use aws_smithy_runtime_api::client::result::ConnectorError;
+use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Environment variable to set the maximum number of API calls per run
+pub const MAX_API_CALLS_ENV: &str = "CFN_TELEPORT_MAX_API_CALLS";
+
+/// Environment variable to set the maximum number of API calls per second
+pub const MAX_API_RATE_ENV: &str = "CFN_TELEPORT_MAX_API_RATE";
+
+static BUDGET: OnceLock<Arc<ApiBudget>> = OnceLock::new();
+
+/// Limits and counts the calls of all AWS APIs during a run. Every request of every client passes
+/// through the HTTP client of the SDK config, so features making many calls, e.g. describing
+/// resources in bulk or listing stacks in several regions, share the budget without knowing it.
+/// Retries count as calls of their own.
+#[derive(Debug, Default)]
+pub struct ApiBudget {
+    max_calls: Option<usize>,
+    interval: Option<Duration>,
+    calls: Mutex<BTreeMap<String, usize>>,
+    next_call: tokio::sync::Mutex<Option<Instant>>,
+}
+
+impl ApiBudget {
+    /// Installs the budget of the run. Without limits, calls are only counted.
+    pub fn install(max_calls: Option<usize>, max_rate: Option<u32>) -> Arc<ApiBudget> {
+        BUDGET
+            .get_or_init(|| {
+                Arc::new(ApiBudget {
+                    max_calls,
+                    interval: max_rate.map(|rate| Duration::from_secs(1) / rate.max(1)),
+                    ..Default::default()
+                })
+            })
+            .clone()
+    }
+
+    /// The budget of the run, if one has been installed
+    pub fn global() -> Option<Arc<ApiBudget>> {
+        BUDGET.get().cloned()
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.max_calls.is_some() || self.interval.is_some()
+    }
+
+    pub fn max_calls(&self) -> Option<usize> {
+        self.max_calls
+    }
+
+    /// Whether a call has been refused because all calls of the budget have been made
+    pub fn is_exhausted(&self) -> bool {
+        self.max_calls.is_some_and(|max| self.total() > max)
+    }
+
+    pub fn total(&self) -> usize {
+        self.calls.lock().unwrap().values().sum()
+    }
+
+    /// Number of calls per service, e.g. `cloudformation`
+    pub fn calls(&self) -> BTreeMap<String, usize> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Wraps an HTTP client, so all requests sent through it count against the budget
+    pub fn wrap(self: &Arc<Self>, client: SharedHttpClient) -> SharedHttpClient {
+        SharedHttpClient::new(BudgetClient {
+            inner: client,
+            budget: self.clone(),
+        })
+    }
+
+    /// Counts a call and waits for its turn. Fails once the budget has been used up, the refused
+    /// call is counted to tell it apart from a budget which has just been reached.
+    async fn acquire(&self, service: &str) -> Result<(), String> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            *calls.entry(service.to_string()).or_default() += 1;
+            let total = calls.values().sum::<usize>();
+            if let Some(max) = self.max_calls.filter(|max| total > *max) {
+                return Err(format!(
+                    "The budget of {} API calls for this run has been used up",
+                    max
+                ));
+            }
+        }
+
+        if let Some(interval) = self.interval {
+            let scheduled = {
+                let mut next_call = self.next_call.lock().await;
+                let now = Instant::now();
+                let scheduled = next_call.map_or(now, |next| next.max(now));
+                *next_call = Some(scheduled + interval);
+                scheduled
+            };
+            tokio::time::sleep_until(scheduled.into()).await;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct BudgetClient {
+    inner: SharedHttpClient,
+    budget: Arc<ApiBudget>,
+}
+
+impl HttpClient for BudgetClient {
+    fn http_connector(
+        &self,
+        settings: &HttpConnectorSettings,
+        components: &RuntimeComponents,
+    ) -> SharedHttpConnector {
+        SharedHttpConnector::new(BudgetConnector {
+            inner: self.inner.http_connector(settings, components),
+            budget: self.budget.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct BudgetConnector {
+    inner: SharedHttpConnector,
+    budget: Arc<ApiBudget>,
+}
+
+impl HttpConnector for BudgetConnector {
+    fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+        let inner = self.inner.clone();
+        let budget = self.budget.clone();
+        HttpConnectorFuture::new(async move {
+            budget
+                .acquire(&service(request.uri()))
+                .await
+                .map_err(|err| ConnectorError::user(err.into()))?;
+            inner.call(request).await
+        })
+    }
+}
+
+/// Service of an endpoint, e.g. `cloudformation` of `https://cloudformation.us-east-1.amazonaws.com/`
+fn service(uri: &str) -> String {
+    let host = uri.split("://").nth(1).unwrap_or(uri);
+    host.split(['.', '/', ':'])
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
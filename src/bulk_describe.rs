@@ -0,0 +1,162 @@
+//! Describes the live state of many physical resources through the Cloud Control API.
+//!
+//! Requests run concurrently up to a fixed limit, are spaced per AWS service to stay clear of
+//! service-side throttling and are cached for the lifetime of the describer, so multiple features
+//! asking for the same resource during one run only cause a single API call.
+
+use aws_config::retry::RetryConfig;
+use aws_config::SdkConfig;
+use aws_sdk_cloudcontrol as cloudcontrol;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+
+use crate::cancellation::Cancellation;
+
+/// Default number of concurrent requests
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Default number of requests per second against a single service
+pub const DEFAULT_RATE_PER_SERVICE: u32 = 5;
+
+type CacheKey = (String, String);
+
+#[derive(Clone)]
+pub struct BulkDescriber {
+    client: cloudcontrol::Client,
+    permits: Arc<Semaphore>,
+    interval: Duration,
+    next_call: Arc<Mutex<HashMap<String, Instant>>>,
+    cache: Arc<Mutex<HashMap<CacheKey, Result<Value, String>>>>,
+}
+
+impl BulkDescriber {
+    pub fn new(config: &SdkConfig) -> Self {
+        Self::with_limits(config, DEFAULT_CONCURRENCY, DEFAULT_RATE_PER_SERVICE)
+    }
+
+    pub fn with_limits(config: &SdkConfig, concurrency: usize, rate_per_service: u32) -> Self {
+        // adaptive retries back off client side as soon as throttling errors show up
+        let cloudcontrol_config = cloudcontrol::config::Builder::from(config)
+            .retry_config(RetryConfig::adaptive().with_max_attempts(5))
+            .build();
+
+        Self {
+            client: cloudcontrol::Client::from_conf(cloudcontrol_config),
+            permits: Arc::new(Semaphore::new(concurrency.max(1))),
+            interval: Duration::from_secs(1) / rate_per_service.max(1),
+            next_call: Arc::new(Mutex::new(HashMap::new())),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns the current properties of a single resource
+    pub async fn describe(&self, resource_type: &str, identifier: &str) -> Result<Value, String> {
+        let key = (resource_type.to_string(), identifier.to_string());
+        if let Some(cached) = self.cache.lock().await.get(&key) {
+            return cached.clone();
+        }
+
+        let result = {
+            let _permit = self
+                .permits
+                .acquire()
+                .await
+                .map_err(|err| err.to_string())?;
+            self.wait_for_turn(resource_type).await;
+            self.get_resource(resource_type, identifier).await
+        };
+
+        self.cache.lock().await.insert(key, result.clone());
+        result
+    }
+
+    /// Describes all given `(resource type, identifier)` pairs. Results are returned in the order of
+    /// the input. Once `cancellation` stops, pending requests are dropped and their results hold the
+    /// reason, the resources described so far are kept.
+    pub async fn describe_all(
+        &self,
+        resources: &[(String, String)],
+        cancellation: &Cancellation,
+    ) -> Vec<Result<Value, String>> {
+        let mut tasks = JoinSet::new();
+
+        for (index, (resource_type, identifier)) in resources.iter().cloned().enumerate() {
+            let describer = self.clone();
+            tasks.spawn(
+                async move { (index, describer.describe(&resource_type, &identifier).await) },
+            );
+        }
+
+        let mut results = vec![None; resources.len()];
+        let mut reason = "Not described".to_string();
+        loop {
+            let result = tokio::select! {
+                result = tasks.join_next() => result,
+                stopped = cancellation.cancelled() => {
+                    tasks.abort_all();
+                    reason = stopped.to_string();
+                    break;
+                }
+            };
+            match result {
+                Some(Ok((index, result))) => results[index] = Some(result),
+                Some(Err(err)) => return vec![Err(err.to_string()); resources.len()],
+                None => break,
+            }
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err(reason.clone())))
+            .collect()
+    }
+
+    /// Spaces out calls to the same service, e.g. all `AWS::S3::*` types share one schedule
+    async fn wait_for_turn(&self, resource_type: &str) {
+        let service = resource_type.split("::").nth(1).unwrap_or(resource_type);
+
+        let scheduled = {
+            let mut next_call = self.next_call.lock().await;
+            let now = Instant::now();
+            let scheduled = next_call.get(service).map_or(now, |next| (*next).max(now));
+            next_call.insert(service.to_string(), scheduled + self.interval);
+            scheduled
+        };
+
+        tokio::time::sleep_until(scheduled.into()).await;
+    }
+
+    async fn get_resource(&self, resource_type: &str, identifier: &str) -> Result<Value, String> {
+        let output = self
+            .client
+            .get_resource()
+            .type_name(resource_type)
+            .identifier(identifier)
+            .send()
+            .await
+            .map_err(|err| {
+                format!(
+                    "Unable to describe {} {}: {}",
+                    resource_type,
+                    identifier,
+                    cloudcontrol::Error::from(err)
+                )
+            })?;
+
+        let properties = output
+            .resource_description()
+            .and_then(|description| description.properties())
+            .ok_or_else(|| {
+                format!(
+                    "No properties returned for {} {}",
+                    resource_type, identifier
+                )
+            })?;
+
+        serde_json::from_str(properties).map_err(|err| err.to_string())
+    }
+}
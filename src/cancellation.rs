@@ -0,0 +1,137 @@
+//! Cooperative cancellation of long-running operations.
+//!
+//! Operations check a shared `Cancellation` at safe points, i.e. between steps which leave the
+//! stacks in a consistent state, and stop there when it has been cancelled or its deadline has
+//! passed. A stack operation which is already running in CloudFormation cannot be stopped, only
+//! waiting for it can.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Interval in which `Cancellation::cancelled` checks the token
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Cancellation token with an optional deadline. Clones share the token, cancelling one cancels all
+/// of them.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+/// Why an operation stopped early
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stopped {
+    Cancelled,
+    DeadlineExceeded,
+}
+
+impl fmt::Display for Stopped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stopped::Cancelled => write!(f, "Cancelled"),
+            Stopped::DeadlineExceeded => write!(f, "Deadline exceeded"),
+        }
+    }
+}
+
+impl Error for Stopped {}
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops operations at the first safe point after `timeout` has elapsed
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            deadline: Some(Instant::now() + timeout),
+            ..self
+        }
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Fails if the operation should stop at this point
+    pub fn check(&self) -> Result<(), Stopped> {
+        if self.is_cancelled() {
+            Err(Stopped::Cancelled)
+        } else if self.is_expired() {
+            Err(Stopped::DeadlineExceeded)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Completes once the token has been cancelled or the deadline has passed
+    pub async fn cancelled(&self) -> Stopped {
+        loop {
+            if let Err(stopped) = self.check() {
+                return stopped;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Steps of a migration, in order. Each phase names the last completed step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Phase {
+    /// Templates have been prepared and validated, no stack has been modified
+    Prepared,
+    /// The resources have a DeletionPolicy of Retain in the source stack
+    Retained,
+    /// The resources have been removed from the source stack and belong to no stack
+    Removed,
+    /// The resources have been imported into the target stack
+    Imported,
+    /// The target stack has its final template
+    Completed,
+}
+
+impl Phase {
+    /// Whether a migration can be stopped after this phase without leaving resources unmanaged
+    pub fn is_safe_to_stop(self) -> bool {
+        self != Phase::Removed
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A migration which stopped at a safe point, with the last completed phase to resume from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupted {
+    pub phase: Phase,
+    pub reason: Stopped,
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} after phase {}", self.reason, self.phase)
+    }
+}
+
+impl Error for Interrupted {}
@@ -0,0 +1,103 @@
+//! Capabilities which have to be acknowledged before CloudFormation deploys a template, and the
+//! template entries requiring them.
+
+use serde_json::Value;
+
+pub const IAM: &str = "CAPABILITY_IAM";
+pub const NAMED_IAM: &str = "CAPABILITY_NAMED_IAM";
+pub const AUTO_EXPAND: &str = "CAPABILITY_AUTO_EXPAND";
+
+/// All capabilities, in the order the AWS console asks for them
+pub static CAPABILITIES: [&str; 3] = [IAM, NAMED_IAM, AUTO_EXPAND];
+
+/// Resource types which can only be deployed with `CAPABILITY_IAM`
+static IAM_RESOURCE_TYPES: [&str; 11] = [
+    "AWS::IAM::AccessKey",
+    "AWS::IAM::Group",
+    "AWS::IAM::GroupPolicy",
+    "AWS::IAM::InstanceProfile",
+    "AWS::IAM::ManagedPolicy",
+    "AWS::IAM::Policy",
+    "AWS::IAM::Role",
+    "AWS::IAM::RolePolicy",
+    "AWS::IAM::User",
+    "AWS::IAM::UserPolicy",
+    "AWS::IAM::UserToGroupAddition",
+];
+
+/// Properties giving IAM resources a custom name, which requires `CAPABILITY_NAMED_IAM`
+static IAM_NAME_PROPERTIES: [&str; 5] = [
+    "GroupName",
+    "InstanceProfileName",
+    "ManagedPolicyName",
+    "RoleName",
+    "UserName",
+];
+
+/// A capability required by a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub capability: &'static str,
+
+    /// Logical IDs of the resources requiring the capability, or `Transform` for the transforms of
+    /// the template
+    pub sources: Vec<String>,
+}
+
+/// Determines the capabilities a template requires, in the order of `CAPABILITIES`
+pub fn required_capabilities(template: &Value) -> Vec<Requirement> {
+    let mut requirements = CAPABILITIES.map(|capability| Requirement {
+        capability,
+        sources: Vec::new(),
+    });
+
+    if template.get("Transform").is_some() {
+        requirements[2].sources.push("Transform".to_string());
+    }
+
+    let resources = template.get("Resources").and_then(|r| r.as_object());
+    for (logical_id, resource) in resources.into_iter().flatten() {
+        let resource_type = resource["Type"].as_str().unwrap_or_default();
+        if IAM_RESOURCE_TYPES.contains(&resource_type) {
+            let named = IAM_NAME_PROPERTIES
+                .iter()
+                .any(|property| resource["Properties"].get(property).is_some());
+            requirements[if named { 1 } else { 0 }]
+                .sources
+                .push(logical_id.to_string());
+        }
+        if uses_transform(resource) {
+            requirements[2].sources.push(logical_id.to_string());
+        }
+    }
+
+    requirements
+        .into_iter()
+        .filter(|requirement| !requirement.sources.is_empty())
+        .collect()
+}
+
+/// The acknowledgement the AWS console asks for before deploying with a capability
+pub fn acknowledgement(capability: &str) -> String {
+    match capability {
+        IAM => "I acknowledge that AWS CloudFormation might create IAM resources".to_string(),
+        NAMED_IAM => {
+            "I acknowledge that AWS CloudFormation might create IAM resources with custom names"
+                .to_string()
+        }
+        _ => format!(
+            "I acknowledge that AWS CloudFormation might require the following capability: {}",
+            capability
+        ),
+    }
+}
+
+fn uses_transform(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .any(|(key, value)| key == "Fn::Transform" || uses_transform(value)),
+        Value::Array(items) => items.iter().any(uses_transform),
+        _ => false,
+    }
+}
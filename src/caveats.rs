@@ -0,0 +1,50 @@
+//! Caveats of resource types whose behavior depends on the stack they belong to, beyond what the
+//! template shows. Moving such a resource succeeds, but something around it keeps referring to the
+//! source stack or is managed elsewhere.
+
+/// A caveat of moving resources of a type
+#[derive(Debug, PartialEq, Eq)]
+pub struct Caveat {
+    pub resource_type: &'static str,
+    pub warning: &'static str,
+}
+
+pub static CAVEATS: [Caveat; 7] = [
+    Caveat {
+        resource_type: "AWS::EC2::Instance",
+        warning: "cfn-init and cfn-signal in the UserData refer to the source stack by name, e.g. through AWS::StackName, and keep reading its metadata until the instance is replaced.",
+    },
+    Caveat {
+        resource_type: "AWS::EC2::Instance",
+        warning: "Changing the UserData, KeyName or the type of the instance stops or replaces it on the next update of the target stack, which erases its instance store volumes. Key pairs, security groups and launch templates defined in the source stack stay there.",
+    },
+    Caveat {
+        resource_type: "AWS::AutoScaling::AutoScalingGroup",
+        warning: "Instances launched by the group signal the source stack if their UserData calls cfn-signal with AWS::StackName. The launch template or configuration stays in the source stack unless it is moved along.",
+    },
+    Caveat {
+        resource_type: "AWS::Logs::LogGroup",
+        warning: "Retention which has been set outside of the template, e.g. by a Lambda function or a policy of the organization, is overwritten as soon as an update of the target stack sets RetentionInDays.",
+    },
+    Caveat {
+        resource_type: "AWS::Lambda::Function",
+        warning: "The log group /aws/lambda/<name>, created by Lambda on the first invocation, is not part of any stack unless it is declared, and stays where it is.",
+    },
+    Caveat {
+        resource_type: "AWS::DynamoDB::Table",
+        warning: "Auto scaling of the capacity, declared as AWS::ApplicationAutoScaling::ScalableTarget, stays in the source stack and keeps adjusting the capacity set in the target template.",
+    },
+    Caveat {
+        resource_type: "AWS::ECS::Service",
+        warning: "Deployments triggered by an update of the target stack use the task definition referenced there; a task definition in the source stack is not updated with the service anymore.",
+    },
+];
+
+/// The caveats of the given resource types, in the order of [`CAVEATS`]
+pub fn caveats<'a>(resource_types: impl IntoIterator<Item = &'a str>) -> Vec<&'static Caveat> {
+    let resource_types = resource_types.into_iter().collect::<Vec<_>>();
+    CAVEATS
+        .iter()
+        .filter(|caveat| resource_types.contains(&caveat.resource_type))
+        .collect()
+}
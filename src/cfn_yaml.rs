@@ -0,0 +1,252 @@
+//! Conversion between CloudFormation templates in YAML and their JSON representation.
+//!
+//! Short-form intrinsic functions like `!Ref Bucket` or `!GetAtt Bucket.Arn` are converted into their
+//! long form (`{"Ref": "Bucket"}`), so the rest of the tool only ever deals with JSON. JSON templates
+//! may contain comments and trailing commas, as hand-maintained templates often do.
+
+use serde_json::{Map, Number, Value};
+use serde_yaml::value::TaggedValue;
+
+use crate::reference_updater;
+
+/// Parses a template, which can be either JSON, with or without comments, or YAML. YAML in flow
+/// style, e.g. `{Resources: {Bucket: {Type: !Sub "AWS::S3::Bucket"}}}`, looks like JSON at first.
+pub fn parse_template(body: &str) -> Result<Value, String> {
+    if is_json(body) {
+        serde_json::from_str(&strip_json_comments(body))
+            .or_else(|err| from_str(body).map_err(|_| err))
+            .map_err(|err| format!("Invalid JSON template: {}", err))
+    } else {
+        from_str(body)
+    }
+}
+
+/// Whether a template is written in JSON, possibly with leading comments
+pub fn is_json(body: &str) -> bool {
+    let body = body.trim_start();
+    body.starts_with('{')
+        || ((body.starts_with("//") || body.starts_with("/*"))
+            && strip_json_comments(body).trim_start().starts_with('{'))
+}
+
+/// Blanks out `//` and `/* */` comments and trailing commas of JSON. Line breaks are kept, so
+/// positions in parse errors still match the original text.
+pub fn strip_json_comments(body: &str) -> String {
+    let chars = body.chars().collect::<Vec<_>>();
+    let mut stripped = String::with_capacity(body.len());
+    let mut index = 0;
+    let mut in_string = false;
+
+    while index < chars.len() {
+        let c = chars[index];
+        let next = chars.get(index + 1).copied();
+
+        if in_string {
+            stripped.push(c);
+            if c == '\\' {
+                if let Some(next) = next {
+                    stripped.push(next);
+                    index += 1;
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '/' && next == Some('/') {
+            while index < chars.len() && chars[index] != '\n' {
+                stripped.push(' ');
+                index += 1;
+            }
+            continue;
+        } else if c == '/' && next == Some('*') {
+            let end = (index + 2..chars.len().saturating_sub(1))
+                .find(|&i| chars[i] == '*' && chars[i + 1] == '/')
+                .map_or(chars.len(), |i| i + 2);
+            for c in &chars[index..end] {
+                stripped.push(if *c == '\n' { '\n' } else { ' ' });
+            }
+            index = end;
+            continue;
+        } else if c == ',' && is_trailing_comma(&chars[index + 1..]) {
+            stripped.push(' ');
+        } else {
+            in_string = c == '"';
+            stripped.push(c);
+        }
+        index += 1;
+    }
+
+    stripped
+}
+
+/// Whether a comma is followed by the end of an object or array, skipping whitespace and comments
+fn is_trailing_comma(rest: &[char]) -> bool {
+    let mut index = 0;
+    while index < rest.len() {
+        match (rest[index], rest.get(index + 1)) {
+            (c, _) if c.is_whitespace() => index += 1,
+            ('/', Some('/')) => {
+                while index < rest.len() && rest[index] != '\n' {
+                    index += 1;
+                }
+            }
+            ('/', Some('*')) => {
+                index = (index + 2..rest.len().saturating_sub(1))
+                    .find(|&i| rest[i] == '*' && rest[i + 1] == '/')
+                    .map_or(rest.len(), |i| i + 2);
+            }
+            (c, _) => return c == '}' || c == ']',
+        }
+    }
+    false
+}
+
+/// Parses a YAML template into its JSON representation
+pub fn from_str(body: &str) -> Result<Value, String> {
+    let yaml: serde_yaml::Value =
+        serde_yaml::from_str(body).map_err(|err| format!("Invalid YAML template: {}", err))?;
+    to_json(yaml)
+}
+
+/// Renders a template as YAML. Intrinsic functions are written in long form.
+pub fn to_string(template: &Value) -> Result<String, String> {
+    serde_yaml::to_string(template).map_err(|err| format!("Unable to render YAML: {}", err))
+}
+
+fn to_json(yaml: serde_yaml::Value) -> Result<Value, String> {
+    Ok(match yaml {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(value) => Value::Bool(value),
+        serde_yaml::Value::Number(number) => to_json_number(&number)?,
+        serde_yaml::Value::String(value) => Value::String(value),
+        serde_yaml::Value::Sequence(items) => Value::Array(
+            items
+                .into_iter()
+                .map(to_json)
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        serde_yaml::Value::Mapping(mapping) => {
+            let mut map = Map::new();
+            for (key, value) in mapping {
+                map.insert(to_key(key)?, to_json(value)?);
+            }
+            Value::Object(map)
+        }
+        serde_yaml::Value::Tagged(tagged) => intrinsic(*tagged)?,
+    })
+}
+
+fn to_json_number(number: &serde_yaml::Number) -> Result<Value, String> {
+    if let Some(value) = number.as_u64() {
+        Ok(Value::Number(value.into()))
+    } else if let Some(value) = number.as_i64() {
+        Ok(Value::Number(value.into()))
+    } else {
+        number
+            .as_f64()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| format!("Unsupported number: {}", number))
+    }
+}
+
+fn to_key(key: serde_yaml::Value) -> Result<String, String> {
+    match key {
+        serde_yaml::Value::String(key) => Ok(key),
+        serde_yaml::Value::Bool(key) => Ok(key.to_string()),
+        serde_yaml::Value::Number(key) => Ok(key.to_string()),
+        key => Err(format!("Unsupported mapping key: {:?}", key)),
+    }
+}
+
+/// Converts a short-form intrinsic function into its long form
+fn intrinsic(tagged: TaggedValue) -> Result<Value, String> {
+    let name = tagged.tag.to_string();
+    let name = name.trim_start_matches('!');
+    // the scalar of a tag is taken literally, e.g. `!GetAZs` without a value is an empty string
+    let value = match tagged.value {
+        serde_yaml::Value::Null => Value::String(String::new()),
+        serde_yaml::Value::Bool(value) => Value::String(value.to_string()),
+        serde_yaml::Value::Number(number) => Value::String(number.to_string()),
+        value => to_json(value)?,
+    };
+
+    let (key, value) = match name {
+        "Ref" => ("Ref".to_string(), value),
+        "Condition" => ("Condition".to_string(), value),
+        "GetAtt" => {
+            let value = match value {
+                // !GetAtt Resource.Attribute is the same as !GetAtt [Resource, Attribute]
+                Value::String(expression) => match reference_updater::split_get_att(&expression) {
+                    Some((id, attribute)) => Value::Array(vec![
+                        Value::String(id.to_string()),
+                        Value::String(attribute.to_string()),
+                    ]),
+                    None => Value::String(expression),
+                },
+                value => value,
+            };
+            ("Fn::GetAtt".to_string(), value)
+        }
+        name => (format!("Fn::{}", name), value),
+    };
+
+    let mut map = Map::new();
+    map.insert(key, value);
+    Ok(Value::Object(map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Templates in `test/fixtures/cfn_yaml`, each YAML file with the JSON it has to parse into
+    static FIXTURES: [(&str, &str, &str); 3] = [
+        (
+            "nested-sub",
+            include_str!("../test/fixtures/cfn_yaml/nested-sub.yaml"),
+            include_str!("../test/fixtures/cfn_yaml/nested-sub.json"),
+        ),
+        (
+            "flow-mappings",
+            include_str!("../test/fixtures/cfn_yaml/flow-mappings.yaml"),
+            include_str!("../test/fixtures/cfn_yaml/flow-mappings.json"),
+        ),
+        (
+            "tagged-scalars",
+            include_str!("../test/fixtures/cfn_yaml/tagged-scalars.yaml"),
+            include_str!("../test/fixtures/cfn_yaml/tagged-scalars.json"),
+        ),
+    ];
+
+    #[test]
+    fn parses_fixtures() {
+        for (name, yaml, json) in FIXTURES {
+            let expected: Value = serde_json::from_str(json).unwrap();
+
+            assert_eq!(parse_template(yaml), Ok(expected), "fixture {}", name);
+        }
+    }
+
+    #[test]
+    fn rendered_fixtures_parse_into_the_same_template() {
+        for (name, yaml, _) in FIXTURES {
+            let template = parse_template(yaml).unwrap();
+
+            let rendered = to_string(&template).unwrap();
+
+            assert_eq!(parse_template(&rendered), Ok(template), "fixture {}", name);
+        }
+    }
+
+    #[test]
+    fn parses_nested_get_att_in_flow_sub() {
+        let template = parse_template("Value: !Sub ['${x}', {x: !GetAtt R.Attr}]").unwrap();
+
+        assert_eq!(
+            template,
+            serde_json::json!({
+                "Value": {"Fn::Sub": ["${x}", {"x": {"Fn::GetAtt": ["R", "Attr"]}}]}
+            })
+        );
+    }
+}
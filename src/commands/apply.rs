@@ -0,0 +1,38 @@
+use cfn_teleport::capabilities;
+use std::error::Error;
+use std::path::PathBuf;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ApplyArgs {
+    /// Plan file written by `cfn-teleport plan --plan-file`
+    #[arg(value_name = "PLAN_FILE", env = "CFN_TELEPORT_APPLY_PLAN_FILE")]
+    plan_file: PathBuf,
+
+    /// Acknowledge a capability required by the templates, e.g. CAPABILITY_IAM
+    #[arg(
+        long,
+        value_name = "CAPABILITY",
+        value_parser = capabilities::CAPABILITIES,
+        value_delimiter = ',',
+        env = "CFN_TELEPORT_CAPABILITY"
+    )]
+    capability: Vec<String>,
+
+    /// Print the changes to both stacks, step by step, and exit without changes
+    #[arg(long, env = "CFN_TELEPORT_DRY_RUN")]
+    dry_run: bool,
+
+    /// Automatically confirm all prompts
+    #[arg(short, long, env = "CFN_TELEPORT_YES")]
+    yes: bool,
+}
+
+/// Executes the migration of a plan file with its templates, after verifying its signature. Like
+/// `--from-artifacts`, the templates are the ones which have been reviewed, not recomputed.
+pub async fn run(args: ApplyArgs, mut migration: crate::Args) -> Result<(), Box<dyn Error>> {
+    migration.apply_plan = Some(args.plan_file);
+    migration.capability = args.capability;
+    migration.dry_run = args.dry_run;
+    migration.yes = args.yes;
+    crate::run(migration).await
+}
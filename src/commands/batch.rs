@@ -0,0 +1,132 @@
+use cfn_teleport::plan::Batch;
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::error::{self, fail, ExitCode, WithExitCode};
+use crate::timing;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct BatchArgs {
+    /// Manifest with a list of migrations, in YAML or JSON
+    #[arg(value_name = "MANIFEST", env = "CFN_TELEPORT_BATCH_MANIFEST")]
+    manifest: PathBuf,
+
+    /// Acknowledge a capability required by the templates, e.g. CAPABILITY_IAM
+    #[arg(
+        long,
+        value_name = "CAPABILITY",
+        value_delimiter = ',',
+        env = "CFN_TELEPORT_CAPABILITY"
+    )]
+    capability: Vec<String>,
+
+    /// Print the changes of every migration, step by step, and exit without changes
+    #[arg(long, env = "CFN_TELEPORT_DRY_RUN")]
+    dry_run: bool,
+
+    /// Continue with the next migration if one fails, instead of stopping the batch
+    #[arg(long, env = "CFN_TELEPORT_CONTINUE_ON_ERROR")]
+    continue_on_error: bool,
+
+    /// Automatically confirm all prompts
+    #[arg(short, long, env = "CFN_TELEPORT_YES")]
+    yes: bool,
+}
+
+enum Outcome {
+    Succeeded(Duration),
+    Failed(Duration, Box<dyn Error>),
+    Skipped,
+}
+
+/// Executes the migrations of a manifest one after another, each with the same code path as
+/// `cfn-teleport --source ... --target ...`, and reports the outcome of all of them at the end
+pub async fn run(args: BatchArgs, migration: crate::Args) -> Result<(), Box<dyn Error>> {
+    let batch = Batch::from_file(&args.manifest).exit_code(ExitCode::Validation)?;
+    let total = batch.migrations.len();
+    let mut outcomes = Vec::with_capacity(total);
+
+    for (index, plan) in batch.migrations.iter().enumerate() {
+        let stop = outcomes
+            .iter()
+            .any(|outcome| matches!(outcome, Outcome::Failed(..)))
+            && !args.continue_on_error;
+        if stop {
+            outcomes.push(Outcome::Skipped);
+            continue;
+        }
+
+        println!(
+            "\nMigration {} of {}: {} resource(s) from {} to {}\n",
+            index + 1,
+            total,
+            plan.resources.len(),
+            plan.source,
+            plan.target
+        );
+        let mut migration = migration.clone();
+        migration.source = Some(plan.source.clone());
+        migration.target = Some(plan.target.clone());
+        migration.resource = Some(plan.resources.clone());
+        migration.physical_id = plan
+            .physical_ids
+            .iter()
+            .map(|(logical_id, physical_id)| format!("{}={}", logical_id, physical_id))
+            .collect();
+        migration.capability = args.capability.clone();
+        migration.dry_run = args.dry_run;
+        migration.yes = args.yes;
+
+        let started = Instant::now();
+        outcomes.push(match crate::run(migration).await {
+            Ok(()) => Outcome::Succeeded(started.elapsed()),
+            Err(err) => {
+                eprintln!("ERROR: {}", err);
+                Outcome::Failed(started.elapsed(), err)
+            }
+        });
+    }
+
+    println!("\nBatch {}:\n", args.manifest.display());
+    for (index, (plan, outcome)) in batch.migrations.iter().zip(&outcomes).enumerate() {
+        let status = match outcome {
+            Outcome::Succeeded(duration) => {
+                format!("succeeded in {}", timing::format_duration(*duration))
+            }
+            Outcome::Failed(duration, err) => format!(
+                "failed after {}: {}",
+                timing::format_duration(*duration),
+                err
+            ),
+            Outcome::Skipped => "skipped".to_string(),
+        };
+        println!(
+            "  {}. {} -> {} ({} resource(s)): {}",
+            index + 1,
+            plan.source,
+            plan.target,
+            plan.resources.len(),
+            status
+        );
+    }
+
+    let succeeded = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome, Outcome::Succeeded(_)))
+        .count();
+    let Some(first_failure) = outcomes.iter().find_map(|outcome| match outcome {
+        Outcome::Failed(_, err) => Some(err),
+        _ => None,
+    }) else {
+        println!("\nAll {} migrations succeeded", total);
+        return Ok(());
+    };
+
+    let message = format!("{} of {} migrations succeeded", succeeded, total);
+    // stacks of earlier migrations have been changed, a rerun must not repeat them
+    if succeeded > 0 && !args.dry_run {
+        return Err(fail(ExitCode::PartialCompletion, message));
+    }
+    Err(fail(error::exit_code_of(first_failure.as_ref()), message))
+}
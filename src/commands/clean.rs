@@ -0,0 +1,37 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::workspace;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct CleanArgs {
+    /// Only delete artifacts older than this number of days
+    #[arg(
+        long,
+        value_name = "DAYS",
+        default_value_t = 0,
+        env = "CFN_TELEPORT_OLDER_THAN"
+    )]
+    older_than: u64,
+
+    /// Directory for the artifacts of each run [default: ~/.cfn-teleport/runs]
+    #[arg(long, value_name = "DIR", env = workspace::WORKSPACE_ENV)]
+    workspace: Option<PathBuf>,
+}
+
+/// Deletes the workspaces of previous runs
+pub fn run(args: CleanArgs) -> Result<(), Box<dyn Error>> {
+    let root = workspace::root(args.workspace);
+    let purged = workspace::purge(&root, workspace::days(args.older_than))?;
+
+    for path in &purged {
+        println!("Deleted {}", path.display());
+    }
+    println!(
+        "Deleted {} workspaces from {}",
+        purged.len(),
+        root.display()
+    );
+
+    Ok(())
+}
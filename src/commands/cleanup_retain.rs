@@ -0,0 +1,129 @@
+use aws_config::SdkConfig;
+use aws_sdk_cloudformation as cloudformation;
+use cfn_teleport::cancellation::Cancellation;
+use cfn_teleport::capabilities;
+use cfn_teleport::composer;
+use cfn_teleport::{migration, stack_api, waiter};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::error::{fail, ExitCode, WithExitCode};
+use crate::{concurrency, spinner, workspace};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct CleanupRetainArgs {
+    /// Workspace directory of the migration, as printed by the run
+    #[arg(long, value_name = "DIR", env = "CFN_TELEPORT_RUN")]
+    run: PathBuf,
+
+    /// Acknowledge a capability required by the templates, e.g. CAPABILITY_IAM
+    #[arg(
+        long,
+        value_name = "CAPABILITY",
+        value_parser = capabilities::CAPABILITIES,
+        value_delimiter = ',',
+        env = "CFN_TELEPORT_CAPABILITY"
+    )]
+    capability: Vec<String>,
+
+    /// Automatically confirm all prompts
+    #[arg(short, long, env = "CFN_TELEPORT_YES")]
+    yes: bool,
+}
+
+/// Restores the DeletionPolicy the moved resources had before a migration, in the source and in the
+/// target stack. Resources which are no longer part of a stack are skipped.
+pub async fn run(args: CleanupRetainArgs, config: &SdkConfig) -> Result<(), Box<dyn Error>> {
+    let workspace = workspace::Workspace::open(&args.run).exit_code(ExitCode::Validation)?;
+    let recorded: BTreeMap<String, BTreeMap<String, Option<String>>> = serde_json::from_value(
+        workspace
+            .read_json("deletion-policies")
+            .exit_code(ExitCode::Validation)?,
+    )
+    .exit_code(ExitCode::Validation)?;
+
+    let client = cloudformation::Client::new(config);
+    let mut updates = Vec::new();
+    let mut required_capabilities = Vec::new();
+
+    for (stack_name, policies) in &recorded {
+        let template = crate::get_template(&client, stack_name)
+            .await
+            .exit_code(ExitCode::AwsApi)?;
+        let restored = composer::restore_deletion_policies(template.clone(), policies);
+        if restored == template {
+            println!("Stack {}: nothing to clean up", stack_name);
+            continue;
+        }
+
+        println!("Stack {}:", stack_name);
+        for (logical_id, policy) in policies {
+            let current = &template["Resources"][logical_id]["DeletionPolicy"];
+            let original = &restored["Resources"][logical_id]["DeletionPolicy"];
+            if current != original {
+                println!(
+                    "  {}: DeletionPolicy {} -> {}",
+                    logical_id,
+                    current.as_str().unwrap_or("(none)"),
+                    policy.as_deref().unwrap_or("(none)")
+                );
+            }
+        }
+
+        let reported = crate::validate_template(&client, restored.clone())
+            .await
+            .exit_code(ExitCode::Validation)?;
+        crate::collect_capabilities(&mut required_capabilities, stack_name, &restored, reported);
+        updates.push((stack_name, restored));
+    }
+
+    if updates.is_empty() {
+        return Ok(());
+    }
+
+    let capabilities =
+        crate::acknowledge_capabilities(&required_capabilities, &args.capability, args.yes)?;
+    let confirmed = args.yes
+        || Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Do you want to restore the original DeletionPolicy?")
+            .default(false)
+            .interact()?;
+    if !confirmed {
+        return Err(fail(ExitCode::Aborted, "Cleanup aborted"));
+    }
+
+    let stall_timeout = Duration::from_secs(waiter::DEFAULT_STALL_TIMEOUT * 60);
+    let api = concurrency::Serialized(&client);
+    let waiter = waiter::StackWaiter::new(&api, stall_timeout, Cancellation::new());
+    for (stack_name, template) in updates {
+        let message = format!(
+            "Restoring the original DeletionPolicy of the resources in stack {}",
+            stack_name
+        );
+        workspace.log(&message)?;
+        let mut spinner = spinner::Spin::new("restore-deletion-policy", &message);
+        let parameters = crate::get_previous_parameters(&client, stack_name)
+            .await
+            .exit_code(ExitCode::AwsApi)?;
+        migration::update_stack(
+            &waiter,
+            stack_name,
+            (
+                &template,
+                &stack_api::parameters_for(&template, &parameters, &[]),
+            ),
+            &capabilities,
+            (Some(&mut spinner), None),
+        )
+        .await
+        .and_then(|outcome| outcome.into_result(stack_name))
+        .exit_code(ExitCode::AwsApi)?;
+        spinner.complete();
+        workspace.log(&format!("DeletionPolicy restored in stack {}", stack_name))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use aws_sdk_cloudformation as cloudformation;
+use clap::CommandFactory;
+use clap_complete::Shell;
+use std::error::Error;
+use std::io;
+
+use crate::error::{fail, ExitCode, WithExitCode};
+
+/// Name of the binary the completions are generated for
+const BIN_NAME: &str = "cfn-teleport";
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct CompletionsArgs {
+    /// Shell to generate the completion script for
+    #[arg(required_unless_present = "list_stacks")]
+    shell: Option<Shell>,
+
+    /// Print the names of the stacks, used by the completion scripts to complete stack names
+    #[arg(long, hide = true)]
+    list_stacks: bool,
+}
+
+/// Prints the completion script of a shell. Scripts for bash, zsh and fish complete the values of
+/// `--source` and `--target` with the names of the stacks, as far as credentials are available.
+pub async fn run(args: CompletionsArgs, global: &crate::Args) -> Result<(), Box<dyn Error>> {
+    if args.list_stacks {
+        let config = crate::load_config(global).await?;
+        let client = cloudformation::Client::new(&config);
+        for stack in crate::get_stacks(&client)
+            .await
+            .exit_code(ExitCode::AwsApi)?
+        {
+            println!("{}", stack.stack_name().unwrap_or_default());
+        }
+        return Ok(());
+    }
+
+    let shell = args
+        .shell
+        .ok_or_else(|| fail(ExitCode::Validation, "No shell given"))?;
+    let mut command = crate::Args::command();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, BIN_NAME, &mut script);
+    let script = String::from_utf8(script)?;
+
+    let script = match shell {
+        Shell::Bash => format!("{}\n{}", script, BASH_STACKS),
+        Shell::Zsh => zsh_with_stacks(&script),
+        Shell::Fish => format!("{}\n{}", script, FISH_STACKS),
+        _ => script,
+    };
+    io::Write::write_all(&mut io::stdout(), script.as_bytes())?;
+    Ok(())
+}
+
+/// Completes stack names after `--source` and `--target`, everything else is left to the generated
+/// completion function
+const BASH_STACKS: &str = r#"_cfn-teleport_stacks() {
+    case "${COMP_WORDS[COMP_CWORD-1]}" in
+        -s|--source|-t|--target)
+            COMPREPLY=( $(compgen -W "$(cfn-teleport completions --list-stacks 2>/dev/null)" -- "${COMP_WORDS[COMP_CWORD]}") )
+            return 0
+            ;;
+    esac
+    _cfn-teleport "$@"
+}
+
+if [[ "${BASH_VERSINFO[0]}" -eq 4 && "${BASH_VERSINFO[1]}" -ge 4 || "${BASH_VERSINFO[0]}" -gt 4 ]]; then
+    complete -F _cfn-teleport_stacks -o nosort -o bashdefault -o default cfn-teleport
+else
+    complete -F _cfn-teleport_stacks -o bashdefault -o default cfn-teleport
+fi
+"#;
+
+const FISH_STACKS: &str = r#"complete -c cfn-teleport -n "__fish_cfn_teleport_needs_command" -s s -l source -x -a "(cfn-teleport completions --list-stacks 2>/dev/null)"
+complete -c cfn-teleport -n "__fish_cfn_teleport_needs_command" -s t -l target -x -a "(cfn-teleport completions --list-stacks 2>/dev/null)"
+"#;
+
+const ZSH_STACKS: &str = r#"(( $+functions[_cfn-teleport_stacks] )) ||
+_cfn-teleport_stacks() {
+    local -a stacks
+    stacks=(${(f)"$(cfn-teleport completions --list-stacks 2>/dev/null)"})
+    _describe 'stack' stacks
+}
+"#;
+
+/// Replaces the default completion of the stack arguments in the generated zsh script. The
+/// `#compdef` line has to stay the first line of the script.
+fn zsh_with_stacks(script: &str) -> String {
+    let script = script
+        .replace(":SOURCE:_default'", ":SOURCE:_cfn-teleport_stacks'")
+        .replace(":TARGET:_default'", ":TARGET:_cfn-teleport_stacks'");
+    match script.split_once('\n') {
+        Some((compdef, rest)) => format!("{}\n\n{}\n{}", compdef, ZSH_STACKS, rest),
+        None => script,
+    }
+}
@@ -0,0 +1,180 @@
+use aws_config::SdkConfig;
+use aws_sdk_cloudformation as cloudformation;
+use aws_sdk_cloudformation::error::ProvideErrorMetadata;
+use cfn_teleport::coupling::{self, Export, StackSnapshot};
+use std::error::Error;
+
+use crate::error::{fail, ExitCode, WithExitCode};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct CouplingArgs {
+    /// Name of a stack, exactly two are compared
+    #[arg(
+        long,
+        num_args = 1,
+        required = true,
+        value_delimiter = ',',
+        env = "CFN_TELEPORT_STACK"
+    )]
+    stack: Vec<String>,
+}
+
+/// Reports all shared references between two stacks and how entangled they are
+pub async fn run(args: CouplingArgs, config: &SdkConfig) -> Result<(), Box<dyn Error>> {
+    let [stack_a, stack_b] = args.stack.as_slice() else {
+        return Err(fail(
+            ExitCode::Validation,
+            "Exactly two stacks are required, pass --stack twice",
+        ));
+    };
+    if stack_a == stack_b {
+        return Err(fail(ExitCode::Validation, "The stacks must be different"));
+    }
+
+    let client = cloudformation::Client::new(config);
+    let a = snapshot(&client, stack_a)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    let b = snapshot(&client, stack_b)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+
+    let links = coupling::analyze(&a, &b);
+    let score = coupling::score(&links);
+
+    println!("Coupling of stacks {} and {}\n", stack_a, stack_b);
+    for category in coupling::CATEGORIES {
+        let entries = links
+            .iter()
+            .filter(|link| link.category() == category)
+            .collect::<Vec<_>>();
+        println!("{} ({}):", category, entries.len());
+        for link in entries {
+            println!("  - {}", link);
+        }
+    }
+    println!(
+        "\nScore: {} ({}, {} shared references)",
+        score,
+        coupling::level(score),
+        links.len()
+    );
+
+    Ok(())
+}
+
+/// Reads the template, resources, parameters and exports of a stack
+async fn snapshot(
+    client: &cloudformation::Client,
+    stack_name: &str,
+) -> Result<StackSnapshot, Box<dyn Error>> {
+    let resp = client
+        .describe_stacks()
+        .stack_name(stack_name)
+        .send()
+        .await
+        .map_err(cloudformation::Error::from)?;
+    let stack = resp.stacks().first().ok_or("Stack not found")?;
+    let stack_id = stack.stack_id().unwrap_or_default().to_string();
+
+    let parameters = stack
+        .parameters()
+        .iter()
+        .filter_map(|parameter| {
+            let value = parameter.resolved_value().or(parameter.parameter_value())?;
+            Some((parameter.parameter_key()?.to_string(), value.to_string()))
+        })
+        .collect();
+
+    let resources = crate::get_all_resources(client, stack_name)
+        .await?
+        .iter()
+        .filter_map(|resource| {
+            Some((
+                resource.logical_resource_id()?.to_string(),
+                resource.physical_resource_id()?.to_string(),
+            ))
+        })
+        .collect();
+
+    let mut exports = Vec::new();
+    for (name, value) in list_exports(client, &stack_id).await? {
+        let importers = list_imports(client, &name).await?;
+        exports.push(Export {
+            name,
+            value,
+            importers,
+        });
+    }
+
+    Ok(StackSnapshot {
+        name: stack_name.to_string(),
+        template: crate::get_template(client, stack_name).await?,
+        resources,
+        parameters,
+        exports,
+    })
+}
+
+/// Names and values of the exports of a stack
+async fn list_exports(
+    client: &cloudformation::Client,
+    stack_id: &str,
+) -> Result<Vec<(String, String)>, cloudformation::Error> {
+    let mut exports = Vec::new();
+    let mut token = None;
+
+    loop {
+        let output = client.list_exports().set_next_token(token).send().await?;
+        exports.extend(
+            output
+                .exports()
+                .iter()
+                .filter(|export| export.exporting_stack_id() == Some(stack_id))
+                .filter_map(|export| {
+                    Some((export.name()?.to_string(), export.value()?.to_string()))
+                }),
+        );
+
+        token = output.next_token().map(str::to_string);
+        if token.is_none() {
+            return Ok(exports);
+        }
+    }
+}
+
+/// Names of the stacks importing an export
+async fn list_imports(
+    client: &cloudformation::Client,
+    export_name: &str,
+) -> Result<Vec<String>, cloudformation::Error> {
+    let mut imports = Vec::new();
+    let mut token = None;
+
+    loop {
+        let output = match client
+            .list_imports()
+            .export_name(export_name)
+            .set_next_token(token)
+            .send()
+            .await
+        {
+            Ok(output) => output,
+            // CloudFormation reports exports without imports as an error
+            Err(err)
+                if err
+                    .message()
+                    .is_some_and(|message| message.contains("is not imported by any stack")) =>
+            {
+                return Ok(imports)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        imports.extend(output.imports().iter().cloned());
+
+        token = output.next_token().map(str::to_string);
+        if token.is_none() {
+            return Ok(imports);
+        }
+    }
+}
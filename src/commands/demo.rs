@@ -0,0 +1,298 @@
+use aws_config::SdkConfig;
+use aws_sdk_cloudformation as cloudformation;
+use aws_sdk_cloudformation::error::ProvideErrorMetadata;
+use aws_sdk_cloudformation::types::{StackStatus, Tag};
+use clap::Parser;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use serde_json::json;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::error::{fail, ExitCode, WithExitCode};
+use crate::{spinner, state};
+
+/// Resources of the source stack which are moved during the demo
+static DEMO_RESOURCES: [&str; 2] = ["DemoBucket", "DemoQueue"];
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct DemoArgs {
+    /// Confirm that the account is a sandbox in which stacks and resources may be created and deleted
+    #[arg(long, env = "CFN_TELEPORT_DEMO_SANDBOX")]
+    sandbox: bool,
+
+    /// Prefix of the names of the demo stacks
+    #[arg(
+        long,
+        default_value = "CfnTeleportDemo",
+        env = "CFN_TELEPORT_DEMO_PREFIX"
+    )]
+    prefix: String,
+
+    /// Keep the demo stacks instead of deleting them at the end
+    #[arg(long, env = "CFN_TELEPORT_DEMO_KEEP")]
+    keep: bool,
+
+    /// Automatically confirm all prompts, e.g. to run the demo as a smoke test
+    #[arg(short, long, env = "CFN_TELEPORT_YES")]
+    yes: bool,
+}
+
+/// Creates two demo stacks, moves an S3 bucket and an SQS queue from one to the other with a real
+/// migration and deletes the stacks again
+pub async fn run(
+    args: DemoArgs,
+    config: &SdkConfig,
+    global: &crate::Args,
+) -> Result<(), Box<dyn Error>> {
+    if !args.sandbox {
+        return Err(fail(
+            ExitCode::Validation,
+            "The demo creates and deletes stacks and resources, run it in a sandbox account and pass --sandbox",
+        ));
+    }
+
+    let source_stack = format!("{}-Source", args.prefix);
+    let target_stack = format!("{}-Target", args.prefix);
+    let caller = state::caller(config).await.exit_code(ExitCode::AwsApi)?;
+
+    println!(
+        "The demo runs a real migration in the account of {}:\n",
+        caller
+    );
+    println!(
+        "  1. Create stack {} with an S3 bucket and an SQS queue",
+        source_stack
+    );
+    println!("  2. Create the empty stack {}", target_stack);
+    println!(
+        "  3. Move the bucket and the queue from {} to {}",
+        source_stack, target_stack
+    );
+    if args.keep {
+        println!("  4. Keep both stacks, delete them when you are done\n");
+    } else {
+        println!("  4. Delete both stacks with the bucket and the queue\n");
+    }
+
+    if !args.yes
+        && !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Start the demo?")
+            .default(false)
+            .interact()?
+    {
+        return Err(fail(ExitCode::Aborted, "Demo has not been confirmed"));
+    }
+
+    let client = cloudformation::Client::new(config);
+    create_stack(&client, &source_stack, source_template())
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    create_stack(&client, &target_stack, target_template())
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+
+    println!(
+        "\nBoth stacks exist now. The migration retains the resources in {}, removes them from it and imports them into {}.\n",
+        source_stack, target_stack
+    );
+    let result = migrate(&args, global, &source_stack, &target_stack).await;
+
+    if args.keep {
+        println!(
+            "\nThe demo stacks {} and {} have been kept",
+            source_stack, target_stack
+        );
+        return result;
+    }
+
+    // both stacks are deleted, also if the migration failed
+    for stack_name in [&target_stack, &source_stack] {
+        delete_stack(&client, stack_name)
+            .await
+            .exit_code(ExitCode::AwsApi)?;
+    }
+    if result.is_err() {
+        println!(
+            "Resources retained by the failed migration have not been deleted, check for a bucket and a queue whose names start with {}",
+            source_stack.to_lowercase()
+        );
+    }
+
+    result
+}
+
+/// Runs the migration with the same code path and prompts as `cfn-teleport --source ... --target ...`
+async fn migrate(
+    args: &DemoArgs,
+    global: &crate::Args,
+    source_stack: &str,
+    target_stack: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut argv = vec![
+        "cfn-teleport".to_string(),
+        "--source".to_string(),
+        source_stack.to_string(),
+        "--target".to_string(),
+        target_stack.to_string(),
+    ];
+    for resource in DEMO_RESOURCES {
+        argv.extend(["--resource".to_string(), resource.to_string()]);
+    }
+    if args.yes {
+        argv.push("--yes".to_string());
+    }
+    if let Some(profile) = &global.profile {
+        argv.extend(["--profile".to_string(), profile.clone()]);
+    }
+    if let Some(role_arn) = &global.role_arn {
+        argv.extend(["--role-arn".to_string(), role_arn.clone()]);
+    }
+    if let Some(mfa_serial) = &global.mfa_serial {
+        argv.extend(["--mfa-serial".to_string(), mfa_serial.clone()]);
+    }
+    if let Some(region) = &global.region {
+        argv.extend(["--region".to_string(), region.clone()]);
+    }
+    if let Some(ca_bundle) = &global.ca_bundle {
+        argv.extend(["--ca-bundle".to_string(), ca_bundle.display().to_string()]);
+    }
+    if let Some(partition) = &global.partition {
+        argv.extend(["--partition".to_string(), partition.clone()]);
+    }
+
+    let migration_args = crate::Args::try_parse_from(argv).exit_code(ExitCode::General)?;
+    crate::run(migration_args).await
+}
+
+fn source_template() -> serde_json::Value {
+    json!({
+        "Description": "cfn-teleport demo: source stack",
+        "Resources": {
+            "DemoBucket": {
+                "Type": "AWS::S3::Bucket",
+                "DeletionPolicy": "Delete"
+            },
+            "DemoQueue": {
+                "Type": "AWS::SQS::Queue",
+                "DeletionPolicy": "Delete"
+            },
+            // keeps the stack valid once the demo resources have been moved
+            "Placeholder": {
+                "Type": "AWS::CloudFormation::WaitConditionHandle"
+            }
+        }
+    })
+}
+
+fn target_template() -> serde_json::Value {
+    json!({
+        "Description": "cfn-teleport demo: target stack",
+        "Resources": {
+            "Placeholder": {
+                "Type": "AWS::CloudFormation::WaitConditionHandle"
+            }
+        }
+    })
+}
+
+async fn create_stack(
+    client: &cloudformation::Client,
+    stack_name: &str,
+    template: serde_json::Value,
+) -> Result<(), Box<dyn Error>> {
+    let message = format!("Creating stack {}", stack_name);
+    let mut spinner = spinner::Spin::new("create-demo-stack", &message);
+    client
+        .create_stack()
+        .stack_name(stack_name)
+        .template_body(serde_json::to_string_pretty(&template)?)
+        .tags(
+            Tag::builder()
+                .key("ApplicationName")
+                .value("cfn-teleport-demo")
+                .build(),
+        )
+        .send()
+        .await
+        .map_err(cloudformation::Error::from)?;
+
+    match wait_for_status(client, stack_name, &mut spinner).await? {
+        Some(StackStatus::CreateComplete) => {
+            spinner.complete();
+            Ok(())
+        }
+        status => {
+            spinner.fail();
+            Err(format!(
+                "Stack {} could not be created: {}",
+                stack_name,
+                status.as_ref().map_or("DELETED", StackStatus::as_str)
+            )
+            .into())
+        }
+    }
+}
+
+async fn delete_stack(
+    client: &cloudformation::Client,
+    stack_name: &str,
+) -> Result<(), Box<dyn Error>> {
+    let message = format!("Deleting stack {}", stack_name);
+    let mut spinner = spinner::Spin::new("delete-demo-stack", &message);
+    client
+        .delete_stack()
+        .stack_name(stack_name)
+        .send()
+        .await
+        .map_err(cloudformation::Error::from)?;
+
+    match wait_for_status(client, stack_name, &mut spinner).await? {
+        None | Some(StackStatus::DeleteComplete) => {
+            spinner.complete();
+            Ok(())
+        }
+        Some(status) => {
+            spinner.fail();
+            Err(format!(
+                "Stack {} could not be deleted: {}",
+                stack_name,
+                status.as_str()
+            )
+            .into())
+        }
+    }
+}
+
+/// Waits until a stack is no longer in progress and returns its final status, none if the stack
+/// does not exist anymore
+async fn wait_for_status(
+    client: &cloudformation::Client,
+    stack_name: &str,
+    spinner: &mut spinner::Spin,
+) -> Result<Option<StackStatus>, Box<dyn Error>> {
+    loop {
+        let status = match client.describe_stacks().stack_name(stack_name).send().await {
+            Ok(output) => output
+                .stacks()
+                .first()
+                .and_then(|stack| stack.stack_status().cloned()),
+            Err(err)
+                if err
+                    .message()
+                    .is_some_and(|message| message.contains("does not exist")) =>
+            {
+                None
+            }
+            Err(err) => return Err(cloudformation::Error::from(err).into()),
+        };
+
+        match status {
+            Some(status) if status.as_str().ends_with("_IN_PROGRESS") => {
+                spinner.update(status.as_str());
+                spinner.tick();
+                tokio::time::sleep(Duration::from_secs(2)).await;
+            }
+            status => return Ok(status),
+        }
+    }
+}
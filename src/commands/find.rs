@@ -0,0 +1,59 @@
+use aws_config::SdkConfig;
+use aws_sdk_cloudformation as cloudformation;
+use cfn_teleport::reference_updater;
+use std::error::Error;
+
+use crate::error::{ExitCode, WithExitCode};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct FindArgs {
+    /// Name of the stack to search
+    #[arg(long, env = "CFN_TELEPORT_STACK")]
+    stack: String,
+
+    /// Logical ID of the resource or parameter to find the references to
+    #[arg(long, value_name = "LOGICAL_ID", env = "CFN_TELEPORT_REFERENCES")]
+    references: String,
+}
+
+/// Prints every location in the template of a stack where a logical ID is referenced, with the
+/// intrinsic function it is referenced with
+pub async fn run(args: FindArgs, config: &SdkConfig) -> Result<(), Box<dyn Error>> {
+    let client = cloudformation::Client::new(config);
+    let template = crate::get_template(&client, &args.stack)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+
+    let references = reference_updater::find_references(&template)
+        .into_iter()
+        .filter(|reference| reference.id == args.references)
+        .collect::<Vec<_>>();
+    if references.is_empty() {
+        println!(
+            "{} is not referenced in stack {}",
+            args.references, args.stack
+        );
+        return Ok(());
+    }
+
+    println!(
+        "References to {} in stack {} ({}):",
+        args.references,
+        args.stack,
+        references.len()
+    );
+    let width = references
+        .iter()
+        .map(|reference| reference.display_path().len())
+        .max()
+        .unwrap_or_default();
+    for reference in references {
+        println!(
+            "  {:width$}  {}",
+            reference.display_path(),
+            reference.kind,
+            width = width
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,12 @@
+pub mod apply;
+pub mod batch;
+pub mod clean;
+pub mod cleanup_retain;
+pub mod completions;
+pub mod coupling;
+pub mod demo;
+pub mod find;
+pub mod plan;
+pub mod status;
+pub mod update_refs;
+pub mod validate;
@@ -0,0 +1,322 @@
+use aws_config::SdkConfig;
+use aws_sdk_cloudformation as cloudformation;
+use cfn_teleport::provenance::{self, Manifest, PlanFile};
+use cfn_teleport::ticket::{self, ChangeTicket, TicketResource};
+use cfn_teleport::{capabilities, plan, plan::Plan, planning, stack_api, template_body};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::error::{fail, ExitCode, WithExitCode};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct PlanArgs {
+    /// Path of a plan manifest (YAML or JSON), instead of --source, --target and --resource
+    #[arg(
+        long,
+        conflicts_with_all = ["source", "target", "resource"],
+        env = "CFN_TELEPORT_PLAN"
+    )]
+    plan: Option<PathBuf>,
+
+    /// Name of the source stack
+    #[arg(
+        short,
+        long,
+        required_unless_present = "plan",
+        env = "CFN_TELEPORT_SOURCE"
+    )]
+    source: Option<String>,
+
+    /// Name of the target stack
+    #[arg(
+        short,
+        long,
+        required_unless_present = "plan",
+        env = "CFN_TELEPORT_TARGET"
+    )]
+    target: Option<String>,
+
+    /// Logical ID of a resource from the source stack - optionally with a new ID for the target stack
+    #[arg(
+        short,
+        long,
+        value_name = "ID[:NEW_ID]",
+        required_unless_present = "plan",
+        value_delimiter = ',',
+        env = "CFN_TELEPORT_RESOURCE"
+    )]
+    resource: Vec<String>,
+
+    /// Physical ID to import a resource with, instead of the ID recorded in the source stack
+    #[arg(
+        long,
+        value_name = "ID=PHYSICAL_ID",
+        value_delimiter = ',',
+        env = "CFN_TELEPORT_PHYSICAL_ID"
+    )]
+    physical_id: Vec<String>,
+
+    /// Directory to write the templates, the changeset input and a manifest with their digests to, instead of printing them
+    #[arg(long, value_name = "DIR", env = "CFN_TELEPORT_OUTPUT_DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// Path of a plan file to write, with the plan and its templates, signed with the key in CFN_TELEPORT_SIGNING_KEY, to execute it with `cfn-teleport apply`
+    #[arg(long, value_name = "FILE", env = "CFN_TELEPORT_PLAN_FILE")]
+    plan_file: Option<PathBuf>,
+
+    /// JSON or YAML template of a change ticket to render for the migration, e.g. for ServiceNow or Jira
+    #[arg(long, value_name = "FILE", env = "CFN_TELEPORT_TICKET_TEMPLATE")]
+    ticket_template: Option<PathBuf>,
+
+    /// Change window of the ticket, e.g. 2024-05-01T22:00Z/2024-05-02T02:00Z
+    #[arg(
+        long,
+        value_name = "WINDOW",
+        requires = "ticket_template",
+        env = "CFN_TELEPORT_CHANGE_WINDOW"
+    )]
+    change_window: Option<String>,
+}
+
+/// Computes the templates and the resources to import of a migration without changing anything,
+/// so it can be executed by other tools
+pub async fn run(args: PlanArgs, config: &SdkConfig) -> Result<(), Box<dyn Error>> {
+    let ticket_template = match &args.ticket_template {
+        Some(path) => Some(ticket::read_template(path).exit_code(ExitCode::Validation)?),
+        None => None,
+    };
+
+    let mut plan = match args.plan {
+        Some(path) => Plan::from_file(&path).exit_code(ExitCode::Validation)?,
+        None => Plan {
+            source: args.source.unwrap_or_default(),
+            target: args.target.unwrap_or_default(),
+            resources: args.resource,
+            physical_ids: Default::default(),
+        },
+    };
+    for value in &args.physical_id {
+        let (logical_id, physical_id) =
+            plan::split_physical_id(value).exit_code(ExitCode::Validation)?;
+        plan.physical_ids.insert(logical_id, physical_id);
+    }
+    let client = cloudformation::Client::new(config);
+    let id_map = plan.resource_ids().into_iter().collect::<HashMap<_, _>>();
+    let source_ids = plan
+        .resource_ids()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+
+    let mut resources = crate::get_all_resources(&client, &plan.source)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    for resource in resources.iter_mut() {
+        let logical_id = resource.logical_resource_id().unwrap_or_default();
+        if let Some(physical_id) = plan.physical_ids.get(logical_id) {
+            resource.physical_resource_id = Some(physical_id.clone());
+        }
+    }
+    if let Some(logical_id) = plan.physical_ids.keys().find(|id| !source_ids.contains(id)) {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "Unable to override the physical ID of {}, because the resource is not part of the plan",
+                logical_id
+            ),
+        ));
+    }
+
+    let mut selected = Vec::new();
+    for id in &source_ids {
+        let resource = resources
+            .iter()
+            .find(|r| r.logical_resource_id() == Some(id.as_str()))
+            .ok_or_else(|| {
+                fail(
+                    ExitCode::Validation,
+                    format!("Resource {} not found in stack {}", id, plan.source),
+                )
+            })?;
+        if !crate::is_supported_resource(resource) {
+            return Err(fail(
+                ExitCode::Validation,
+                format!(
+                    "Resource {} of type {} does not support being imported",
+                    id,
+                    resource.resource_type().unwrap_or_default()
+                ),
+            ));
+        }
+        selected.push(resource);
+    }
+
+    let template_source = crate::get_template(&client, &plan.source)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    let template_target = crate::get_template(&client, &plan.target)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    crate::check_template_format(&plan.source, &template_source)?;
+    crate::check_template_format(&plan.target, &template_target)?;
+
+    let migration = planning::plan(
+        &plan,
+        &template_source,
+        &template_target,
+        &planning::Options::default(),
+    )
+    .exit_code(ExitCode::Validation)?;
+    if !crate::spinner::is_quiet() {
+        eprint!("{}", migration);
+    }
+    let template_import = migration.template("target-import").clone();
+    let source_parameters = crate::get_previous_parameters(&client, &plan.source)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    let target_parameters = crate::get_previous_parameters(&client, &plan.target)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    crate::check_copied_parameters(
+        &template_import,
+        &target_parameters,
+        (&plan.source, &template_source),
+        &plan.target,
+    )?;
+
+    let body = template_body::prepare(&template_import)
+        .await
+        .map_err(|err| err as Box<dyn Error>)?;
+    let resources_to_import =
+        crate::get_resources_to_import((config, &client), &body, &selected, &id_map)
+            .await
+            .exit_code(ExitCode::AwsApi)?;
+
+    // input for `aws cloudformation create-change-set --cli-input-json`
+    let mut changeset = serde_json::json!({
+        "StackName": plan.target,
+        "ChangeSetName": format!("{}-{}", plan.target, Uuid::new_v4()),
+        "ChangeSetType": "IMPORT",
+        "Parameters": crate::parameters_json(&stack_api::parameters_for(
+            &template_import,
+            &target_parameters,
+            &source_parameters,
+        )),
+        "ResourcesToImport": crate::resources_to_import_json(&resources_to_import),
+        "Capabilities": capabilities::required_capabilities(&template_import)
+            .iter()
+            .map(|requirement| requirement.capability)
+            .collect::<Vec<_>>(),
+    });
+    match template_body::keep(&body) {
+        Some(location) => {
+            eprintln!(
+                "The template of the change set has been uploaded to {}, its URL is valid for {}",
+                location,
+                crate::timing::format_duration(template_body::URL_EXPIRY)
+            );
+            changeset["TemplateURL"] = body.url().into();
+        }
+        None => changeset["TemplateBody"] = body.body().into(),
+    }
+
+    let mut artifacts = provenance::TEMPLATES
+        .iter()
+        .chain(&provenance::ORIGINALS)
+        .map(|name| (*name, migration.template(name).clone()))
+        .collect::<Vec<_>>();
+    artifacts.push(("changeset", changeset));
+
+    if let Some(template) = ticket_template {
+        let mut stack_arns = Vec::new();
+        for stack_name in [&plan.source, &plan.target] {
+            let output = client
+                .describe_stacks()
+                .stack_name(stack_name)
+                .send()
+                .await
+                .exit_code(ExitCode::AwsApi)?;
+            stack_arns.extend(
+                output
+                    .stacks()
+                    .first()
+                    .and_then(|stack| stack.stack_id())
+                    .map(str::to_string),
+            );
+        }
+        let change_ticket = ChangeTicket {
+            source_stack: plan.source.clone(),
+            target_stack: plan.target.clone(),
+            stack_arns,
+            resources: selected
+                .iter()
+                .map(|resource| {
+                    let source_id = resource.logical_resource_id().unwrap_or_default();
+                    TicketResource {
+                        source_id: source_id.to_string(),
+                        target_id: id_map[source_id].clone(),
+                        resource_type: resource.resource_type().unwrap_or_default().to_string(),
+                        physical_id: resource
+                            .physical_resource_id()
+                            .unwrap_or_default()
+                            .to_string(),
+                    }
+                })
+                .collect(),
+            change_window: args.change_window,
+        };
+        artifacts.push((
+            "ticket",
+            ticket::render(&template, &change_ticket).exit_code(ExitCode::Validation)?,
+        ));
+    }
+
+    if let Some(path) = &args.plan_file {
+        let templates = provenance::TEMPLATES
+            .iter()
+            .chain(&provenance::ORIGINALS)
+            .map(|name| (name.to_string(), migration.template(name).clone()))
+            .collect::<BTreeMap<_, _>>();
+        let key = std::env::var(provenance::SIGNING_KEY_ENV).ok();
+        PlanFile::create(
+            env!("CARGO_PKG_VERSION"),
+            plan.clone(),
+            templates,
+            key.as_deref().map(str::as_bytes),
+        )
+        .and_then(|plan_file| plan_file.write(path))?;
+        eprintln!("Wrote {}", path.display());
+    }
+
+    match args.output_dir {
+        Some(dir) => {
+            fs::create_dir_all(&dir)?;
+            let mut files = Vec::new();
+            for (name, value) in &artifacts {
+                let file = format!("{}.json", name);
+                let path = dir.join(&file);
+                fs::write(&path, serde_json::to_string_pretty(value)?)?;
+                eprintln!("Wrote {}", path.display());
+                files.push(file);
+            }
+
+            // digests of the files, verified when the migration is executed with --from-artifacts
+            Manifest::create(&dir, env!("CARGO_PKG_VERSION"), plan, &files)
+                .and_then(|manifest| manifest.write(&dir))?;
+            eprintln!("Wrote {}", dir.join(provenance::MANIFEST).display());
+        }
+        None if args.plan_file.is_some() => {}
+        None => {
+            let document = artifacts
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect::<serde_json::Map<_, _>>();
+            println!("{}", serde_json::to_string_pretty(&document)?);
+        }
+    }
+
+    Ok(())
+}
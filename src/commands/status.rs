@@ -0,0 +1,81 @@
+use aws_config::SdkConfig;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::error::{fail, ExitCode, WithExitCode};
+use crate::state;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct StatusArgs {
+    /// Release the stacks of a migration which did not release them itself
+    #[arg(long, value_name = "MIGRATION_ID", env = "CFN_TELEPORT_RELEASE")]
+    release: Option<String>,
+}
+
+/// Lists the migrations in progress registered in the state table
+pub async fn run(
+    args: StatusArgs,
+    config: &SdkConfig,
+    table: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let Some(table) = table else {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "No state table configured, pass --state-table or set {}",
+                state::STATE_TABLE_ENV
+            ),
+        ));
+    };
+
+    if let Some(migration_id) = args.release {
+        let stacks = state::release_migration(config, table, &migration_id)
+            .await
+            .exit_code(ExitCode::AwsApi)?;
+        if stacks.is_empty() {
+            return Err(fail(
+                ExitCode::Validation,
+                format!("Migration {} is not registered", migration_id),
+            ));
+        }
+        println!(
+            "Released migration {} of stacks {}",
+            migration_id,
+            stacks.join(", ")
+        );
+        return Ok(());
+    }
+
+    let registrations = state::list(config, table)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    if registrations.is_empty() {
+        println!("No migrations in progress");
+        return Ok(());
+    }
+
+    let mut migrations = BTreeMap::new();
+    for registration in &registrations {
+        migrations
+            .entry((&registration.started_at, &registration.migration_id))
+            .or_insert_with(Vec::new)
+            .push(registration);
+    }
+
+    for ((started_at, migration_id), stacks) in migrations {
+        let first = stacks[0];
+        let (source, target) = match first.role.as_str() {
+            "Source" => (&first.stack_name, &first.counterpart),
+            _ => (&first.counterpart, &first.stack_name),
+        };
+        println!("{}", migration_id);
+        println!("  Stacks:    {} → {}", source, target);
+        println!("  User:      {}", first.user);
+        println!("  Started:   {}", started_at);
+        println!("  Phase:     {}", first.phase);
+        println!("  Resources: {}", first.resources.join(", "));
+        println!();
+    }
+
+    Ok(())
+}
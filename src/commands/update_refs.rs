@@ -0,0 +1,89 @@
+use cfn_teleport::{cfn_yaml, reference_updater};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::{fail, ExitCode, WithExitCode};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct UpdateRefsArgs {
+    /// Path of the template file (JSON, JSON with comments or YAML)
+    #[arg(long, env = "CFN_TELEPORT_TEMPLATE")]
+    template: PathBuf,
+
+    /// Logical IDs to rename
+    #[arg(
+        long,
+        value_name = "OLD=NEW",
+        value_delimiter = ',',
+        required = true,
+        env = "CFN_TELEPORT_MAP"
+    )]
+    map: Vec<String>,
+
+    /// Path to write the updated template to. Prints to stdout if omitted
+    #[arg(long, env = "CFN_TELEPORT_OUT")]
+    out: Option<PathBuf>,
+}
+
+/// Renames resources in a local template file and updates all references to them
+pub fn run(args: UpdateRefsArgs) -> Result<(), Box<dyn Error>> {
+    let id_map = parse_map(&args.map)?;
+
+    let body = fs::read_to_string(&args.template).exit_code(ExitCode::Validation)?;
+    let mut template = cfn_yaml::parse_template(&body).exit_code(ExitCode::Validation)?;
+
+    reference_updater::update_template(&mut template, &id_map).exit_code(ExitCode::Validation)?;
+
+    // Renaming the IDs in the original text keeps comments, formatting and short-form functions.
+    // This only works if the IDs are not used in any other meaning, otherwise the template is
+    // rendered from scratch.
+    let renamed = reference_updater::rename_in_source(&body, &id_map);
+    let output = match cfn_yaml::parse_template(&renamed) {
+        Ok(parsed) if parsed == template => renamed,
+        _ => {
+            eprintln!("WARNING: Unable to keep the formatting and comments of the template, it is rendered from scratch");
+            if cfn_yaml::is_json(&body) {
+                serde_json::to_string_pretty(&template)? + "\n"
+            } else {
+                cfn_yaml::to_string(&template)?
+            }
+        }
+    };
+
+    match args.out {
+        Some(path) => fs::write(path, output)?,
+        None => print!("{}", output),
+    }
+
+    Ok(())
+}
+
+fn parse_map(entries: &[String]) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut id_map = HashMap::new();
+
+    for entry in entries {
+        match entry.split_once('=') {
+            Some((old_id, new_id)) if !old_id.is_empty() && !new_id.is_empty() => {
+                if id_map
+                    .insert(old_id.to_string(), new_id.to_string())
+                    .is_some()
+                {
+                    return Err(fail(
+                        ExitCode::Validation,
+                        format!("{} is mapped more than once", old_id),
+                    ));
+                }
+            }
+            _ => {
+                return Err(fail(
+                    ExitCode::Validation,
+                    format!("Invalid mapping '{}', expected OLD=NEW", entry),
+                ))
+            }
+        }
+    }
+
+    Ok(id_map)
+}
@@ -0,0 +1,282 @@
+use aws_config::SdkConfig;
+use aws_sdk_cloudformation as cloudformation;
+use cfn_teleport::{analysis, diff, plan::Plan, planning, template_body};
+use console::style;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::error::{fail, ExitCode, WithExitCode};
+use crate::permissions;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ValidateArgs {
+    /// Path of the plan manifest (YAML or JSON)
+    #[arg(long, env = "CFN_TELEPORT_PLAN")]
+    plan: PathBuf,
+}
+
+enum Outcome {
+    Pass,
+    Fail,
+    Skip,
+}
+
+struct Report {
+    failed: bool,
+}
+
+impl Report {
+    fn check(&mut self, outcome: Outcome, name: &str, details: &[String]) {
+        let label = match outcome {
+            Outcome::Pass => style("PASS").green(),
+            Outcome::Fail => {
+                self.failed = true;
+                style("FAIL").red()
+            }
+            Outcome::Skip => style("SKIP").yellow(),
+        };
+        println!("{}  {}", label, name);
+        for detail in details {
+            println!("      - {}", detail);
+        }
+    }
+
+    fn expect(&mut self, name: &str, problems: Vec<String>) {
+        let outcome = if problems.is_empty() {
+            Outcome::Pass
+        } else {
+            Outcome::Fail
+        };
+        self.check(outcome, name, &problems);
+    }
+}
+
+/// Runs all non-mutating checks of a plan and prints a pass/fail report
+pub async fn run(args: ValidateArgs, config: &SdkConfig) -> Result<(), Box<dyn Error>> {
+    let plan = Plan::from_file(&args.plan).exit_code(ExitCode::Validation)?;
+    let client = cloudformation::Client::new(config);
+    let mut report = Report { failed: false };
+
+    let resource_ids = plan.resource_ids();
+    let id_map = resource_ids.iter().cloned().collect::<HashMap<_, _>>();
+    let source_ids = resource_ids
+        .iter()
+        .map(|(id, _)| id.clone())
+        .collect::<Vec<_>>();
+
+    report.expect(
+        "Source and target stack are different",
+        if plan.source == plan.target {
+            vec![format!("Both stacks are {}", plan.source)]
+        } else {
+            vec![]
+        },
+    );
+
+    let mut stack_arns = Vec::new();
+    for stack_name in [&plan.source, &plan.target] {
+        match stack_id(&client, stack_name).await {
+            Ok(arn) => {
+                stack_arns.push(arn);
+                report.check(Outcome::Pass, &format!("Stack {} exists", stack_name), &[]);
+            }
+            Err(err) => report.check(
+                Outcome::Fail,
+                &format!("Stack {} exists", stack_name),
+                &[err.to_string()],
+            ),
+        }
+    }
+
+    if stack_arns.len() == 2 {
+        let resources = crate::get_all_resources(&client, &plan.source)
+            .await
+            .exit_code(ExitCode::AwsApi)?;
+        let template_source = crate::get_template(&client, &plan.source)
+            .await
+            .exit_code(ExitCode::AwsApi)?;
+        let template_target = crate::get_template(&client, &plan.target)
+            .await
+            .exit_code(ExitCode::AwsApi)?;
+
+        let mut format_errors = Vec::new();
+        for (stack_name, template) in [
+            (&plan.source, &template_source),
+            (&plan.target, &template_target),
+        ] {
+            format_errors.extend(
+                analysis::format_errors(template)
+                    .into_iter()
+                    .map(|error| format!("{}: {}", stack_name, error)),
+            );
+        }
+        let malformed = !format_errors.is_empty();
+        report.expect("Templates are well-formed", format_errors);
+        if malformed {
+            return Err(fail(ExitCode::Validation, "Plan validation failed"));
+        }
+
+        report.expect(
+            &format!("Resources exist in stack {}", plan.source),
+            source_ids
+                .iter()
+                .filter(|id| {
+                    !resources
+                        .iter()
+                        .any(|r| r.logical_resource_id() == Some(id.as_str()))
+                })
+                .map(|id| id.to_string())
+                .collect(),
+        );
+
+        report.expect(
+            "Physical ID overrides refer to resources of the plan",
+            plan.physical_ids
+                .keys()
+                .filter(|id| !source_ids.contains(id))
+                .map(|id| id.to_string())
+                .collect(),
+        );
+
+        report.expect(
+            "Resources support being imported",
+            resources
+                .iter()
+                .filter(|r| {
+                    source_ids.contains(&r.logical_resource_id().unwrap_or_default().into())
+                })
+                .filter(|r| !crate::is_supported_resource(r))
+                .map(|r| {
+                    format!(
+                        "{} ({})",
+                        r.logical_resource_id().unwrap_or_default(),
+                        r.resource_type().unwrap_or_default()
+                    )
+                })
+                .collect(),
+        );
+
+        let mut new_ids = id_map.values().collect::<Vec<_>>();
+        new_ids.sort();
+        report.expect(
+            &format!("Logical IDs are available in stack {}", plan.target),
+            new_ids
+                .iter()
+                .filter(|id| template_target["Resources"].get(id.as_str()).is_some())
+                .map(|id| id.to_string())
+                .collect(),
+        );
+
+        let migration = match planning::plan(
+            &plan,
+            &template_source,
+            &template_target,
+            &planning::Options::default(),
+        ) {
+            Ok(migration) => {
+                report.check(Outcome::Pass, "Template dependencies can be copied", &[]);
+                migration
+            }
+            Err(err) => {
+                report.check(Outcome::Fail, "Template dependencies can be copied", &[err]);
+                return Err(fail(ExitCode::Validation, "Plan validation failed"));
+            }
+        };
+        let template_target_added = migration.template("target-final");
+
+        report.expect(
+            &format!(
+                "No references to moved resources remain in stack {}",
+                plan.source
+            ),
+            analysis::dangling_references(&template_source, &source_ids)
+                .iter()
+                .map(|r| r.to_string())
+                .collect(),
+        );
+
+        report.expect(
+            &format!("All references resolve in stack {}", plan.target),
+            analysis::unresolved_references(template_target_added)
+                .iter()
+                .map(|r| r.to_string())
+                .collect(),
+        );
+
+        report.expect(
+            &format!(
+                "Template of stack {} stays within the quotas of CloudFormation",
+                plan.target
+            ),
+            analysis::quota_usage(template_target_added, template_body::body_limit())
+                .iter()
+                .filter(|usage| usage.is_exceeded())
+                .map(|usage| usage.to_string())
+                .collect(),
+        );
+
+        for (stack_name, old, new) in [
+            (
+                &plan.source,
+                &template_source,
+                migration.template("source-removed"),
+            ),
+            (&plan.target, &template_target, template_target_added),
+        ] {
+            let changes = diff::diff_templates(old, new)
+                .iter()
+                .map(|change| change.to_string())
+                .collect::<Vec<_>>();
+            report.check(
+                Outcome::Pass,
+                &format!("Changes to the template of stack {}", stack_name),
+                &changes,
+            );
+        }
+
+        let mut invalid = Vec::new();
+        for (name, template) in [
+            ("source with DeletionPolicy", "source-retained"),
+            ("source without resources", "source-removed"),
+            ("target for import", "target-import"),
+            ("target", "target-final"),
+        ] {
+            let template = migration.template(template).clone();
+            if let Err(err) = crate::validate_template(&client, template).await {
+                invalid.push(format!("{}: {}", name, err));
+            }
+        }
+        report.expect("Templates are valid", invalid);
+
+        match permissions::denied_actions(config, &permissions::REQUIRED_ACTIONS, &stack_arns).await
+        {
+            Ok(denied) => report.expect("Required permissions are granted", denied),
+            Err(err) => report.check(
+                Outcome::Skip,
+                "Required permissions are granted",
+                &[format!("Unable to simulate policies: {}", err)],
+            ),
+        }
+    }
+
+    if report.failed {
+        return Err(fail(ExitCode::Validation, "Plan validation failed"));
+    }
+
+    Ok(())
+}
+
+async fn stack_id(
+    client: &cloudformation::Client,
+    stack_name: &str,
+) -> Result<String, Box<dyn Error>> {
+    let resp = client
+        .describe_stacks()
+        .stack_name(stack_name)
+        .send()
+        .await
+        .map_err(cloudformation::Error::from)?;
+    let stack = resp.stacks().first().ok_or("Stack not found")?;
+    Ok(stack.stack_id().unwrap_or_default().to_string())
+}
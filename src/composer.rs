@@ -0,0 +1,846 @@
+//! Composes the templates of the source and target stack for a migration.
+
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::{analysis, reference_updater};
+
+/// Template of a stack without resources, the starting point of a stack created by importing
+/// resources
+pub fn empty_template() -> Value {
+    serde_json::json!({
+        "AWSTemplateFormatVersion": "2010-09-09",
+        "Resources": {},
+    })
+}
+
+pub fn retain_resources(mut template: Value, resource_ids: Vec<String>) -> Value {
+    let resources = template["Resources"].as_object_mut().unwrap();
+
+    for resource_id in resource_ids {
+        if let Some(resource) = resources.get_mut(&resource_id) {
+            resource["DeletionPolicy"] = Value::String("Retain".to_string());
+        }
+    }
+
+    template
+}
+
+/// Records the DeletionPolicy of resources before a migration changes it. Resources without a
+/// DeletionPolicy are recorded as `None`.
+pub fn deletion_policies(
+    template: &Value,
+    resource_ids: &[String],
+) -> BTreeMap<String, Option<String>> {
+    resource_ids
+        .iter()
+        .filter_map(|resource_id| {
+            let resource = template["Resources"].get(resource_id)?;
+            let policy = resource["DeletionPolicy"].as_str().map(str::to_string);
+            Some((resource_id.to_string(), policy))
+        })
+        .collect()
+}
+
+/// Sets the DeletionPolicy of resources back to the recorded values, removing it from resources
+/// which had none. Resources which are not part of the template are skipped.
+pub fn restore_deletion_policies(
+    mut template: Value,
+    policies: &BTreeMap<String, Option<String>>,
+) -> Value {
+    let Some(resources) = template["Resources"].as_object_mut() else {
+        return template;
+    };
+
+    for (resource_id, policy) in policies {
+        let Some(resource) = resources
+            .get_mut(resource_id)
+            .and_then(|resource| resource.as_object_mut())
+        else {
+            continue;
+        };
+        match policy {
+            Some(policy) => {
+                resource.insert("DeletionPolicy".to_string(), Value::String(policy.clone()));
+            }
+            None => {
+                resource.shift_remove("DeletionPolicy");
+            }
+        }
+    }
+
+    template
+}
+
+// for reasons unknown, importing resource requires a DeletionPolicy to be set. Se we add the documented defaults
+// https://docs.aws.amazon.com/AWSCloudFormation/latest/UserGuide/aws-attribute-deletionpolicy.html
+pub fn set_default_deletion_policy(mut template: Value, resource_ids: Vec<String>) -> Value {
+    let resources = template["Resources"].as_object_mut().unwrap();
+
+    for resource_id in resource_ids {
+        if let Some(resource) = resources.get_mut(&resource_id) {
+            if resource.is_object() {
+                let resource_object = resource.as_object_mut().unwrap();
+                if !resource_object.contains_key("DeletionPolicy") {
+                    let resource_type = resource_object["Type"].as_str().unwrap();
+                    let deletion_policy = match resource_type {
+                        "AWS::RDS::DBCluster" => "Snapshot",
+                        "AWS::RDS::DBInstance" => {
+                            if resource_object.contains_key("DBClusterIdentifier") {
+                                "Delete"
+                            } else {
+                                "Snapshot"
+                            }
+                        }
+                        _ => "Delete",
+                    };
+                    resource["DeletionPolicy"] = Value::String(deletion_policy.to_string());
+                }
+            }
+        }
+    }
+
+    template
+}
+
+/// Removes resources from a template, together with the Outputs which only reference them
+pub fn remove_resources(mut template: Value, resource_ids: Vec<String>) -> Value {
+    let outputs = dependent_outputs(&template, &resource_ids);
+    let resources = template["Resources"].as_object_mut().unwrap();
+
+    for resource_id in resource_ids {
+        // shift_remove keeps the order of the remaining resources
+        resources.shift_remove(&resource_id);
+    }
+
+    if let Some(entries) = template.get_mut("Outputs").and_then(|o| o.as_object_mut()) {
+        for name in outputs {
+            entries.shift_remove(&name);
+        }
+        if entries.is_empty() {
+            template.as_object_mut().unwrap().shift_remove("Outputs");
+        }
+    }
+
+    template
+}
+
+/// Names of the Outputs which reference resources of the template, but only the given ones. They
+/// cannot stay in the template without these resources and are moved along with them.
+pub fn dependent_outputs(template: &Value, resource_ids: &[String]) -> Vec<String> {
+    let Some(outputs) = template.get("Outputs").and_then(|o| o.as_object()) else {
+        return Vec::new();
+    };
+
+    outputs
+        .iter()
+        .filter(|(_, output)| {
+            let resources = reference_updater::collect_references(output)
+                .into_iter()
+                .filter(|id| template["Resources"].get(id).is_some())
+                .collect::<Vec<_>>();
+            !resources.is_empty() && resources.iter().all(|id| resource_ids.contains(id))
+        })
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+/// Renames Outputs and overrides their export names, keyed by the original name of the output, to
+/// avoid collisions with Outputs and exports of the target stack
+pub fn rename_outputs(
+    mut template: Value,
+    names: &BTreeMap<String, String>,
+    export_names: &BTreeMap<String, String>,
+) -> Value {
+    let Some(outputs) = template.get_mut("Outputs").and_then(|o| o.as_object_mut()) else {
+        return template;
+    };
+
+    for (name, export_name) in export_names {
+        if let Some(output) = outputs.get_mut(name) {
+            output["Export"] = serde_json::json!({ "Name": export_name });
+        }
+    }
+
+    *outputs = std::mem::take(outputs)
+        .into_iter()
+        .map(|(name, output)| (names.get(&name).cloned().unwrap_or(name), output))
+        .collect();
+    template
+}
+
+/// Declares transforms in a template, which does not declare them yet. `AWS::LanguageExtensions`
+/// has to run before `AWS::Serverless-2016-10-31` and therefore comes first, other transforms are
+/// appended.
+pub fn add_transforms(mut template: Value, transforms: &[String]) -> Value {
+    let mut declared = match template.get("Transform") {
+        Some(Value::String(transform)) => vec![Value::String(transform.clone())],
+        Some(Value::Array(transforms)) => transforms.clone(),
+        _ => Vec::new(),
+    };
+    for transform in transforms {
+        let transform = Value::String(transform.clone());
+        if declared.contains(&transform) {
+            continue;
+        }
+        if transform == analysis::LANGUAGE_EXTENSIONS_TRANSFORM {
+            declared.insert(0, transform);
+        } else {
+            declared.push(transform);
+        }
+    }
+
+    let Some(sections) = template.as_object_mut().filter(|_| !declared.is_empty()) else {
+        return template;
+    };
+    let transform = match declared.len() {
+        1 => declared.remove(0),
+        _ => Value::Array(declared),
+    };
+    if let Some(existing) = sections.get_mut("Transform") {
+        *existing = transform;
+    } else {
+        // next to the other sections describing the template as a whole
+        let index = sections
+            .keys()
+            .take_while(|key| *key == "AWSTemplateFormatVersion" || *key == "Description")
+            .count();
+        sections.shift_insert(index, "Transform".to_string(), transform);
+    }
+    template
+}
+
+/// Removes resources which are aliases of another resource, i.e. refer to the same physical
+/// resource, and points all references to them to the resource which is kept. `aliases` maps the
+/// logical IDs of the aliases to the logical ID of the kept resource.
+pub fn merge_aliases(mut template: Value, aliases: &HashMap<String, String>) -> Value {
+    if let Some(resources) = template["Resources"].as_object_mut() {
+        for alias in aliases.keys() {
+            resources.shift_remove(alias);
+        }
+
+        for resource in resources.values_mut() {
+            reference_updater::update_depends_on(resource, aliases);
+            reference_updater::traverse_and_update(resource, aliases);
+
+            // a resource which depended on both, the alias and the kept resource
+            if let Some(Value::Array(ids)) = resource.get_mut("DependsOn") {
+                let mut seen = BTreeSet::new();
+                ids.retain(|id| seen.insert(id.to_string()));
+            }
+        }
+    }
+
+    if let Some(outputs) = template.get_mut("Outputs") {
+        reference_updater::traverse_and_update(outputs, aliases);
+    }
+
+    template
+}
+
+/// Copies resources from the source into the target template, together with the Parameters,
+/// Conditions and Mappings they depend on. Returns the target template with default deletion
+/// policies for the import and the final target template, which also receives the Outputs moved
+/// along with the resources. An import must not change Outputs.
+pub fn add_resources(
+    mut target_template: Value,
+    source_template: Value,
+    resource_id_map: HashMap<String, String>,
+) -> Result<(Value, Value), String> {
+    let source_resources = source_template["Resources"].as_object().unwrap();
+
+    let mut resources = Vec::new();
+    // resources are added in the order of the source template, keeping diffs reviewable
+    for (resource_id, resource) in source_resources {
+        if let Some(new_resource_id) = resource_id_map.get(resource_id) {
+            // references between co-moved resources need to follow renames
+            let mut resource = resource.clone();
+            reference_updater::update_depends_on(&mut resource, &resource_id_map);
+            reference_updater::traverse_and_update(&mut resource, &resource_id_map);
+            resources.push((new_resource_id.to_string(), resource));
+        }
+    }
+
+    // values and export names of the outputs, including Fn::Sub expressions, follow renames too
+    let moved_ids = resource_id_map.keys().cloned().collect::<Vec<_>>();
+    let mut outputs = Vec::new();
+    for name in dependent_outputs(&source_template, &moved_ids) {
+        let mut output = source_template["Outputs"][&name].clone();
+        reference_updater::traverse_and_update(&mut output, &resource_id_map);
+        outputs.push((name, output));
+    }
+
+    let renames = copy_dependencies(
+        &mut target_template,
+        &source_template,
+        &resources
+            .iter()
+            .chain(outputs.iter())
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>(),
+    )?;
+
+    let target_resources = target_template["Resources"].as_object_mut().unwrap();
+    for (new_resource_id, mut resource) in resources {
+        renames.apply(&mut resource);
+        insert_resource(target_resources, new_resource_id, resource);
+    }
+
+    let target_template_with_deletion_policy = set_default_deletion_policy(
+        target_template.clone(),
+        resource_id_map.values().map(|x| x.to_string()).collect(),
+    );
+
+    if !outputs.is_empty() && target_template.get("Outputs").is_none() {
+        target_template["Outputs"] = Value::Object(Default::default());
+    }
+    for (name, mut output) in outputs {
+        renames.apply(&mut output);
+        let target_outputs = target_template["Outputs"]
+            .as_object_mut()
+            .ok_or("Outputs section of the target template is not an object")?;
+        match target_outputs.get(&name) {
+            Some(existing) if *existing == output => {}
+            Some(_) => {
+                return Err(format!(
+                    "Outputs {} exists in the target template with a different definition",
+                    name
+                ))
+            }
+            None => {
+                target_outputs.insert(name, output);
+            }
+        }
+    }
+
+    Ok((target_template_with_deletion_policy, target_template))
+}
+
+/// Expressions with which the policy documents of the remaining resources refer to the given
+/// resources, like `Table` for a `Ref` or `Table.Arn` for an attribute, by the logical ID of the
+/// referencing resource. Such policies turn invalid once the resources are removed from the
+/// template.
+pub fn policy_references(
+    template: &Value,
+    resource_ids: &[String],
+) -> BTreeMap<String, BTreeSet<String>> {
+    let mut references = BTreeMap::new();
+    let Some(resources) = template["Resources"].as_object() else {
+        return references;
+    };
+
+    for (logical_id, resource) in resources {
+        if resource_ids.contains(logical_id) {
+            continue;
+        }
+        let mut expressions = BTreeSet::new();
+        for document in policy_documents(resource) {
+            collect_expressions(document, resource_ids, &mut expressions);
+        }
+        if !expressions.is_empty() {
+            references.insert(logical_id.clone(), expressions);
+        }
+    }
+    references
+}
+
+/// Replaces the references of [`policy_references`] with fixed values, e.g. the ARN of a moved
+/// table, keyed by the expression. Resources in `resource_ids` keep their policies.
+pub fn hardcode_policy_references(
+    mut template: Value,
+    resource_ids: &[String],
+    values: &HashMap<String, String>,
+) -> Value {
+    let Some(resources) = template["Resources"].as_object_mut() else {
+        return template;
+    };
+
+    for (logical_id, resource) in resources.iter_mut() {
+        if resource_ids.contains(logical_id) {
+            continue;
+        }
+        for document in policy_documents_mut(resource) {
+            replace_expressions(document, values);
+        }
+    }
+    template
+}
+
+/// Policy documents of a resource, e.g. of an `AWS::IAM::Policy` or the inline policies of an
+/// `AWS::IAM::Role`
+fn policy_documents(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .flat_map(|(key, value)| match key.as_str() {
+                "PolicyDocument" => vec![value],
+                _ => policy_documents(value),
+            })
+            .collect(),
+        Value::Array(items) => items.iter().flat_map(policy_documents).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn policy_documents_mut(value: &mut Value) -> Vec<&mut Value> {
+    match value {
+        Value::Object(map) => map
+            .iter_mut()
+            .flat_map(|(key, value)| match key.as_str() {
+                "PolicyDocument" => vec![value],
+                _ => policy_documents_mut(value),
+            })
+            .collect(),
+        Value::Array(items) => items.iter_mut().flat_map(policy_documents_mut).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn collect_expressions(value: &Value, resource_ids: &[String], expressions: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value) {
+                    ("Ref", Value::String(id)) if resource_ids.contains(id) => {
+                        expressions.insert(id.clone());
+                    }
+                    ("Fn::GetAtt", value) => {
+                        if let Some(expression) = get_att_expression(value)
+                            .filter(|(id, _)| resource_ids.iter().any(|r| r == id))
+                        {
+                            expressions.insert(format!("{}.{}", expression.0, expression.1));
+                        }
+                    }
+                    ("Fn::Sub", value) => {
+                        let (template, variables) = reference_updater::sub_parts(value);
+                        for expression in
+                            reference_updater::sub_expressions(template.unwrap_or_default())
+                        {
+                            if reference_updater::sub_reference(expression, &variables)
+                                .is_some_and(|id| resource_ids.iter().any(|r| r == id))
+                            {
+                                expressions.insert(expression.trim().to_string());
+                            }
+                        }
+                        if let Value::Array(items) = value {
+                            items.iter().skip(1).for_each(|item| {
+                                collect_expressions(item, resource_ids, expressions)
+                            });
+                        }
+                    }
+                    (_, value) => collect_expressions(value, resource_ids, expressions),
+                }
+            }
+        }
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| collect_expressions(item, resource_ids, expressions)),
+        _ => {}
+    }
+}
+
+/// Logical ID and attribute of both forms of `Fn::GetAtt`
+fn get_att_expression(value: &Value) -> Option<(&str, &str)> {
+    match value {
+        Value::String(expression) => reference_updater::split_get_att(expression),
+        Value::Array(items) => match items.as_slice() {
+            [Value::String(id), Value::String(attribute)] => Some((id, attribute)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn replace_expressions(value: &mut Value, values: &HashMap<String, String>) {
+    let replacement = value
+        .as_object()
+        .filter(|map| map.len() == 1)
+        .and_then(|map| {
+            let (key, value) = map.iter().next()?;
+            match (key.as_str(), value) {
+                ("Ref", Value::String(id)) => values.get(id),
+                ("Fn::GetAtt", value) => {
+                    let (id, attribute) = get_att_expression(value)?;
+                    values.get(&format!("{}.{}", id, attribute))
+                }
+                _ => None,
+            }
+        });
+    if let Some(replacement) = replacement {
+        *value = Value::String(replacement.clone());
+        return;
+    }
+
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match (key.as_str(), value) {
+                    ("Fn::Sub", Value::String(template)) => {
+                        *template = substitute(template, values)
+                    }
+                    ("Fn::Sub", Value::Array(items)) => {
+                        if let Some(Value::String(template)) = items.first_mut() {
+                            *template = substitute(template, values);
+                        }
+                        items
+                            .iter_mut()
+                            .skip(1)
+                            .for_each(|item| replace_expressions(item, values));
+                    }
+                    (_, value) => replace_expressions(value, values),
+                }
+            }
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| replace_expressions(item, values)),
+        _ => {}
+    }
+}
+
+/// Replaces the `${...}` placeholders of a `Fn::Sub` template string with known values
+fn substitute(template: &str, values: &HashMap<String, String>) -> String {
+    let mut template = template.to_string();
+    for (expression, value) in values {
+        template = template.replace(&format!("${{{}}}", expression), value);
+    }
+    template
+}
+
+/// Inserts a resource after the last resource it depends on, or else after the last resource of the
+/// same service, e.g. `AWS::S3`, so reviewers find it next to related resources. Resources with
+/// neither are appended.
+fn insert_resource(resources: &mut Map<String, Value>, resource_id: String, resource: Value) {
+    let references = reference_updater::collect_references(&resource);
+    let service = service_of(&resource);
+
+    let index = resources
+        .keys()
+        .rposition(|id| references.contains(id))
+        .or_else(|| {
+            resources
+                .values()
+                .rposition(|other| service.is_some() && service_of(other) == service)
+        })
+        .map_or(resources.len(), |index| index + 1);
+    resources.shift_insert(index, resource_id, resource);
+}
+
+/// Service part of the type of a resource, e.g. `AWS::S3` of `AWS::S3::Bucket`
+fn service_of(resource: &Value) -> Option<&str> {
+    let resource_type = resource["Type"].as_str()?;
+    resource_type
+        .rfind("::")
+        .map(|index| &resource_type[..index])
+}
+
+/// Conditions and Mappings which have been reused under a different name in the target template
+#[derive(Default)]
+struct Renames {
+    conditions: HashMap<String, String>,
+    mappings: HashMap<String, String>,
+}
+
+impl Renames {
+    fn apply(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    match (key.as_str(), value) {
+                        ("Condition", Value::String(name)) => {
+                            if let Some(new_name) = self.conditions.get(name.as_str()) {
+                                *name = new_name.to_string();
+                            }
+                        }
+                        ("Fn::If", Value::Array(items)) => {
+                            if let Some(Value::String(name)) = items.first_mut() {
+                                if let Some(new_name) = self.conditions.get(name.as_str()) {
+                                    *name = new_name.to_string();
+                                }
+                            }
+                            items.iter_mut().skip(1).for_each(|item| self.apply(item));
+                        }
+                        ("Fn::FindInMap", Value::Array(items)) => {
+                            if let Some(Value::String(name)) = items.first_mut() {
+                                if let Some(new_name) = self.mappings.get(name.as_str()) {
+                                    *name = new_name.to_string();
+                                }
+                            }
+                            items.iter_mut().skip(1).for_each(|item| self.apply(item));
+                        }
+                        (_, value) => self.apply(value),
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.apply(item)),
+            _ => {}
+        }
+    }
+}
+
+/// Names of Parameters, Conditions and Mappings a value depends on
+#[derive(Default)]
+struct Dependencies {
+    parameters: BTreeSet<String>,
+    conditions: BTreeSet<String>,
+    mappings: BTreeSet<String>,
+}
+
+impl Dependencies {
+    fn collect(&mut self, value: &Value, source_template: &Value) {
+        for id in reference_updater::collect_references(value) {
+            if source_template["Parameters"].get(&id).is_some() {
+                self.parameters.insert(id);
+            }
+        }
+        self.collect_names(value);
+    }
+
+    fn collect_names(&mut self, value: &Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    match (key.as_str(), value) {
+                        ("Condition", Value::String(name)) => {
+                            self.conditions.insert(name.to_string());
+                        }
+                        ("Fn::If", Value::Array(items))
+                        | ("Fn::FindInMap", Value::Array(items)) => {
+                            if let Some(Value::String(name)) = items.first() {
+                                match key.as_str() {
+                                    "Fn::If" => self.conditions.insert(name.to_string()),
+                                    _ => self.mappings.insert(name.to_string()),
+                                };
+                            }
+                            items
+                                .iter()
+                                .skip(1)
+                                .for_each(|item| self.collect_names(item));
+                        }
+                        (_, value) => self.collect_names(value),
+                    }
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|item| self.collect_names(item)),
+            _ => {}
+        }
+    }
+}
+
+/// Copies the Parameters, Conditions and Mappings the resources depend on into the target
+/// template. Entries which are structurally identical to an existing target entry are reused,
+/// Conditions and Mappings even if they exist under a different name. Entries with the same name
+/// but a different definition are a conflict.
+fn copy_dependencies(
+    target_template: &mut Value,
+    source_template: &Value,
+    resources: &[&Value],
+) -> Result<Renames, String> {
+    let mut dependencies = Dependencies::default();
+    resources
+        .iter()
+        .for_each(|resource| dependencies.collect(resource, source_template));
+
+    // conditions can depend on other conditions, parameters and mappings
+    let mut pending = dependencies.conditions.iter().cloned().collect::<Vec<_>>();
+    while let Some(name) = pending.pop() {
+        if let Some(condition) = source_template["Conditions"].get(&name) {
+            let known = dependencies.conditions.clone();
+            dependencies.collect(condition, source_template);
+            pending.extend(dependencies.conditions.difference(&known).cloned());
+        }
+    }
+
+    let mut renames = Renames::default();
+
+    for name in &dependencies.mappings {
+        if let Some(new_name) =
+            copy_entry(target_template, source_template, "Mappings", name, true)?
+        {
+            renames.mappings.insert(name.to_string(), new_name);
+        }
+    }
+
+    for name in &dependencies.parameters {
+        copy_entry(target_template, source_template, "Parameters", name, false)?;
+    }
+
+    // conditions are compared after renames of their own dependencies have been applied, so
+    // they need to be copied in order of their dependencies
+    let mut copied = BTreeSet::new();
+    while copied.len() < dependencies.conditions.len() {
+        let ready = dependencies
+            .conditions
+            .iter()
+            .filter(|name| !copied.contains(*name))
+            .find(|name| {
+                let mut nested = Dependencies::default();
+                nested.collect_names(&source_template["Conditions"][name.as_str()]);
+                nested
+                    .conditions
+                    .iter()
+                    .all(|n| copied.contains(n) || n == *name)
+            })
+            .ok_or("Conditions of the source template reference each other in a cycle")?
+            .to_string();
+
+        let mut source = source_template.clone();
+        if let Some(condition) = source["Conditions"].get_mut(&ready) {
+            renames.apply(condition);
+        }
+        if let Some(new_name) = copy_entry(target_template, &source, "Conditions", &ready, true)? {
+            renames.conditions.insert(ready.clone(), new_name);
+        }
+        copied.insert(ready);
+    }
+
+    Ok(renames)
+}
+
+/// Copies a single entry of a template section. Returns the name of an identical target entry
+/// with a different name, if `reuse_by_value` is set and such an entry exists.
+fn copy_entry(
+    target_template: &mut Value,
+    source_template: &Value,
+    section: &str,
+    name: &str,
+    reuse_by_value: bool,
+) -> Result<Option<String>, String> {
+    let Some(definition) = source_template[section].get(name) else {
+        return Ok(None);
+    };
+
+    if target_template.get(section).is_none() {
+        target_template[section] = Value::Object(Default::default());
+    }
+    let entries = target_template[section].as_object_mut().ok_or_else(|| {
+        format!(
+            "{} section of the target template is not an object",
+            section
+        )
+    })?;
+
+    match entries.get(name) {
+        Some(existing) if existing == definition => return Ok(None),
+        Some(_) => {
+            return Err(format!(
+                "{} {} exists in the target template with a different definition",
+                section, name
+            ))
+        }
+        None => {}
+    }
+
+    if reuse_by_value {
+        if let Some((existing_name, _)) = entries.iter().find(|(_, e)| *e == definition) {
+            return Ok(Some(existing_name.to_string()));
+        }
+    }
+
+    entries.insert(name.to_string(), definition.clone());
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn id_map(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(old_id, new_id)| (old_id.to_string(), new_id.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn case_only_rename_within_a_stack() {
+        let template = json!({
+            "Resources": {
+                "mybucket": {"Type": "AWS::S3::Bucket"},
+                "Queue": {"Type": "AWS::SQS::Queue"},
+            }
+        });
+        let id_map = id_map(&[("mybucket", "MyBucket")]);
+        let removed = remove_resources(template.clone(), vec!["mybucket".to_string()]);
+
+        let (import, final_template) = add_resources(removed, template, id_map).unwrap();
+
+        assert_eq!(
+            import["Resources"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["Queue", "MyBucket"]
+        );
+        // every resource to import needs a DeletionPolicy
+        assert_eq!(import["Resources"]["MyBucket"]["DeletionPolicy"], "Delete");
+        assert_eq!(
+            final_template["Resources"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["Queue", "MyBucket"]
+        );
+        assert_eq!(
+            final_template["Resources"]["MyBucket"],
+            json!({"Type": "AWS::S3::Bucket"})
+        );
+    }
+
+    #[test]
+    fn add_resources_renames_sub_based_export_names_of_moved_outputs() {
+        let source = json!({
+            "Resources": {
+                "Bucket": {"Type": "AWS::S3::Bucket"},
+                "Queue": {"Type": "AWS::SQS::Queue"},
+                "Topic": {"Type": "AWS::SNS::Topic"},
+            },
+            "Outputs": {
+                "BucketName": {
+                    "Value": {"Ref": "Bucket"},
+                    "Export": {"Name": {"Fn::Sub": "${AWS::StackName}-${Bucket}"}},
+                },
+                "Endpoints": {
+                    "Value": {"Fn::Sub": "${Bucket.Arn},${Queue.Arn}"},
+                    "Export": {
+                        "Name": {
+                            "Fn::Sub": ["${Prefix}-${Queue}", {"Prefix": {"Fn::GetAtt": ["Bucket", "DomainName"]}}]
+                        }
+                    },
+                },
+                "TopicArn": {"Value": {"Ref": "Topic"}},
+            },
+        });
+        let target = json!({"Resources": {"Function": {"Type": "AWS::Lambda::Function"}}});
+
+        let (import, final_template) = add_resources(
+            target,
+            source,
+            id_map(&[("Bucket", "Logs"), ("Queue", "Jobs")]),
+        )
+        .unwrap();
+
+        // Outputs are only added after the import, which must not change anything else
+        assert!(import.get("Outputs").is_none());
+        assert_eq!(
+            final_template["Outputs"],
+            json!({
+                "BucketName": {
+                    "Value": {"Ref": "Logs"},
+                    "Export": {"Name": {"Fn::Sub": "${AWS::StackName}-${Logs}"}},
+                },
+                "Endpoints": {
+                    "Value": {"Fn::Sub": "${Logs.Arn},${Jobs.Arn}"},
+                    "Export": {
+                        "Name": {
+                            "Fn::Sub": ["${Prefix}-${Jobs}", {"Prefix": {"Fn::GetAtt": ["Logs", "DomainName"]}}]
+                        }
+                    },
+                },
+            })
+        );
+    }
+}
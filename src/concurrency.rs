@@ -0,0 +1,205 @@
+use aws_sdk_cloudformation as cloudformation;
+use aws_sdk_cloudformation::error::ProvideErrorMetadata;
+use aws_sdk_cloudformation::types::{
+    Capability, ChangeSetStatus, Parameter, ResourceToImport, StackEvent, StackResourceSummary,
+    StackStatus, StackSummary,
+};
+use cfn_teleport::stack_api::{ApiResult, StackApi};
+use serde_json::Value;
+use std::error::Error;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::{spinner, timing};
+
+/// Number of attempts of a stack operation refused because of the concurrency limit
+const MAX_ATTEMPTS: u32 = 8;
+
+/// Seconds to wait after the first refusal, doubled after every further one
+const INITIAL_DELAY: u64 = 5;
+
+/// Upper bound of the seconds to wait between two attempts
+const MAX_DELAY: u64 = 120;
+
+/// Held while a stack operation is started, so the operations of one run, e.g. of the migrations
+/// of a batch, never compete with each other for the limit of the account
+static OPERATIONS: Mutex<()> = Mutex::const_new(());
+
+/// Whether CloudFormation refused an operation because the account has too many stack operations
+/// in progress. CloudFormation reports this as `LimitExceededException` mentioning concurrent
+/// operations, which unlike other quotas clears up once operations finish. `Throttling` is the
+/// request rate limit of the API, which the retry config of the SDK takes care of.
+pub fn is_concurrency_limit(err: &(dyn Error + 'static)) -> bool {
+    let Some(err) = err.downcast_ref::<cloudformation::Error>() else {
+        return false;
+    };
+    err.code() == Some("LimitExceededException")
+        && err
+            .message()
+            .is_some_and(|message| message.to_lowercase().contains("concurrent"))
+}
+
+/// Starts a stack operation, one at a time per run. While CloudFormation refuses it because of the
+/// concurrency limit of the account, the operation is retried with exponential backoff, telling
+/// what it waits for, instead of failing the migration with the raw error.
+pub async fn serialized<T, F, Fut>(description: &str, mut operation: F) -> ApiResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ApiResult<T>>,
+{
+    let _guard = OPERATIONS.lock().await;
+    let mut delay = INITIAL_DELAY;
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(err) if attempt < MAX_ATTEMPTS && is_concurrency_limit(err.as_ref()) => {
+                tracing::debug!(attempt, delay, error = %err, "Concurrency limit of CloudFormation");
+                if !spinner::is_quiet() {
+                    println!(
+                        "CloudFormation is busy with other stack operations in this account, retrying to {} in {} (attempt {} of {})",
+                        description,
+                        timing::format_duration(Duration::from_secs(delay)),
+                        attempt + 1,
+                        MAX_ATTEMPTS
+                    );
+                }
+                tokio::time::sleep(Duration::from_secs(delay)).await;
+                delay = (delay * 2).min(MAX_DELAY);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The SDK client, starting stack operations through [`serialized`]
+pub struct Serialized<'a>(pub &'a cloudformation::Client);
+
+impl StackApi for Serialized<'_> {
+    async fn list_stacks(&self) -> ApiResult<Vec<StackSummary>> {
+        StackApi::list_stacks(self.0).await
+    }
+
+    async fn list_stack_resources(
+        &self,
+        stack_name: &str,
+        next_token: Option<String>,
+    ) -> ApiResult<(Vec<StackResourceSummary>, Option<String>)> {
+        StackApi::list_stack_resources(self.0, stack_name, next_token).await
+    }
+
+    async fn get_template(&self, stack_name: &str) -> ApiResult<Value> {
+        StackApi::get_template(self.0, stack_name).await
+    }
+
+    async fn stack_parameters(&self, stack_name: &str) -> ApiResult<Vec<Parameter>> {
+        StackApi::stack_parameters(self.0, stack_name).await
+    }
+
+    async fn update_stack(
+        &self,
+        stack_name: &str,
+        template: &Value,
+        parameters: &[Parameter],
+        capabilities: &[Capability],
+    ) -> ApiResult<()> {
+        serialized(&format!("update stack {}", stack_name), || {
+            StackApi::update_stack(self.0, stack_name, template, parameters, capabilities)
+        })
+        .await
+    }
+
+    async fn create_import_changeset(
+        &self,
+        stack_name: &str,
+        changeset_name: &str,
+        template: &Value,
+        parameters: &[Parameter],
+        resources: &[ResourceToImport],
+        capabilities: &[Capability],
+    ) -> ApiResult<String> {
+        serialized(&format!("create changeset {}", changeset_name), || {
+            StackApi::create_import_changeset(
+                self.0,
+                stack_name,
+                changeset_name,
+                template,
+                parameters,
+                resources,
+                capabilities,
+            )
+        })
+        .await
+    }
+
+    async fn changeset_status(
+        &self,
+        stack_name: &str,
+        changeset_name: &str,
+    ) -> ApiResult<(ChangeSetStatus, Option<String>)> {
+        StackApi::changeset_status(self.0, stack_name, changeset_name).await
+    }
+
+    async fn execute_changeset(&self, stack_name: &str, changeset_name: &str) -> ApiResult<()> {
+        serialized(&format!("execute changeset {}", changeset_name), || {
+            StackApi::execute_changeset(self.0, stack_name, changeset_name)
+        })
+        .await
+    }
+
+    async fn stack_status(&self, stack_name: &str) -> ApiResult<(StackStatus, Option<String>)> {
+        StackApi::stack_status(self.0, stack_name).await
+    }
+
+    async fn describe_stack_events(
+        &self,
+        stack_name: &str,
+        next_token: Option<String>,
+    ) -> ApiResult<(Vec<StackEvent>, Option<String>)> {
+        StackApi::describe_stack_events(self.0, stack_name, next_token).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_cloudformation::error::ErrorMetadata;
+    use aws_sdk_cloudformation::operation::update_stack::UpdateStackError;
+
+    fn error(code: &str, message: &str) -> cloudformation::Error {
+        UpdateStackError::generic(ErrorMetadata::builder().code(code).message(message).build())
+            .into()
+    }
+
+    #[test]
+    fn detects_the_concurrent_operations_limit() {
+        let err = error(
+            "LimitExceededException",
+            "Exceeded the limit of concurrent stack operations",
+        );
+        assert!(is_concurrency_limit(&err));
+    }
+
+    #[test]
+    fn ignores_rate_throttling() {
+        assert!(!is_concurrency_limit(&error("Throttling", "Rate exceeded")));
+        assert!(!is_concurrency_limit(&error(
+            "ThrottlingException",
+            "Rate exceeded"
+        )));
+    }
+
+    #[test]
+    fn ignores_other_limits_and_errors() {
+        assert!(!is_concurrency_limit(&error(
+            "LimitExceededException",
+            "Limit on the number of stacks has been exceeded"
+        )));
+        assert!(!is_concurrency_limit(&error(
+            "ValidationError",
+            "No updates are to be performed."
+        )));
+        assert!(!is_concurrency_limit(&std::io::Error::other("concurrent")));
+    }
+}
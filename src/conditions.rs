@@ -0,0 +1,190 @@
+//! Evaluation of template `Conditions` against concrete parameter values.
+//!
+//! Supports the condition functions (`Fn::Equals`, `Fn::And`, `Fn::Or`, `Fn::Not`, `Condition`) and
+//! the intrinsic functions allowed inside of them which can be resolved without AWS access (`Ref`
+//! to parameters and pseudo parameters, `Fn::FindInMap`, `Fn::Join`, `Fn::Select`, `Fn::Split`).
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Evaluates the condition `name` of a template. `parameters` holds parameter values as well as
+/// pseudo parameters like `AWS::Region`. Parameters without a value fall back to their default.
+pub fn evaluate_condition(
+    template: &Value,
+    name: &str,
+    parameters: &HashMap<String, String>,
+) -> Result<bool, String> {
+    let evaluator = Evaluator {
+        template,
+        parameters,
+        depth: 0,
+    };
+    evaluator.condition(name)
+}
+
+struct Evaluator<'a> {
+    template: &'a Value,
+    parameters: &'a HashMap<String, String>,
+    depth: usize,
+}
+
+impl Evaluator<'_> {
+    fn nested(&self) -> Result<Self, String> {
+        // conditions referencing each other in a cycle are invalid, but must not hang the tool
+        if self.depth > 64 {
+            return Err("Conditions are nested too deeply".to_string());
+        }
+        Ok(Evaluator {
+            template: self.template,
+            parameters: self.parameters,
+            depth: self.depth + 1,
+        })
+    }
+
+    fn condition(&self, name: &str) -> Result<bool, String> {
+        let expression = self
+            .template
+            .get("Conditions")
+            .and_then(|conditions| conditions.get(name))
+            .ok_or_else(|| format!("Condition {} is not defined", name))?;
+        self.nested()?.boolean(expression)
+    }
+
+    fn boolean(&self, expression: &Value) -> Result<bool, String> {
+        let (function, argument) = single_entry(expression)
+            .ok_or_else(|| format!("Unsupported condition expression: {}", expression))?;
+
+        match function {
+            "Fn::Equals" => {
+                let arguments = arguments(argument, 2, function)?;
+                Ok(self.scalar(&arguments[0])? == self.scalar(&arguments[1])?)
+            }
+            "Fn::Not" => Ok(!self.boolean(&arguments(argument, 1, function)?[0])?),
+            "Fn::And" => {
+                for argument in argument.as_array().ok_or("Fn::And expects a list")? {
+                    if !self.boolean(argument)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            "Fn::Or" => {
+                for argument in argument.as_array().ok_or("Fn::Or expects a list")? {
+                    if self.boolean(argument)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            "Condition" => self.condition(argument.as_str().ok_or("Condition expects a name")?),
+            _ => Err(format!("Unsupported condition function {}", function)),
+        }
+    }
+
+    fn scalar(&self, value: &Value) -> Result<String, String> {
+        match self.resolve(value)? {
+            Value::String(value) => Ok(value),
+            Value::Number(value) => Ok(value.to_string()),
+            Value::Bool(value) => Ok(value.to_string()),
+            value => Err(format!("Expected a single value, got {}", value)),
+        }
+    }
+
+    fn resolve(&self, value: &Value) -> Result<Value, String> {
+        let Some((function, argument)) = single_entry(value) else {
+            return Ok(value.clone());
+        };
+
+        match function {
+            "Ref" => {
+                let name = argument.as_str().ok_or("Ref expects a name")?;
+                self.parameter(name)
+            }
+            "Fn::FindInMap" => {
+                let arguments = arguments(argument, 3, function)?;
+                let keys = arguments
+                    .iter()
+                    .map(|argument| self.scalar(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.template
+                    .get("Mappings")
+                    .and_then(|mappings| mappings.get(&keys[0]))
+                    .and_then(|mapping| mapping.get(&keys[1]))
+                    .and_then(|mapping| mapping.get(&keys[2]))
+                    .cloned()
+                    .ok_or_else(|| format!("Mapping {} not found", keys.join(".")))
+            }
+            "Fn::Join" => {
+                let arguments = arguments(argument, 2, function)?;
+                let delimiter = self.scalar(&arguments[0])?;
+                let items = self.list(&arguments[1])?;
+                Ok(Value::String(items.join(&delimiter)))
+            }
+            "Fn::Select" => {
+                let arguments = arguments(argument, 2, function)?;
+                let index = self
+                    .scalar(&arguments[0])?
+                    .parse::<usize>()
+                    .map_err(|err| err.to_string())?;
+                self.list(&arguments[1])?
+                    .get(index)
+                    .map(|item| Value::String(item.to_string()))
+                    .ok_or_else(|| format!("Fn::Select index {} out of range", index))
+            }
+            "Fn::Split" => {
+                let arguments = arguments(argument, 2, function)?;
+                let delimiter = self.scalar(&arguments[0])?;
+                let value = self.scalar(&arguments[1])?;
+                Ok(Value::Array(
+                    value
+                        .split(delimiter.as_str())
+                        .map(|item| Value::String(item.to_string()))
+                        .collect(),
+                ))
+            }
+            _ => Err(format!(
+                "Unable to evaluate {} without AWS access",
+                function
+            )),
+        }
+    }
+
+    fn list(&self, value: &Value) -> Result<Vec<String>, String> {
+        match self.resolve(value)? {
+            Value::Array(items) => items.iter().map(|item| self.scalar(item)).collect(),
+            Value::String(value) => Ok(value.split(',').map(str::to_string).collect()),
+            value => Err(format!("Expected a list, got {}", value)),
+        }
+    }
+
+    fn parameter(&self, name: &str) -> Result<Value, String> {
+        if let Some(value) = self.parameters.get(name) {
+            return Ok(Value::String(value.to_string()));
+        }
+
+        let default = self
+            .template
+            .get("Parameters")
+            .and_then(|parameters| parameters.get(name))
+            .and_then(|parameter| parameter.get("Default"));
+
+        match default {
+            Some(default) => Ok(default.clone()),
+            None => Err(format!("No value for parameter {}", name)),
+        }
+    }
+}
+
+fn single_entry(value: &Value) -> Option<(&str, &Value)> {
+    match value.as_object() {
+        Some(map) if map.len() == 1 => map.iter().next().map(|(k, v)| (k.as_str(), v)),
+        _ => None,
+    }
+}
+
+fn arguments<'a>(value: &'a Value, count: usize, function: &str) -> Result<&'a [Value], String> {
+    match value.as_array() {
+        Some(items) if items.len() == count => Ok(items),
+        _ => Err(format!("{} expects {} arguments", function, count)),
+    }
+}
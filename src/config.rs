@@ -0,0 +1,191 @@
+use cfn_teleport::attributes::Treatment;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+/// Environment variable to override the default location of the config file
+pub const CONFIG_ENV: &str = "CFN_TELEPORT_CONFIG";
+
+/// Settings read from `~/.cfn-teleport/config.yaml`
+///
+/// ```yaml
+/// routing:
+///   - resource_type: AWS::DynamoDB::Table
+///     source_tags:
+///       team: payments
+///     target: payments-data
+/// attributes:
+///   Metadata: strip
+///   Condition: ask
+/// confirmation:
+///   - environment: prod
+///     level: interactive
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Rules to pre-select the target stack, the first matching rule wins
+    #[serde(default)]
+    pub routing: Vec<RoutingRule>,
+
+    /// Treatment of resource attributes when resources are copied into the target stack
+    #[serde(default)]
+    pub attributes: BTreeMap<String, Treatment>,
+
+    /// How a migration has to be confirmed, per environment or stack
+    #[serde(default)]
+    pub confirmation: Vec<ConfirmationRule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoutingRule {
+    /// Type of the moved resources, `*` matches any sequence of characters, e.g. `AWS::S3::*`
+    pub resource_type: Option<String>,
+
+    /// Name of the source stack, `*` matches any sequence of characters
+    pub source_stack: Option<String>,
+
+    /// Tags the source stack must have
+    #[serde(default)]
+    pub source_tags: HashMap<String, String>,
+
+    /// Name of the target stack
+    pub target: String,
+}
+
+/// How a migration is confirmed before any stack is changed, from the least to the most strict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfirmationLevel {
+    /// The summary is printed and the migration starts without asking
+    None,
+
+    /// The summary is confirmed with y/N, `--yes` skips the confirmation
+    SummaryOnly,
+
+    /// The name of the target stack has to be typed, also with `--yes`
+    Interactive,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfirmationRule {
+    /// Environment of the source or target stack, `*` matches any sequence of characters
+    pub environment: Option<String>,
+
+    /// Name of the source or target stack, `*` matches any sequence of characters
+    pub stack: Option<String>,
+
+    pub level: ConfirmationLevel,
+}
+
+impl Config {
+    /// Reads the config file from `path` or `~/.cfn-teleport/config.yaml`. A missing file at the
+    /// default location is not an error.
+    pub fn load(path: Option<PathBuf>) -> Result<Self, Box<dyn Error>> {
+        let path = match path {
+            Some(path) => path,
+            None => {
+                let path = default_path();
+                if !path.exists() {
+                    return Ok(Self::default());
+                }
+                path
+            }
+        };
+
+        let body = fs::read_to_string(&path)
+            .map_err(|err| format!("Unable to read config {}: {}", path.display(), err))?;
+        let config = serde_yaml::from_str(&body)
+            .map_err(|err| format!("Invalid config {}: {}", path.display(), err))?;
+        Ok(config)
+    }
+
+    /// Finds the target stack for moving resources of the given types out of a stack
+    pub fn route(
+        &self,
+        source_stack: &str,
+        source_tags: &HashMap<String, String>,
+        resource_types: &[&str],
+    ) -> Option<&str> {
+        self.routing
+            .iter()
+            .find(|rule| {
+                rule.source_stack
+                    .as_deref()
+                    .is_none_or(|pattern| matches_pattern(pattern, source_stack))
+                    && rule
+                        .source_tags
+                        .iter()
+                        .all(|(key, value)| source_tags.get(key) == Some(value))
+                    && rule.resource_type.as_deref().is_none_or(|pattern| {
+                        resource_types
+                            .iter()
+                            .all(|resource_type| matches_pattern(pattern, resource_type))
+                    })
+            })
+            .map(|rule| rule.target.as_str())
+    }
+
+    /// The confirmation level of a migration between stacks, given with their environment. The
+    /// first rule matching a stack applies to it, the stricter level of both stacks wins. Without a
+    /// matching rule, the summary is confirmed.
+    pub fn confirmation_level(&self, stacks: [(&str, Option<&str>); 2]) -> ConfirmationLevel {
+        stacks
+            .iter()
+            .map(|(stack_name, environment)| {
+                self.confirmation
+                    .iter()
+                    .find(|rule| {
+                        rule.stack
+                            .as_deref()
+                            .is_none_or(|pattern| matches_pattern(pattern, stack_name))
+                            && rule.environment.as_deref().is_none_or(|pattern| {
+                                environment.is_some_and(|env| matches_pattern(pattern, env))
+                            })
+                    })
+                    .map_or(ConfirmationLevel::SummaryOnly, |rule| rule.level)
+            })
+            .max()
+            .unwrap_or(ConfirmationLevel::SummaryOnly)
+    }
+}
+
+fn default_path() -> PathBuf {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".cfn-teleport")
+        .join("config.yaml")
+}
+
+/// Matches a value against a pattern in which `*` stands for any sequence of characters
+pub fn matches_pattern(pattern: &str, value: &str) -> bool {
+    let parts = pattern.split('*').collect::<Vec<_>>();
+    if parts.len() == 1 {
+        return pattern == value;
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !value.starts_with(first) || value.len() < first.len() + last.len() {
+        return false;
+    }
+
+    let mut rest = &value[first.len()..value.len() - last.len()];
+    if !value.ends_with(last) {
+        return false;
+    }
+
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    true
+}
@@ -0,0 +1,242 @@
+//! Read-only analysis of how entangled two stacks are, to judge whether consolidating or splitting
+//! them is feasible before planning individual moves.
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Categories of links, from the strongest to the weakest
+pub static CATEGORIES: [&str; 4] = [
+    "Exports and imports",
+    "Shared physical resources",
+    "Hardcoded physical IDs",
+    "Common parameters",
+];
+
+/// Deployed state of a stack, as far as it matters for coupling
+#[derive(Debug, Clone, Default)]
+pub struct StackSnapshot {
+    pub name: String,
+    pub template: Value,
+
+    /// Logical IDs mapped to physical IDs
+    pub resources: BTreeMap<String, String>,
+
+    /// Parameter names mapped to their deployed values
+    pub parameters: BTreeMap<String, String>,
+
+    /// Exports of the stack, with the stacks importing them
+    pub exports: Vec<Export>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Export {
+    pub name: String,
+    pub value: String,
+    pub importers: Vec<String>,
+}
+
+/// A single shared reference between two stacks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Link {
+    /// One stack imports an export of the other stack
+    Import {
+        exporter: String,
+        importer: String,
+        export_name: String,
+    },
+
+    /// Both stacks manage a resource with the same physical ID
+    SharedResource {
+        physical_id: String,
+        logical_ids: (String, String),
+    },
+
+    /// A template or parameter value of one stack contains the physical ID of a resource of the
+    /// other stack, instead of importing it
+    HardcodedId {
+        stack: String,
+        location: String,
+        owner: String,
+        logical_id: String,
+    },
+
+    /// Both stacks have a parameter with the same name
+    CommonParameter { name: String, same_value: bool },
+}
+
+impl Link {
+    /// How much the link constrains changes to the stacks. Imports and shared resources block
+    /// updates and deletions, hardcoded IDs break silently when the resource is replaced and common
+    /// parameters only hint at a shared configuration.
+    pub fn weight(&self) -> usize {
+        match self {
+            Link::Import { .. } | Link::SharedResource { .. } => 3,
+            Link::HardcodedId { .. } => 2,
+            Link::CommonParameter { .. } => 1,
+        }
+    }
+
+    pub fn category(&self) -> &'static str {
+        match self {
+            Link::Import { .. } => CATEGORIES[0],
+            Link::SharedResource { .. } => CATEGORIES[1],
+            Link::HardcodedId { .. } => CATEGORIES[2],
+            Link::CommonParameter { .. } => CATEGORIES[3],
+        }
+    }
+}
+
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Link::Import {
+                exporter,
+                importer,
+                export_name,
+            } => write!(f, "{} imports {} from {}", importer, export_name, exporter),
+            Link::SharedResource {
+                physical_id,
+                logical_ids,
+            } => write!(
+                f,
+                "{} is managed as {} and as {}",
+                physical_id, logical_ids.0, logical_ids.1
+            ),
+            Link::HardcodedId {
+                stack,
+                location,
+                owner,
+                logical_id,
+            } => write!(
+                f,
+                "{} {} refers to {}.{}",
+                stack, location, owner, logical_id
+            ),
+            Link::CommonParameter { name, same_value } => write!(
+                f,
+                "{} ({})",
+                name,
+                if *same_value {
+                    "same value"
+                } else {
+                    "different values"
+                }
+            ),
+        }
+    }
+}
+
+/// Finds all shared references between two stacks
+pub fn analyze(a: &StackSnapshot, b: &StackSnapshot) -> Vec<Link> {
+    let mut links = Vec::new();
+
+    for (exporter, importer) in [(a, b), (b, a)] {
+        for export in &exporter.exports {
+            if export.importers.contains(&importer.name) {
+                links.push(Link::Import {
+                    exporter: exporter.name.clone(),
+                    importer: importer.name.clone(),
+                    export_name: export.name.clone(),
+                });
+            }
+        }
+    }
+
+    for (logical_id_a, physical_id) in &a.resources {
+        for (logical_id_b, _) in b.resources.iter().filter(|(_, id)| *id == physical_id) {
+            links.push(Link::SharedResource {
+                physical_id: physical_id.clone(),
+                logical_ids: (
+                    format!("{}.{}", a.name, logical_id_a),
+                    format!("{}.{}", b.name, logical_id_b),
+                ),
+            });
+        }
+    }
+
+    for (stack, owner) in [(a, b), (b, a)] {
+        let mut values = Vec::new();
+        for section in ["Resources", "Outputs"] {
+            collect_strings(&stack.template[section], section, &mut values);
+        }
+        values.extend(
+            stack
+                .parameters
+                .iter()
+                .map(|(name, value)| (format!("Parameters.{}", name), value.clone())),
+        );
+
+        for (location, value) in values {
+            if let Some((logical_id, _)) = owner
+                .resources
+                .iter()
+                .find(|(_, physical_id)| refers_to(&value, physical_id))
+            {
+                links.push(Link::HardcodedId {
+                    stack: stack.name.clone(),
+                    location,
+                    owner: owner.name.clone(),
+                    logical_id: logical_id.clone(),
+                });
+            }
+        }
+    }
+
+    let parameters_b = b.template["Parameters"].as_object();
+    for name in a.template["Parameters"]
+        .as_object()
+        .into_iter()
+        .flat_map(|p| p.keys())
+    {
+        if parameters_b.is_some_and(|parameters| parameters.contains_key(name)) {
+            links.push(Link::CommonParameter {
+                name: name.clone(),
+                same_value: a.parameters.get(name) == b.parameters.get(name),
+            });
+        }
+    }
+
+    links
+}
+
+/// Sum of the weights of all links, 0 for independent stacks
+pub fn score(links: &[Link]) -> usize {
+    links.iter().map(Link::weight).sum()
+}
+
+/// Classification of a coupling score
+pub fn level(score: usize) -> &'static str {
+    match score {
+        0 => "independent",
+        1..=5 => "loosely coupled",
+        6..=15 => "moderately coupled",
+        _ => "tightly coupled",
+    }
+}
+
+/// Whether a value is a physical ID or an ARN ending in it. Physical IDs shorter than 8 characters
+/// are too generic to be recognized reliably.
+fn refers_to(value: &str, physical_id: &str) -> bool {
+    physical_id.len() >= 8
+        && (value == physical_id
+            || value.ends_with(&format!(":{}", physical_id))
+            || value.ends_with(&format!("/{}", physical_id)))
+}
+
+fn collect_strings(value: &Value, path: &str, strings: &mut Vec<(String, String)>) {
+    match value {
+        Value::String(string) => strings.push((path.to_string(), string.clone())),
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_strings(item, &format!("{}[{}]", path, index), strings);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map {
+                collect_strings(item, &format!("{}.{}", path, key), strings);
+            }
+        }
+        _ => {}
+    }
+}
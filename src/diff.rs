@@ -0,0 +1,152 @@
+//! Semantic differences between two templates.
+//!
+//! Templates are compared per section and entry, e.g. `Resources.Bucket.Properties.BucketName`,
+//! after normalizing notations CloudFormation treats as equivalent: the string and list form of
+//! `Fn::GetAtt`, `Fn::Sub` without variables, a single `DependsOn` and scalars given as numbers,
+//! booleans or strings.
+
+use serde_json::{Map, Value};
+use std::fmt;
+
+use crate::reference_updater;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Modified {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Modified { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { path, .. } => write!(f, "+ {}", path),
+            Change::Removed { path, .. } => write!(f, "- {}", path),
+            Change::Modified { path, old, new } if is_scalar(old) && is_scalar(new) => {
+                write!(f, "~ {}: {} → {}", path, old, new)
+            }
+            Change::Modified { path, .. } => write!(f, "~ {}", path),
+        }
+    }
+}
+
+/// Lists the semantic changes from `old` to `new`. Entries of the sections are compared property
+/// by property, in the order of the sections in the templates.
+pub fn diff_templates(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    compare(&normalize(old), &normalize(new), "", &mut changes);
+    changes
+}
+
+/// Whether two templates are equivalent, i.e. differ only in notation
+pub fn equivalent(old: &Value, new: &Value) -> bool {
+    normalize(old) == normalize(new)
+}
+
+fn compare(old: &Value, new: &Value, path: &str, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) if !is_intrinsic(old) && !is_intrinsic(new) => {
+            for (key, old_value) in old {
+                let path = join(path, key);
+                match new.get(key) {
+                    Some(new_value) => compare(old_value, new_value, &path, changes),
+                    None => changes.push(Change::Removed {
+                        path,
+                        value: old_value.clone(),
+                    }),
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    changes.push(Change::Added {
+                        path: join(path, key),
+                        value: new_value.clone(),
+                    });
+                }
+            }
+        }
+        (old, new) if old != new => changes.push(Change::Modified {
+            path: path.to_string(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Rewrites equivalent notations into a single form
+fn normalize(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut normalized = Map::new();
+            for (key, value) in map {
+                let value = match (key.as_str(), value) {
+                    ("Fn::GetAtt", Value::String(expression)) => {
+                        match reference_updater::split_get_att(expression) {
+                            Some((id, attribute)) => Value::Array(vec![
+                                Value::String(id.to_string()),
+                                Value::String(attribute.to_string()),
+                            ]),
+                            None => value.clone(),
+                        }
+                    }
+                    ("Fn::Sub", Value::Array(items))
+                        if items.len() == 2
+                            && items[1].as_object().is_some_and(|vars| vars.is_empty()) =>
+                    {
+                        normalize(&items[0])
+                    }
+                    ("DependsOn", Value::String(id)) => {
+                        Value::Array(vec![Value::String(id.clone())])
+                    }
+                    _ => normalize(value),
+                };
+                normalized.insert(key.clone(), value);
+            }
+            Value::Object(normalized)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(normalize).collect()),
+        Value::Number(number) => Value::String(number.to_string()),
+        Value::Bool(boolean) => Value::String(boolean.to_string()),
+        _ => value.clone(),
+    }
+}
+
+fn is_intrinsic(map: &Map<String, Value>) -> bool {
+    map.len() == 1
+        && map
+            .keys()
+            .all(|key| key == "Ref" || key == "Condition" || key.starts_with("Fn::"))
+}
+
+fn is_scalar(value: &Value) -> bool {
+    !value.is_object() && !value.is_array()
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
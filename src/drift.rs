@@ -0,0 +1,106 @@
+use aws_sdk_cloudformation as cloudformation;
+use aws_sdk_cloudformation::types::{StackDriftDetectionStatus, StackResourceDriftStatus};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use crate::spinner;
+
+/// Minutes to wait for a drift detection to finish
+const DETECTION_TIMEOUT: u64 = 10;
+
+/// Result of a drift detection of a stack
+pub struct DriftReport {
+    /// Drift status of the stack, e.g. `IN_SYNC` or `DRIFTED`
+    pub status: String,
+
+    /// Drifted resources, as `LOGICAL_ID (TYPE): STATUS`
+    pub drifted: Vec<String>,
+}
+
+/// Detects the drift of a stack and waits for the result
+pub async fn detect(
+    client: &cloudformation::Client,
+    stack_name: &str,
+    spinner: &mut spinner::Spin,
+) -> Result<DriftReport, Box<dyn Error>> {
+    let detection_id = client
+        .detect_stack_drift()
+        .stack_name(stack_name)
+        .send()
+        .await?
+        .stack_drift_detection_id()
+        .ok_or("No drift detection ID returned")?
+        .to_string();
+
+    let started = Instant::now();
+    let status = loop {
+        let output = client
+            .describe_stack_drift_detection_status()
+            .stack_drift_detection_id(&detection_id)
+            .send()
+            .await?;
+        tracing::debug!(
+            stack = stack_name,
+            status = output.detection_status().map(|status| status.as_str()),
+            "Waiting for drift detection"
+        );
+        match output.detection_status() {
+            Some(StackDriftDetectionStatus::DetectionInProgress) => {}
+            Some(StackDriftDetectionStatus::DetectionFailed) => {
+                return Err(format!(
+                    "Drift detection of stack {} failed: {}",
+                    stack_name,
+                    output.detection_status_reason().unwrap_or_default()
+                )
+                .into())
+            }
+            _ => {
+                break output
+                    .stack_drift_status()
+                    .map_or("UNKNOWN", |status| status.as_str())
+                    .to_string()
+            }
+        }
+
+        if started.elapsed() > Duration::from_secs(DETECTION_TIMEOUT * 60) {
+            return Err(format!(
+                "Drift detection of stack {} did not finish within {} minutes",
+                stack_name, DETECTION_TIMEOUT
+            )
+            .into());
+        }
+        spinner.tick();
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    };
+
+    let mut drifted = Vec::new();
+    let mut token = None;
+    loop {
+        let output = client
+            .describe_stack_resource_drifts()
+            .stack_name(stack_name)
+            .set_stack_resource_drift_status_filters(Some(vec![
+                StackResourceDriftStatus::Modified,
+                StackResourceDriftStatus::Deleted,
+            ]))
+            .set_next_token(token)
+            .send()
+            .await?;
+        drifted.extend(output.stack_resource_drifts().iter().map(|drift| {
+            format!(
+                "{} ({}): {}",
+                drift.logical_resource_id().unwrap_or_default(),
+                drift.resource_type().unwrap_or_default(),
+                drift
+                    .stack_resource_drift_status()
+                    .map_or("UNKNOWN", |status| status.as_str())
+            )
+        }));
+        token = output.next_token().map(str::to_string);
+        if token.is_none() {
+            break;
+        }
+    }
+
+    Ok(DriftReport { status, drifted })
+}
@@ -0,0 +1,44 @@
+use aws_config::SdkConfig;
+use aws_sdk_secretsmanager as secretsmanager;
+use aws_sdk_ssm as ssm;
+use cfn_teleport::analysis::DynamicReference;
+use std::error::Error;
+
+/// Looks up the parameter or secret of a dynamic reference with the credentials and region of a
+/// configuration. Returns whether it exists, or an error if that cannot be told, e.g. because the
+/// name depends on `Fn::Sub` or the lookup is denied.
+pub async fn exists(
+    config: &SdkConfig,
+    reference: &DynamicReference,
+) -> Result<bool, Box<dyn Error>> {
+    if reference.is_substituted() {
+        return Err("the name depends on Fn::Sub".into());
+    }
+    match reference.service.as_str() {
+        "ssm" | "ssm-secure" => {
+            let result = ssm::Client::new(config)
+                .get_parameter()
+                .name(&reference.name)
+                .send()
+                .await;
+            match result.map_err(ssm::Error::from) {
+                Ok(_) => Ok(true),
+                Err(ssm::Error::ParameterNotFound(_)) => Ok(false),
+                Err(err) => Err(err.into()),
+            }
+        }
+        "secretsmanager" => {
+            let result = secretsmanager::Client::new(config)
+                .describe_secret()
+                .secret_id(&reference.name)
+                .send()
+                .await;
+            match result.map_err(secretsmanager::Error::from) {
+                Ok(_) => Ok(true),
+                Err(secretsmanager::Error::ResourceNotFoundException(_)) => Ok(false),
+                Err(err) => Err(err.into()),
+            }
+        }
+        service => Err(format!("unknown service {}", service).into()),
+    }
+}
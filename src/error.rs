@@ -0,0 +1,104 @@
+use cfn_teleport::waiter::DeadlineReached;
+use std::error::Error;
+use std::fmt;
+
+/// Process exit codes. These are part of the public interface of the tool and must not change,
+/// wrapper scripts rely on them to decide whether to retry, page someone or resume a migration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Everything went fine
+    Success = 0,
+
+    /// Unexpected error which does not fall into any other category
+    General = 1,
+
+    /// Input or templates did not pass validation, nothing has been changed
+    Validation = 2,
+
+    /// The user aborted the operation, nothing has been changed
+    Aborted = 3,
+
+    /// A call to the AWS API failed before any stack has been modified
+    AwsApi = 4,
+
+    /// The import changeset could not be created or failed to execute
+    ChangesetFailed = 5,
+
+    /// The source stack has been modified but the migration did not complete
+    PartialCompletion = 6,
+}
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// An error annotated with the exit code the process should terminate with
+#[derive(Debug)]
+pub struct Failure {
+    code: ExitCode,
+    source: Box<dyn Error>,
+}
+
+impl Failure {
+    pub fn new(code: ExitCode, source: impl Into<Box<dyn Error>>) -> Self {
+        Self {
+            code,
+            source: source.into(),
+        }
+    }
+
+    pub fn code(&self) -> ExitCode {
+        self.code
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Error for Failure {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.source()
+    }
+}
+
+/// Attaches an exit code to the error of a result. Errors which already carry an exit code keep it,
+/// so the most specific category always wins. Waiting which stopped at the deadline always leaves the
+/// migration partially completed.
+pub trait WithExitCode<T> {
+    fn exit_code(self, code: ExitCode) -> Result<T, Box<dyn Error>>;
+}
+
+impl<T, E: Into<Box<dyn Error>>> WithExitCode<T> for Result<T, E> {
+    fn exit_code(self, code: ExitCode) -> Result<T, Box<dyn Error>> {
+        self.map_err(|err| {
+            let err: Box<dyn Error> = err.into();
+            if err.is::<Failure>() {
+                err
+            } else if err.is::<DeadlineReached>() {
+                Box::new(Failure::new(ExitCode::PartialCompletion, err))
+            } else {
+                Box::new(Failure::new(code, err))
+            }
+        })
+    }
+}
+
+/// Creates an error with the given exit code from a message
+pub fn fail(code: ExitCode, message: impl Into<String>) -> Box<dyn Error> {
+    Box::new(Failure::new(code, message.into()))
+}
+
+/// Determines the exit code for an error. Errors without an explicit category exit with
+/// `ExitCode::General`.
+pub fn exit_code_of(err: &(dyn Error + 'static)) -> ExitCode {
+    match err.downcast_ref::<Failure>() {
+        Some(failure) => failure.code(),
+        None if err.is::<DeadlineReached>() => ExitCode::PartialCompletion,
+        None => ExitCode::General,
+    }
+}
@@ -0,0 +1,93 @@
+//! Migration records kept in the templates of the affected stacks.
+//!
+//! With annotations enabled, the source and the target stack each receive a compact record of the
+//! migration under the `cfn-teleport` key of their template `Metadata`, so operators looking only at
+//! a stack can tell that resources were moved in or out and where to find the artifacts of the run.
+//!
+//! ```yaml
+//! Metadata:
+//!   cfn-teleport:
+//!     - MigrationId: 2024-05-01T12-34-56Z-1a2b3c4d
+//!       Date: 2024-05-01T12:34:56Z
+//!       Direction: Out
+//!       CounterpartStack: Stack2
+//!       Resources:
+//!         Bucket21D68F7E8: NewBucket
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Key of the records in the template `Metadata`
+pub const METADATA_KEY: &str = "cfn-teleport";
+
+/// Number of records kept per stack, older records are dropped
+pub const MAX_RECORDS: usize = 20;
+
+/// Whether resources were moved out of or into the annotated stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Direction {
+    Out,
+    In,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Record {
+    pub migration_id: String,
+
+    /// Start of the migration, RFC 3339
+    pub date: String,
+
+    pub direction: Direction,
+
+    /// The stack the resources were moved to or came from
+    pub counterpart_stack: String,
+
+    /// Logical IDs in the source stack mapped to the logical IDs in the target stack
+    pub resources: BTreeMap<String, String>,
+
+    /// Location of the artifacts of the run, on the machine which executed it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifacts: Option<String>,
+}
+
+/// Appends a record to the `Metadata` of a template, keeping the latest `MAX_RECORDS` records
+pub fn annotate(template: &mut Value, record: &Record) -> Result<(), String> {
+    let template = template
+        .as_object_mut()
+        .ok_or("Unable to annotate the template, it is not an object")?;
+    let metadata = template
+        .entry("Metadata")
+        .or_insert_with(|| Value::Object(Default::default()))
+        .as_object_mut()
+        .ok_or("Unable to annotate the template, its Metadata is not an object")?;
+
+    let mut records = match metadata.get(METADATA_KEY) {
+        Some(Value::Array(records)) => records.clone(),
+        Some(_) => {
+            return Err(format!(
+                "Unable to annotate the template, Metadata.{} is not a list",
+                METADATA_KEY
+            ))
+        }
+        None => Vec::new(),
+    };
+    records.push(serde_json::to_value(record).map_err(|err| err.to_string())?);
+    let excess = records.len().saturating_sub(MAX_RECORDS);
+    records.drain(..excess);
+
+    metadata.insert(METADATA_KEY.to_string(), Value::Array(records));
+    Ok(())
+}
+
+/// Reads the records of a template, skipping entries which are not valid records
+pub fn records(template: &Value) -> Vec<Record> {
+    template["Metadata"][METADATA_KEY]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|record| serde_json::from_value(record.clone()).ok())
+        .collect()
+}
@@ -0,0 +1,63 @@
+use aws_sdk_cloudformation::config::SharedHttpClient;
+use aws_smithy_http_client::proxy::ProxyConfig;
+use aws_smithy_http_client::tls::{self, rustls_provider::CryptoMode, TlsContext, TrustStore};
+use aws_smithy_http_client::{Builder, ConnectorBuilder};
+use aws_smithy_runtime_api::client::orchestrator::HttpRequest;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Environment variable with the path of a CA bundle, as used by the AWS CLI
+pub const CA_BUNDLE_ENV: &str = "AWS_CA_BUNDLE";
+
+/// HTTPS client which trusts the certificates of a PEM bundle in addition to the native root
+/// certificates, e.g. of a proxy intercepting TLS. Like the default client, it sends requests
+/// through the proxies configured in `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY`.
+pub fn with_ca_bundle(path: &Path) -> Result<SharedHttpClient, Box<dyn Error>> {
+    let pem = fs::read(path)
+        .map_err(|err| format!("Unable to read CA bundle {}: {}", path.display(), err))?;
+    let tls_context = TlsContext::builder()
+        .with_trust_store(TrustStore::default().with_pem_certificate(pem))
+        .build()
+        .map_err(|err| format!("Invalid CA bundle {}: {}", path.display(), err))?;
+
+    Ok(https_client(tls_context))
+}
+
+/// HTTPS client like the default client of the SDK, which trusts the native root certificates
+pub fn default_client() -> SharedHttpClient {
+    https_client(TlsContext::default())
+}
+
+fn https_client(tls_context: TlsContext) -> SharedHttpClient {
+    Builder::new().build_with_connector_fn(move |settings, runtime_components| {
+        let mut connector = ConnectorBuilder::default()
+            .tls_provider(tls::Provider::Rustls(CryptoMode::AwsLc))
+            .tls_context(tls_context.clone());
+        connector.set_connector_settings(settings.cloned());
+        if let Some(components) = runtime_components {
+            connector.set_sleep_impl(components.sleep_impl());
+        }
+        connector.set_proxy_config(Some(ProxyConfig::from_env()));
+        connector.build()
+    })
+}
+
+/// Operation of an AWS API request, from the `X-Amz-Target` header of JSON protocols, e.g.
+/// `ListResources` of `CloudApiService.ListResources`, or the `Action` parameter of the query
+/// protocol, e.g. `DescribeStacks`. Requests for credentials, e.g. of the instance metadata
+/// service, have none.
+pub fn operation(request: &HttpRequest) -> Option<String> {
+    if let Some(target) = request.headers().get("x-amz-target") {
+        return Some(target.rsplit('.').next().unwrap_or(target).to_string());
+    }
+    request
+        .body()
+        .bytes()
+        .and_then(|body| std::str::from_utf8(body).ok())
+        .and_then(|body| {
+            body.split('&')
+                .find_map(|parameter| parameter.strip_prefix("Action="))
+        })
+        .map(str::to_string)
+}
@@ -0,0 +1,222 @@
+use aws_config::SdkConfig;
+use aws_sdk_cloudcontrol as cloudcontrol;
+use aws_sdk_iam as iam;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::error::{fail, ExitCode};
+
+/// Resolves the identifier properties a resource is imported with from its physical ID. The
+/// physical ID reported by CloudFormation is not always the identifier the import expects, e.g. an
+/// ARN where a name is expected, or only one part of a compound identifier. Types of IAM, Route 53
+/// and ECS are resolved by looking up the missing values, other types with a single identifier use
+/// the physical ID as it is.
+pub async fn resolve(
+    config: &SdkConfig,
+    (resource_type, logical_id, physical_id): (&str, &str, &str),
+    identifiers: &[String],
+) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+    let mut resolved = BTreeMap::new();
+
+    // compound identifiers of registry types are reported as their parts, joined by |
+    let parts = physical_id.split('|').collect::<Vec<_>>();
+    if identifiers.len() > 1 && parts.len() == identifiers.len() {
+        for (identifier, part) in identifiers.iter().zip(parts) {
+            resolved.insert(identifier.clone(), part.to_string());
+        }
+        return Ok(resolved);
+    }
+
+    for identifier in identifiers {
+        let single = identifiers.len() == 1;
+        let value = match resource_type.split("::").nth(1) {
+            Some("IAM") if single => {
+                iam_identifier(config, resource_type, identifier, physical_id).await?
+            }
+            Some("Route53") if single => {
+                route53_identifier(config, identifier, physical_id).await?
+            }
+            Some("ECS") => ecs_identifier(config, resource_type, identifier, physical_id).await?,
+            _ if single => Some(physical_id.to_string()),
+            _ => None,
+        };
+        let value = value.ok_or_else(|| {
+            fail(
+                ExitCode::Validation,
+                format!(
+                    "Unable to determine the identifier {} of {} ({}) from its physical ID {}, override it with --physical-id",
+                    identifier, logical_id, resource_type, physical_id
+                ),
+            )
+        })?;
+        resolved.insert(identifier.clone(), value);
+    }
+    Ok(resolved)
+}
+
+/// Whether a resource exists, looked up through the Cloud Control API with its physical ID. `None`
+/// if it cannot be told, e.g. for types without Cloud Control support or identifiers which differ
+/// from the physical ID.
+pub async fn exists(config: &SdkConfig, resource_type: &str, physical_id: &str) -> Option<bool> {
+    let result = cloudcontrol::Client::new(config)
+        .get_resource()
+        .type_name(resource_type)
+        .identifier(physical_id)
+        .send()
+        .await;
+    match result.map_err(cloudcontrol::Error::from) {
+        Ok(_) => Some(true),
+        Err(cloudcontrol::Error::ResourceNotFoundException(_)) => Some(false),
+        Err(_) => None,
+    }
+}
+
+/// Last segment of the resource of an ARN, which is the name of most resources, e.g. `MyRole` of
+/// `arn:aws:iam::123456789012:role/path/MyRole`
+fn name_of_arn(arn: &str) -> Option<&str> {
+    let resource = arn.strip_prefix("arn:")?.splitn(5, ':').nth(4)?;
+    resource.rsplit(['/', ':']).next()
+}
+
+/// IAM reports names as physical IDs, except for policies, providers and MFA devices, which are
+/// identified by their ARN. Managed policies are looked up by name if only the name is known.
+async fn iam_identifier(
+    config: &SdkConfig,
+    resource_type: &str,
+    identifier: &str,
+    physical_id: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let is_arn = physical_id.starts_with("arn:");
+    let wants_arn = identifier.ends_with("Arn") || identifier == "SerialNumber";
+    match (wants_arn, is_arn) {
+        (true, true) | (false, false) => Ok(Some(physical_id.to_string())),
+        (false, true) => Ok(name_of_arn(physical_id).map(str::to_string)),
+        (true, false) if resource_type == "AWS::IAM::ManagedPolicy" => {
+            managed_policy_arn(config, physical_id).await
+        }
+        (true, false) => Ok(None),
+    }
+}
+
+/// ARN of a customer managed policy of the account
+async fn managed_policy_arn(
+    config: &SdkConfig,
+    policy_name: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let client = iam::Client::new(config);
+    let mut marker = None;
+    loop {
+        let output = client
+            .list_policies()
+            .scope(iam::types::PolicyScopeType::Local)
+            .set_marker(marker)
+            .send()
+            .await
+            .map_err(iam::Error::from)?;
+        if let Some(policy) = output
+            .policies()
+            .iter()
+            .find(|policy| policy.policy_name() == Some(policy_name))
+        {
+            return Ok(policy.arn().map(str::to_string));
+        }
+        marker = output.marker().map(str::to_string);
+        if marker.is_none() {
+            return Ok(None);
+        }
+    }
+}
+
+/// Hosted zones are identified by their ID without the `/hostedzone/` prefix. Zones known only by
+/// their domain name are looked up.
+async fn route53_identifier(
+    config: &SdkConfig,
+    identifier: &str,
+    physical_id: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let id = physical_id
+        .strip_prefix("/hostedzone/")
+        .unwrap_or(physical_id);
+    if !matches!(identifier, "Id" | "HostedZoneId") || !id.contains('.') {
+        return Ok(Some(id.to_string()));
+    }
+
+    let domain = id.trim_end_matches('.');
+    let zones = list_resources(config, "AWS::Route53::HostedZone", None).await?;
+    let mut matching = zones.iter().filter(|(_, properties)| {
+        properties["Name"]
+            .as_str()
+            .map(|name| name.trim_end_matches('.'))
+            == Some(domain)
+    });
+    match (matching.next(), matching.next()) {
+        (Some((zone_id, _)), None) => Ok(Some(zone_id.clone())),
+        _ => Ok(None),
+    }
+}
+
+/// ECS services are reported by their ARN, but imported with their cluster as well. The cluster is
+/// part of the ARN in the current format and looked up for services with ARNs in the old format.
+async fn ecs_identifier(
+    config: &SdkConfig,
+    resource_type: &str,
+    identifier: &str,
+    physical_id: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+    if resource_type != "AWS::ECS::Service" || identifier != "Cluster" {
+        return Ok(Some(physical_id.to_string()));
+    }
+
+    // arn:aws:ecs:<region>:<account>:service/<cluster>/<service>
+    let resource = physical_id.splitn(6, ':').nth(5).unwrap_or_default();
+    if let ["service", cluster, _] = resource.split('/').collect::<Vec<_>>().as_slice() {
+        return Ok(Some(cluster.to_string()));
+    }
+
+    for (cluster, _) in list_resources(config, "AWS::ECS::Cluster", None).await? {
+        let model = serde_json::json!({ "Cluster": cluster }).to_string();
+        let services = list_resources(config, "AWS::ECS::Service", Some(model)).await?;
+        if services
+            .iter()
+            .any(|(id, _)| id.split('|').any(|part| part == physical_id))
+        {
+            return Ok(Some(cluster));
+        }
+    }
+    Ok(None)
+}
+
+/// Identifiers and properties of all resources of a type, listed through the Cloud Control API
+async fn list_resources(
+    config: &SdkConfig,
+    type_name: &str,
+    resource_model: Option<String>,
+) -> Result<Vec<(String, serde_json::Value)>, Box<dyn Error>> {
+    let client = cloudcontrol::Client::new(config);
+    let mut resources = Vec::new();
+    let mut token = None;
+    loop {
+        let output = client
+            .list_resources()
+            .type_name(type_name)
+            .set_resource_model(resource_model.clone())
+            .set_next_token(token)
+            .send()
+            .await
+            .map_err(cloudcontrol::Error::from)?;
+        for description in output.resource_descriptions() {
+            let properties = description
+                .properties()
+                .and_then(|properties| serde_json::from_str(properties).ok())
+                .unwrap_or_default();
+            resources.push((
+                description.identifier().unwrap_or_default().to_string(),
+                properties,
+            ));
+        }
+        token = output.next_token().map(str::to_string);
+        if token.is_none() {
+            return Ok(resources);
+        }
+    }
+}
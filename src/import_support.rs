@@ -0,0 +1,136 @@
+//! Resource types which support being imported into a stack. The list in
+//! `supported_resource_types.rs` is generated daily from the documentation of CloudFormation and
+//! indexed once, so every lookup takes constant time, however many resources a stack has.
+
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use crate::supported_resource_types::SUPPORTED_RESOURCE_TYPES;
+
+static INDEX: OnceLock<HashSet<&'static str>> = OnceLock::new();
+
+/// Prefixes of the types of global services, whose resources are not bound to the region of a stack
+static GLOBAL_TYPE_PREFIXES: [&str; 5] = [
+    "AWS::IAM::",
+    "AWS::Route53::",
+    "AWS::CloudFront::",
+    "AWS::GlobalAccelerator::",
+    "AWS::Organizations::",
+];
+
+/// All resource types which support being imported
+pub fn supported_types() -> &'static HashSet<&'static str> {
+    INDEX.get_or_init(|| SUPPORTED_RESOURCE_TYPES.iter().copied().collect())
+}
+
+/// Whether resources of a type, e.g. `AWS::S3::Bucket`, can be imported into a stack
+pub fn supports(resource_type: &str) -> bool {
+    supported_types().contains(resource_type)
+}
+
+/// Whether a resource of a type can be moved from a stack of `source_region` into a stack of
+/// `target_region`. Resources exist in the region of the stack they belong to, so within a region
+/// only the type matters, see [`importable_into_region`] for moves between regions.
+pub fn supports_in_region(
+    resource_type: &str,
+    definition: &Value,
+    (source_region, target_region): (&str, &str),
+) -> bool {
+    supports(resource_type)
+        && (source_region == target_region
+            || importable_into_region(resource_type, definition, target_region))
+}
+
+/// Types which are listed more than once or out of order, both of which hint at a broken
+/// generation of the list
+pub fn list_issues() -> Vec<String> {
+    SUPPORTED_RESOURCE_TYPES
+        .windows(2)
+        .filter_map(|pair| match pair[0].cmp(pair[1]) {
+            Ordering::Less => None,
+            Ordering::Equal => Some(format!("{} is listed more than once", pair[1])),
+            Ordering::Greater => Some(format!("{} is listed after {}", pair[1], pair[0])),
+        })
+        .collect()
+}
+
+/// Whether a resource can be imported into a stack of another region than its own. CloudFormation
+/// only imports resources which exist in the region of the stack: resources of global services, and
+/// DynamoDB global tables with a replica in that region.
+pub fn importable_into_region(resource_type: &str, definition: &Value, region: &str) -> bool {
+    if GLOBAL_TYPE_PREFIXES
+        .iter()
+        .any(|prefix| resource_type.starts_with(prefix))
+    {
+        return true;
+    }
+    resource_type == "AWS::DynamoDB::GlobalTable"
+        && definition["Properties"]["Replicas"]
+            .as_array()
+            .is_some_and(|replicas| {
+                replicas
+                    .iter()
+                    .any(|replica| replica["Region"].as_str() == Some(region))
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn list_is_sorted_without_duplicates() {
+        assert_eq!(list_issues(), Vec::<String>::new());
+        assert_eq!(supported_types().len(), SUPPORTED_RESOURCE_TYPES.len());
+    }
+
+    #[test]
+    fn supports_listed_types_only() {
+        assert!(supports("AWS::S3::Bucket"));
+        assert!(!supports("AWS::S3::bucket"));
+        assert!(!supports("Custom::Resource"));
+    }
+
+    #[test]
+    fn supports_in_region_within_a_region() {
+        let bucket = json!({"Type": "AWS::S3::Bucket"});
+
+        assert!(supports_in_region(
+            "AWS::S3::Bucket",
+            &bucket,
+            ("eu-west-1", "eu-west-1")
+        ));
+        assert!(!supports_in_region(
+            "Custom::Resource",
+            &json!({}),
+            ("eu-west-1", "eu-west-1")
+        ));
+    }
+
+    #[test]
+    fn supports_in_region_between_regions() {
+        let bucket = json!({"Type": "AWS::S3::Bucket"});
+        let role = json!({"Type": "AWS::IAM::Role"});
+        let table = json!({
+            "Type": "AWS::DynamoDB::GlobalTable",
+            "Properties": {"Replicas": [{"Region": "eu-west-1"}, {"Region": "us-east-1"}]},
+        });
+        let regions = ("eu-west-1", "us-east-1");
+
+        assert!(!supports_in_region("AWS::S3::Bucket", &bucket, regions));
+        assert!(supports_in_region("AWS::IAM::Role", &role, regions));
+        assert!(supports_in_region(
+            "AWS::DynamoDB::GlobalTable",
+            &table,
+            regions
+        ));
+        assert!(!supports_in_region(
+            "AWS::DynamoDB::GlobalTable",
+            &table,
+            ("eu-west-1", "ap-south-1")
+        ));
+    }
+}
@@ -0,0 +1,30 @@
+//! Building blocks of cfn-teleport, usable without the command-line interface.
+
+pub mod analysis;
+pub mod attributes;
+pub mod bulk_describe;
+pub mod cancellation;
+pub mod capabilities;
+pub mod caveats;
+pub mod cfn_yaml;
+pub mod composer;
+pub mod conditions;
+pub mod coupling;
+pub mod diff;
+pub mod history;
+pub mod import_support;
+pub mod migration;
+pub mod plan;
+pub mod planning;
+pub mod provenance;
+pub mod recovery;
+pub mod reduction;
+pub mod reference_updater;
+pub mod render;
+pub mod sanitize;
+pub mod stack_api;
+pub mod supported_resource_types;
+pub mod template_body;
+pub mod ticket;
+pub mod triage;
+pub mod waiter;
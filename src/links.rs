@@ -0,0 +1,110 @@
+//! Deep links into the AWS console, to verify the result of a migration.
+
+/// Builds console URLs for the stacks and resources of one region
+pub struct ConsoleLinks {
+    host: &'static str,
+    region: String,
+}
+
+impl ConsoleLinks {
+    pub fn new(partition: &str, region: &str) -> Self {
+        let host = match partition {
+            "aws-cn" => "console.amazonaws.cn",
+            "aws-us-gov" => "console.amazonaws-us-gov.com",
+            _ => "console.aws.amazon.com",
+        };
+        Self {
+            host,
+            region: region.to_string(),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("https://{}/{}", self.host, path)
+    }
+
+    pub fn stack(&self, stack_id: &str) -> String {
+        self.url(&format!(
+            "cloudformation/home?region={}#/stacks/stackinfo?stackId={}",
+            self.region,
+            encode(stack_id)
+        ))
+    }
+
+    pub fn changeset(&self, stack_id: &str, changeset_id: &str) -> String {
+        self.url(&format!(
+            "cloudformation/home?region={}#/stacks/changesets/changes?stackId={}&changeSetId={}",
+            self.region,
+            encode(stack_id),
+            encode(changeset_id)
+        ))
+    }
+
+    /// Link to a resource in the console of its service, for the resource types with a known
+    /// console page
+    pub fn resource(&self, resource_type: &str, physical_id: &str) -> Option<String> {
+        let region = &self.region;
+        let id = encode(physical_id);
+        let path = match resource_type {
+            "AWS::CloudFormation::Stack" => {
+                return Some(self.stack(physical_id));
+            }
+            "AWS::DynamoDB::Table" => {
+                format!("dynamodbv2/home?region={}#table?name={}", region, id)
+            }
+            "AWS::EC2::Instance" => format!(
+                "ec2/home?region={}#InstanceDetails:instanceId={}",
+                region, id
+            ),
+            "AWS::EC2::SecurityGroup" => {
+                format!("ec2/home?region={}#SecurityGroup:groupId={}", region, id)
+            }
+            "AWS::EC2::Subnet" => format!(
+                "vpcconsole/home?region={}#SubnetDetails:subnetId={}",
+                region, id
+            ),
+            "AWS::EC2::VPC" => format!("vpcconsole/home?region={}#VpcDetails:VpcId={}", region, id),
+            "AWS::IAM::Role" => format!("iam/home#/roles/details/{}", id),
+            "AWS::IAM::User" => format!("iam/home#/users/details/{}", id),
+            "AWS::KMS::Key" => format!("kms/home?region={}#/kms/keys/{}", region, id),
+            "AWS::Lambda::Function" => {
+                format!("lambda/home?region={}#/functions/{}", region, id)
+            }
+            // the console expects the name encoded twice, with `$` instead of `%`
+            "AWS::Logs::LogGroup" => format!(
+                "cloudwatch/home?region={}#logsV2:log-groups/log-group/{}",
+                region,
+                encode(&id).replace('%', "$")
+            ),
+            "AWS::RDS::DBCluster" => format!(
+                "rds/home?region={}#database:id={};is-cluster=true",
+                region, id
+            ),
+            "AWS::RDS::DBInstance" => format!("rds/home?region={}#database:id={}", region, id),
+            "AWS::S3::Bucket" => format!("s3/buckets/{}?region={}", id, region),
+            "AWS::SecretsManager::Secret" => {
+                format!("secretsmanager/secret?name={}&region={}", id, region)
+            }
+            "AWS::SNS::Topic" => format!("sns/v3/home?region={}#/topic/{}", region, id),
+            "AWS::SQS::Queue" => format!("sqs/v3/home?region={}#/queues/{}", region, id),
+            "AWS::StepFunctions::StateMachine" => {
+                format!("states/home?region={}#/statemachines/view/{}", region, id)
+            }
+            _ => return None,
+        };
+        Some(self.url(&path))
+    }
+}
+
+/// Percent-encodes everything but unreserved characters
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,94 @@
+use aws_sdk_cloudformation::config::SharedHttpClient;
+use aws_smithy_runtime_api::client::http::{
+    HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpConnector,
+};
+use aws_smithy_runtime_api::client::orchestrator::HttpRequest;
+use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+use std::time::Instant;
+use tracing_subscriber::EnvFilter;
+
+use crate::http_client;
+
+/// Environment variable with a filter of the log, e.g. `cfn_teleport=debug,aws_smithy_runtime=debug`,
+/// which takes precedence over `--verbose`
+pub const FILTER_ENV: &str = "RUST_LOG";
+
+/// Writes the log to stderr. Without `--verbose` only warnings are logged, `-v` logs every AWS API
+/// call and every step of a migration, `-vv` also every iteration of a wait and every composed
+/// template, `-vvv` the composed templates themselves and the debug log of the AWS SDK.
+pub fn init(verbose: u8) {
+    let filter = EnvFilter::try_from_env(FILTER_ENV).unwrap_or_else(|_| {
+        EnvFilter::new(match verbose {
+            0 => "warn",
+            1 => "warn,cfn_teleport=info",
+            2 => "warn,cfn_teleport=debug",
+            _ => "debug,cfn_teleport=trace",
+        })
+    });
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+/// Whether AWS API calls are logged, so clients only pass through [`wrap`] when needed
+pub fn logs_api_calls() -> bool {
+    tracing::enabled!(tracing::Level::INFO)
+}
+
+/// Wraps an HTTP client, so all requests sent through it are logged with their outcome
+pub fn wrap(client: SharedHttpClient) -> SharedHttpClient {
+    SharedHttpClient::new(LoggingClient { inner: client })
+}
+
+#[derive(Debug)]
+struct LoggingClient {
+    inner: SharedHttpClient,
+}
+
+impl HttpClient for LoggingClient {
+    fn http_connector(
+        &self,
+        settings: &HttpConnectorSettings,
+        components: &RuntimeComponents,
+    ) -> SharedHttpConnector {
+        SharedHttpConnector::new(LoggingConnector {
+            inner: self.inner.http_connector(settings, components),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct LoggingConnector {
+    inner: SharedHttpConnector,
+}
+
+impl HttpConnector for LoggingConnector {
+    fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+        let inner = self.inner.clone();
+        let host = request
+            .uri()
+            .split("://")
+            .nth(1)
+            .and_then(|rest| rest.split('/').next())
+            .unwrap_or_default()
+            .to_string();
+        let operation = http_client::operation(&request).unwrap_or_else(|| "unknown".to_string());
+        HttpConnectorFuture::new(async move {
+            let started = Instant::now();
+            let result = inner.call(request).await;
+            let millis = started.elapsed().as_millis();
+            match &result {
+                Ok(response) => tracing::info!(
+                    host,
+                    operation,
+                    status = response.status().as_u16(),
+                    millis,
+                    "AWS API call"
+                ),
+                Err(err) => tracing::warn!(host, operation, millis, "AWS API call failed: {}", err),
+            }
+            result
+        })
+    }
+}
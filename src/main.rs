@@ -1,726 +1,4136 @@
-use aws_config::BehaviorVersion;
+use aws_config::{BehaviorVersion, Region};
 use aws_sdk_cloudformation as cloudformation;
-use clap::Parser;
-use dialoguer::{console::Term, theme::ColorfulTheme, Confirm, Input, MultiSelect, Select};
+use aws_sdk_sts as sts;
+use cfn_teleport::bulk_describe::BulkDescriber;
+use cfn_teleport::cancellation::{Cancellation, Interrupted, Phase};
+use cfn_teleport::composer::{
+    self, add_resources, deletion_policies, merge_aliases, remove_resources,
+};
+use cfn_teleport::provenance::{self, Manifest, PlanFile};
+use cfn_teleport::stack_api::{self, StackApi};
+use cfn_teleport::template_body::{self, TemplateBody};
+use cfn_teleport::{
+    analysis, attributes, capabilities, caveats, conditions, diff, history, import_support,
+    migration, plan, planning, recovery, reduction, reference_updater, render, sanitize, triage,
+    waiter,
+};
+use clap::{Parser, Subcommand};
+use dialoguer::{
+    console::Term, theme::ColorfulTheme, BasicHistory, Confirm, Editor, FuzzySelect, Input,
+    MultiSelect, Select,
+};
 use std::error::Error;
 use std::process;
-use uuid::Uuid;
+
+/// Prints an informational line to stdout, unless the output is suppressed with `--quiet`
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !$crate::spinner::is_quiet() {
+            println!($($arg)*)
+        }
+    };
+}
+
+mod budget;
+mod commands;
+mod concurrency;
+mod config;
+mod drift;
+mod dynamic_references;
+mod error;
+mod http_client;
+mod identifiers;
+mod links;
+mod logging;
+mod mfa;
+mod permissions;
+mod read_only;
+mod regions;
+mod schedule;
 mod spinner;
-use std::collections::HashMap;
+mod stabilization;
+mod state;
+use error::{fail, ExitCode, WithExitCode};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io;
-mod supported_resource_types;
+use std::path::PathBuf;
+mod timing;
+mod workspace;
 
 const DEMO: bool = false;
 
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[derive(Parser, Debug, Clone)]
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Name of the source stack
-    #[arg(short, long)]
+    #[arg(short, long, env = "CFN_TELEPORT_SOURCE")]
     source: Option<String>,
 
     /// Name of the target stack
-    #[arg(short, long)]
+    #[arg(short, long, env = "CFN_TELEPORT_TARGET")]
     target: Option<String>,
 
+    /// Create the target stack with the moved resources if it does not exist
+    #[arg(long, env = "CFN_TELEPORT_CREATE_TARGET")]
+    create_target: bool,
+
     /// Logical ID of a resource from the source stack - optionally with a new ID for the target stack
-    #[arg(short, long, value_name = "ID[:NEW_ID]")]
+    #[arg(
+        short,
+        long,
+        value_name = "ID[:NEW_ID]",
+        value_delimiter = ',',
+        env = "CFN_TELEPORT_RESOURCE"
+    )]
     resource: Option<Vec<String>>,
 
+    /// Prefix for the logical IDs of all moved resources which are not renamed explicitly
+    #[arg(long, env = "CFN_TELEPORT_PREFIX")]
+    prefix: Option<String>,
+
+    /// Suffix for the logical IDs of all moved resources which are not renamed explicitly
+    #[arg(long, env = "CFN_TELEPORT_SUFFIX")]
+    suffix: Option<String>,
+
+    /// Physical ID to import a resource with, instead of the ID recorded in the source stack
+    #[arg(
+        long,
+        value_name = "ID=PHYSICAL_ID",
+        value_delimiter = ',',
+        env = "CFN_TELEPORT_PHYSICAL_ID"
+    )]
+    physical_id: Vec<String>,
+
+    /// Only offer stacks whose name matches a pattern, e.g. prod-*
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        value_delimiter = ',',
+        env = "CFN_TELEPORT_STACK_FILTER"
+    )]
+    stack_filter: Vec<String>,
+
+    /// Region of the source stack [default: --region]
+    #[arg(long, value_name = "REGION", env = "CFN_TELEPORT_SOURCE_REGION")]
+    source_region: Option<String>,
+
+    /// Region of the target stack [default: --region]
+    #[arg(long, value_name = "REGION", env = "CFN_TELEPORT_TARGET_REGION")]
+    target_region: Option<String>,
+
+    /// AWS profile of the account of the target stack [default: --profile]
+    #[arg(long, value_name = "PROFILE", env = "CFN_TELEPORT_TARGET_PROFILE")]
+    target_profile: Option<String>,
+
+    /// IAM role in the account of the target stack, assumed with the credentials of --target-profile or --profile
+    #[arg(long, value_name = "ARN", env = "CFN_TELEPORT_TARGET_ROLE_ARN")]
+    target_role_arn: Option<String>,
+
+    /// Select all resources of the source stack which can be imported
+    #[arg(long, conflicts_with = "resource", env = "CFN_TELEPORT_ALL_RESOURCES")]
+    all_resources: bool,
+
+    /// Only offer resources of a type, e.g. AWS::DynamoDB::Table or AWS::SQS::*
+    #[arg(
+        long,
+        value_name = "TYPE",
+        value_delimiter = ',',
+        conflicts_with = "resource",
+        env = "CFN_TELEPORT_RESOURCE_TYPE"
+    )]
+    resource_type: Vec<String>,
+
+    /// Print a feasibility report for moving the selected resources and exit without changes
+    #[arg(long, env = "CFN_TELEPORT_FEASIBILITY")]
+    feasibility: bool,
+
+    /// Directory written by `cfn-teleport plan --output-dir`, to execute its reviewed templates
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["source", "target", "resource", "all_resources", "resource_type", "physical_id"],
+        env = "CFN_TELEPORT_FROM_ARTIFACTS"
+    )]
+    from_artifacts: Option<PathBuf>,
+
+    /// Plan file of `cfn-teleport apply`
+    #[arg(skip)]
+    apply_plan: Option<PathBuf>,
+
+    /// Print the changes to both stacks, step by step, and exit without changes
+    #[arg(long, env = "CFN_TELEPORT_DRY_RUN")]
+    dry_run: bool,
+
     /// Automatically confirm all prompts
-    #[arg(short, long)]
+    #[arg(short, long, env = "CFN_TELEPORT_YES")]
     yes: bool,
-}
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+    /// Like --yes, but count down SECONDS before the migration changes anything, so an operator watching it can abort with Ctrl+C
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        conflicts_with = "yes",
+        env = "CFN_TELEPORT_ASSUME_YES_WITH_TIMEOUT"
+    )]
+    assume_yes_with_timeout: Option<u64>,
 
-    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
-    let client = cloudformation::Client::new(&config);
-    let stacks = get_stacks(&client).await?;
+    /// Treat warnings as errors and abort before anything is changed
+    #[arg(long, env = "CFN_TELEPORT_STRICT")]
+    strict: bool,
 
-    let stack_names: Vec<&str> = stacks
-        .iter()
-        .map(|s| s.stack_name().unwrap_or_default())
-        .collect();
+    /// Acknowledge a capability required by the templates, e.g. CAPABILITY_IAM
+    #[arg(
+        long,
+        value_name = "CAPABILITY",
+        value_parser = capabilities::CAPABILITIES,
+        value_delimiter = ',',
+        env = "CFN_TELEPORT_CAPABILITY"
+    )]
+    capability: Vec<String>,
 
-    let source_stack = args.source.unwrap_or_else(|| {
-        select_stack("Select source stack", &stack_names)
-            .unwrap()
-            .to_string()
-    });
+    /// Review the definition of each resource in the target stack, to accept, edit or skip it
+    #[arg(long, conflicts_with = "yes", env = "CFN_TELEPORT_REVIEW")]
+    review: bool,
 
-    let resources = get_resources(&client, &source_stack).await?;
+    /// Compare the createOnly properties of the resources with the live resources before the import
+    #[arg(long, env = "CFN_TELEPORT_CHECK_CREATE_ONLY")]
+    check_create_only: bool,
 
-    if resources.is_empty() {
-        return Err(format!("No resources found in stack '{}'", source_stack).into());
-    }
+    /// Leave the resources unmanaged when the import into the target stack fails, instead of importing them back into the source stack
+    #[arg(long, env = "CFN_TELEPORT_NO_ROLLBACK")]
+    no_rollback: bool,
 
-    let target_stack = args.target.unwrap_or_else(|| {
-        select_stack("Select target stack", &stack_names)
-            .unwrap()
-            .to_string()
-    });
+    /// Apply the suggested size reductions to the copied resources without asking, once the target template approaches the quota of CloudFormation
+    #[arg(long, env = "CFN_TELEPORT_REDUCE_TEMPLATE")]
+    reduce_template: bool,
 
-    if source_stack == target_stack {
-        return Err("Source and target stack must be different".into());
-    }
+    /// Keep, strip or ask for a resource attribute of the copied resources, e.g. Metadata=strip
+    #[arg(
+        long,
+        value_name = "ATTRIBUTE=ACTION",
+        value_delimiter = ',',
+        env = "CFN_TELEPORT_ATTRIBUTE"
+    )]
+    attribute: Vec<String>,
 
-    let resource_refs = &resources.iter().collect::<Vec<_>>();
+    /// Sanitization profile of the tool which created the source stack, or none [default: detected from the template]
+    #[arg(long, value_name = "PROFILE", value_parser = clap::builder::PossibleValuesParser::new(
+        sanitize::PROFILES.iter().map(|profile| profile.name).chain(["none"])
+    ), env = "CFN_TELEPORT_SANITIZE")]
+    sanitize: Option<String>,
 
-    let selected_resources = match args.resource.clone() {
-        Some(resource) => {
-            let source_ids = resource
-                .iter()
-                .map(|r| split_ids(r.clone()).0)
-                .collect::<Vec<_>>();
+    /// AWS profile allowed to modify the stacks, only used to execute the migration [default: the profile used for planning]
+    #[arg(long, value_name = "PROFILE", env = "CFN_TELEPORT_EXECUTE_PROFILE")]
+    execute_profile: Option<String>,
 
-            let non_existing_ids: Vec<String> = source_ids
-                .iter()
-                .filter(|id| {
-                    !resource_refs
-                        .iter()
-                        .any(|r| r.logical_resource_id().unwrap_or_default() == **id)
-                })
-                .map(|id| id.to_string())
-                .collect();
+    /// Wait until the given time before making any changes, e.g. 2024-05-01T22:00:00Z
+    #[arg(long, value_name = "TIMESTAMP", env = "CFN_TELEPORT_SCHEDULE_AT")]
+    schedule_at: Option<String>,
 
-            if !non_existing_ids.is_empty() {
-                eprintln!(
-                    "ERROR: The following resources do not exist on stack '{}':\n - {}",
-                    source_stack,
-                    non_existing_ids.to_owned().join("\n - "),
-                );
-                process::exit(1);
-            }
-            filter_resources(resource_refs, &source_ids).await?
-        }
-        None => select_resources("Select resources to copy", resource_refs).await?,
-    };
+    /// Record the migration in the template metadata of both stacks
+    #[arg(long, env = "CFN_TELEPORT_ANNOTATE")]
+    annotate: bool,
 
-    if selected_resources.is_empty() {
-        return Err("No resources have been selected".into());
-    }
+    /// Shell command to run when the migration has finished or failed
+    #[arg(long, value_name = "COMMAND", env = "CFN_TELEPORT_NOTIFY")]
+    notify: Option<String>,
 
-    let mut new_logical_ids_map = HashMap::new();
-    //let mut resource_has_been_renamed = false;
+    /// Path of the config file [default: ~/.cfn-teleport/config.yaml]
+    #[arg(long, value_name = "FILE", env = config::CONFIG_ENV)]
+    config: Option<PathBuf>,
 
-    match args.resource.clone() {
-        None => {
-            for resource in selected_resources.clone() {
-                let old_logical_id = resource
-                    .logical_resource_id()
-                    .unwrap_or_default()
-                    .to_owned();
-                let mut new_logical_id: String;
-                if false {
-                    // resource renaming is disabled for now
-                    new_logical_id = Input::new()
-                        .with_prompt(format!(
-                            "Optionally provide a new logical ID for resource '{}'",
-                            old_logical_id
-                        ))
-                        .default(old_logical_id.clone())
-                        .interact_text()?;
-
-                    if new_logical_id.is_empty() {
-                        new_logical_id = resource
-                            .logical_resource_id()
-                            .unwrap_or_default()
-                            .to_string();
-                    } else {
-                        //resource_has_been_renamed = true;
-                    }
-                } else {
-                    new_logical_id = old_logical_id.clone();
-                }
+    /// Directory for the artifacts of each run [default: ~/.cfn-teleport/runs]
+    #[arg(long, value_name = "DIR", env = workspace::WORKSPACE_ENV)]
+    workspace: Option<PathBuf>,
 
-                new_logical_ids_map.insert(old_logical_id, new_logical_id);
-            }
-            //            println!()
-        }
-        Some(resources) => {
-            for resource in resources {
-                let ids = split_ids(resource.clone());
-                let source_id = ids.0.clone();
-                let target_id = ids.1.clone();
-                new_logical_ids_map.insert(source_id, target_id);
-            }
-        }
-    };
+    /// AWS profile to use instead of the default credentials, like AWS_PROFILE
+    #[arg(long, global = true, env = "CFN_TELEPORT_PROFILE")]
+    profile: Option<String>,
 
-    if source_stack == target_stack {
-        let mut duplicate_ids = Vec::new();
-        for (old_id, new_id) in &new_logical_ids_map {
-            if old_id == new_id {
-                duplicate_ids.push(old_id);
-            }
-        }
+    /// IAM role to assume with the credentials, asking for the MFA token code if the role requires it
+    #[arg(long, global = true, value_name = "ARN", env = "CFN_TELEPORT_ROLE_ARN")]
+    role_arn: Option<String>,
 
-        if !duplicate_ids.is_empty() {
-            let error_message = format!(
-                "Unable to proceed, because you said you want to rename resources in stack {} but did not provide new logical IDs for: {}",
-                source_stack,
-                duplicate_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ")
-            );
-            return Err(error_message.into());
-        }
+    /// Serial number or ARN of the MFA device for --role-arn [default: the MFA device of the IAM user]
+    #[arg(
+        long,
+        global = true,
+        value_name = "SERIAL",
+        requires = "role_arn",
+        env = "CFN_TELEPORT_MFA_SERIAL"
+    )]
+    mfa_serial: Option<String>,
 
-        println!(
-            "The following resources in stack {} will be renamed:",
-            source_stack
-        );
-    } else {
-        println!(
-            "The following resources will be moved from stack {} to {}:",
-            source_stack, target_stack
-        );
-    }
+    /// AWS region of the stacks [default: the region of the environment or profile]
+    #[arg(long, global = true, env = "CFN_TELEPORT_REGION")]
+    region: Option<String>,
 
-    for resource in format_resources(&selected_resources, Some(new_logical_ids_map.clone())).await?
-    {
-        println!("  {}", resource);
-    }
+    /// PEM bundle of additional CA certificates to trust, e.g. of a TLS-intercepting proxy
+    #[arg(long, global = true, value_name = "FILE", env = http_client::CA_BUNDLE_ENV)]
+    ca_bundle: Option<PathBuf>,
 
-    if !args.yes {
-        user_confirm()?;
-    }
+    /// AWS partition of the stacks, e.g. aws-cn or aws-us-gov [default: derived from the region]
+    #[arg(long, global = true, value_parser = regions::PARTITIONS, env = "CFN_TELEPORT_PARTITION")]
+    partition: Option<String>,
 
-    let template_source = get_template(&client, &source_stack).await?;
-    let template_source_str = serde_json::to_string(&template_source)?;
+    /// Print one line per phase, PHASE <phase> START|OK|FAIL <details>, instead of spinners and status lines
+    #[arg(long, global = true, env = "CFN_TELEPORT_PLAIN")]
+    plain: bool,
 
-    let resource_ids_to_remove: Vec<_> = new_logical_ids_map.keys().cloned().collect();
+    /// Print only errors and warnings, no spinners, progress or other information
+    #[arg(
+        short,
+        long,
+        global = true,
+        conflicts_with = "plain",
+        env = "CFN_TELEPORT_QUIET"
+    )]
+    quiet: bool,
 
-    let template_retained =
-        retain_resources(template_source.clone(), resource_ids_to_remove.clone());
-    let template_retained_str = serde_json::to_string(&template_retained)?;
+    /// Print the outcome of the run as a line of JSON to stdout when it has finished
+    #[arg(long, global = true, env = "CFN_TELEPORT_RESULT_JSON")]
+    result_json: bool,
 
-    let template_removed =
-        remove_resources(template_source.clone(), resource_ids_to_remove.clone());
+    /// Log AWS API calls and the steps of a migration to stderr, -vv also every wait iteration, -vvv the composed templates
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        env = "CFN_TELEPORT_VERBOSE"
+    )]
+    verbose: u8,
 
-    let (template_target_with_deletion_policy, template_target) = add_resources(
-        get_template(&client, &target_stack).await?,
-        template_source.clone(),
-        new_logical_ids_map.clone(),
-    );
+    /// Refuse every AWS API call which could change anything, e.g. to explore production accounts in a demo or an audit
+    #[arg(long, global = true, env = "CFN_TELEPORT_READ_ONLY")]
+    read_only: bool,
 
-    for template in [
-        template_retained.clone(),
-        template_removed.clone(),
-        template_target.clone(),
-        template_target_with_deletion_policy.clone(),
-    ] {
-        let result = validate_template(&client, template).await;
-        if result.is_err() {
-            return Err(format!(
-                "Unable to proceed, because the template is invalid: {}",
-                result.err().unwrap()
-            )
-            .into());
-        }
-    }
+    /// S3 bucket to upload templates larger than 51,200 bytes to, which CloudFormation only accepts from S3
+    #[arg(
+        long,
+        global = true,
+        value_name = "BUCKET",
+        conflicts_with = "read_only",
+        env = "CFN_TELEPORT_TEMPLATE_BUCKET"
+    )]
+    template_bucket: Option<String>,
 
-    let spinner = spinner::Spin::new(
-        format!(
-            "Removing {} resources from stack {}",
-            resource_ids_to_remove.len(),
-            source_stack
-        )
-        .as_str(),
-    );
+    /// Maximum number of AWS API calls of the run, including retries
+    #[arg(long, global = true, value_name = "CALLS", env = budget::MAX_API_CALLS_ENV)]
+    max_api_calls: Option<usize>,
 
-    if template_source_str != template_retained_str {
-        update_stack(&client, &source_stack, template_retained).await?;
-        wait_for_stack_update_completion(&client, &source_stack, None).await?;
-    }
+    /// Maximum number of AWS API calls per second
+    #[arg(long, global = true, value_name = "CALLS", env = budget::MAX_API_RATE_ENV)]
+    max_api_rate: Option<u32>,
 
-    update_stack(&client, &source_stack, template_removed).await?;
-    wait_for_stack_update_completion(&client, &source_stack, Some(spinner)).await?;
+    /// Number of days the artifacts of previous runs are kept
+    #[arg(
+        long,
+        value_name = "DAYS",
+        default_value_t = workspace::DEFAULT_RETENTION_DAYS,
+        env = "CFN_TELEPORT_RETENTION_DAYS"
+    )]
+    retention_days: u64,
 
-    let spinner = spinner::Spin::new(&format!(
-        "Importing {} resources into stack {}",
-        resource_ids_to_remove.len(),
-        target_stack,
-    ));
+    /// Minutes without any stack event after which a stack operation is considered stuck
+    #[arg(
+        long,
+        value_name = "MINUTES",
+        default_value_t = waiter::DEFAULT_STALL_TIMEOUT,
+        env = "CFN_TELEPORT_STALL_TIMEOUT"
+    )]
+    stall_timeout: u64,
 
-    let changeset_name = create_changeset(
-        &client,
-        &target_stack,
-        template_target_with_deletion_policy,
-        selected_resources,
-        new_logical_ids_map,
-    )
-    .await?;
+    /// Wait until the moved resources report a stable state after the import, e.g. a deployed CloudFront distribution
+    #[arg(long, env = "CFN_TELEPORT_WAIT_FOR_STABILIZATION")]
+    wait_for_stabilization: bool,
 
-    wait_for_changeset_created(&client, &target_stack, &changeset_name).await?;
-    execute_changeset(&client, &target_stack, &changeset_name).await?;
-    wait_for_stack_update_completion(&client, &target_stack, None).await?;
+    /// Detect the drift of the source stack after the migration and include it in the summary
+    #[arg(long, env = "CFN_TELEPORT_DETECT_DRIFT")]
+    detect_drift: bool,
 
-    update_stack(&client, &target_stack, template_target).await?;
-    wait_for_stack_update_completion(&client, &target_stack, Some(spinner)).await?;
+    /// Minutes to wait for the moved resources to stabilize
+    #[arg(
+        long,
+        value_name = "MINUTES",
+        default_value_t = stabilization::DEFAULT_STABILIZATION_TIMEOUT,
+        env = "CFN_TELEPORT_STABILIZATION_TIMEOUT"
+    )]
+    stabilization_timeout: u64,
 
-    Ok(())
-}
+    /// DynamoDB table to register running migrations in, so the team can see them and overlapping migrations of a stack are refused
+    #[arg(long, global = true, value_name = "TABLE", env = state::STATE_TABLE_ENV)]
+    state_table: Option<String>,
 
-fn split_ids(id: String) -> (String, String) {
-    if id.contains(&":".to_string()) {
-        let parts: Vec<String> = id.split(':').map(String::from).collect();
-        (parts[0].clone(), parts[1].clone())
-    } else {
-        (id.clone(), id)
-    }
+    /// Minutes after which the migration stops at the next safe point
+    #[arg(long, value_name = "MINUTES", env = "CFN_TELEPORT_TIMEOUT")]
+    timeout: Option<u64>,
 }
 
-async fn get_stacks(
-    client: &cloudformation::Client,
-) -> Result<Vec<cloudformation::types::StackSummary>, cloudformation::Error> {
-    let mut stacks = Vec::new();
-    let mut token = None;
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Rename resources in a local template file and update all references to them
+    UpdateRefs(commands::update_refs::UpdateRefsArgs),
 
-    let stack_filter = vec![
-        cloudformation::types::StackStatus::CreateInProgress,
-        cloudformation::types::StackStatus::CreateFailed,
-        cloudformation::types::StackStatus::CreateComplete,
-        cloudformation::types::StackStatus::RollbackInProgress,
-        cloudformation::types::StackStatus::RollbackFailed,
-        cloudformation::types::StackStatus::RollbackComplete,
-        cloudformation::types::StackStatus::DeleteFailed,
-        cloudformation::types::StackStatus::UpdateInProgress,
-        cloudformation::types::StackStatus::UpdateCompleteCleanupInProgress,
-        cloudformation::types::StackStatus::UpdateComplete,
-        cloudformation::types::StackStatus::UpdateFailed,
-        cloudformation::types::StackStatus::UpdateRollbackInProgress,
-        cloudformation::types::StackStatus::UpdateRollbackFailed,
-        cloudformation::types::StackStatus::UpdateRollbackCompleteCleanupInProgress,
-        cloudformation::types::StackStatus::UpdateRollbackComplete,
-        cloudformation::types::StackStatus::ReviewInProgress,
-        cloudformation::types::StackStatus::ImportInProgress,
-        cloudformation::types::StackStatus::ImportComplete,
-        cloudformation::types::StackStatus::ImportRollbackInProgress,
-        cloudformation::types::StackStatus::ImportRollbackFailed,
-        cloudformation::types::StackStatus::ImportRollbackComplete,
-    ];
+    /// Run all non-mutating checks of a plan manifest and report the results
+    Validate(commands::validate::ValidateArgs),
 
-    loop {
-        let query = match token {
-            Some(token) => client.list_stacks().next_token(token),
-            None => client.list_stacks(),
-        };
+    /// Delete the artifacts of previous runs
+    Clean(commands::clean::CleanArgs),
 
-        let resp = query
-            .set_stack_status_filter(Some(stack_filter.clone()))
-            .send()
-            .await?;
+    /// Print the templates and resources to import of a migration, to execute it with other tools
+    Plan(commands::plan::PlanArgs),
 
-        let new_stacks = resp.stack_summaries().to_vec();
-        stacks.append(&mut new_stacks.clone());
+    /// Execute the migration of a plan file written by `cfn-teleport plan --plan-file`
+    Apply(commands::apply::ApplyArgs),
 
-        if let Some(next_token) = resp.next_token() {
-            token = Some(next_token.to_owned());
-        } else {
-            break;
-        }
-    }
+    /// Execute the migrations of a manifest one after another and report the outcome of all of them
+    Batch(commands::batch::BatchArgs),
 
-    let mut stacks = stacks
-        .into_iter()
-        .filter(|stack| !stack.stack_status().unwrap().as_str().starts_with("DELETE"))
-        .collect::<Vec<_>>();
+    /// Restore the original DeletionPolicy of the resources of a stopped or failed migration
+    CleanupRetain(commands::cleanup_retain::CleanupRetainArgs),
 
-    if DEMO {
-        // filter by name, for demo purposes
-        stacks = stacks
-            .into_iter()
-            .filter(|stack| {
-                stack
-                    .stack_name()
-                    .unwrap_or_default()
-                    .contains("CfnTeleportTest")
-            })
-            .collect::<Vec<_>>();
-    }
+    /// Report the shared references between two stacks and how entangled they are
+    Coupling(commands::coupling::CouplingArgs),
 
-    // Sort the stacks by name
-    let mut sorted_stacks = stacks;
-    sorted_stacks.sort_by_key(|stack| stack.stack_name().unwrap_or_default().to_string());
+    /// Print every location in the template of a stack where a logical ID is referenced
+    Find(commands::find::FindArgs),
 
-    Ok(sorted_stacks)
-}
+    /// List the migrations in progress registered in the state table
+    Status(commands::status::StatusArgs),
 
-fn select_stack<'a>(prompt: &str, items: &'a [&str]) -> Result<&'a str, Box<dyn Error>> {
-    let selection = Select::with_theme(&ColorfulTheme::default())
-        .with_prompt(prompt)
-        .items(items)
-        .report(false)
-        .default(0)
-        .interact_on_opt(&Term::stderr())?;
+    /// Move resources between two demo stacks in a sandbox account, to learn the tool or smoke test a release
+    Demo(commands::demo::DemoArgs),
 
-    match selection {
-        Some(index) => Ok(items[index]),
-        None => Err("User did not select anything".into()),
-    }
+    /// Print the shell completion script for bash, zsh, fish, elvish or powershell
+    Completions(commands::completions::CompletionsArgs),
 }
 
-async fn get_resources(
-    client: &cloudformation::Client,
-    stack_name: &str,
-) -> Result<Vec<cloudformation::types::StackResourceSummary>, cloudformation::Error> {
-    let resp = client
-        .list_stack_resources()
-        .stack_name(stack_name)
-        .send()
-        .await?;
+#[tokio::main]
+async fn main() {
+    let mut args = Args::parse();
+    logging::init(args.verbose);
+    let budget = budget::ApiBudget::install(args.max_api_calls, args.max_api_rate);
+    spinner::set_plain(args.plain);
+    spinner::set_quiet(args.quiet);
+    let result_json = args.result_json;
+
+    let result = match args.command.take() {
+        Some(Command::UpdateRefs(args)) => commands::update_refs::run(args),
+        Some(Command::Validate(validate_args)) => match load_config(&args).await {
+            Ok(config) => commands::validate::run(validate_args, &config).await,
+            Err(err) => Err(err),
+        },
+        Some(Command::Clean(args)) => commands::clean::run(args),
+        Some(Command::Plan(plan_args)) => match load_config(&args).await {
+            Ok(config) => commands::plan::run(plan_args, &config).await,
+            Err(err) => Err(err),
+        },
+        Some(Command::CleanupRetain(cleanup_args)) => match load_config(&args).await {
+            Ok(config) => commands::cleanup_retain::run(cleanup_args, &config).await,
+            Err(err) => Err(err),
+        },
+        Some(Command::Coupling(coupling_args)) => match load_config(&args).await {
+            Ok(config) => commands::coupling::run(coupling_args, &config).await,
+            Err(err) => Err(err),
+        },
+        Some(Command::Find(find_args)) => match load_config(&args).await {
+            Ok(config) => commands::find::run(find_args, &config).await,
+            Err(err) => Err(err),
+        },
+        Some(Command::Status(status_args)) => match load_config(&args).await {
+            Ok(config) => {
+                commands::status::run(status_args, &config, args.state_table.as_deref()).await
+            }
+            Err(err) => Err(err),
+        },
+        Some(Command::Demo(demo_args)) => match load_config(&args).await {
+            Ok(config) => commands::demo::run(demo_args, &config, &args).await,
+            Err(err) => Err(err),
+        },
+        Some(Command::Completions(completions_args)) => {
+            commands::completions::run(completions_args, &args).await
+        }
+        command @ (None | Some(Command::Apply(_)) | Some(Command::Batch(_))) => {
+            let notify = args.notify.clone();
+            let result = match command {
+                Some(Command::Apply(apply_args)) => commands::apply::run(apply_args, args).await,
+                Some(Command::Batch(batch_args)) => commands::batch::run(batch_args, args).await,
+                _ => run(args).await,
+            };
+            if let Some(command) = notify {
+                if let Err(err) = schedule::notify(&command, &result) {
+                    eprintln!("WARNING: {}", err);
+                }
+            }
+            result
+        }
+    };
 
-    let resources = resp.stack_resource_summaries().to_vec();
+    for location in template_body::cleanup().await {
+        eprintln!(
+            "WARNING: Unable to delete the uploaded template {}",
+            location
+        );
+    }
 
-    // Filter resources based on supported types
-    let filtered_resources = resources
-        .into_iter()
-        .filter(|resource| {
-            let resource_type = resource.resource_type().unwrap_or_default();
-            supported_resource_types::SUPPORTED_RESOURCE_TYPES.contains(&resource_type)
-        })
-        .collect::<Vec<_>>();
+    if budget.is_limited() {
+        print_api_calls(&budget);
+    }
 
-    // Sort the resources by type, logical ID, and name
-    let mut sorted_resources = filtered_resources;
-    sorted_resources.sort_by_key(|resource| {
-        (
-            resource.resource_type().unwrap_or_default().to_string(),
-            resource
-                .logical_resource_id()
-                .unwrap_or_default()
-                .to_string(),
-            resource
-                .physical_resource_id()
-                .unwrap_or_default()
-                .to_string(),
-        )
-    });
+    if result_json {
+        print_result(&result);
+    }
 
-    Ok(sorted_resources)
+    match result {
+        Ok(()) => process::exit(ExitCode::Success.code()),
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            process::exit(error::exit_code_of(err.as_ref()).code());
+        }
+    }
 }
 
-async fn filter_resources<'a>(
-    resources: &'a [&aws_sdk_cloudformation::types::StackResourceSummary],
-    filter: &[String],
-) -> Result<Vec<&'a aws_sdk_cloudformation::types::StackResourceSummary>, Box<dyn Error>> {
-    let mut filtered_resources = Vec::new();
+async fn load_config(args: &Args) -> Result<aws_config::SdkConfig, Box<dyn Error>> {
+    load_regional_config(args, args.region.as_deref()).await
+}
 
-    for resource in resources {
-        let logical_id = resource.logical_resource_id().unwrap_or_default();
+/// Loads the configuration for an explicit region, or the region of the environment
+async fn load_regional_config(
+    args: &Args,
+    region: Option<&str>,
+) -> Result<aws_config::SdkConfig, Box<dyn Error>> {
+    let mut client = match &args.ca_bundle {
+        Some(ca_bundle) => {
+            Some(http_client::with_ca_bundle(ca_bundle).exit_code(ExitCode::Validation)?)
+        }
+        None => None,
+    };
+    if let Some(budget) = budget::ApiBudget::global().filter(|budget| budget.is_limited()) {
+        client = Some(budget.wrap(client.unwrap_or_else(http_client::default_client)));
+    }
+    if args.read_only {
+        client = Some(read_only::wrap(
+            client.unwrap_or_else(http_client::default_client),
+        ));
+    }
+    if logging::logs_api_calls() {
+        client = Some(logging::wrap(
+            client.unwrap_or_else(http_client::default_client),
+        ));
+    }
+    let loader = |profile: Option<&str>| {
+        let mut loader = aws_config::defaults(BehaviorVersion::v2026_01_12());
+        if let Some(profile) = profile {
+            loader = loader.profile_name(profile);
+        }
+        if let Some(region) = region {
+            loader = loader.region(Region::new(region.to_string()));
+        }
+        if let Some(client) = &client {
+            loader = loader.http_client(client.clone());
+        }
+        loader
+    };
 
-        if filter.contains(&logical_id.to_owned()) {
-            filtered_resources.push(resource.to_owned());
+    // roles requiring MFA are assumed here, the SDK does not ask for the token code
+    let mut credentials = None;
+    if let Some(role) = mfa::profile_role(args.profile.as_deref()) {
+        let source_config = loader(role.source_profile.as_deref()).load().await;
+        credentials = Some(
+            mfa::assume_role(&source_config, &role)
+                .await
+                .exit_code(ExitCode::AwsApi)?,
+        );
+    }
+    if let Some(role_arn) = &args.role_arn {
+        let mut source_loader = loader(args.profile.as_deref());
+        if let Some(credentials) = credentials.take() {
+            source_loader = source_loader.credentials_provider(credentials);
         }
+        let role = mfa::AssumeRole {
+            role_arn: role_arn.clone(),
+            mfa_serial: args.mfa_serial.clone(),
+            source_profile: None,
+            duration_seconds: None,
+        };
+        credentials = Some(
+            mfa::assume_role(&source_loader.load().await, &role)
+                .await
+                .exit_code(ExitCode::AwsApi)?,
+        );
     }
 
-    Ok(filtered_resources)
+    let mut loader = loader(args.profile.as_deref());
+    if let Some(credentials) = credentials {
+        loader = loader.credentials_provider(credentials);
+    }
+    let config = loader.load().await;
+    regions::check_region(&config, args.partition.as_deref())
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    if let Some(bucket) = &args.template_bucket {
+        template_body::install(aws_sdk_s3::Client::new(&config), bucket);
+    }
+    Ok(config)
 }
 
-async fn select_resources<'a>(
-    prompt: &str,
-    resources: &'a [&aws_sdk_cloudformation::types::StackResourceSummary],
-) -> Result<Vec<&'a aws_sdk_cloudformation::types::StackResourceSummary>, Box<dyn Error>> {
-    let items = format_resources(resources, None).await?;
-    let selection = MultiSelect::with_theme(&ColorfulTheme::default())
-        .with_prompt(prompt)
-        .report(false)
-        .items(&items)
-        .interact_on_opt(&Term::stderr())?;
+/// Prints the outcome of the run for scripts, e.g.
+/// `{"Status":"failure","ExitCode":4,"Message":"..."}`, with the error as the message
+fn print_result(result: &Result<(), Box<dyn Error>>) {
+    let (status, code, message) = match result {
+        Ok(()) => ("success", ExitCode::Success, None),
+        Err(err) => (
+            "failure",
+            error::exit_code_of(err.as_ref()),
+            Some(err.to_string()),
+        ),
+    };
+    println!(
+        "{}",
+        serde_json::json!({
+            "Status": status,
+            "ExitCode": code.code(),
+            "Message": message,
+        })
+    );
+}
 
-    match selection {
-        Some(indices) => Ok(indices
-            .into_iter()
-            .map(|index| resources[index])
-            .collect::<Vec<_>>()),
-        None => Err("User did not select anything".into()),
+/// Prints the number of API calls of the run per service
+fn print_api_calls(budget: &budget::ApiBudget) {
+    let calls = budget
+        .calls()
+        .iter()
+        .map(|(service, count)| format!("{} {}", service, count))
+        .collect::<Vec<_>>();
+    eprintln!("\nAPI calls: {} ({})", budget.total(), calls.join(", "));
+    if budget.is_exhausted() {
+        eprintln!(
+            "The budget of {} API calls has been used up, raise it with --max-api-calls",
+            budget.max_calls().unwrap_or_default()
+        );
     }
 }
 
-fn user_confirm() -> Result<(), Box<dyn Error>> {
-    let confirmed = Confirm::new()
-        .with_prompt("Please confirm your selection:")
-        .default(false)
-        .interact_on_opt(&Term::stderr())?;
+/// Finds Outputs moving along with the resources whose name is taken in the target stack, or whose
+/// export name is taken in the account and region, and asks for new names. The update of the target
+/// stack would fail on such a collision after the resources have been removed from the source
+/// stack. Returns the new output names and export names, keyed by the original output name.
+async fn resolve_output_collisions(
+    client: &cloudformation::Client,
+    (source_stacks, target_stacks): (
+        &[cloudformation::types::StackSummary],
+        &[cloudformation::types::StackSummary],
+    ),
+    (source_stack, target_stack): (&str, &str),
+    (template_source, template_target): (&serde_json::Value, &serde_json::Value),
+    resource_id_map: &HashMap<String, String>,
+    yes: bool,
+) -> Result<(BTreeMap<String, String>, BTreeMap<String, String>), Box<dyn Error>> {
+    let mut names = BTreeMap::new();
+    let mut export_names = BTreeMap::new();
+    let moved_ids = resource_id_map.keys().cloned().collect::<Vec<_>>();
+    let mut outputs = composer::dependent_outputs(template_source, &moved_ids);
+    if outputs.is_empty() {
+        return Ok((names, export_names));
+    }
+    outputs.sort();
 
-    println!();
+    let stack_id = |stacks: &[cloudformation::types::StackSummary], name: &str| {
+        stacks
+            .iter()
+            .find(|stack| stack.stack_name() == Some(name))
+            .and_then(|stack| stack.stack_id())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let source_stack_id = stack_id(source_stacks, source_stack);
+    let target_stack_id = stack_id(target_stacks, target_stack);
+    // arn:aws:cloudformation:REGION:ACCOUNT:stack/NAME/ID
+    let arn = target_stack_id.split(':').collect::<Vec<_>>();
+    let region = arn.get(3).copied().unwrap_or_default();
+    let account = arn.get(4).copied().unwrap_or_default();
 
-    match confirmed {
-        Some(true) => Ok(()),
-        _ => Err("Selection has not been cofirmed".into()),
+    // the exports of the source stack are removed before the target stack is updated
+    let mut taken_exports = get_exports(client)
+        .await
+        .exit_code(ExitCode::AwsApi)?
+        .into_iter()
+        .filter(|(_, exporting_stack)| *exporting_stack != source_stack_id)
+        .map(|(name, _)| name)
+        .collect::<HashSet<_>>();
+    let mut taken_names = template_target["Outputs"]
+        .as_object()
+        .map(|outputs| outputs.keys().cloned().collect::<HashSet<_>>())
+        .unwrap_or_default();
+
+    let mut collisions = Vec::new();
+    for name in outputs {
+        let mut output = template_source["Outputs"][&name].clone();
+        reference_updater::traverse_and_update(&mut output, resource_id_map);
+        if template_target["Outputs"].get(&name) == Some(&output) {
+            // the same output exists in the target stack already
+            continue;
+        }
+
+        if taken_names.contains(&name) {
+            let message = format!("Output {} exists in stack {}", name, target_stack);
+            if yes {
+                collisions.push(message);
+            } else {
+                println!("{}", message);
+                let new_name: String = Input::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!("New name of output {}", name))
+                    .validate_with(|input: &String| -> Result<(), String> {
+                        if !plan::is_valid_logical_id(input) {
+                            Err("Only up to 255 alphanumeric characters are allowed".to_string())
+                        } else if taken_names.contains(input) {
+                            Err(format!("Output {} exists already", input))
+                        } else {
+                            Ok(())
+                        }
+                    })
+                    .interact_text()?;
+                taken_names.insert(new_name.clone());
+                names.insert(name.clone(), new_name);
+            }
+        } else {
+            taken_names.insert(name.clone());
+        }
+
+        let Some(export_name) =
+            analysis::resolve_export_name(&output, (target_stack, region, account))
+        else {
+            continue;
+        };
+        if taken_exports.contains(&export_name) {
+            let message = format!(
+                "Export {} of output {} exists in the account and region",
+                export_name, name
+            );
+            if yes {
+                collisions.push(message);
+            } else {
+                println!("{}", message);
+                let new_export_name: String = Input::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!("New export name of output {}", name))
+                    .validate_with(|input: &String| -> Result<(), String> {
+                        if taken_exports.contains(input) {
+                            Err(format!("Export {} exists already", input))
+                        } else {
+                            Ok(())
+                        }
+                    })
+                    .interact_text()?;
+                taken_exports.insert(new_export_name.clone());
+                export_names.insert(name.clone(), new_export_name);
+            }
+        } else {
+            taken_exports.insert(export_name);
+        }
+    }
+
+    if !collisions.is_empty() {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "Unable to proceed, because Outputs moving along collide with the target stack:\n - {}\nRun without --yes to rename them",
+                collisions.join("\n - ")
+            ),
+        ));
+    }
+
+    Ok((names, export_names))
+}
+
+/// Compares the createOnly properties of the moved resources, as they will be imported, with the
+/// live resources. Such properties cannot change without replacing the resource and the import
+/// would fail with a generic error. For each mismatch, the live value can be taken over into the
+/// definition in the target stack. Resources whose schema or live state cannot be read are skipped.
+async fn resolve_create_only_mismatches(
+    (sdk_config, client): (&aws_config::SdkConfig, &cloudformation::Client),
+    resources: &[&cloudformation::types::StackResourceSummary],
+    (preview, resource_id_map): (&serde_json::Value, &HashMap<String, String>),
+    edited_resources: &mut HashMap<String, serde_json::Value>,
+    yes: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut schemas = HashMap::new();
+    for resource in resources {
+        let resource_type = resource.resource_type().unwrap_or_default();
+        if schemas.contains_key(resource_type) {
+            continue;
+        }
+        let schema = client
+            .describe_type()
+            .r#type(cloudformation::types::RegistryType::Resource)
+            .type_name(resource_type)
+            .send()
+            .await
+            .ok()
+            .and_then(|output| serde_json::from_str(output.schema()?).ok());
+        schemas.insert(resource_type.to_string(), schema);
+    }
+
+    let pairs = resources
+        .iter()
+        .map(|resource| {
+            (
+                resource.resource_type().unwrap_or_default().to_string(),
+                resource
+                    .physical_resource_id()
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let live = BulkDescriber::new(sdk_config)
+        .describe_all(&pairs, &Cancellation::new())
+        .await;
+
+    let mut unresolved = Vec::new();
+    for (resource, live) in resources.iter().zip(live) {
+        let source_id = resource.logical_resource_id().unwrap_or_default();
+        let target_id = resource_id_map
+            .get(source_id)
+            .map_or(source_id, String::as_str);
+        let (Some(Some(schema)), Ok(live)) = (
+            schemas.get(resource.resource_type().unwrap_or_default()),
+            live,
+        ) else {
+            continue;
+        };
+        let mut definition = edited_resources
+            .get(target_id)
+            .unwrap_or(&preview["Resources"][target_id])
+            .clone();
+        let mismatches = analysis::create_only_mismatches(schema, &definition["Properties"], &live);
+        if mismatches.is_empty() {
+            continue;
+        }
+
+        for mismatch in mismatches {
+            let message = format!(
+                "Property {} of {} is createOnly, but the template value {} differs from the live value {}",
+                mismatch.property.trim_start_matches('/'),
+                target_id,
+                mismatch.template,
+                mismatch.live
+            );
+            if yes {
+                unresolved.push(message);
+                continue;
+            }
+            println!("\n{}", message);
+            let choice = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!(
+                    "Resolve {}",
+                    mismatch.property.trim_start_matches('/')
+                ))
+                .items(&["Use the live value", "Abort"])
+                .default(0)
+                .interact()?;
+            if choice == 1 {
+                return Err(fail(
+                    ExitCode::Aborted,
+                    format!(
+                        "The import of {} would fail because of createOnly property {}",
+                        target_id,
+                        mismatch.property.trim_start_matches('/')
+                    ),
+                ));
+            }
+            if let Some(value) = definition["Properties"].pointer_mut(&mismatch.property) {
+                *value = mismatch.live;
+            }
+        }
+        edited_resources.insert(target_id.to_string(), definition);
+    }
+
+    if !unresolved.is_empty() {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "Unable to proceed, because the import would fail:\n - {}\nRun without --yes to use the live values",
+                unresolved.join("\n - ")
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// The plan manifest of the migration of this run
+fn migration_manifest(
+    source_stack: &str,
+    target_stack: &str,
+    resource_id_map: &HashMap<String, String>,
+) -> plan::Plan {
+    let mut resources = resource_id_map
+        .iter()
+        .map(|(source_id, target_id)| format!("{}:{}", source_id, target_id))
+        .collect::<Vec<_>>();
+    resources.sort();
+    plan::Plan {
+        source: source_stack.to_string(),
+        target: target_stack.to_string(),
+        resources,
+        physical_ids: Default::default(),
+    }
+}
+
+/// Composes the templates of the migration with the decisions taken in this run, see
+/// [`planning::plan`], and reduces the target templates if confirmed. The reviewed templates of a
+/// migration executed with `--from-artifacts` or `cfn-teleport apply` replace the computed ones.
+fn compose_migration(
+    (source_stack, target_stack): (&str, &str),
+    (template_source, template_target): (&serde_json::Value, &serde_json::Value),
+    resource_id_map: &HashMap<String, String>,
+    options: &planning::Options,
+    (reviewed, reduce, yes): (&Option<HashMap<String, serde_json::Value>>, bool, bool),
+) -> Result<planning::MigrationPlan, Box<dyn Error>> {
+    let mut migration = planning::plan(
+        &migration_manifest(source_stack, target_stack, resource_id_map),
+        template_source,
+        template_target,
+        options,
+    )
+    .exit_code(ExitCode::Validation)?;
+    let (template_import, template_final) = reduce_target_templates(
+        (
+            migration.template("target-import").clone(),
+            migration.template("target-final").clone(),
+        ),
+        resource_id_map,
+        target_stack,
+        (reduce, yes),
+    )?;
+    migration
+        .templates
+        .insert("target-import".to_string(), template_import);
+    migration
+        .templates
+        .insert("target-final".to_string(), template_final);
+
+    for name in provenance::TEMPLATES {
+        let template = reviewed_template(reviewed, name, migration.template(name).clone());
+        migration.templates.insert(name.to_string(), template);
+    }
+    Ok(migration)
+}
+
+/// Suggests reductions of the copied resources once the composed target template approaches the
+/// quota on the template body, and applies them if confirmed. Returns the template of the import
+/// and the final template.
+fn reduce_target_templates(
+    (template_import, template_final): (serde_json::Value, serde_json::Value),
+    resource_id_map: &HashMap<String, String>,
+    target_stack: &str,
+    (reduce, yes): (bool, bool),
+) -> Result<(serde_json::Value, serde_json::Value), Box<dyn Error>> {
+    // the import template is the larger one, with the DeletionPolicy of the copied resources
+    let size = reduction::body_size(&template_import);
+    let limit = template_body::body_limit();
+    if size < reduction::advice_threshold(limit) {
+        return Ok((template_import, template_final));
+    }
+
+    say!(
+        "\nThe template of stack {} has {} of {} bytes, its largest resources are:",
+        target_stack,
+        size,
+        limit
+    );
+    for (id, bytes) in reduction::largest_resources(&template_import, 5) {
+        say!("  {}: {} bytes", id, bytes);
+    }
+
+    let mut ids = resource_id_map.values().cloned().collect::<Vec<_>>();
+    ids.sort();
+    let reductions = reduction::REDUCTIONS
+        .iter()
+        .filter_map(|reduction| {
+            let reduced = reduction.apply(template_import.clone(), &ids);
+            let saved = size - reduction::body_size(&reduced);
+            (saved > 0).then_some((reduction, saved))
+        })
+        .collect::<Vec<_>>();
+    if reductions.is_empty() {
+        say!("No reductions apply to the copied resources\n");
+        return Ok((template_import, template_final));
+    }
+    say!("Reductions of the copied resources:");
+    for (reduction, saved) in &reductions {
+        say!("  {}: {} bytes", reduction.description(), saved);
+    }
+
+    let apply = reduce
+        || (!yes
+            && Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("Apply the reductions to stack {}?", target_stack))
+                .default(false)
+                .interact()?);
+    if !apply {
+        say!();
+        return Ok((template_import, template_final));
+    }
+
+    let (mut template_import, mut template_final) = (template_import, template_final);
+    for (reduction, _) in reductions {
+        template_import = reduction.apply(template_import, &ids);
+        template_final = reduction.apply(template_final, &ids);
+        tracing::info!(
+            stack = target_stack,
+            reduction = reduction.description(),
+            "Reduced template"
+        );
+    }
+    say!(
+        "Reduced the template of stack {} to {} bytes\n",
+        target_stack,
+        reduction::body_size(&template_import)
+    );
+    Ok((template_import, template_final))
+}
+
+/// Prints the semantic changes of each stack update of a migration
+fn print_dry_run(steps: &[(String, &serde_json::Value, &serde_json::Value)]) {
+    println!("\nDry run, no stack is changed:");
+    for (index, (step, old, new)) in steps.iter().enumerate() {
+        println!("\n{}. {}", index + 1, step);
+        let changes = diff::diff_templates(old, new);
+        if changes.is_empty() {
+            println!("  No changes");
+        }
+        for change in changes {
+            println!("  {}", change);
+        }
+    }
+}
+
+/// Finds transforms of the source stack which the copied resources rely on, but the target stack
+/// does not declare, and asks to declare them in the target stack. Copied without them, the
+/// definitions of the resources are invalid in the target stack. Returns the transforms to add.
+fn resolve_missing_transforms(
+    (template_source, template_target): (&serde_json::Value, &serde_json::Value),
+    resource_id_map: &HashMap<String, String>,
+    target_stack: &str,
+    yes: bool,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut ids = resource_id_map.keys().cloned().collect::<Vec<_>>();
+    ids.sort();
+    let missing = analysis::missing_transforms(template_source, template_target, &ids);
+    if missing.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    say!(
+        "\nThe following resources rely on transforms stack {} does not declare:",
+        target_stack
+    );
+    for transform in &missing {
+        say!(
+            "  {}: {}",
+            transform.transform,
+            transform.resources.join(", ")
+        );
+    }
+
+    let add = yes
+        || Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Add the transforms to stack {}?", target_stack))
+            .default(false)
+            .interact()?;
+    if !add {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "Unable to proceed, because the resources would be invalid in stack {} without the transforms. Declare them in the target stack or rewrite the resources without them",
+                target_stack
+            ),
+        ));
+    }
+    say!();
+
+    Ok(missing
+        .into_iter()
+        .map(|transform| transform.transform)
+        .collect())
+}
+
+/// Finds policies remaining in the source stack which refer to moved resources, e.g. the ARN of a
+/// moved table in the inline policy of a role, and offers to replace the references with their
+/// current values. The source stack could not be updated with references to removed resources.
+/// Returns the values by the referencing expression, like `Table.Arn`.
+async fn resolve_policy_references(
+    sdk_config: &aws_config::SdkConfig,
+    (template_source, source_stack): (&serde_json::Value, &str),
+    resources: &[&cloudformation::types::StackResourceSummary],
+    (resource_id_map, aliases): (&HashMap<String, String>, &HashMap<String, String>),
+    yes: bool,
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let moved_ids = resource_id_map
+        .keys()
+        .chain(aliases.keys())
+        .cloned()
+        .collect::<Vec<_>>();
+    let references = composer::policy_references(template_source, &moved_ids);
+    if references.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let describer = BulkDescriber::new(sdk_config);
+    let mut values = HashMap::new();
+    let mut unresolved = Vec::new();
+    say!(
+        "\nThe following policies of stack {} refer to moved resources:",
+        source_stack
+    );
+    for (policy, expressions) in &references {
+        for expression in expressions {
+            let (id, attribute) = match expression.split_once('.') {
+                Some((id, attribute)) => (id, Some(attribute)),
+                None => (expression.as_str(), None),
+            };
+            let kept_id = aliases.get(id).map_or(id, String::as_str);
+            let physical_id = resources
+                .iter()
+                .find(|resource| resource.logical_resource_id() == Some(kept_id))
+                .and_then(|resource| resource.physical_resource_id())
+                .unwrap_or_default();
+            let resource_type = template_source["Resources"][id]["Type"]
+                .as_str()
+                .unwrap_or_default();
+
+            let value = match attribute {
+                None => Some(physical_id.to_string()),
+                Some(attribute) => describer
+                    .describe(resource_type, physical_id)
+                    .await
+                    .ok()
+                    .and_then(|properties| {
+                        let pointer = format!("/{}", attribute.replace('.', "/"));
+                        properties.pointer(&pointer)?.as_str().map(str::to_string)
+                    })
+                    .or_else(|| {
+                        (attribute == "Arn" && physical_id.starts_with("arn:"))
+                            .then(|| physical_id.to_string())
+                    }),
+            };
+            match value {
+                Some(value) => {
+                    say!("  {}: {} → {}", policy, expression, value);
+                    values.insert(expression.clone(), value);
+                }
+                None => {
+                    say!("  {}: {} → unknown", policy, expression);
+                    unresolved.push(expression.clone());
+                }
+            }
+        }
+    }
+
+    if !unresolved.is_empty() {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "Unable to proceed, because the current values of {} are unknown. Move the policies along or remove the references from them",
+                unresolved.join(", ")
+            ),
+        ));
+    }
+    let replace = yes
+        || Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "Replace the references with the current values in stack {}?",
+                source_stack
+            ))
+            .default(false)
+            .interact()?;
+    if !replace {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "Unable to proceed, because the policies would refer to resources removed from stack {}. Move the policies along or remove the references from them",
+                source_stack
+            ),
+        ));
+    }
+    say!();
+
+    Ok(values)
+}
+
+/// Names of all exports of the account and region, with the ID of the exporting stack
+async fn get_exports(
+    client: &cloudformation::Client,
+) -> Result<Vec<(String, String)>, cloudformation::Error> {
+    let mut exports = Vec::new();
+    let mut token = None;
+
+    loop {
+        let output = client.list_exports().set_next_token(token).send().await?;
+        exports.extend(output.exports().iter().filter_map(|export| {
+            Some((
+                export.name()?.to_string(),
+                export.exporting_stack_id()?.to_string(),
+            ))
+        }));
+
+        token = output.next_token().map(str::to_string);
+        if token.is_none() {
+            return Ok(exports);
+        }
+    }
+}
+
+/// Loads the credentials of `--execute-profile` for the region of the planning credentials. Returns
+/// them with the ARN of their identity, which also makes sure they are valid before anything is
+/// changed.
+async fn load_execute_config(
+    config: &aws_config::SdkConfig,
+    profile: &str,
+) -> Result<(aws_config::SdkConfig, String), Box<dyn Error>> {
+    let loader = |profile: &str| {
+        let mut loader = aws_config::defaults(BehaviorVersion::v2026_01_12())
+            .profile_name(profile)
+            .region(config.region().cloned());
+        if let Some(http_client) = config.http_client() {
+            loader = loader.http_client(http_client);
+        }
+        loader
+    };
+    let mut execute_loader = loader(profile);
+    if let Some(role) = mfa::profile_role(Some(profile)) {
+        let source_config = loader(role.source_profile.as_deref().unwrap_or(profile))
+            .load()
+            .await;
+        execute_loader =
+            execute_loader.credentials_provider(mfa::assume_role(&source_config, &role).await?);
+    }
+    let execute_config = execute_loader.load().await;
+
+    let identity = sts::Client::new(&execute_config)
+        .get_caller_identity()
+        .send()
+        .await
+        .map_err(|err| {
+            format!(
+                "Unable to use the credentials of profile {}: {}",
+                profile,
+                sts::Error::from(err)
+            )
+        })?;
+    Ok((
+        execute_config,
+        identity.arn().unwrap_or_default().to_string(),
+    ))
+}
+
+async fn run(mut args: Args) -> Result<(), Box<dyn Error>> {
+    let reviewed = match (args.from_artifacts.clone(), args.apply_plan.clone()) {
+        (Some(dir), _) => Some(load_artifacts(&mut args, &dir)?),
+        (None, Some(path)) => Some(load_plan_file(&mut args, &path)?),
+        (None, None) => None,
+    };
+    if args.read_only && !args.dry_run && !args.feasibility {
+        return Err(fail(
+            ExitCode::Validation,
+            "Unable to proceed, because a migration changes the stacks. With --read-only, only --dry-run and --feasibility are possible",
+        ));
+    }
+    // all prompts are confirmed, only the start of the migration is delayed by the countdown
+    if args.assume_yes_with_timeout.is_some() {
+        args.yes = true;
+    }
+    let config = config::Config::load(args.config.clone()).exit_code(ExitCode::Validation)?;
+    if args.yes {
+        check_unattended(&args, !config.routing.is_empty())?;
+    }
+    let schedule_at = match &args.schedule_at {
+        Some(timestamp) => {
+            Some(timing::parse_timestamp(timestamp).exit_code(ExitCode::Validation)?)
+        }
+        None => None,
+    };
+    let sdk_config = load_regional_config(&args, source_region(&args)).await?;
+    let client = cloudformation::Client::new(&sdk_config);
+    let stacks = get_stacks(&client).await.exit_code(ExitCode::AwsApi)?;
+
+    // resources are removed in the region of the source stack and imported in the region of the
+    // target stack, each with its own client
+    let target_config = target_config(&args, &sdk_config).await?;
+    let target_client = cloudformation::Client::new(&target_config);
+    let cross_region = target_config.region() != sdk_config.region();
+    let accounts = match args.target_profile.is_some() || args.target_role_arn.is_some() {
+        true => Some((
+            account_of(&sdk_config).await?,
+            account_of(&target_config).await?,
+        )),
+        false => None,
+    };
+    let cross_account = accounts
+        .as_ref()
+        .is_some_and(|(source_account, target_account)| source_account != target_account);
+    let target_stacks = match cross_region || cross_account {
+        true => get_stacks(&target_client)
+            .await
+            .exit_code(ExitCode::AwsApi)?,
+        false => stacks.clone(),
+    };
+
+    let stack_names = filter_stack_names(&stacks, &args.stack_filter);
+    if stack_names.is_empty() && (args.source.is_none() || args.target.is_none()) {
+        return Err(fail(
+            ExitCode::Validation,
+            format!("No stacks found matching {}", args.stack_filter.join(", ")),
+        ));
+    }
+
+    let source_stack = match args.source {
+        Some(source) => source,
+        None => select_stack(
+            "Select source stack",
+            &filter_by_environment(&client, &stack_names).await?,
+            None,
+        )?
+        .to_string(),
+    };
+
+    let (resources, unsupported_resources): (Vec<_>, Vec<_>) =
+        get_all_resources(&client, &source_stack)
+            .await
+            .exit_code(ExitCode::AwsApi)?
+            .into_iter()
+            .partition(is_supported_resource);
+
+    if resources.is_empty() {
+        return Err(fail(
+            ExitCode::Validation,
+            format!("No resources found in stack '{}'", source_stack),
+        ));
+    }
+
+    let resource_refs = &resources
+        .iter()
+        .filter(|resource| {
+            args.resource_type.is_empty()
+                || args.resource_type.iter().any(|pattern| {
+                    config::matches_pattern(pattern, resource.resource_type().unwrap_or_default())
+                })
+        })
+        .collect::<Vec<_>>();
+    if resource_refs.is_empty() {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "No resources of type {} found in stack '{}'",
+                args.resource_type.join(", "),
+                source_stack
+            ),
+        ));
+    }
+
+    let selected_resources = match args.resource.clone() {
+        None if args.all_resources => resource_refs.to_vec(),
+        Some(resource) => {
+            let source_ids = resource
+                .iter()
+                .map(|r| plan::split_ids(r).0)
+                .collect::<Vec<_>>();
+
+            let non_existing_ids: Vec<String> = source_ids
+                .iter()
+                .filter(|id| {
+                    !resource_refs
+                        .iter()
+                        .any(|r| r.logical_resource_id().unwrap_or_default() == **id)
+                })
+                .map(|id| id.to_string())
+                .collect();
+
+            if !non_existing_ids.is_empty() {
+                return Err(fail(
+                    ExitCode::Validation,
+                    format!(
+                        "The following resources do not exist on stack '{}':\n - {}",
+                        source_stack,
+                        non_existing_ids.to_owned().join("\n - "),
+                    ),
+                ));
+            }
+            filter_resources(resource_refs, &source_ids).await?
+        }
+        None => select_resources("Select resources to copy", resource_refs).await?,
+    };
+
+    if selected_resources.is_empty() {
+        return Err(fail(ExitCode::Aborted, "No resources have been selected"));
+    }
+
+    let physical_ids = if !args.physical_id.is_empty() {
+        parse_physical_ids(&args.physical_id, &selected_resources)?
+    } else if args.yes || args.resource.is_some() {
+        HashMap::new()
+    } else {
+        prompt_physical_ids(&selected_resources).await?
+    };
+    let overridden_resources = override_physical_ids(&selected_resources, &physical_ids);
+    let mut selected_resources = overridden_resources.iter().collect::<Vec<_>>();
+
+    let aliases = select_aliases(&mut selected_resources, args.resource.is_some(), args.yes)?;
+
+    let target_stack = match args.target {
+        Some(target) => target,
+        None => {
+            let mut default = None;
+            if !config.routing.is_empty() {
+                let source_tags = get_stack_tags(&client, &source_stack)
+                    .await
+                    .exit_code(ExitCode::AwsApi)?;
+                let resource_types = selected_resources
+                    .iter()
+                    .map(|r| r.resource_type().unwrap_or_default())
+                    .collect::<Vec<_>>();
+                default = config.route(&source_stack, &source_tags, &resource_types);
+            }
+            match default {
+                Some(target) if args.yes => target.to_string(),
+                _ if args.yes => {
+                    return Err(fail(
+                        ExitCode::Validation,
+                        format!(
+                        "No routing rule matches the selected resources of stack {}, pass --target",
+                        source_stack
+                    ),
+                    ))
+                }
+                _ if cross_region || cross_account => select_stack(
+                    "Select target stack",
+                    &filter_stack_names(&target_stacks, &args.stack_filter),
+                    default,
+                )?
+                .to_string(),
+                _ => select_stack("Select target stack", &stack_names, default)?.to_string(),
+            }
+        }
+    };
+
+    if source_stack == target_stack && !cross_region && !cross_account {
+        return Err(fail(
+            ExitCode::Validation,
+            "Source and target stack must be different",
+        ));
+    }
+    let create_target = !target_stacks
+        .iter()
+        .any(|stack| stack.stack_name() == Some(target_stack.as_str()));
+    if create_target {
+        confirm_create_target(&target_stack, args.create_target, args.yes)?;
+    }
+
+    let template_source = get_template(&client, &source_stack)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    check_template_format(&source_stack, &template_source)?;
+    if cross_region {
+        check_cross_region(
+            &selected_resources,
+            &template_source,
+            (
+                sdk_config.region().map_or("", |region| region.as_ref()),
+                target_config.region().map_or("", |region| region.as_ref()),
+            ),
+        )?;
+    }
+    if let Some((source_account, target_account)) = accounts.as_ref().filter(|_| cross_account) {
+        say!(
+            "The resources are removed from stack {} in account {} and imported into stack {} in account {}",
+            source_stack, source_account, target_stack, target_account
+        );
+        check_cross_account(&target_config, &selected_resources, target_account).await?;
+    }
+
+    let mut new_logical_ids_map = HashMap::new();
+
+    match args.resource.clone() {
+        None => {
+            let rename = !args.yes
+                && Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Do you want to rename any of the resources in the target stack?")
+                    .default(false)
+                    .interact()?;
+
+            if rename {
+                let target_ids = match create_target {
+                    true => Vec::new(),
+                    false => get_all_resources(&target_client, &target_stack)
+                        .await
+                        .exit_code(ExitCode::AwsApi)?,
+                }
+                .iter()
+                .map(|r| r.logical_resource_id().unwrap_or_default().to_string())
+                .collect::<HashSet<_>>();
+                new_logical_ids_map =
+                    prompt_logical_ids(&selected_resources, &template_source, target_ids)?;
+            } else {
+                for resource in selected_resources.clone() {
+                    let logical_id = resource.logical_resource_id().unwrap_or_default();
+                    new_logical_ids_map.insert(logical_id.to_string(), logical_id.to_string());
+                }
+            }
+        }
+        Some(resources) => {
+            for resource in resources {
+                let (source_id, target_id) = plan::split_ids(&resource);
+                if !plan::is_valid_logical_id(&target_id) {
+                    return Err(fail(
+                        ExitCode::Validation,
+                        format!(
+                            "Invalid logical ID '{}', only up to 255 alphanumeric characters are allowed",
+                            target_id
+                        ),
+                    ));
+                }
+                new_logical_ids_map.insert(source_id, target_id);
+            }
+        }
+    };
+
+    if args.prefix.is_some() || args.suffix.is_some() {
+        for (source_id, target_id) in new_logical_ids_map.iter_mut() {
+            if source_id != target_id {
+                continue;
+            }
+            *target_id = format!(
+                "{}{}{}",
+                args.prefix.as_deref().unwrap_or_default(),
+                source_id,
+                args.suffix.as_deref().unwrap_or_default()
+            );
+            if !plan::is_valid_logical_id(target_id) {
+                return Err(fail(
+                    ExitCode::Validation,
+                    format!(
+                        "Invalid logical ID '{}' with --prefix and --suffix, only up to 255 alphanumeric characters are allowed",
+                        target_id
+                    ),
+                ));
+            }
+        }
+    }
+
+    let renaming = source_stack == target_stack && !cross_region && !cross_account;
+    if renaming {
+        let duplicate_ids = plan::unrenamed_ids(&new_logical_ids_map);
+        if !duplicate_ids.is_empty() {
+            let error_message = format!(
+                "Unable to proceed, because you said you want to rename resources in stack {} but did not provide new logical IDs for: {}",
+                source_stack,
+                duplicate_ids.join(", ")
+            );
+            return Err(fail(ExitCode::Validation, error_message));
+        }
+
+        say!(
+            "The following resources in stack {} will be renamed:",
+            source_stack
+        );
+    } else if cross_region {
+        say!(
+            "The following resources will be moved from stack {} in region {} to {} in region {}:",
+            source_stack,
+            sdk_config.region().map_or("", |region| region.as_ref()),
+            target_stack,
+            target_config.region().map_or("", |region| region.as_ref())
+        );
+    } else {
+        say!(
+            "The following resources will be moved from stack {} to {}:",
+            source_stack,
+            target_stack
+        );
+    }
+
+    for line in resource_table(&selected_resources, Some(&new_logical_ids_map)).grouped_lines() {
+        say!("  {}", line);
+    }
+
+    let mut alias_ids = aliases.keys().collect::<Vec<_>>();
+    alias_ids.sort();
+    for alias in alias_ids {
+        say!(
+            "  {} refers to the same physical resource as {} and will only be removed from stack {}",
+            alias, aliases[alias], source_stack
+        );
+    }
+
+    let template_target_original = match create_target {
+        true => {
+            say!(
+                "Stack {} does not exist and will be created by importing the resources",
+                target_stack
+            );
+            composer::empty_template()
+        }
+        false => get_template(&target_client, &target_stack)
+            .await
+            .exit_code(ExitCode::AwsApi)?,
+    };
+    check_template_format(&target_stack, &template_target_original)?;
+    check_reviewed_original(
+        &reviewed,
+        "source-original",
+        &source_stack,
+        &template_source,
+    )?;
+    check_reviewed_original(
+        &reviewed,
+        "target-original",
+        &target_stack,
+        &template_target_original,
+    )?;
+
+    if args.all_resources || args.feasibility {
+        let ids = new_logical_ids_map
+            .keys()
+            .chain(aliases.keys())
+            .cloned()
+            .collect::<Vec<_>>();
+        let template_target_added = add_resources(
+            template_target_original.clone(),
+            merge_aliases(template_source.clone(), &aliases),
+            new_logical_ids_map.clone(),
+        )
+        .ok()
+        .map(|(_, template)| template);
+        let feasible = print_feasibility_report(
+            &unsupported_resources.iter().collect::<Vec<_>>(),
+            &template_source,
+            &ids,
+            (&target_stack, template_target_added.as_ref()),
+        )
+        .await?;
+
+        if args.feasibility && !feasible {
+            return Err(fail(
+                ExitCode::Validation,
+                "Moving the selected resources is not feasible",
+            ));
+        } else if args.feasibility {
+            return Ok(());
+        }
+    }
+
+    let target_parameters = match create_target {
+        true => HashMap::new(),
+        false => get_stack_parameters(&target_client, &target_stack)
+            .await
+            .exit_code(ExitCode::AwsApi)?,
+    };
+    let mut warnings = print_condition_preview(
+        &template_source,
+        &template_target_original,
+        &new_logical_ids_map,
+        &target_stack,
+        &target_parameters,
+    );
+
+    let source_tags = get_stack_tags(&client, &source_stack)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    let target_tags = match create_target {
+        true => HashMap::new(),
+        false => get_stack_tags(&target_client, &target_stack)
+            .await
+            .exit_code(ExitCode::AwsApi)?,
+    };
+    warnings += print_tag_changes(
+        &template_source,
+        &new_logical_ids_map,
+        (&source_stack, &source_tags),
+        (&target_stack, &target_tags),
+    );
+
+    warnings += print_moved_outputs(&template_source, &new_logical_ids_map, &source_stack);
+    if cross_region || cross_account {
+        warnings += print_unresolvable_references(
+            &target_config,
+            &template_source,
+            &new_logical_ids_map,
+            &target_stack,
+        )
+        .await;
+    }
+    print_caveats(&selected_resources);
+    let output_renames = resolve_output_collisions(
+        &target_client,
+        (&stacks, &target_stacks),
+        (&source_stack, &target_stack),
+        (&template_source, &template_target_original),
+        &new_logical_ids_map,
+        args.yes,
+    )
+    .await?;
+    let added_transforms = resolve_missing_transforms(
+        (&template_source, &template_target_original),
+        &new_logical_ids_map,
+        &target_stack,
+        args.yes,
+    )?;
+
+    let profiles = match args.sanitize.as_deref() {
+        Some("none") => Vec::new(),
+        Some(name) => sanitize::find(name).into_iter().collect(),
+        None => sanitize::detect(&template_source),
+    };
+    warnings += print_tool_advice(&profiles, &source_stack);
+
+    let mut treatments = BTreeMap::new();
+    for (name, treatment) in &config.attributes {
+        treatments.insert(
+            attributes::attribute(name).exit_code(ExitCode::Validation)?,
+            *treatment,
+        );
+    }
+    for rule in &args.attribute {
+        let (attribute, treatment) =
+            attributes::split_rule(rule).exit_code(ExitCode::Validation)?;
+        treatments.insert(attribute, treatment);
+    }
+    let stripped_attributes = select_stripped_attributes(
+        &template_source,
+        &new_logical_ids_map,
+        &treatments,
+        args.yes,
+    )?;
+    warnings += stripped_attributes.len();
+    let (output_names, export_names) = output_renames;
+    let mut options = planning::Options {
+        aliases: aliases.clone(),
+        transforms: Some(added_transforms),
+        profiles: Some(profiles),
+        stripped_attributes,
+        output_names,
+        export_names,
+        renaming,
+        ..Default::default()
+    };
+
+    let mut edited_resources = HashMap::new();
+    if args.review || args.check_create_only {
+        let preview = planning::plan(
+            &migration_manifest(&source_stack, &target_stack, &new_logical_ids_map),
+            &template_source,
+            &template_target_original,
+            &options,
+        )
+        .exit_code(ExitCode::Validation)?
+        .template("target-final")
+        .clone();
+        if args.review {
+            edited_resources = review_resources(
+                &preview,
+                &template_source,
+                &aliases,
+                &mut new_logical_ids_map,
+            )?;
+            selected_resources.retain(|resource| {
+                new_logical_ids_map.contains_key(resource.logical_resource_id().unwrap_or_default())
+            });
+        }
+        if args.check_create_only {
+            resolve_create_only_mismatches(
+                (&sdk_config, &client),
+                &selected_resources,
+                (&preview, &new_logical_ids_map),
+                &mut edited_resources,
+                args.yes,
+            )
+            .await?;
+        }
+    }
+
+    options.policy_values = resolve_policy_references(
+        &sdk_config,
+        (&template_source, &source_stack),
+        &selected_resources,
+        (&new_logical_ids_map, &aliases),
+        args.yes,
+    )
+    .await?;
+    options.edited_resources = edited_resources;
+
+    if args.strict {
+        warnings += print_source_drift(&client, &source_stack).await?;
+    }
+    if args.strict && warnings > 0 {
+        return Err(fail(
+            ExitCode::Validation,
+            format!("Aborting because of {} warnings in strict mode", warnings),
+        ));
+    }
+
+    if args.dry_run {
+        let migration = compose_migration(
+            (&source_stack, &target_stack),
+            (&template_source, &template_target_original),
+            &new_logical_ids_map,
+            &options,
+            (&reviewed, args.reduce_template, args.yes),
+        )?;
+        print_dry_run(
+            &migration
+                .phases
+                .iter()
+                .map(|phase| {
+                    (
+                        phase.description.clone(),
+                        migration.template(&phase.before),
+                        migration.template(&phase.after),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        );
+        return Ok(());
+    }
+
+    let confirmation = config.confirmation_level([
+        (
+            &source_stack,
+            environment_of(&source_stack, &source_tags).as_deref(),
+        ),
+        (
+            &target_stack,
+            environment_of(&target_stack, &target_tags).as_deref(),
+        ),
+    ]);
+    match confirmation {
+        config::ConfirmationLevel::Interactive => typed_confirm(&target_stack)?,
+        config::ConfirmationLevel::SummaryOnly => match args.assume_yes_with_timeout {
+            Some(seconds) => schedule::countdown(seconds).await?,
+            None if !args.yes => user_confirm()?,
+            None => {}
+        },
+        config::ConfirmationLevel::None => {}
+    }
+
+    if let Some(schedule_at) = schedule_at {
+        schedule::wait_until(schedule_at).await?;
+
+        // the stacks might have changed while waiting
+        check_stack_unchanged(&client, &source_stack, &template_source).await?;
+        if !create_target {
+            check_stack_unchanged(&target_client, &target_stack, &template_target_original).await?;
+        }
+    }
+
+    // privileged credentials are only used from here on, selecting and planning runs with the
+    // default credentials
+    let mut identity = None;
+    let (client, target_client) = match &args.execute_profile {
+        Some(profile) => {
+            let (execute_config, arn) = load_execute_config(&sdk_config, profile)
+                .await
+                .exit_code(ExitCode::AwsApi)?;
+            say!("Executing the migration as {}", arn);
+            identity = Some(arn);
+            // the target stack of another account is changed with the credentials of that account
+            let target_client = match cross_account {
+                true => target_client,
+                false => cloudformation::Client::new(
+                    &execute_config
+                        .to_builder()
+                        .region(target_config.region().cloned())
+                        .build(),
+                ),
+            };
+            (cloudformation::Client::new(&execute_config), target_client)
+        }
+        None => (client, target_client),
+    };
+
+    let started_at = timing::timestamp();
+    let started = std::time::Instant::now();
+
+    let mut cancellation = Cancellation::new();
+    if let Some(timeout) = args.timeout {
+        cancellation = cancellation.with_timeout(std::time::Duration::from_secs(timeout * 60));
+    }
+    handle_interrupts(cancellation.clone());
+
+    let workspace_root = workspace::root(args.workspace.clone());
+    workspace::purge(&workspace_root, workspace::days(args.retention_days))?;
+    let workspace = workspace::Workspace::create(&workspace_root)?;
+    workspace.log(&format!(
+        "Moving resources from stack {} to {}: {:?}",
+        source_stack, target_stack, new_logical_ids_map
+    ))?;
+    if let Some(identity) = &identity {
+        workspace.log(&format!("Executing as {}", identity))?;
+    }
+
+    // other migrations of the stacks are refused until this one is released
+    let state = match &args.state_table {
+        Some(table) => {
+            let user = match &identity {
+                Some(identity) => identity.clone(),
+                None => state::caller(&sdk_config)
+                    .await
+                    .exit_code(ExitCode::AwsApi)?,
+            };
+            let mut resources = new_logical_ids_map.keys().cloned().collect::<Vec<_>>();
+            resources.sort();
+            let registration = state::Registration {
+                stack_name: source_stack.clone(),
+                migration_id: workspace.id(),
+                role: "Source".to_string(),
+                counterpart: target_stack.clone(),
+                user,
+                started_at: started_at.clone(),
+                phase: "Started".to_string(),
+                resources,
+            };
+            let registrations = vec![
+                registration.clone(),
+                state::Registration {
+                    stack_name: target_stack.clone(),
+                    role: "Target".to_string(),
+                    counterpart: source_stack.clone(),
+                    ..registration
+                },
+            ];
+            let state = state::StateTable::register(&sdk_config, table, registrations)
+                .await
+                .exit_code(ExitCode::AwsApi)?;
+            workspace.log(&format!("Registered migration in table {}", table))?;
+            Some(state)
+        }
+        None => None,
+    };
+
+    // resources removed from the source stack are orphaned until they are imported into either stack
+    let mut orphaned = false;
+    let orphan_candidates = selected_resources
+        .iter()
+        .map(|resource| {
+            let source_id = resource.logical_resource_id().unwrap_or_default();
+            recovery::OrphanedResource {
+                source_id: source_id.to_string(),
+                target_id: new_logical_ids_map[source_id].clone(),
+                resource_type: resource.resource_type().unwrap_or_default().to_string(),
+                physical_id: resource
+                    .physical_resource_id()
+                    .unwrap_or_default()
+                    .to_string(),
+                identifier: None,
+                definition: template_source["Resources"][source_id].clone(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let result = async {
+        let resource_ids_to_remove: Vec<_> = new_logical_ids_map
+            .keys()
+            .chain(aliases.keys())
+            .cloned()
+            .collect();
+
+        let migration = compose_migration(
+            (&source_stack, &target_stack),
+            (&template_source, &template_target_original),
+            &new_logical_ids_map,
+            &options,
+            (&reviewed, args.reduce_template, args.yes),
+        )?;
+        for path in &migration.removed {
+            workspace.log(&format!("Removed {}", path))?;
+        }
+        let template_retained = migration.template("source-retained").clone();
+
+        // aliases cannot be imported a second time, they are consolidated when restoring
+        let template_retained_for_restore = merge_aliases(template_retained.clone(), &aliases);
+        let template_source_restored = merge_aliases(template_source.clone(), &aliases);
+
+        let mut template_removed = migration.template("source-removed").clone();
+        let template_target_with_deletion_policy = migration.template("target-import").clone();
+        let mut template_target = migration.template("target-final").clone();
+        for logical_id in options.edited_resources.keys() {
+            workspace.log(&format!("Edited Resources.{} during review", logical_id))?;
+        }
+
+        // the import changeset must not contain other changes, the target is annotated afterwards
+        if args.annotate {
+            let resources = new_logical_ids_map
+                .iter()
+                .map(|(source_id, target_id)| (source_id.clone(), target_id.clone()))
+                .collect();
+            let mut record = history::Record {
+                migration_id: workspace.id(),
+                date: started_at.clone(),
+                direction: history::Direction::Out,
+                counterpart_stack: target_stack.clone(),
+                resources,
+                artifacts: Some(workspace.path().display().to_string()),
+            };
+            history::annotate(&mut template_removed, &record).exit_code(ExitCode::Validation)?;
+            record.direction = history::Direction::In;
+            record.counterpart_stack = source_stack.clone();
+            history::annotate(&mut template_target, &record).exit_code(ExitCode::Validation)?;
+            workspace.log(&format!(
+                "Annotated both stacks with migration {}",
+                record.migration_id
+            ))?;
+        }
+
+        for (name, template) in [
+            ("source-original", &template_source),
+            ("source-retained", &template_retained),
+            ("source-removed", &template_removed),
+            ("target-import", &template_target_with_deletion_policy),
+            ("target-final", &template_target),
+            ("source-restore-import", &template_retained_for_restore),
+            ("source-restore-final", &template_source_restored),
+        ] {
+            workspace.write_json(name, template)?;
+        }
+
+        // the final target template carries the original DeletionPolicy of the moved resources
+        let new_ids = new_logical_ids_map.values().cloned().collect::<Vec<_>>();
+        workspace.write_json(
+            "deletion-policies",
+            &serde_json::json!({
+                &source_stack: deletion_policies(&template_source, &resource_ids_to_remove),
+                &target_stack: deletion_policies(&template_target, &new_ids),
+            }),
+        )?;
+
+        let mut required_capabilities = Vec::new();
+        for (client, stack_name, template) in [
+            (&client, &source_stack, &template_retained),
+            (&client, &source_stack, &template_removed),
+            (&target_client, &target_stack, &template_target),
+            (&target_client, &target_stack, &template_target_with_deletion_policy),
+        ] {
+            let exceeded = analysis::quota_usage(template, template_body::body_limit())
+                .into_iter()
+                .filter(|usage| usage.is_exceeded())
+                .collect::<Vec<_>>();
+            if !exceeded.is_empty() {
+                // without a bucket, only the quota on the request body applies
+                let hint = if exceeded
+                    .iter()
+                    .any(|usage| usage.limit == analysis::TEMPLATE_BODY_LIMIT)
+                {
+                    ". Templates of up to 1 MB can be uploaded to S3 with --template-bucket"
+                } else {
+                    ""
+                };
+                let exceeded = exceeded
+                    .iter()
+                    .map(|usage| usage.to_string())
+                    .collect::<Vec<_>>();
+                return Err(fail(
+                    ExitCode::Validation,
+                    format!(
+                        "Unable to proceed, because the template of stack {} exceeds quotas of CloudFormation: {}{}",
+                        stack_name,
+                        exceeded.join(", "),
+                        hint
+                    ),
+                ));
+            }
+
+            let reported = match validate_template(client, template.clone()).await {
+                Ok(reported) => reported,
+                Err(err) => {
+                    return Err(fail(
+                        ExitCode::Validation,
+                        format!(
+                            "Unable to proceed, because the template is invalid: {}",
+                            err
+                        ),
+                    ))
+                }
+            };
+            collect_capabilities(&mut required_capabilities, stack_name, template, reported);
+        }
+
+        // Parameters copied along with the resources get the values they have in the source stack
+        let source_parameters = get_previous_parameters(&client, &source_stack)
+            .await
+            .exit_code(ExitCode::AwsApi)?;
+        let target_previous_parameters = match create_target {
+            true => Vec::new(),
+            false => get_previous_parameters(&target_client, &target_stack)
+                .await
+                .exit_code(ExitCode::AwsApi)?,
+        };
+        check_copied_parameters(
+            &template_target_with_deletion_policy,
+            &target_previous_parameters,
+            (&source_stack, &template_source),
+            &target_stack,
+        )?;
+
+        let capabilities =
+            acknowledge_capabilities(&required_capabilities, &args.capability, args.yes)?;
+        checkpoint(&workspace, &cancellation, state.as_ref(), Phase::Prepared).await?;
+
+        let stall_timeout = std::time::Duration::from_secs(args.stall_timeout * 60);
+        let source_api = concurrency::Serialized(&client);
+        let target_api = concurrency::Serialized(&target_client);
+        let waiter = waiter::StackWaiter::new(&source_api, stall_timeout, cancellation.clone());
+        let target_waiter =
+            waiter::StackWaiter::new(&target_api, stall_timeout, cancellation.clone());
+
+        let mut new_ids = new_logical_ids_map.values().cloned().collect::<Vec<_>>();
+        new_ids.sort();
+        let executed = migration::execute(
+            (&waiter, &target_waiter),
+            &migration::Migration {
+                source_stack: &source_stack,
+                target_stack: &target_stack,
+                source_original: &template_source,
+                source_retained: &template_retained,
+                source_removed: &template_removed,
+                target_import: &template_target_with_deletion_policy,
+                target_final: &template_target,
+                source_restore: (&template_retained_for_restore, &template_source_restored),
+                source_parameters: &source_parameters,
+                target_parameters: &target_previous_parameters,
+                capabilities: &capabilities,
+                imported: &new_ids,
+                removed: resource_ids_to_remove.len(),
+                rollback: !args.no_rollback,
+                completed: None,
+            },
+            &mut RunHooks {
+                workspace: &workspace,
+                cancellation: &cancellation,
+                state: state.as_ref(),
+                source: (&sdk_config, &client),
+                target: (&target_config, &target_client),
+                stacks: (&source_stack, &target_stack),
+                resources: &selected_resources,
+                new_logical_ids_map: &new_logical_ids_map,
+                removed: resource_ids_to_remove.len(),
+            },
+        )
+        .await;
+        let changeset_id = match executed {
+            Ok(changeset_id) => changeset_id.unwrap_or_default(),
+            Err(failure) => {
+                orphaned = failure.is_orphaned();
+                return migration_failure(
+                    failure,
+                    (&source_stack, &target_stack),
+                    create_target,
+                    &workspace,
+                );
+            }
+        };
+
+        if args.wait_for_stabilization {
+            let message = "Waiting for the moved resources to stabilize";
+            workspace.log(message)?;
+            let mut spinner = spinner::Spin::new("stabilize", message);
+            let resources = selected_resources
+                .iter()
+                .map(|resource| {
+                    let logical_id = resource.logical_resource_id().unwrap_or_default();
+                    stabilization::MovedResource {
+                        logical_id: new_logical_ids_map
+                            .get(logical_id)
+                            .cloned()
+                            .unwrap_or_else(|| logical_id.to_string()),
+                        resource_type: resource.resource_type().unwrap_or_default().to_string(),
+                        physical_id: resource.physical_resource_id().unwrap_or_default().to_string(),
+                    }
+                })
+                .collect();
+            let timeout = std::time::Duration::from_secs(args.stabilization_timeout * 60);
+            if let Err(err) =
+                stabilization::wait(&target_config, resources, timeout, &cancellation, &mut spinner).await
+            {
+                spinner.fail();
+                return Err(err);
+            }
+            spinner.complete();
+            workspace.log("Moved resources are stable")?;
+        }
+
+        // the migration is complete at this point, a failed detection is only a warning
+        let drift = if args.detect_drift {
+            let message = format!("Detecting drift of stack {}", source_stack);
+            workspace.log(&message)?;
+            let mut spinner = spinner::Spin::new("detect-drift", &message);
+            match drift::detect(&client, &source_stack, &mut spinner).await {
+                Ok(report) => {
+                    spinner.complete();
+                    workspace.log(&format!(
+                        "Drift status of stack {}: {}",
+                        source_stack, report.status
+                    ))?;
+                    Some(report)
+                }
+                Err(err) => {
+                    spinner.fail();
+                    workspace.log(&format!("Drift detection failed: {}", err))?;
+                    eprintln!("WARNING: {}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        workspace.log("Migration completed")?;
+        say!(
+            "\nMoved {} resources from stack {} to {}\n  Started:   {}\n  Finished:  {}\n  Duration:  {}\n  Artifacts: {}",
+            new_logical_ids_map.len(),
+            source_stack,
+            target_stack,
+            started_at,
+            timing::timestamp(),
+            timing::format_duration(started.elapsed()),
+            workspace.path().display(),
+        );
+        if let Some(drift) = drift {
+            say!("  Drift:     {} (stack {})", drift.status, source_stack);
+            for resource in drift.drifted {
+                say!("    - {}", resource);
+            }
+        }
+        print_console_links(
+            &target_config,
+            &target_stacks,
+            &target_stack,
+            &changeset_id,
+            &selected_resources,
+            &new_logical_ids_map,
+        );
+
+        Ok::<_, Box<dyn Error>>(())
+    }
+    .await;
+
+    if orphaned && result.is_err() {
+        report_orphaned_resources(
+            &target_client,
+            &workspace,
+            orphan_candidates,
+            (&source_stack, &target_stack),
+        )
+        .await;
+    }
+
+    if let (Some(state), Some(table)) = (&state, &args.state_table) {
+        release_state(state, table, &result, &workspace).await;
+    }
+    result
+}
+
+/// Prints the resources a failed migration removed from the source stack without importing them
+/// anywhere, with the commands to import them into either stack, and writes the report into the
+/// workspace. Reporting must not hide the error of the migration, its own failures are warnings.
+async fn report_orphaned_resources(
+    client: &cloudformation::Client,
+    workspace: &workspace::Workspace,
+    mut resources: Vec<recovery::OrphanedResource>,
+    stacks: (&str, &str),
+) {
+    let identifiers = match workspace.read_json("target-import") {
+        Ok(template) => match template_body::prepare(&template).await {
+            Ok(body) => get_resource_identifier_mapping(client, &body)
+                .await
+                .unwrap_or_default(),
+            Err(_) => HashMap::new(),
+        },
+        Err(_) => HashMap::new(),
+    };
+    for resource in &mut resources {
+        resource.identifier = identifiers
+            .get(&resource.target_id)
+            .and_then(|identifiers| identifiers.first())
+            .cloned();
+    }
+
+    let report = recovery::render(&resources, stacks, &workspace.path().display().to_string());
+    eprintln!("\n{}", report);
+
+    let written = workspace
+        .write_json(
+            "orphaned-resources",
+            &recovery::report_json(&resources, stacks),
+        )
+        .and_then(|()| {
+            workspace.write_json(
+                "orphaned-resources-target",
+                &recovery::target_imports(&resources),
+            )
+        })
+        .and_then(|()| {
+            workspace.write_json(
+                "orphaned-resources-source",
+                &recovery::source_imports(&resources),
+            )
+        })
+        .and_then(|()| {
+            std::fs::write(workspace.path().join("orphaned-resources.txt"), &report)
+                .map_err(Into::into)
+        });
+    match written {
+        Ok(()) => eprintln!(
+            "The report is in {}",
+            workspace.path().join("orphaned-resources.txt").display()
+        ),
+        Err(err) => eprintln!(
+            "WARNING: Unable to write the orphaned-resource report: {}",
+            err
+        ),
+    }
+}
+
+/// Releases the stacks of a migration in the state table, unless the migration left them in a state
+/// which needs attention first
+async fn release_state(
+    state: &state::StateTable,
+    table: &str,
+    result: &Result<(), Box<dyn Error>>,
+    workspace: &workspace::Workspace,
+) {
+    let code = result
+        .as_ref()
+        .err()
+        .map(|err| error::exit_code_of(err.as_ref()));
+    if matches!(
+        code,
+        Some(ExitCode::PartialCompletion) | Some(ExitCode::ChangesetFailed)
+    ) {
+        eprintln!(
+            "NOTE: Migration {} stays registered in table {}. Once the stacks have been cleaned up, release it with cfn-teleport status --release {}",
+            state.migration_id(),
+            table,
+            state.migration_id()
+        );
+        return;
+    }
+
+    match state.release().await {
+        Ok(()) => {
+            let _ = workspace.log(&format!("Released migration in table {}", table));
+        }
+        Err(err) => eprintln!(
+            "WARNING: Unable to release migration {} in table {}: {}",
+            state.migration_id(),
+            table,
+            err
+        ),
+    }
+}
+
+/// Stops the migration at the next safe point on the first Ctrl+C and exits right away on the second
+fn handle_interrupts(cancellation: Cancellation) {
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        cancellation.cancel();
+        eprintln!("\nStopping at the next safe point, press Ctrl+C again to exit right away");
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            process::exit(ExitCode::General.code());
+        }
+    });
+}
+
+/// Records a completed phase in the workspace. If the migration has been cancelled or its deadline
+/// has passed, it stops here, unless that would leave the resources without a stack.
+async fn checkpoint(
+    workspace: &workspace::Workspace,
+    cancellation: &Cancellation,
+    state: Option<&state::StateTable>,
+    phase: Phase,
+) -> Result<(), Box<dyn Error>> {
+    workspace.write_json("state", &serde_json::json!({ "Phase": phase }))?;
+    workspace.log(&format!("Phase {} completed", phase))?;
+    if let Some(state) = state {
+        // the shared state is informational, the migration goes on without it
+        if let Err(err) = state.update_phase(phase).await {
+            eprintln!(
+                "WARNING: Unable to record phase {} in the state table: {}",
+                phase, err
+            );
+        }
+    }
+    if !phase.is_safe_to_stop() || phase == Phase::Completed {
+        return Ok(());
+    }
+
+    if let Err(reason) = cancellation.check() {
+        let interrupted = Interrupted { phase, reason };
+        workspace.log(&interrupted.to_string())?;
+        // setting the DeletionPolicy alone does not count as partial completion
+        let code = if phase < Phase::Removed {
+            ExitCode::Aborted
+        } else {
+            ExitCode::PartialCompletion
+        };
+        let mut message = format!(
+            "{}. The templates to resume the migration are in {}",
+            interrupted,
+            workspace.path().display()
+        );
+        if phase == Phase::Retained {
+            message.push_str(&format!(
+                "\nThe moved resources have a DeletionPolicy of Retain in the source stack, run cfn-teleport cleanup-retain --run {} to restore their original DeletionPolicy",
+                workspace.path().display()
+            ));
+        }
+        return Err(fail(code, message));
+    }
+    Ok(())
+}
+
+/// Decides which attributes of the moved resources are not copied into the target stack, asking
+/// for attributes configured as `ask`. With `--yes`, these are kept. Returns pairs of the logical
+/// ID in the source stack and the attribute.
+fn select_stripped_attributes(
+    source_template: &serde_json::Value,
+    resource_id_map: &HashMap<String, String>,
+    treatments: &BTreeMap<&'static str, attributes::Treatment>,
+    yes: bool,
+) -> Result<Vec<(String, &'static str)>, Box<dyn Error>> {
+    let mut ids = resource_id_map.keys().collect::<Vec<_>>();
+    ids.sort();
+
+    let mut stripped = Vec::new();
+    for id in ids {
+        for attribute in attributes::ATTRIBUTES {
+            let Some(value) = source_template["Resources"][id].get(attribute) else {
+                continue;
+            };
+            let strip = match treatments.get(attribute).copied().unwrap_or_default() {
+                attributes::Treatment::Keep => false,
+                attributes::Treatment::Strip => true,
+                attributes::Treatment::Ask if yes => false,
+                attributes::Treatment::Ask => !Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!(
+                        "Keep {} of resource {}: {}?",
+                        attribute,
+                        resource_id_map[id],
+                        serde_json::to_string(value)?
+                    ))
+                    .default(true)
+                    .interact()?,
+            };
+            if strip {
+                stripped.push((id.to_string(), attribute));
+            }
+        }
+    }
+
+    if !stripped.is_empty() {
+        say!("\nAttributes which are not copied into the target stack:");
+        for (id, attribute) in &stripped {
+            say!("  {}.{}", resource_id_map[id], attribute);
+        }
+        say!();
+    }
+
+    Ok(stripped)
+}
+
+/// Shows the definition each selected resource gets in the target stack and lets the user accept,
+/// edit or skip it. Skipped resources are removed from `resource_id_map`. Returns the edited
+/// definitions by their logical ID in the target stack.
+fn review_resources(
+    preview: &serde_json::Value,
+    template_source: &serde_json::Value,
+    aliases: &HashMap<String, String>,
+    resource_id_map: &mut HashMap<String, String>,
+) -> Result<HashMap<String, serde_json::Value>, Box<dyn Error>> {
+    let mut source_ids = resource_id_map.keys().cloned().collect::<Vec<_>>();
+    source_ids.sort();
+
+    let mut edited = HashMap::new();
+    for source_id in source_ids {
+        let target_id = resource_id_map[&source_id].clone();
+        let mut resource = preview["Resources"][&target_id].clone();
+
+        loop {
+            println!(
+                "\nResource {} as it will be added to the target stack:\n{}",
+                target_id,
+                serde_json::to_string_pretty(&resource)?
+            );
+            let choice = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("Review of {}", target_id))
+                .items(&["Accept", "Edit", "Skip"])
+                .default(0)
+                .interact()?;
+
+            match choice {
+                0 => break,
+                1 => {
+                    let Some(text) = Editor::new()
+                        .extension(".json")
+                        .edit(&serde_json::to_string_pretty(&resource)?)?
+                    else {
+                        continue;
+                    };
+                    match parse_reviewed_resource(&text, &resource) {
+                        Ok(changed) => {
+                            resource = changed;
+                            edited.insert(target_id.clone(), resource.clone());
+                        }
+                        Err(err) => eprintln!("ERROR: {}", err),
+                    }
+                }
+                _ => match skip_blocker(&source_id, template_source, aliases, resource_id_map) {
+                    Some(reason) => eprintln!("ERROR: {} cannot be skipped, {}", source_id, reason),
+                    None => {
+                        resource_id_map.remove(&source_id);
+                        edited.remove(&target_id);
+                        println!("Skipped {}", source_id);
+                        break;
+                    }
+                },
+            }
+        }
+    }
+
+    if resource_id_map.is_empty() {
+        return Err(fail(ExitCode::Aborted, "All resources have been skipped"));
+    }
+
+    Ok(edited)
+}
+
+/// Parses the definition of a resource edited during the review. The type cannot be changed, as the
+/// resource is imported.
+fn parse_reviewed_resource(
+    text: &str,
+    original: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let resource: serde_json::Value =
+        serde_json::from_str(text).map_err(|err| format!("Invalid JSON: {}", err))?;
+    if !resource.is_object() {
+        return Err("The resource definition must be an object".to_string());
+    }
+    if resource["Type"] != original["Type"] {
+        return Err(format!(
+            "The type of the resource cannot be changed from {}",
+            original["Type"]
+        ));
+    }
+    Ok(resource)
+}
+
+/// Why a resource has to be moved along, if it does
+fn skip_blocker(
+    source_id: &str,
+    template_source: &serde_json::Value,
+    aliases: &HashMap<String, String>,
+    resource_id_map: &HashMap<String, String>,
+) -> Option<String> {
+    if aliases.values().any(|kept| kept == source_id) {
+        return Some("other logical IDs of the same physical resource are moved".to_string());
+    }
+    let mut dependents = resource_id_map
+        .keys()
+        .filter(|id| *id != source_id)
+        .filter(|id| {
+            reference_updater::collect_references(&template_source["Resources"][id.as_str()])
+                .contains(source_id)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    dependents.sort();
+    (!dependents.is_empty()).then(|| format!("{} refer to it", dependents.join(", ")))
+}
+
+/// Prints what has to change in the sources of the tools which created the source stack, so their
+/// next deployment does not recreate the moved resources. Returns the number of tools, whose
+/// metadata is scrubbed from the moved resources.
+fn print_tool_advice(profiles: &[&sanitize::Profile], source_stack: &str) -> usize {
+    for profile in profiles {
+        say!(
+            "\nNOTE: Stack {} has been created with {}. {}",
+            source_stack,
+            profile.tool,
+            profile.advice
+        );
+    }
+    if !profiles.is_empty() {
+        say!();
+    }
+    profiles.len()
+}
+
+/// Detects the drift of the source stack before anything is changed and prints the drifted
+/// resources. Returns their number, a failed detection is an error.
+async fn print_source_drift(
+    client: &cloudformation::Client,
+    source_stack: &str,
+) -> Result<usize, Box<dyn Error>> {
+    let message = format!("Detecting drift of stack {}", source_stack);
+    let mut spinner = spinner::Spin::new("detect-drift", &message);
+    let report = match drift::detect(client, source_stack, &mut spinner).await {
+        Ok(report) => {
+            spinner.complete();
+            report
+        }
+        Err(err) => {
+            spinner.fail();
+            return Err(fail(ExitCode::AwsApi, err.to_string()));
+        }
+    };
+    if !report.drifted.is_empty() {
+        say!(
+            "
+WARNING: Stack {} has drifted:",
+            source_stack
+        );
+        for resource in &report.drifted {
+            say!("  {}", resource);
+        }
+        say!();
+    }
+    Ok(report.drifted.len())
+}
+
+/// Prints links to the target stack, the import changeset and the moved resources in the AWS
+/// console
+fn print_console_links(
+    sdk_config: &aws_config::SdkConfig,
+    stacks: &[cloudformation::types::StackSummary],
+    target_stack: &str,
+    changeset_id: &str,
+    resources: &[&cloudformation::types::StackResourceSummary],
+    resource_id_map: &HashMap<String, String>,
+) {
+    let region = sdk_config
+        .region()
+        .map(|region| region.to_string())
+        .unwrap_or_default();
+    let links = links::ConsoleLinks::new(regions::partition_of(&region), &region);
+    let stack_id = stacks
+        .iter()
+        .find(|stack| stack.stack_name() == Some(target_stack))
+        .and_then(|stack| stack.stack_id())
+        .unwrap_or(target_stack);
+
+    say!("\nAWS console:");
+    say!("  Stack:     {}", links.stack(stack_id));
+    say!("  Changeset: {}", links.changeset(stack_id, changeset_id));
+    for resource in resources {
+        let logical_id = resource.logical_resource_id().unwrap_or_default();
+        let link = links.resource(
+            resource.resource_type().unwrap_or_default(),
+            resource.physical_resource_id().unwrap_or_default(),
+        );
+        if let Some(link) = link {
+            let new_logical_id = resource_id_map
+                .get(logical_id)
+                .map_or(logical_id, String::as_str);
+            say!("  {}: {}", new_logical_id, link);
+        }
+    }
+}
+
+async fn get_stacks(
+    client: &impl StackApi,
+) -> Result<Vec<cloudformation::types::StackSummary>, Box<dyn Error>> {
+    let mut stacks = client
+        .list_stacks()
+        .await
+        .map_err(|err| err as Box<dyn Error>)?;
+
+    if DEMO {
+        // filter by name, for demo purposes
+        stacks = stacks
+            .into_iter()
+            .filter(|stack| {
+                stack
+                    .stack_name()
+                    .unwrap_or_default()
+                    .contains("CfnTeleportTest")
+            })
+            .collect::<Vec<_>>();
+    }
+
+    // Sort the stacks by name
+    let mut sorted_stacks = stacks;
+    sorted_stacks.sort_by_key(|stack| stack.stack_name().unwrap_or_default().to_string());
+
+    Ok(sorted_stacks)
+}
+
+/// Environment tag keys, checked in this order. Stacks without any of these tags fall back to the
+/// prefix of their name, e.g. `prod` for `prod-api`.
+const ENVIRONMENT_TAGS: [&str; 3] = ["env", "Env", "Environment"];
+
+async fn get_stack_environments(
+    client: &cloudformation::Client,
+) -> Result<HashMap<String, String>, cloudformation::Error> {
+    let mut environments = HashMap::new();
+    let mut token = None;
+
+    loop {
+        let resp = client
+            .describe_stacks()
+            .set_next_token(token)
+            .send()
+            .await?;
+
+        for stack in resp.stacks() {
+            let environment = ENVIRONMENT_TAGS.iter().find_map(|key| {
+                stack
+                    .tags()
+                    .iter()
+                    .find(|tag| tag.key() == Some(key))
+                    .and_then(|tag| tag.value())
+            });
+            if let (Some(name), Some(environment)) = (stack.stack_name(), environment) {
+                environments.insert(name.to_string(), environment.to_string());
+            }
+        }
+
+        match resp.next_token() {
+            Some(next_token) => token = Some(next_token.to_owned()),
+            None => break,
+        }
+    }
+
+    Ok(environments)
+}
+
+fn stack_environment(stack_name: &str, environments: &HashMap<String, String>) -> Option<String> {
+    match environments.get(stack_name) {
+        Some(environment) => Some(environment.clone()),
+        None => stack_name
+            .split_once(['-', '_'])
+            .map(|(prefix, _)| prefix.to_string()),
+    }
+}
+
+/// Environment of a stack by its tags, or else by the prefix of its name
+fn environment_of(stack_name: &str, tags: &HashMap<String, String>) -> Option<String> {
+    match ENVIRONMENT_TAGS.iter().find_map(|key| tags.get(*key)) {
+        Some(environment) => Some(environment.clone()),
+        None => stack_environment(stack_name, &HashMap::new()),
+    }
+}
+
+/// Offers to narrow the stacks to pick the source stack from to an environment. The target stack is
+/// picked from all stacks, so resources can be moved between environments. The tags of the stacks
+/// are only described if enough stacks are left to form two environments.
+async fn filter_by_environment<'a>(
+    client: &cloudformation::Client,
+    stack_names: &[&'a str],
+) -> Result<Vec<&'a str>, Box<dyn Error>> {
+    // two environments with more than one stack each
+    if stack_names.len() < 4 {
+        return Ok(stack_names.to_vec());
+    }
+    let environments = get_stack_environments(client)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    let Some(environment) = select_environment(stack_names, &environments)? else {
+        return Ok(stack_names.to_vec());
+    };
+    Ok(stack_names
+        .iter()
+        .filter(|name| {
+            stack_environment(name, &environments).as_deref() == Some(environment.as_str())
+        })
+        .copied()
+        .collect())
+}
+
+fn select_environment(
+    stack_names: &[&str],
+    environments: &HashMap<String, String>,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for stack_name in stack_names {
+        if let Some(environment) = stack_environment(stack_name, environments) {
+            match counts.iter_mut().find(|(env, _)| *env == environment) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((environment, 1)),
+            }
+        }
+    }
+
+    // A prefix used by a single stack is most likely not an environment
+    counts.retain(|(_, count)| *count > 1);
+    if counts.len() < 2 {
+        return Ok(None);
+    }
+    counts.sort();
+
+    let mut items = vec![format!("All environments ({} stacks)", stack_names.len())];
+    items.extend(
+        counts
+            .iter()
+            .map(|(environment, count)| format!("{} ({} stacks)", environment, count)),
+    );
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Filter stacks by environment")
+        .items(&items)
+        .report(false)
+        .default(0)
+        .interact_on_opt(&Term::stderr())?;
+
+    match selection {
+        Some(0) => Ok(None),
+        Some(index) => Ok(Some(counts[index - 1].0.clone())),
+        None => Err(fail(ExitCode::Aborted, "User did not select anything")),
+    }
+}
+
+/// Names of the stacks matching any of the patterns, or of all stacks without patterns
+fn filter_stack_names<'a>(
+    stacks: &'a [cloudformation::types::StackSummary],
+    patterns: &[String],
+) -> Vec<&'a str> {
+    stacks
+        .iter()
+        .map(|s| s.stack_name().unwrap_or_default())
+        .filter(|name| {
+            patterns.is_empty()
+                || patterns
+                    .iter()
+                    .any(|pattern| config::matches_pattern(pattern, name))
+        })
+        .collect()
+}
+
+fn select_stack<'a>(
+    prompt: &str,
+    items: &'a [&str],
+    default: Option<&str>,
+) -> Result<&'a str, Box<dyn Error>> {
+    let default_index = default
+        .and_then(|default| items.iter().position(|item| *item == default))
+        .unwrap_or(0);
+
+    // typing filters the stacks, accounts can have hundreds of them
+    let selection = FuzzySelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(items)
+        .report(false)
+        .default(default_index)
+        .interact_on_opt(&Term::stderr())?;
+
+    match selection {
+        Some(index) => Ok(items[index]),
+        None => Err(fail(ExitCode::Aborted, "User did not select anything")),
+    }
+}
+
+fn is_supported_resource(resource: &cloudformation::types::StackResourceSummary) -> bool {
+    let resource_type = resource.resource_type().unwrap_or_default();
+    import_support::supports(resource_type)
+}
+
+/// Finds selected resources which refer to the same physical resource. Only one logical ID of
+/// each physical resource can be imported, the others are removed from `resources` and returned
+/// as aliases of the kept logical ID.
+fn select_aliases(
+    resources: &mut Vec<&cloudformation::types::StackResourceSummary>,
+    explicit: bool,
+    yes: bool,
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut groups: Vec<Vec<&cloudformation::types::StackResourceSummary>> = Vec::new();
+    for resource in resources.iter() {
+        let key = (resource.resource_type(), resource.physical_resource_id());
+        match groups.iter_mut().find(|group| {
+            key.1.is_some_and(|id| !id.is_empty())
+                && (group[0].resource_type(), group[0].physical_resource_id()) == key
+        }) {
+            Some(group) => group.push(resource),
+            None => groups.push(vec![resource]),
+        }
+    }
+
+    let mut aliases = HashMap::new();
+    for group in groups.iter().filter(|group| group.len() > 1) {
+        let ids = group
+            .iter()
+            .map(|r| r.logical_resource_id().unwrap_or_default())
+            .collect::<Vec<_>>();
+        let physical_id = group[0].physical_resource_id().unwrap_or_default();
+
+        if explicit {
+            return Err(fail(
+                ExitCode::Validation,
+                format!(
+                    "The resources {} refer to the same physical resource {}, select only one of them",
+                    ids.join(", "),
+                    physical_id
+                ),
+            ));
+        }
+
+        let kept = if yes {
+            0
+        } else {
+            Select::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!(
+                    "The resources {} refer to the same physical resource {}. Which logical ID do you want to keep?",
+                    ids.join(", "),
+                    physical_id
+                ))
+                .items(&ids)
+                .default(0)
+                .interact()?
+        };
+
+        for (index, id) in ids.iter().enumerate() {
+            if index != kept {
+                aliases.insert(id.to_string(), ids[kept].to_string());
+            }
+        }
+    }
+
+    resources.retain(|r| !aliases.contains_key(r.logical_resource_id().unwrap_or_default()));
+    Ok(aliases)
+}
+
+/// Asks for the logical ID of each resource in the target stack. `taken` holds the logical IDs
+/// of the target stack, IDs entered during the session are offered as history.
+fn prompt_logical_ids(
+    resources: &[&cloudformation::types::StackResourceSummary],
+    template: &serde_json::Value,
+    mut taken: HashSet<String>,
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut history = BasicHistory::new().no_duplicates(true);
+    let mut new_logical_ids_map = HashMap::new();
+
+    for resource in resources {
+        let logical_id = resource.logical_resource_id().unwrap_or_default();
+        let suggestion = plan::suggest_logical_id(
+            &suggested_logical_id(resource, &template["Resources"][logical_id]),
+            &taken,
+        );
+
+        let new_logical_id: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "New logical ID for {} ({})",
+                logical_id,
+                resource.resource_type().unwrap_or_default()
+            ))
+            .default(suggestion)
+            .history_with(&mut history)
+            .validate_with(|input: &String| -> Result<(), String> {
+                if !plan::is_valid_logical_id(input) {
+                    Err("Only up to 255 alphanumeric characters are allowed".to_string())
+                } else if taken.contains(input) {
+                    Err(format!("Logical ID {} is already in use", input))
+                } else {
+                    Ok(())
+                }
+            })
+            .interact_text()?;
+
+        taken.insert(new_logical_id.clone());
+        new_logical_ids_map.insert(logical_id.to_string(), new_logical_id);
+    }
+
+    Ok(new_logical_ids_map)
+}
+
+/// Parses the `--physical-id` overrides, which must refer to selected resources
+fn parse_physical_ids(
+    values: &[String],
+    resources: &[&cloudformation::types::StackResourceSummary],
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut physical_ids = HashMap::new();
+    for value in values {
+        let (logical_id, physical_id) =
+            plan::split_physical_id(value).exit_code(ExitCode::Validation)?;
+        if !resources
+            .iter()
+            .any(|r| r.logical_resource_id() == Some(logical_id.as_str()))
+        {
+            return Err(fail(
+                ExitCode::Validation,
+                format!(
+                    "Unable to override the physical ID of {}, because the resource has not been selected",
+                    logical_id
+                ),
+            ));
+        }
+        physical_ids.insert(logical_id, physical_id);
+    }
+    Ok(physical_ids)
+}
+
+/// Asks for the physical IDs to import resources with, for resources whose ID recorded in the
+/// stack is stale, e.g. after they have been replaced manually
+async fn prompt_physical_ids(
+    resources: &[&cloudformation::types::StackResourceSummary],
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut physical_ids = HashMap::new();
+
+    let edit = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Do you want to override the physical ID of any of the resources?")
+        .default(false)
+        .interact()?;
+    if !edit {
+        return Ok(physical_ids);
+    }
+
+    for resource in select_resources("Select resources to override", resources).await? {
+        let logical_id = resource.logical_resource_id().unwrap_or_default();
+        let recorded_id = resource.physical_resource_id().unwrap_or_default();
+        let physical_id: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Physical ID of {}", logical_id))
+            .default(recorded_id.to_string())
+            .validate_with(|input: &String| -> Result<(), &str> {
+                if input.trim().is_empty() {
+                    Err("The physical ID must not be empty")
+                } else {
+                    Ok(())
+                }
+            })
+            .interact_text()?;
+
+        if physical_id != recorded_id {
+            physical_ids.insert(logical_id.to_string(), physical_id);
+        }
+    }
+
+    Ok(physical_ids)
+}
+
+/// Copies of the resources with the physical IDs replaced by the given overrides
+fn override_physical_ids(
+    resources: &[&cloudformation::types::StackResourceSummary],
+    physical_ids: &HashMap<String, String>,
+) -> Vec<cloudformation::types::StackResourceSummary> {
+    resources
+        .iter()
+        .map(|resource| {
+            let mut resource = (*resource).clone();
+            if let Some(physical_id) =
+                physical_ids.get(resource.logical_resource_id().unwrap_or_default())
+            {
+                resource.physical_resource_id = Some(physical_id.clone());
+            }
+            resource
+        })
+        .collect()
+}
+
+/// Human-readable logical ID for a resource, derived from its CDK construct path or, if the
+/// current logical ID is generated, its physical name
+fn suggested_logical_id(
+    resource: &cloudformation::types::StackResourceSummary,
+    definition: &serde_json::Value,
+) -> String {
+    let logical_id = resource.logical_resource_id().unwrap_or_default();
+    definition["Metadata"]["aws:cdk:path"]
+        .as_str()
+        .and_then(plan::logical_id_from_cdk_path)
+        .or_else(|| {
+            resource
+                .physical_resource_id()
+                .filter(|_| plan::has_generated_suffix(logical_id))
+                .and_then(plan::logical_id_from_name)
+        })
+        .unwrap_or_else(|| logical_id.to_string())
+}
+
+/// Lists all resources of a stack, including those which do not support being imported
+async fn get_all_resources(
+    client: &impl StackApi,
+    stack_name: &str,
+) -> Result<Vec<cloudformation::types::StackResourceSummary>, Box<dyn Error>> {
+    stack_api::list_all_resources(client, stack_name)
+        .await
+        .map_err(|err| err as Box<dyn Error>)
+}
+
+/// Prints which resources of the source stack cannot be moved and which entries remaining in the
+/// source stack would reference moved resources. Returns whether the move is feasible.
+async fn print_feasibility_report(
+    unsupported_resources: &[&cloudformation::types::StackResourceSummary],
+    source_template: &serde_json::Value,
+    moved_ids: &[String],
+    (target_stack, target_template): (&str, Option<&serde_json::Value>),
+) -> Result<bool, Box<dyn Error>> {
+    println!("\nFeasibility report:");
+
+    if unsupported_resources.is_empty() {
+        println!("  All resources of the source stack support being imported");
+    } else {
+        println!(
+            "  {} resources cannot be moved, because their type does not support being imported:",
+            unsupported_resources.len()
+        );
+        for resource in format_resources(unsupported_resources, None).await? {
+            println!("    {}", resource);
+        }
+    }
+
+    let remaining_template = remove_resources(source_template.clone(), moved_ids.to_vec());
+    let count = |section: &str| {
+        remaining_template
+            .get(section)
+            .and_then(|s| s.as_object())
+            .map_or(0, |s| s.len())
+    };
+    println!(
+        "  The source stack would retain {} resources and {} outputs",
+        count("Resources"),
+        count("Outputs"),
+    );
+
+    let mut exceeded = Vec::new();
+    if let Some(target_template) = target_template {
+        let usage = analysis::quota_usage(target_template, template_body::body_limit());
+        exceeded = usage
+            .iter()
+            .filter(|usage| usage.is_exceeded())
+            .cloned()
+            .collect();
+        if exceeded.is_empty() {
+            println!(
+                "  The template of stack {} would stay within the quotas of CloudFormation ({})",
+                target_stack, usage[0]
+            );
+        } else {
+            println!(
+                "  The template of stack {} would exceed quotas of CloudFormation:",
+                target_stack
+            );
+            for usage in &exceeded {
+                println!("    {}", usage);
+            }
+        }
+    }
+
+    let dangling = analysis::dangling_references(source_template, moved_ids);
+    if dangling.is_empty() {
+        println!("  No remaining resources or outputs depend on the moved resources\n");
+    } else {
+        println!("  The following remaining entries depend on moved resources and block the move:");
+        for reference in &dangling {
+            println!("    {}", reference);
+        }
+        println!();
+    }
+
+    Ok(dangling.is_empty() && exceeded.is_empty())
+}
+
+async fn filter_resources<'a>(
+    resources: &'a [&aws_sdk_cloudformation::types::StackResourceSummary],
+    filter: &[String],
+) -> Result<Vec<&'a aws_sdk_cloudformation::types::StackResourceSummary>, Box<dyn Error>> {
+    let mut filtered_resources = Vec::new();
+
+    for resource in resources {
+        let logical_id = resource.logical_resource_id().unwrap_or_default();
+
+        if filter.contains(&logical_id.to_owned()) {
+            filtered_resources.push(resource.to_owned());
+        }
+    }
+
+    Ok(filtered_resources)
+}
+
+/// Lists with more resources than this offer to narrow them down to some resource types first
+const TYPE_FILTER_THRESHOLD: usize = 20;
+
+async fn select_resources<'a>(
+    prompt: &str,
+    resources: &'a [&aws_sdk_cloudformation::types::StackResourceSummary],
+) -> Result<Vec<&'a aws_sdk_cloudformation::types::StackResourceSummary>, Box<dyn Error>> {
+    let resources = &filter_resource_types(resources)?;
+    let items = format_resources(resources, None).await?;
+    let mut chosen = vec![false; items.len()];
+    loop {
+        let indices = MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .report(false)
+            .items(&items)
+            .defaults(&chosen)
+            .interact_on_opt(&Term::stderr())?
+            .ok_or_else(|| fail(ExitCode::Aborted, "User did not select anything"))?;
+        if items.len() <= TYPE_FILTER_THRESHOLD || indices.is_empty() {
+            return Ok(indices.into_iter().map(|index| resources[index]).collect());
+        }
+
+        // in a long list, a mis-click is easier to spot and undo among the selected resources only
+        let selected_items = indices
+            .iter()
+            .map(|index| items[*index].as_str())
+            .collect::<Vec<_>>();
+        let review = MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "Review the {} selected resources, deselect to remove them (Esc to go back to all resources)",
+                indices.len()
+            ))
+            .report(false)
+            .items(&selected_items)
+            .defaults(&vec![true; indices.len()])
+            .interact_on_opt(&Term::stderr())?;
+        match review {
+            Some(kept) => {
+                return Ok(kept
+                    .into_iter()
+                    .map(|position| resources[indices[position]])
+                    .collect())
+            }
+            None => {
+                chosen = vec![false; items.len()];
+                indices.iter().for_each(|index| chosen[*index] = true);
+            }
+        }
+    }
+}
+
+/// Asks for the resource types to choose resources from, if there are many resources of different
+/// types. Selecting no type keeps all resources.
+fn filter_resource_types<'a>(
+    resources: &[&'a aws_sdk_cloudformation::types::StackResourceSummary],
+) -> Result<Vec<&'a aws_sdk_cloudformation::types::StackResourceSummary>, Box<dyn Error>> {
+    let mut counts = BTreeMap::new();
+    for resource in resources {
+        *counts
+            .entry(resource.resource_type().unwrap_or_default())
+            .or_insert(0) += 1;
+    }
+    if resources.len() <= TYPE_FILTER_THRESHOLD || counts.len() < 2 {
+        return Ok(resources.to_vec());
+    }
+
+    let types = counts.keys().copied().collect::<Vec<_>>();
+    let items = counts
+        .iter()
+        .map(|(resource_type, count)| format!("{} ({})", resource_type, count))
+        .collect::<Vec<_>>();
+    let selection = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "{} resources, filter by type (select none for all)",
+            resources.len()
+        ))
+        .report(false)
+        .items(&items)
+        .interact_on_opt(&Term::stderr())?
+        .ok_or_else(|| fail(ExitCode::Aborted, "User did not select anything"))?;
+
+    if selection.is_empty() {
+        return Ok(resources.to_vec());
+    }
+    let selected = selection
+        .into_iter()
+        .map(|index| types[index])
+        .collect::<HashSet<_>>();
+    Ok(resources
+        .iter()
+        .filter(|resource| selected.contains(resource.resource_type().unwrap_or_default()))
+        .copied()
+        .collect())
+}
+
+/// Makes sure a stack is ready for an update and its template is still the one the migration has
+/// been prepared with
+async fn check_stack_unchanged(
+    client: &cloudformation::Client,
+    stack_name: &str,
+    template: &serde_json::Value,
+) -> Result<(), Box<dyn Error>> {
+    let resp = client
+        .describe_stacks()
+        .stack_name(stack_name)
+        .send()
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    let status = resp
+        .stacks()
+        .first()
+        .and_then(|stack| stack.stack_status())
+        .map(|status| status.as_str())
+        .unwrap_or_default();
+    if status.ends_with("_IN_PROGRESS") || status.ends_with("_FAILED") {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "Stack {} is not ready for an update: {}",
+                stack_name, status
+            ),
+        ));
+    }
+
+    let current = get_template(client, stack_name)
+        .await
+        .exit_code(ExitCode::AwsApi)?;
+    if !diff::equivalent(&current, template) {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "The template of stack {} has changed since the migration was scheduled",
+                stack_name
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Verifies the artifacts of `cfn-teleport plan --output-dir` against their manifest and takes the
+/// stacks and resources of the migration from it. Returns the reviewed templates by artifact name.
+fn load_artifacts(
+    args: &mut Args,
+    dir: &std::path::Path,
+) -> Result<HashMap<String, serde_json::Value>, Box<dyn Error>> {
+    let manifest = Manifest::read(dir).exit_code(ExitCode::Validation)?;
+    manifest.verify(dir).exit_code(ExitCode::Validation)?;
+    if manifest.tool_version != env!("CARGO_PKG_VERSION") {
+        eprintln!(
+            "The artifacts in {} have been exported by cfn-teleport {}, this is version {}",
+            dir.display(),
+            manifest.tool_version,
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+
+    let mut templates = HashMap::new();
+    for name in provenance::TEMPLATES.iter().chain(&provenance::ORIGINALS) {
+        let file = format!("{}.json", name);
+        if !manifest.files.contains_key(&file) {
+            return Err(fail(
+                ExitCode::Validation,
+                format!("The manifest in {} does not list {}", dir.display(), file),
+            ));
+        }
+        let body = std::fs::read_to_string(dir.join(&file))?;
+        let template = serde_json::from_str(&body)
+            .map_err(|err| fail(ExitCode::Validation, format!("Invalid {}: {}", file, err)))?;
+        templates.insert(name.to_string(), template);
+    }
+
+    let plan = manifest.plan;
+    say!(
+        "Executing the reviewed migration of {} from stack {} to {} (digests verified)",
+        dir.display(),
+        plan.source,
+        plan.target
+    );
+    use_plan(args, plan);
+    Ok(templates)
+}
+
+/// Reads the plan file of `cfn-teleport apply` and sets the migration of its plan. Returns the
+/// templates of the plan file by their name.
+fn load_plan_file(
+    args: &mut Args,
+    path: &std::path::Path,
+) -> Result<HashMap<String, serde_json::Value>, Box<dyn Error>> {
+    let plan_file = PlanFile::read(path).exit_code(ExitCode::Validation)?;
+    let key = std::env::var(provenance::SIGNING_KEY_ENV).ok();
+    plan_file
+        .verify(key.as_deref().map(str::as_bytes))
+        .map_err(|err| format!("Unable to apply plan file {}: {}", path.display(), err))
+        .exit_code(ExitCode::Validation)?;
+    if plan_file.tool_version != env!("CARGO_PKG_VERSION") {
+        eprintln!(
+            "The plan file {} has been created by cfn-teleport {}, this is version {}",
+            path.display(),
+            plan_file.tool_version,
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+    if let Some(name) = provenance::TEMPLATES
+        .iter()
+        .chain(&provenance::ORIGINALS)
+        .find(|name| !plan_file.templates.contains_key(**name))
+    {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "The plan file {} lacks the template {}",
+                path.display(),
+                name
+            ),
+        ));
+    }
+
+    say!(
+        "Applying the plan {} to move resources from stack {} to {} ({} verified)",
+        path.display(),
+        plan_file.plan.source,
+        plan_file.plan.target,
+        if key.is_some() { "signature" } else { "digest" }
+    );
+    use_plan(args, plan_file.plan);
+    Ok(plan_file.templates.into_iter().collect())
+}
+
+/// Makes sure a stack still has the template a reviewed migration has been computed from. Else the
+/// reviewed templates would revert the changes made to the stack since, and drop the resources
+/// added to it.
+fn check_reviewed_original(
+    reviewed: &Option<HashMap<String, serde_json::Value>>,
+    name: &str,
+    stack_name: &str,
+    current: &serde_json::Value,
+) -> Result<(), Box<dyn Error>> {
+    let Some(planned) = reviewed.as_ref().and_then(|templates| templates.get(name)) else {
+        return Ok(());
+    };
+    if !diff::equivalent(current, planned) {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "The template of stack {} has changed since the migration was planned, plan it again",
+                stack_name
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Sets the stacks, resources and physical IDs of the migration to the ones of a plan
+fn use_plan(args: &mut Args, plan: plan::Plan) {
+    args.source = Some(plan.source);
+    args.target = Some(plan.target);
+    args.resource = Some(plan.resources);
+    args.physical_id = plan
+        .physical_ids
+        .into_iter()
+        .map(|(logical_id, physical_id)| format!("{}={}", logical_id, physical_id))
+        .collect();
+}
+
+/// The reviewed template of a migration executed with `--from-artifacts` or `cfn-teleport apply`,
+/// else the computed one
+fn reviewed_template(
+    reviewed: &Option<HashMap<String, serde_json::Value>>,
+    artifact: &str,
+    computed: serde_json::Value,
+) -> serde_json::Value {
+    let template = match reviewed
+        .as_ref()
+        .and_then(|templates| templates.get(artifact).cloned())
+    {
+        Some(template) => {
+            tracing::info!(
+                artifact,
+                "Using the reviewed template instead of the computed one"
+            );
+            template
+        }
+        None => computed,
+    };
+    tracing::debug!(
+        artifact,
+        resources = template["Resources"]
+            .as_object()
+            .map_or(0, |resources| resources.len()),
+        bytes = template.to_string().len(),
+        "Composed template"
+    );
+    tracing::trace!(artifact, "{}", template);
+    template
+}
+
+/// Region of the source stack, if set explicitly
+fn source_region(args: &Args) -> Option<&str> {
+    args.source_region.as_deref().or(args.region.as_deref())
+}
+
+/// Configuration for the account and region of the target stack. Without separate credentials for
+/// the target stack, it is derived from the configuration of the source stack, so credentials are
+/// only resolved, and MFA token codes asked for, once.
+async fn target_config(
+    args: &Args,
+    config: &aws_config::SdkConfig,
+) -> Result<aws_config::SdkConfig, Box<dyn Error>> {
+    let region = args.target_region.as_deref().or(args.region.as_deref());
+    if args.target_profile.is_some() || args.target_role_arn.is_some() {
+        let mut target_args = args.clone();
+        target_args.profile = args.target_profile.clone().or(target_args.profile);
+        target_args.role_arn = args.target_role_arn.clone();
+        let region = region.or(config.region().map(|region| region.as_ref()));
+        return load_regional_config(&target_args, region).await;
+    }
+    match region {
+        Some(region) if Some(region) != config.region().map(|region| region.as_ref()) => {
+            let target_config = config
+                .to_builder()
+                .region(Region::new(region.to_string()))
+                .build();
+            regions::check_region(&target_config, args.partition.as_deref())
+                .await
+                .exit_code(ExitCode::AwsApi)?;
+            Ok(target_config)
+        }
+        _ => Ok(config.clone()),
+    }
+}
+
+/// Fails if Parameters would be copied into the target stack whose value cannot be read from the
+/// source stack, see [`stack_api::masked_parameters`]
+fn check_copied_parameters(
+    template: &serde_json::Value,
+    previous: &[cloudformation::types::Parameter],
+    (source_stack, source_template): (&str, &serde_json::Value),
+    target_stack: &str,
+) -> Result<(), Box<dyn Error>> {
+    let masked = stack_api::masked_parameters(template, previous, source_template);
+    if masked.is_empty() {
+        return Ok(());
+    }
+    Err(fail(
+        ExitCode::Validation,
+        format!(
+            "Unable to copy the NoEcho parameters {} of stack {}, because their values cannot be read. Add them to stack {} first",
+            masked.join(", "),
+            source_stack,
+            target_stack
+        ),
+    ))
+}
+
+/// Fails unless all resources can be moved into a stack of the target region, see
+/// [`import_support::supports_in_region`]
+fn check_cross_region(
+    resources: &[&cloudformation::types::StackResourceSummary],
+    template: &serde_json::Value,
+    (source_region, region): (&str, &str),
+) -> Result<(), Box<dyn Error>> {
+    let regional = resources
+        .iter()
+        .filter(|resource| {
+            let logical_id = resource.logical_resource_id().unwrap_or_default();
+            !import_support::supports_in_region(
+                resource.resource_type().unwrap_or_default(),
+                &template["Resources"][logical_id],
+                (source_region, region),
+            )
+        })
+        .map(|resource| {
+            format!(
+                "{} ({})",
+                resource.logical_resource_id().unwrap_or_default(),
+                resource.resource_type().unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>();
+    if regional.is_empty() {
+        return Ok(());
+    }
+    Err(fail(
+        ExitCode::Validation,
+        format!(
+            "Unable to proceed, because CloudFormation only imports resources which exist in region {}. Only resources of global services like IAM, and DynamoDB global tables with a replica in the region, can be moved between regions:\n - {}",
+            region,
+            regional.join("\n - ")
+        ),
+    ))
+}
+
+/// Asks whether to create a target stack which does not exist. Without a prompt, the stack is only
+/// created with `--create-target`, so a typo in the name of the target stack does not create one.
+fn confirm_create_target(
+    target_stack: &str,
+    create_target: bool,
+    assume_yes: bool,
+) -> Result<(), Box<dyn Error>> {
+    if create_target {
+        return Ok(());
+    }
+    if assume_yes {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "Stack {} does not exist, pass --create-target to create it",
+                target_stack
+            ),
+        ));
+    }
+    let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Stack {} does not exist. Do you want to create it with the moved resources?",
+            target_stack
+        ))
+        .default(false)
+        .interact()?;
+    match confirmed {
+        true => Ok(()),
+        false => Err(fail(
+            ExitCode::Aborted,
+            format!("Stack {} does not exist", target_stack),
+        )),
+    }
+}
+
+/// ID of the account of the credentials of a configuration
+async fn account_of(config: &aws_config::SdkConfig) -> Result<String, Box<dyn Error>> {
+    let arn = state::caller(config).await.exit_code(ExitCode::AwsApi)?;
+    // arn:aws:sts::ACCOUNT:assumed-role/NAME/SESSION
+    Ok(arn.split(':').nth(4).unwrap_or_default().to_string())
+}
+
+/// Fails if resources do not exist in the account of the target stack. CloudFormation only imports
+/// resources of its own account, so moving a resource to another account requires a resource with
+/// the same identifier there, e.g. a table created and filled in advance. Resources which cannot be
+/// looked up are left to the import.
+async fn check_cross_account(
+    config: &aws_config::SdkConfig,
+    resources: &[&cloudformation::types::StackResourceSummary],
+    account: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut missing = Vec::new();
+    for resource in resources {
+        let resource_type = resource.resource_type().unwrap_or_default();
+        let physical_id = resource.physical_resource_id().unwrap_or_default();
+        if identifiers::exists(config, resource_type, physical_id).await == Some(false) {
+            missing.push(format!(
+                "{} ({} {})",
+                resource.logical_resource_id().unwrap_or_default(),
+                resource_type,
+                physical_id
+            ));
+        }
+    }
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(fail(
+        ExitCode::Validation,
+        format!(
+            "Unable to proceed, because CloudFormation only imports resources of the account of the stack, and these resources do not exist in account {}. Create them there first, or import other resources with --physical-id:\n - {}",
+            account,
+            missing.join("\n - ")
+        ),
+    ))
+}
+
+/// Fails if inputs which would be asked for interactively are missing in a run with `--yes`. The
+/// target stack may also come from a routing rule of the config.
+fn check_unattended(args: &Args, routed: bool) -> Result<(), Box<dyn Error>> {
+    let missing = [
+        ("--source", args.source.is_none()),
+        ("--target", args.target.is_none() && !routed),
+        (
+            "--resource or --all-resources",
+            args.resource.is_none() && !args.all_resources,
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, missing)| *missing)
+    .map(|(option, _)| option)
+    .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(fail(
+        ExitCode::Validation,
+        format!(
+            "--yes runs without any prompts and requires {} as well",
+            missing.join(", ")
+        ),
+    ))
+}
+
+fn user_confirm() -> Result<(), Box<dyn Error>> {
+    let confirmed = Confirm::new()
+        .with_prompt("Please confirm your selection:")
+        .default(false)
+        .interact_on_opt(&Term::stderr())?;
+
+    println!();
+
+    match confirmed {
+        Some(true) => Ok(()),
+        _ => Err(fail(ExitCode::Aborted, "Selection has not been cofirmed")),
+    }
+}
+
+/// Asks to type the name of the target stack, which the config requires for some environments
+/// even with `--yes`
+fn typed_confirm(target_stack: &str) -> Result<(), Box<dyn Error>> {
+    let term = Term::stderr();
+    if !term.is_term() {
+        return Err(fail(
+            ExitCode::Aborted,
+            format!(
+                "The config requires to confirm migrations into stack {} by typing its name, which is not possible without a terminal",
+                target_stack
+            ),
+        ));
+    }
+
+    let typed: String = Input::new()
+        .with_prompt(format!("Type {} to confirm the migration", target_stack))
+        .allow_empty(true)
+        .interact_text_on(&term)?;
+    println!();
+
+    if typed != target_stack {
+        return Err(fail(ExitCode::Aborted, "Migration has not been confirmed"));
     }
+    Ok(())
 }
 
-async fn get_template(
+async fn get_stack_tags(
     client: &cloudformation::Client,
     stack_name: &str,
-) -> Result<serde_json::Value, Box<dyn Error>> {
-    let resp = client.get_template().stack_name(stack_name).send().await?;
-    let template = resp.template_body().ok_or("No template found")?;
-    let parsed_template = serde_json::from_str(template)?;
-    Ok(parsed_template)
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let resp = client
+        .describe_stacks()
+        .stack_name(stack_name)
+        .send()
+        .await?;
+    let stack = resp.stacks().first().ok_or("Stack not found")?;
+
+    Ok(stack
+        .tags()
+        .iter()
+        .filter_map(|tag| Some((tag.key()?.to_string(), tag.value()?.to_string())))
+        .collect())
 }
 
-async fn format_resources(
-    resources: &[&cloudformation::types::StackResourceSummary],
-    resource_id_map: Option<HashMap<String, String>>,
-) -> Result<Vec<String>, io::Error> {
-    let mut max_lengths = [0; 3];
-    let mut formatted_resources = Vec::new();
+/// Parameter values of a stack, including pseudo parameters which can be derived from the stack
+async fn get_stack_parameters(
+    client: &cloudformation::Client,
+    stack_name: &str,
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let resp = client
+        .describe_stacks()
+        .stack_name(stack_name)
+        .send()
+        .await?;
+    let stack = resp.stacks().first().ok_or("Stack not found")?;
 
-    let mut renamed = false;
+    let mut parameters = HashMap::new();
+    for parameter in stack.parameters() {
+        let value = parameter.resolved_value().or(parameter.parameter_value());
+        if let (Some(key), Some(value)) = (parameter.parameter_key(), value) {
+            parameters.insert(key.to_string(), value.to_string());
+        }
+    }
 
-    for resource in resources.iter() {
-        let resource_type = resource.resource_type().unwrap_or_default();
-        let logical_id = resource.logical_resource_id().unwrap_or_default();
+    // arn:<partition>:cloudformation:<region>:<account>:stack/<name>/<id>
+    let stack_id = stack.stack_id().unwrap_or_default();
+    let arn = stack_id.split(':').collect::<Vec<_>>();
+    if arn.len() >= 5 {
+        parameters.insert("AWS::Partition".to_string(), arn[1].to_string());
+        parameters.insert(
+            "AWS::URLSuffix".to_string(),
+            regions::url_suffix(arn[1]).to_string(),
+        );
+        parameters.insert("AWS::Region".to_string(), arn[3].to_string());
+        parameters.insert("AWS::AccountId".to_string(), arn[4].to_string());
+    }
+    parameters.insert("AWS::StackId".to_string(), stack_id.to_string());
+    parameters.insert("AWS::StackName".to_string(), stack_name.to_string());
 
-        let new_logical_id = match resource_id_map {
-            Some(ref map) => match map.get(logical_id) {
-                Some(new_id) => new_id.to_string(),
-                None => logical_id.to_string(),
-            },
-            None => logical_id.to_string(),
-        };
+    Ok(parameters)
+}
 
-        max_lengths[0] = max_lengths[0].max(resource_type.len());
-        max_lengths[1] = max_lengths[1].max(logical_id.len());
-        if logical_id != new_logical_id {
-            max_lengths[2] = max_lengths[2].max(new_logical_id.len());
-            renamed = true;
-        }
-    }
+/// Shows whether moved resources with a `Condition` would be active in the target stack. The
+/// condition is taken from the target template if it exists there, otherwise from the source.
+/// Returns the number of conditions which are false or cannot be evaluated.
+fn print_condition_preview(
+    source_template: &serde_json::Value,
+    target_template: &serde_json::Value,
+    resource_id_map: &HashMap<String, String>,
+    target_stack: &str,
+    target_parameters: &HashMap<String, String>,
+) -> usize {
+    let mut lines = Vec::new();
+    let mut warnings = 0;
 
-    for resource in resources.iter() {
-        let resource_type = resource.resource_type().unwrap_or_default();
-        let logical_id = resource.logical_resource_id().unwrap_or_default();
-        let physical_id = resource.physical_resource_id().unwrap_or_default();
-        let new_logical_id = match resource_id_map {
-            Some(ref map) => match map.get(logical_id) {
-                Some(new_id) => new_id.to_string(),
-                None => logical_id.to_string(),
-            },
-            None => logical_id.to_string(),
+    let mut ids = resource_id_map.keys().collect::<Vec<_>>();
+    ids.sort();
+
+    for id in ids {
+        let condition = source_template["Resources"][id]["Condition"].as_str();
+        let Some(condition) = condition else {
+            continue;
         };
 
-        let output = if renamed {
-            let renamed = if logical_id != new_logical_id {
-                format!(" ► {}", new_logical_id)
-            } else {
-                "".to_string()
-            };
-            format!(
-                "{:<width1$}  {:<width2$}{:<width3$}   {}",
-                resource_type,
-                logical_id,
-                renamed,
-                physical_id,
-                width1 = max_lengths[0] + 2,
-                width2 = max_lengths[1],
-                width3 = max_lengths[2] + 4
-            )
+        let template = if target_template["Conditions"][condition].is_null() {
+            source_template
         } else {
-            format!(
-                "{:<width1$}  {:<width2$}  {}",
-                resource_type,
-                logical_id,
-                physical_id,
-                width1 = max_lengths[0] + 2,
-                width2 = max_lengths[1] + 2
-            )
+            target_template
         };
 
-        formatted_resources.push(output);
+        let outcome = match conditions::evaluate_condition(template, condition, target_parameters) {
+            Ok(true) => "true, resource is active".to_string(),
+            Ok(false) => {
+                warnings += 1;
+                "false, WARNING: resource would be considered absent".to_string()
+            }
+            Err(err) => {
+                warnings += 1;
+                format!("unknown, {}", err)
+            }
+        };
+        lines.push(format!(
+            "  {} ({}) ► {}",
+            resource_id_map[id], condition, outcome
+        ));
+    }
+
+    if !lines.is_empty() {
+        say!(
+            "\nConditions of moved resources evaluated with the parameters of stack {}:",
+            target_stack
+        );
+        lines.iter().for_each(|line| say!("{}", line));
+        say!();
     }
 
-    Ok(formatted_resources)
+    warnings
 }
 
-fn retain_resources(
-    mut template: serde_json::Value,
-    resource_ids: Vec<String>,
-) -> serde_json::Value {
-    let resources = template["Resources"].as_object_mut().unwrap();
+/// Warns about stack tags propagated to the moved resources, which will be replaced by the tags of
+/// the target stack. Returns the number of affected resources.
+fn print_tag_changes(
+    source_template: &serde_json::Value,
+    resource_id_map: &HashMap<String, String>,
+    (source_stack, source_tags): (&str, &HashMap<String, String>),
+    (target_stack, target_tags): (&str, &HashMap<String, String>),
+) -> usize {
+    let mut lines = Vec::new();
+    let mut warnings = 0;
+
+    let mut ids = resource_id_map.keys().collect::<Vec<_>>();
+    ids.sort();
 
-    for resource_id in resource_ids {
-        if let Some(resource) = resources.get_mut(&resource_id) {
-            resource["DeletionPolicy"] = serde_json::Value::String("Retain".to_string());
+    for id in ids {
+        let changes = analysis::propagated_tag_changes(
+            &source_template["Resources"][id],
+            source_tags,
+            target_tags,
+        );
+        if changes.is_empty() {
+            continue;
+        }
+
+        warnings += 1;
+        lines.push(format!("  {}", resource_id_map[id]));
+        for change in changes {
+            lines.push(match (change.old_value, change.new_value) {
+                (Some(old), Some(new)) => format!("    ~ {}: {} → {}", change.key, old, new),
+                (Some(old), None) => format!("    - {}: {}", change.key, old),
+                (None, Some(new)) => format!("    + {}: {}", change.key, new),
+                (None, None) => continue,
+            });
         }
     }
 
-    template
+    if !lines.is_empty() {
+        say!(
+            "\nWARNING: Tags propagated from stack {} will be replaced by the tags of stack {} on the next update of taggable resources:",
+            source_stack, target_stack
+        );
+        lines.iter().for_each(|line| say!("{}", line));
+        say!();
+    }
+
+    warnings
 }
 
-// for reasons unknown, importing resource requires a DeletionPolicy to be set. Se we add the documented defaults
-// https://docs.aws.amazon.com/AWSCloudFormation/latest/UserGuide/aws-attribute-deletionpolicy.html
-fn set_default_deletion_policy(
-    mut template: serde_json::Value,
-    resource_ids: Vec<String>,
-) -> serde_json::Value {
-    let resources = template["Resources"].as_object_mut().unwrap();
-
-    for resource_id in resource_ids {
-        if let Some(resource) = resources.get_mut(&resource_id) {
-            if resource.is_object() {
-                let resource_object = resource.as_object_mut().unwrap();
-                if !resource_object.contains_key("DeletionPolicy") {
-                    let resource_type = resource_object["Type"].as_str().unwrap();
-                    let deletion_policy = match resource_type {
-                        "AWS::RDS::DBCluster" => "Snapshot",
-                        "AWS::RDS::DBInstance" => {
-                            if resource_object.contains_key("DBClusterIdentifier") {
-                                "Delete"
-                            } else {
-                                "Snapshot"
-                            }
-                        }
-                        _ => "Delete",
-                    };
-                    resource["DeletionPolicy"] =
-                        serde_json::Value::String(deletion_policy.to_string());
-                }
-            }
+/// Lists the Outputs which move along with the resources and warns about their exports, which
+/// have to be free of imports to be removed from the source stack. Returns the number of exports.
+fn print_moved_outputs(
+    source_template: &serde_json::Value,
+    resource_id_map: &HashMap<String, String>,
+    source_stack: &str,
+) -> usize {
+    let moved_ids = resource_id_map.keys().cloned().collect::<Vec<_>>();
+    let outputs = composer::dependent_outputs(source_template, &moved_ids);
+    if outputs.is_empty() {
+        return 0;
+    }
+
+    say!("\nOutputs moving along with the resources:");
+    let mut exports = Vec::new();
+    for name in &outputs {
+        say!("  {}", name);
+        if source_template["Outputs"][name].get("Export").is_some() {
+            exports.push(name.as_str());
         }
     }
 
-    template
+    if !exports.is_empty() {
+        say!(
+            "\nWARNING: The exports of {} are removed from stack {} before they are created in the target stack. The removal fails while other stacks import them, and export names based on AWS::StackName change.",
+            exports.join(", "),
+            source_stack
+        );
+    }
+    say!();
+
+    exports.len()
 }
 
-fn remove_resources(
-    mut template: serde_json::Value,
-    resource_ids: Vec<String>,
-) -> serde_json::Value {
-    let resources = template["Resources"].as_object_mut().unwrap();
+/// Warns about dynamic references of the moved resources which cannot be resolved in the account
+/// and region of the target stack. CloudFormation only resolves them during the import, after the
+/// resources have been removed from the source stack. Returns the number of warnings.
+async fn print_unresolvable_references(
+    config: &aws_config::SdkConfig,
+    source_template: &serde_json::Value,
+    resource_id_map: &HashMap<String, String>,
+    target_stack: &str,
+) -> usize {
+    let mut ids = resource_id_map.keys().cloned().collect::<Vec<_>>();
+    ids.sort();
 
-    for resource_id in resource_ids {
-        resources.remove(&resource_id);
+    let mut lines = Vec::new();
+    for reference in analysis::dynamic_references(source_template, &ids) {
+        let problem = match dynamic_references::exists(config, &reference).await {
+            Ok(true) => continue,
+            Ok(false) => "does not exist".to_string(),
+            Err(err) => format!("cannot be verified: {}", err),
+        };
+        lines.push(format!(
+            "  {}: {} {}",
+            resource_id_map[&reference.resource_id], reference.reference, problem
+        ));
     }
 
-    template
+    if !lines.is_empty() {
+        say!(
+            "\nWARNING: Dynamic references of moved resources might not resolve for stack {}, which makes the import fail after the resources have been removed from the source stack:",
+            target_stack
+        );
+        lines.iter().for_each(|line| say!("{}", line));
+        say!();
+    }
+
+    lines.len()
 }
 
-fn add_resources(
-    mut target_template: serde_json::Value,
-    source_template: serde_json::Value,
-    resource_id_map: HashMap<String, String>,
-) -> (serde_json::Value, serde_json::Value) {
-    let target_resources = target_template["Resources"].as_object_mut().unwrap();
-    let source_resources = source_template["Resources"].as_object().unwrap();
+/// Warns about the caveats of the types of the selected resources. They are not counted as warnings
+/// for `--strict`, as they describe the type rather than the migration.
+fn print_caveats(resources: &[&cloudformation::types::StackResourceSummary]) {
+    let caveats = caveats::caveats(
+        resources
+            .iter()
+            .map(|resource| resource.resource_type().unwrap_or_default()),
+    );
+    if caveats.is_empty() {
+        return;
+    }
 
-    for (resource_id, new_resource_id) in resource_id_map.clone() {
-        if let Some(resource) = source_resources.get(&resource_id) {
-            target_resources.insert(new_resource_id, resource.clone());
-        }
+    say!("\nWARNING: Resources of these types depend on their stack beyond the template:");
+    for caveat in caveats {
+        say!("  {}: {}", caveat.resource_type, caveat.warning);
     }
+    say!();
+}
 
-    let target_template_with_deletion_policy = set_default_deletion_policy(
-        target_template.clone(),
-        resource_id_map.values().map(|x| x.to_string()).collect(),
-    );
+/// The Parameters of a stack with their current values
+async fn get_previous_parameters(
+    client: &impl StackApi,
+    stack_name: &str,
+) -> Result<Vec<cloudformation::types::Parameter>, Box<dyn Error>> {
+    client
+        .stack_parameters(stack_name)
+        .await
+        .map_err(|err| err as Box<dyn Error>)
+}
+
+async fn get_template(
+    client: &impl StackApi,
+    stack_name: &str,
+) -> Result<serde_json::Value, Box<dyn Error>> {
+    client
+        .get_template(stack_name)
+        .await
+        .map_err(|err| err as Box<dyn Error>)
+}
 
-    (target_template_with_deletion_policy, target_template)
+/// Fails if the template of a stack is not shaped the way cfn-teleport can compose it
+fn check_template_format(
+    stack_name: &str,
+    template: &serde_json::Value,
+) -> Result<(), Box<dyn Error>> {
+    let errors = analysis::format_errors(template);
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(fail(
+        ExitCode::Validation,
+        format!(
+            "Unable to proceed, because the template of stack {} is malformed:\n - {}",
+            stack_name,
+            errors.join("\n - ")
+        ),
+    ))
+}
+
+fn resource_table(
+    resources: &[&cloudformation::types::StackResourceSummary],
+    resource_id_map: Option<&HashMap<String, String>>,
+) -> render::ResourceTable {
+    render::ResourceTable::new(
+        resources
+            .iter()
+            .map(|resource| {
+                let logical_id = resource.logical_resource_id().unwrap_or_default();
+                let row = render::ResourceRow::new(
+                    resource.resource_type().unwrap_or_default(),
+                    logical_id,
+                    resource.physical_resource_id().unwrap_or_default(),
+                );
+                match resource_id_map.and_then(|map| map.get(logical_id)) {
+                    Some(new_logical_id) => row.renamed_to(new_logical_id),
+                    None => row,
+                }
+            })
+            .collect(),
+    )
+}
+
+async fn format_resources(
+    resources: &[&cloudformation::types::StackResourceSummary],
+    resource_id_map: Option<HashMap<String, String>>,
+) -> Result<Vec<String>, io::Error> {
+    Ok(resource_table(resources, resource_id_map.as_ref()).lines())
 }
 
+/// Validates a template and returns the capabilities CloudFormation reports as required
 async fn validate_template(
     client: &cloudformation::Client,
     template: serde_json::Value,
-) -> Result<(), cloudformation::Error> {
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let body = template_body::prepare(&template)
+        .await
+        .map_err(|err| err as Box<dyn Error>)?;
     match client
         .validate_template()
-        .template_body(serde_json::to_string(&template).unwrap())
+        .set_template_body(body.body())
+        .set_template_url(body.url())
         .send()
         .await
     {
-        Ok(_output) => Ok(()),
-        Err(err) => Err(err.into()),
+        Ok(output) => Ok(output
+            .capabilities()
+            .iter()
+            .map(|capability| capability.as_str().to_string())
+            .collect()),
+        Err(err) => Err(cloudformation::Error::from(err).into()),
     }
 }
 
-async fn update_stack(
-    client: &cloudformation::Client,
+/// Adds the capabilities required by a template of a stack. Capabilities only reported by
+/// CloudFormation, e.g. for nested stacks, are added without the resources requiring them.
+fn collect_capabilities(
+    required: &mut Vec<(String, capabilities::Requirement)>,
     stack_name: &str,
-    template: serde_json::Value,
-) -> Result<(), cloudformation::Error> {
-    match client
-        .update_stack()
-        .stack_name(stack_name)
-        .template_body(serde_json::to_string(&template).unwrap())
-        // @TODO: we can detect the required capabilities from the output of validate_template()
-        .capabilities(cloudformation::types::Capability::CapabilityIam)
-        .capabilities(cloudformation::types::Capability::CapabilityNamedIam)
-        .capabilities(cloudformation::types::Capability::CapabilityAutoExpand)
-        .send()
-        .await
-    {
-        Ok(_output) => Ok(()),
-        Err(err) => Err(err.into()),
+    template: &serde_json::Value,
+    reported: Vec<String>,
+) {
+    let mut requirements = capabilities::required_capabilities(template);
+    for capability in capabilities::CAPABILITIES {
+        if reported.iter().any(|reported| reported == capability)
+            && !requirements.iter().any(|r| r.capability == capability)
+        {
+            requirements.push(capabilities::Requirement {
+                capability,
+                sources: Vec::new(),
+            });
+        }
+    }
+
+    for requirement in requirements {
+        match required.iter_mut().find(|(stack, existing)| {
+            stack == stack_name && existing.capability == requirement.capability
+        }) {
+            Some((_, existing)) => {
+                for source in requirement.sources {
+                    if !existing.sources.contains(&source) {
+                        existing.sources.push(source);
+                    }
+                }
+            }
+            None => required.push((stack_name.to_string(), requirement)),
+        }
     }
 }
 
-async fn get_stack_status(
-    client: &cloudformation::Client,
-    stack_name: &str,
-) -> Result<Option<cloudformation::types::StackStatus>, Box<dyn std::error::Error>> {
-    let describe_stacks_output = match client.describe_stacks().stack_name(stack_name).send().await
-    {
-        Ok(output) => output,
-        Err(err) => return Err(Box::new(err)),
-    };
+/// Lists the resources requiring each capability and asks to acknowledge the capabilities which
+/// have not been passed with `--capability`, like the AWS console does. Returns the capabilities to
+/// deploy the templates with.
+fn acknowledge_capabilities(
+    required: &[(String, capabilities::Requirement)],
+    acknowledged: &[String],
+    yes: bool,
+) -> Result<Vec<cloudformation::types::Capability>, Box<dyn Error>> {
+    let mut result = Vec::new();
 
-    let stacks = describe_stacks_output.stacks();
-    let stack = stacks.first();
+    for capability in capabilities::CAPABILITIES {
+        let requirements = required
+            .iter()
+            .filter(|(_, requirement)| requirement.capability == capability)
+            .collect::<Vec<_>>();
+        if requirements.is_empty() {
+            continue;
+        }
 
-    if let Some(stack) = stack {
-        Ok(stack.stack_status.clone())
-    } else {
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            "Failed to determine stack status",
-        )))
-    }
-}
+        say!("\n{} is required by:", capability);
+        for (stack_name, requirement) in requirements {
+            let sources = if requirement.sources.is_empty() {
+                "reported by CloudFormation".to_string()
+            } else {
+                requirement.sources.join(", ")
+            };
+            say!("  {}: {}", stack_name, sources);
+        }
 
-async fn wait_for_stack_update_completion(
-    client: &cloudformation::Client,
-    stack_name: &str,
-    mut spinner: Option<spinner::Spin>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut stack_status = get_stack_status(client, stack_name).await?;
-
-    while let Some(status) = stack_status.clone() {
-        if status == cloudformation::types::StackStatus::UpdateInProgress
-            || status == cloudformation::types::StackStatus::UpdateCompleteCleanupInProgress
-            || status == cloudformation::types::StackStatus::ImportInProgress
-        {
-            std::thread::sleep(std::time::Duration::from_secs(1));
-            stack_status = get_stack_status(client, stack_name).await?;
-        } else {
-            if status != cloudformation::types::StackStatus::UpdateComplete
-                && status != cloudformation::types::StackStatus::ImportComplete
-            {
-                return Err(
-                    format!("Stack update failed {}", stack_status.unwrap().as_str()).into(),
-                );
+        if !acknowledged.iter().any(|a| a == capability) {
+            if yes {
+                return Err(fail(
+                    ExitCode::Validation,
+                    format!(
+                        "{} has not been acknowledged, pass --capability {} to do so",
+                        capability, capability
+                    ),
+                ));
+            }
+            let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(capabilities::acknowledgement(capability))
+                .default(false)
+                .interact()?;
+            if !confirmed {
+                return Err(fail(
+                    ExitCode::Aborted,
+                    format!("{} has not been acknowledged", capability),
+                ));
             }
-            break;
         }
+        result.push(cloudformation::types::Capability::from(capability));
     }
 
-    if let Some(spinner) = spinner.as_mut() {
-        spinner.complete();
-    }
-
-    Ok(())
+    Ok(result)
 }
 
+/// The identifier properties of the resources of a template, by logical ID
 async fn get_resource_identifier_mapping(
     client: &cloudformation::Client,
-    template_body: &str,
-) -> Result<HashMap<String, String>, cloudformation::Error> {
+    body: &TemplateBody,
+) -> Result<HashMap<String, Vec<String>>, cloudformation::Error> {
     match client
         .get_template_summary()
-        .template_body(template_body)
+        .set_template_body(body.body())
+        .set_template_url(body.url())
         .send()
         .await
     {
@@ -728,8 +4138,7 @@ async fn get_resource_identifier_mapping(
             let mut map = HashMap::new();
             for item in output.resource_identifier_summaries().iter() {
                 item.logical_resource_ids().iter().for_each(|logical_id| {
-                    let resource_identifier = item.resource_identifiers().first().unwrap();
-                    map.insert(logical_id.to_string(), resource_identifier.to_string());
+                    map.insert(logical_id.to_string(), item.resource_identifiers().to_vec());
                 });
             }
             Ok(map)
@@ -738,127 +4147,392 @@ async fn get_resource_identifier_mapping(
     }
 }
 
-async fn create_changeset(
-    client: &cloudformation::Client,
-    stack_name: &str,
-    template: serde_json::Value,
-    resources_to_import: Vec<&cloudformation::types::StackResourceSummary>,
-    new_logical_ids_map: HashMap<String, String>,
-) -> Result<std::string::String, cloudformation::Error> {
-    let template_string = serde_json::to_string(&template).unwrap();
-    let resource_identifiers = get_resource_identifier_mapping(client, &template_string).await?;
-    let resources = resources_to_import
-        .iter()
-        .map(|resource| {
-            let resource_type = resource.resource_type().unwrap_or_default();
-            let logical_id = resource.logical_resource_id().unwrap_or_default();
-            let logical_id_new = match new_logical_ids_map.get(logical_id) {
-                Some(key) => key,
-                None => logical_id,
-            };
+/// Progress output, artifacts and checkpoints of the migration of a run, see [`migration::execute`]
+struct RunHooks<'a> {
+    workspace: &'a workspace::Workspace,
+    cancellation: &'a Cancellation,
+    state: Option<&'a state::StateTable>,
 
-            let physical_id = resource.physical_resource_id().unwrap_or_default();
+    /// Configuration and client of the source and of the target account, to look up the
+    /// identifiers of the resources to import
+    source: (&'a aws_config::SdkConfig, &'a cloudformation::Client),
+    target: (&'a aws_config::SdkConfig, &'a cloudformation::Client),
 
-            let resouce_identifier = resource_identifiers.get(logical_id_new).unwrap();
+    stacks: (&'a str, &'a str),
+    resources: &'a [&'a cloudformation::types::StackResourceSummary],
+    new_logical_ids_map: &'a HashMap<String, String>,
+    removed: usize,
+}
 
-            cloudformation::types::ResourceToImport::builder()
-                .resource_type(resource_type.to_string())
-                .logical_resource_id(logical_id_new.to_string())
-                .set_resource_identifier(Some(
-                    vec![(resouce_identifier.to_string(), physical_id.to_string())]
-                        .into_iter()
-                        .collect(),
-                ))
-                .build()
-        })
-        .collect::<Vec<_>>();
+impl migration::Hooks for RunHooks<'_> {
+    fn start(
+        &mut self,
+        step: migration::Step,
+    ) -> Result<Box<dyn migration::Progress>, Box<dyn Error>> {
+        use migration::Step;
 
-    let change_set_name = format!("{}-{}", stack_name, Uuid::new_v4());
+        let (source_stack, target_stack) = self.stacks;
+        let (phase, message) = match step {
+            Step::RetainSource => (
+                "retain-source",
+                format!(
+                    "Retaining {} resources in stack {}",
+                    self.removed, source_stack
+                ),
+            ),
+            Step::RemoveSource => (
+                "remove-source",
+                format!(
+                    "Removing {} resources from stack {}",
+                    self.removed, source_stack
+                ),
+            ),
+            Step::CreateImport => (
+                "import-target",
+                format!(
+                    "Importing {} resources into stack {}",
+                    self.new_logical_ids_map.len(),
+                    target_stack
+                ),
+            ),
+            Step::ExecuteImport => {
+                let mut new_ids = self
+                    .new_logical_ids_map
+                    .values()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                new_ids.sort();
+                return Ok(Box::new(spinner::StatusTable::new(&new_ids)));
+            }
+            Step::UpdateTarget => (
+                "update-target",
+                format!("Updating stack {} to its final template", target_stack),
+            ),
+            Step::RestoreDeletionPolicy => (
+                "restore-deletion-policy",
+                format!(
+                    "Restoring the original DeletionPolicy of the resources in stack {}",
+                    source_stack
+                ),
+            ),
+            Step::RestoreSource => (
+                "import-source",
+                format!(
+                    "Importing {} resources back into stack {}",
+                    self.resources.len(),
+                    source_stack
+                ),
+            ),
+        };
+        self.workspace.log(&message)?;
+        Ok(Box::new(spinner::Spin::new(phase, &message)))
+    }
 
-    match client
-        .create_change_set()
-        .stack_name(stack_name)
-        .change_set_name(change_set_name.clone())
-        .template_body(template_string)
-        .change_set_type(cloudformation::types::ChangeSetType::Import)
-        .set_resources_to_import(resources.into())
-        // @TODO: we can detect the required capabilities from the output of validate_template()
-        .capabilities(cloudformation::types::Capability::CapabilityIam)
-        .capabilities(cloudformation::types::Capability::CapabilityNamedIam)
-        .capabilities(cloudformation::types::Capability::CapabilityAutoExpand)
-        .send()
+    fn finished(&mut self, step: migration::Step) {
+        let (source_stack, _) = self.stacks;
+        let message = match step {
+            migration::Step::RestoreDeletionPolicy => {
+                format!("DeletionPolicy restored in stack {}", source_stack)
+            }
+            migration::Step::RestoreSource => {
+                format!("Resources restored to stack {}", source_stack)
+            }
+            _ => return,
+        };
+        // the log must not hide the outcome of the rollback
+        let _ = self.workspace.log(&message);
+    }
+
+    fn failed(&mut self, step: migration::Step, err: &dyn Error) {
+        let err = match step {
+            migration::Step::RemoveSource => {
+                let _ = self.workspace.log(&format!("Removal failed: {}", err));
+                err.to_string().into()
+            }
+            _ => {
+                let err = explain_failure(err.to_string());
+                let _ = self.workspace.log(&format!("Import failed: {}", err));
+                err
+            }
+        };
+        eprintln!("\nERROR: {}\n", err);
+    }
+
+    async fn resources_to_import(
+        &mut self,
+        step: migration::Step,
+        stack_names: (&str, &str),
+        template: (&serde_json::Value, &[cloudformation::types::Parameter]),
+    ) -> Result<Vec<cloudformation::types::ResourceToImport>, Box<dyn Error>> {
+        // the resources go back under their own logical IDs when restoring them
+        if step == migration::Step::RestoreSource {
+            let logical_ids = self
+                .resources
+                .iter()
+                .map(|resource| {
+                    let logical_id = resource.logical_resource_id().unwrap_or_default();
+                    (logical_id.to_string(), logical_id.to_string())
+                })
+                .collect();
+            return prepare_import(
+                self.source,
+                stack_names,
+                template,
+                self.resources,
+                &logical_ids,
+                (self.workspace, "changeset-restore"),
+            )
+            .await;
+        }
+        prepare_import(
+            self.target,
+            stack_names,
+            template,
+            self.resources,
+            self.new_logical_ids_map,
+            (self.workspace, "changeset"),
+        )
         .await
-    {
-        Ok(_) => Ok(change_set_name),
-        Err(err) => Err(err.into()),
+    }
+
+    async fn checkpoint(&mut self, phase: Phase) -> Result<(), Box<dyn Error>> {
+        checkpoint(self.workspace, self.cancellation, self.state, phase).await
     }
 }
 
-async fn execute_changeset(
-    client: &cloudformation::Client,
-    stack_name: &str,
-    change_set_name: &str,
-) -> Result<(), cloudformation::Error> {
-    match client
-        .execute_change_set()
-        .stack_name(stack_name)
-        .change_set_name(change_set_name)
-        .send()
-        .await
-    {
-        Ok(_) => Ok(()),
-        Err(err) => Err(err.into()),
+/// Fails the run with the error of a failed migration, saying how far it has been rolled back and
+/// what is left to do
+fn migration_failure(
+    failure: migration::Failure,
+    (source_stack, target_stack): (&str, &str),
+    create_target: bool,
+    workspace: &workspace::Workspace,
+) -> Result<(), Box<dyn Error>> {
+    use migration::{Failure, Rollback};
+
+    let templates = workspace.path().display();
+    match failure {
+        // setting the DeletionPolicy alone does not count as partial completion
+        Failure::Remove {
+            error,
+            restored: None,
+        }
+        | Failure::RemoveTimedOut(error) => Err(error).exit_code(ExitCode::AwsApi),
+        Failure::Remove {
+            restored: Some(Ok(())),
+            ..
+        } => Err(fail(
+            ExitCode::AwsApi,
+            format!(
+                "Unable to remove the resources from stack {}, their original DeletionPolicy has been restored",
+                source_stack
+            ),
+        )),
+        Failure::Remove {
+            restored: Some(Err(err)),
+            ..
+        } => Err(fail(
+            ExitCode::AwsApi,
+            format!(
+                "Unable to restore the original DeletionPolicy of the resources in stack {}: {}\nRun cfn-teleport cleanup-retain --run {} to retry",
+                source_stack, err, templates
+            ),
+        )),
+        Failure::ImportInterrupted(error) => {
+            workspace.log(&format!("Import interrupted: {}", error))?;
+            Err(fail(
+                ExitCode::PartialCompletion,
+                format!(
+                    "{}\nCheck the status of stack {} before resuming. The templates are in {}",
+                    error, target_stack, templates
+                ),
+            ))
+        }
+        // The resources are orphaned now, they belong to neither stack
+        Failure::Import {
+            rollback: Rollback::Disabled,
+            ..
+        } => Err(fail(
+            ExitCode::ChangesetFailed,
+            format!(
+                "The resources are not managed by any stack. The templates to recover them manually are in {}",
+                templates
+            ),
+        )),
+        Failure::Import {
+            rollback: Rollback::StillImported(still_imported),
+            ..
+        } => Err(fail(
+            ExitCode::ChangesetFailed,
+            format!(
+                "Unable to import the resources back into stack {}, because stack {} still manages {}. Fix the rollback of stack {} first, the templates to recover the resources are in {}",
+                source_stack,
+                target_stack,
+                still_imported.join(", "),
+                target_stack,
+                templates
+            ),
+        )),
+        Failure::Import {
+            rollback: Rollback::Restored,
+            ..
+        } => {
+            // the stack created for the import stays behind without resources
+            let created = match create_target {
+                true => format!(
+                    ". Stack {} has been created for the import and can be deleted",
+                    target_stack
+                ),
+                false => String::new(),
+            };
+            Err(fail(
+                ExitCode::ChangesetFailed,
+                format!(
+                    "Import into stack {} failed, the resources have been restored to stack {}{}",
+                    target_stack, source_stack, created
+                ),
+            ))
+        }
+        Failure::Import {
+            rollback: Rollback::Failed(err),
+            ..
+        } => Err(fail(
+            ExitCode::ChangesetFailed,
+            format!(
+                "Unable to restore the resources to stack {}: {}\nThe templates to recover them manually are in {}",
+                source_stack, err, templates
+            ),
+        )),
+        // The resources are managed by the target stack, only the DeletionPolicy cleanup is missing
+        Failure::Update(error) => Err(error).exit_code(ExitCode::PartialCompletion),
+        Failure::Stopped { error, .. } => Err(error),
     }
 }
 
-async fn get_changeset_status(
-    client: &cloudformation::Client,
-    stack_name: &str,
-    changeset_name: &str,
-) -> Result<Option<cloudformation::types::ChangeSetStatus>, Box<dyn std::error::Error>> {
-    let change_set = match client
-        .describe_change_set()
-        .stack_name(stack_name)
-        .change_set_name(changeset_name)
-        .send()
+/// Looks up the resources to import into a stack and records the change set in the artifacts of
+/// the run
+async fn prepare_import(
+    (sdk_config, client): (&aws_config::SdkConfig, &cloudformation::Client),
+    (stack_name, changeset_name): (&str, &str),
+    (template, parameters): (&serde_json::Value, &[cloudformation::types::Parameter]),
+    resources_to_import: &[&cloudformation::types::StackResourceSummary],
+    new_logical_ids_map: &HashMap<String, String>,
+    (workspace, artifact): (&workspace::Workspace, &str),
+) -> Result<Vec<cloudformation::types::ResourceToImport>, Box<dyn Error>> {
+    let body = template_body::prepare(template)
         .await
-    {
-        Ok(output) => output,
-        Err(err) => return Err(Box::new(err)),
-    };
+        .map_err(|err| err as Box<dyn Error>)?;
+    let resources = get_resources_to_import(
+        (sdk_config, client),
+        &body,
+        resources_to_import,
+        new_logical_ids_map,
+    )
+    .await?;
+
+    workspace.write_json(
+        artifact,
+        &serde_json::json!({
+            "StackName": stack_name,
+            "ChangeSetName": changeset_name,
+            "ChangeSetType": "IMPORT",
+            "Parameters": parameters_json(parameters),
+            "ResourcesToImport": resources_to_import_json(&resources),
+        }),
+    )?;
+    Ok(resources)
+}
 
-    if change_set.status == Some(cloudformation::types::ChangeSetStatus::Failed) {
-        println!("{:?}", change_set);
-        return Err(change_set.status_reason().unwrap().to_string().into());
+/// Builds the resources to import into a template, identified by the physical IDs of the resources
+/// in the source stack. Identifiers which differ from the physical ID are looked up, see
+/// [`identifiers::resolve`].
+async fn get_resources_to_import(
+    (sdk_config, client): (&aws_config::SdkConfig, &cloudformation::Client),
+    body: &TemplateBody,
+    resources: &[&cloudformation::types::StackResourceSummary],
+    new_logical_ids_map: &HashMap<String, String>,
+) -> Result<Vec<cloudformation::types::ResourceToImport>, Box<dyn Error>> {
+    let resource_identifiers = get_resource_identifier_mapping(client, body).await?;
+    let mut resources_to_import = Vec::new();
+    for resource in resources {
+        let resource_type = resource.resource_type().unwrap_or_default();
+        let logical_id = resource.logical_resource_id().unwrap_or_default();
+        let logical_id_new = match new_logical_ids_map.get(logical_id) {
+            Some(key) => key,
+            None => logical_id,
+        };
+
+        let physical_id = resource.physical_resource_id().unwrap_or_default();
+
+        let identifiers = resource_identifiers
+            .get(logical_id_new)
+            .filter(|identifiers| !identifiers.is_empty())
+            .ok_or_else(|| format!("No resource identifier known for {}", logical_id_new))?;
+        let resource_identifier = identifiers::resolve(
+            sdk_config,
+            (resource_type, logical_id, physical_id),
+            identifiers,
+        )
+        .await?;
+
+        resources_to_import.push(
+            cloudformation::types::ResourceToImport::builder()
+                .resource_type(resource_type.to_string())
+                .logical_resource_id(logical_id_new.to_string())
+                .set_resource_identifier(Some(resource_identifier.into_iter().collect()))
+                .build(),
+        );
     }
+    Ok(resources_to_import)
+}
 
-    Ok(change_set.status)
+/// Parameters in the format of the CloudFormation API
+fn parameters_json(parameters: &[cloudformation::types::Parameter]) -> serde_json::Value {
+    parameters
+        .iter()
+        .map(|parameter| match parameter.use_previous_value() {
+            Some(true) => serde_json::json!({
+                "ParameterKey": parameter.parameter_key(),
+                "UsePreviousValue": true,
+            }),
+            _ => serde_json::json!({
+                "ParameterKey": parameter.parameter_key(),
+                "ParameterValue": parameter.parameter_value(),
+            }),
+        })
+        .collect()
 }
 
-async fn wait_for_changeset_created(
-    client: &cloudformation::Client,
-    stack_name: &str,
-    changeset_name: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut changeset_status = get_changeset_status(client, stack_name, changeset_name).await?;
+/// The resources to import in the format of the CloudFormation API
+fn resources_to_import_json(
+    resources: &[cloudformation::types::ResourceToImport],
+) -> serde_json::Value {
+    resources
+        .iter()
+        .map(|resource| {
+            serde_json::json!({
+                "ResourceType": resource.resource_type(),
+                "LogicalResourceId": resource.logical_resource_id(),
+                "ResourceIdentifier": resource.resource_identifier(),
+            })
+        })
+        .collect()
+}
 
-    while let Some(status) = changeset_status.clone() {
-        if status == cloudformation::types::ChangeSetStatus::CreateInProgress
-            || status == cloudformation::types::ChangeSetStatus::CreatePending
-        {
-            std::thread::sleep(std::time::Duration::from_secs(1));
-            changeset_status = get_changeset_status(client, stack_name, changeset_name).await?;
-        } else {
-            if status != cloudformation::types::ChangeSetStatus::CreateComplete {
-                return Err(format!(
-                    "Changeset creation failed {}",
-                    changeset_status.unwrap().as_str()
-                )
-                .into());
-            }
-            break;
-        }
+/// Appends explanations and fixes for known causes of a failed import to the error message
+fn explain_failure<E: Into<Box<dyn Error>>>(err: E) -> Box<dyn Error> {
+    let err: Box<dyn Error> = err.into();
+    let known_failures = triage::diagnose(&err.to_string());
+    if known_failures.is_empty() {
+        return err;
     }
 
-    Ok(())
+    let mut message = err.to_string();
+    for failure in known_failures {
+        message.push_str(&format!(
+            "\n\n  Cause: {}\n  Fix:   {}",
+            failure.explanation, failure.fix
+        ));
+    }
+    message.into()
 }
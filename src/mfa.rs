@@ -0,0 +1,209 @@
+use aws_config::SdkConfig;
+use aws_sdk_iam as iam;
+use aws_sdk_sts as sts;
+use aws_sdk_sts::config::Credentials;
+use aws_sdk_sts::error::ProvideErrorMetadata;
+use console::Term;
+use dialoguer::Input;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{fail, ExitCode};
+
+/// Role ARN and MFA device of an assumed session
+type SessionKey = (String, Option<String>);
+
+/// Sessions assumed during the run, so the token code is only asked for once
+static SESSIONS: OnceLock<Mutex<HashMap<SessionKey, Credentials>>> = OnceLock::new();
+
+/// Sessions are assumed again when they expire within this time
+const EXPIRY_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// A role to assume, optionally with an MFA device
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssumeRole {
+    pub role_arn: String,
+
+    /// Serial number or ARN of the MFA device
+    pub mfa_serial: Option<String>,
+
+    /// Profile providing the credentials to assume the role with
+    pub source_profile: Option<String>,
+    pub duration_seconds: Option<i32>,
+}
+
+/// The role of a profile of the AWS config file which requires MFA, as the AWS SDK for Rust does
+/// not prompt for the token code of `mfa_serial` itself
+pub fn profile_role(profile: Option<&str>) -> Option<AssumeRole> {
+    let profile = profile
+        .map(str::to_string)
+        .or_else(|| std::env::var("AWS_PROFILE").ok())
+        .unwrap_or_else(|| "default".to_string());
+    let path = std::env::var_os("AWS_CONFIG_FILE")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".aws/config")))?;
+    let body = std::fs::read_to_string(path).ok()?;
+
+    let settings = profile_settings(&body, &profile);
+    let role = AssumeRole {
+        role_arn: settings.get("role_arn")?.clone(),
+        mfa_serial: Some(settings.get("mfa_serial")?.clone()),
+        source_profile: Some(settings.get("source_profile")?.clone()),
+        duration_seconds: settings
+            .get("duration_seconds")
+            .and_then(|value| value.parse().ok()),
+    };
+    Some(role)
+}
+
+/// Settings of a profile in an AWS config file
+fn profile_settings(body: &str, profile: &str) -> HashMap<String, String> {
+    let mut settings = HashMap::new();
+    let mut selected = false;
+    for line in body.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let section = section.trim();
+            let name = section.strip_prefix("profile ").unwrap_or(section).trim();
+            selected = name == profile;
+            continue;
+        }
+        if let (true, Some((key, value))) = (selected, line.split_once('=')) {
+            settings.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    settings
+}
+
+/// Assumes a role with the credentials of `config`. The token code of the MFA device is asked
+/// for if the role requires MFA, either because the device is known or because the role refuses
+/// to be assumed without it and the device can be found among the MFA devices of the IAM user.
+pub async fn assume_role(
+    config: &SdkConfig,
+    role: &AssumeRole,
+) -> Result<Credentials, Box<dyn Error>> {
+    let sessions = SESSIONS.get_or_init(Default::default);
+    let key = (role.role_arn.clone(), role.mfa_serial.clone());
+    if let Some(credentials) = sessions.lock().unwrap().get(&key) {
+        let valid = credentials.expiry().is_none_or(|expiry| {
+            expiry
+                .duration_since(SystemTime::now())
+                .is_ok_and(|remaining| remaining > EXPIRY_MARGIN)
+        });
+        if valid {
+            return Ok(credentials.clone());
+        }
+    }
+
+    let client = sts::Client::new(config);
+    let mfa_serial = match &role.mfa_serial {
+        Some(serial) => Some(serial.clone()),
+        None => match request(&client, role, None).await {
+            Ok(credentials) => {
+                sessions.lock().unwrap().insert(key, credentials.clone());
+                return Ok(credentials);
+            }
+            Err(err) if err.code() == Some("AccessDenied") => match find_mfa_device(config).await {
+                Some(serial) => Some(serial),
+                None => return Err(err.into()),
+            },
+            Err(err) => return Err(err.into()),
+        },
+    };
+
+    let token_code = prompt_token_code(&role.role_arn, mfa_serial.as_deref().unwrap_or_default())?;
+    let credentials = request(&client, role, mfa_serial.zip(Some(token_code)))
+        .await
+        .map_err(|err| {
+            fail(
+                ExitCode::AwsApi,
+                format!(
+                    "Unable to assume role {}: {}",
+                    role.role_arn,
+                    sts::Error::from(err)
+                ),
+            )
+        })?;
+    sessions.lock().unwrap().insert(key, credentials.clone());
+    Ok(credentials)
+}
+
+async fn request(
+    client: &sts::Client,
+    role: &AssumeRole,
+    mfa: Option<(String, String)>,
+) -> Result<Credentials, sts::error::SdkError<sts::operation::assume_role::AssumeRoleError>> {
+    let (serial_number, token_code) = mfa.unzip();
+    let output = client
+        .assume_role()
+        .role_arn(&role.role_arn)
+        .role_session_name(session_name())
+        .set_duration_seconds(role.duration_seconds)
+        .set_serial_number(serial_number)
+        .set_token_code(token_code)
+        .send()
+        .await?;
+
+    let credentials = output.credentials();
+    Ok(Credentials::new(
+        credentials.map(|c| c.access_key_id()).unwrap_or_default(),
+        credentials
+            .map(|c| c.secret_access_key())
+            .unwrap_or_default(),
+        credentials.map(|c| c.session_token().to_string()),
+        credentials.and_then(|c| SystemTime::try_from(*c.expiration()).ok()),
+        "cfn-teleport",
+    ))
+}
+
+/// The MFA device of the IAM user of the credentials, if there is exactly one
+async fn find_mfa_device(config: &SdkConfig) -> Option<String> {
+    let output = iam::Client::new(config)
+        .list_mfa_devices()
+        .send()
+        .await
+        .ok()?;
+    match output.mfa_devices() {
+        [device] => Some(device.serial_number().to_string()),
+        _ => None,
+    }
+}
+
+fn session_name() -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    format!("cfn-teleport-{}", timestamp)
+}
+
+fn prompt_token_code(role_arn: &str, mfa_serial: &str) -> Result<String, Box<dyn Error>> {
+    let term = Term::stderr();
+    if !term.is_term() {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "Assuming role {} requires the token code of MFA device {}, which cannot be asked for without a terminal. Use the credentials of an existing session instead",
+                role_arn, mfa_serial
+            ),
+        ));
+    }
+
+    let code: String = Input::new()
+        .with_prompt(format!("MFA token code of {}", mfa_serial))
+        .validate_with(|code: &String| {
+            if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+                Ok(())
+            } else {
+                Err("The token code has 6 digits")
+            }
+        })
+        .interact_text_on(&term)?;
+    Ok(code)
+}
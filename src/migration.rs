@@ -0,0 +1,1124 @@
+//! The stack operations a migration is made of. Every step starts an operation through
+//! [`StackApi`] and waits for it with a [`StackWaiter`]. [`execute`] runs the steps in order, with
+//! a checkpoint after each phase, and rolls back a failed removal or import as far as possible.
+//! Progress output and the lookup of resource identifiers are up to the caller, see [`Hooks`].
+
+use aws_sdk_cloudformation::types::{Capability, Parameter, ResourceToImport};
+use serde_json::Value;
+use std::error::Error;
+use std::future::Future;
+use uuid::Uuid;
+
+use crate::cancellation::Phase;
+use crate::stack_api::{self, parameters_for, StackApi};
+use crate::waiter::{DeadlineReached, Indicator, Outcome, StackWaiter};
+
+/// A step of a migration, see [`execute`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Sets the DeletionPolicy of the moved resources to Retain in the source stack
+    RetainSource,
+
+    /// Removes the moved resources from the source stack
+    RemoveSource,
+
+    /// Creates the change set importing the resources into the target stack
+    CreateImport,
+
+    /// Executes the import change set
+    ExecuteImport,
+
+    /// Updates the target stack to its final template
+    UpdateTarget,
+
+    /// Rolls back a failed removal by restoring the original DeletionPolicy in the source stack
+    RestoreDeletionPolicy,
+
+    /// Rolls back a failed import by importing the resources back into the source stack
+    RestoreSource,
+}
+
+/// Shows the progress of a step and how it ended
+pub trait Progress: Indicator {
+    fn complete(&mut self);
+    fn fail(&mut self);
+}
+
+/// What the caller contributes to a migration: the progress output, the identifiers of the
+/// resources to import and a checkpoint after every phase
+pub trait Hooks {
+    /// Called when a step starts, returns the indicator to show its progress on
+    fn start(&mut self, step: Step) -> Result<Box<dyn Progress>, Box<dyn Error>>;
+
+    /// Called when a step has succeeded
+    fn finished(&mut self, step: Step);
+
+    /// Called when a step has failed and the migration is about to be rolled back
+    fn failed(&mut self, step: Step, err: &dyn Error);
+
+    /// The resources to import with a change set, for [`Step::CreateImport`] and
+    /// [`Step::RestoreSource`]
+    fn resources_to_import(
+        &mut self,
+        step: Step,
+        stack_names: (&str, &str),
+        template: (&Value, &[Parameter]),
+    ) -> impl Future<Output = Result<Vec<ResourceToImport>, Box<dyn Error>>>;
+
+    /// Called after every phase. Failing stops the migration, e.g. once it has been cancelled.
+    fn checkpoint(&mut self, phase: Phase) -> impl Future<Output = Result<(), Box<dyn Error>>>;
+}
+
+/// The stacks and templates of a migration
+#[derive(Debug, Clone, Copy)]
+pub struct Migration<'a> {
+    pub source_stack: &'a str,
+    pub target_stack: &'a str,
+
+    /// Template of the source stack before the migration
+    pub source_original: &'a Value,
+
+    /// Source template with the DeletionPolicy of the moved resources set to Retain
+    pub source_retained: &'a Value,
+
+    /// Source template without the moved resources
+    pub source_removed: &'a Value,
+
+    /// Target template the resources are imported with
+    pub target_import: &'a Value,
+
+    /// Final template of the target stack
+    pub target_final: &'a Value,
+
+    /// Templates to import the resources back into the source stack with and to update it to
+    /// afterwards, when the import fails
+    pub source_restore: (&'a Value, &'a Value),
+
+    /// Current Parameters of the stacks. Parameters copied along with the resources get the values
+    /// they have in the source stack.
+    pub source_parameters: &'a [Parameter],
+    pub target_parameters: &'a [Parameter],
+
+    pub capabilities: &'a [Capability],
+
+    /// Logical IDs of the moved resources in the target stack
+    pub imported: &'a [String],
+
+    /// Number of resources removed from the source stack
+    pub removed: usize,
+
+    /// Whether a failed import is rolled back by importing the resources back into the source stack
+    pub rollback: bool,
+
+    /// The last phase an earlier run has completed, the migration resumes after it
+    pub completed: Option<Phase>,
+}
+
+/// Why a migration failed and how far it has been rolled back
+#[derive(Debug)]
+pub enum Failure {
+    /// Setting the DeletionPolicy or removing the resources from the source stack failed, the
+    /// resources are still in the source stack. If their DeletionPolicy had been set to Retain,
+    /// `restored` is the result of restoring it.
+    Remove {
+        error: Box<dyn Error>,
+        restored: Option<Result<(), Box<dyn Error>>>,
+    },
+
+    /// Waiting for the removal stopped, the removal continues in CloudFormation
+    RemoveTimedOut(Box<dyn Error>),
+
+    /// Waiting for the import stopped at the deadline, the import continues in CloudFormation
+    ImportInterrupted(Box<dyn Error>),
+
+    /// The import into the target stack failed
+    Import {
+        error: Box<dyn Error>,
+        rollback: Rollback,
+    },
+
+    /// Updating the target stack to its final template failed, the resources are in the target
+    /// stack already
+    Update(Box<dyn Error>),
+
+    /// The checkpoint after the phase failed
+    Stopped { error: Box<dyn Error>, phase: Phase },
+}
+
+/// How far a failed import has been rolled back
+#[derive(Debug)]
+pub enum Rollback {
+    /// Rolling back is disabled
+    Disabled,
+
+    /// The target stack still manages some of the resources, they cannot be imported back yet
+    StillImported(Vec<String>),
+
+    /// The resources have been imported back into the source stack
+    Restored,
+
+    /// Importing the resources back into the source stack failed
+    Failed(Box<dyn Error>),
+}
+
+impl Failure {
+    /// Whether the resources have been left in neither stack
+    pub fn is_orphaned(&self) -> bool {
+        match self {
+            Failure::Remove { .. } | Failure::Update(_) => false,
+            Failure::RemoveTimedOut(_) | Failure::ImportInterrupted(_) => true,
+            Failure::Import { rollback, .. } => !matches!(rollback, Rollback::Restored),
+            Failure::Stopped { phase, .. } => *phase == Phase::Removed,
+        }
+    }
+}
+
+/// Moves resources from the source to the target stack: sets their DeletionPolicy to Retain,
+/// removes them from the source stack, imports them into the target stack and updates it to its
+/// final template. Phases an earlier run has completed are skipped. A failed removal restores the
+/// original DeletionPolicy, a failed import imports the resources back into the source stack.
+/// Rolling back does not stop at the deadline. Returns the ID of the import change set, unless the
+/// import has been done by an earlier run.
+pub async fn execute<A: StackApi, B: StackApi>(
+    (source, target): (&StackWaiter<'_, A>, &StackWaiter<'_, B>),
+    migration: &Migration<'_>,
+    hooks: &mut impl Hooks,
+) -> Result<Option<String>, Failure> {
+    let done = |phase| {
+        migration
+            .completed
+            .is_some_and(|completed| completed >= phase)
+    };
+    let source_stack = migration.source_stack;
+    let retains = migration.source_retained != migration.source_original;
+
+    // Until the resources have been removed from the source stack, nothing has been lost. Setting
+    // the DeletionPolicy alone does not count as partial completion and a failed removal is rolled
+    // back by CloudFormation.
+    if !done(Phase::Retained) {
+        if retains {
+            update_step(
+                (source, hooks),
+                Step::RetainSource,
+                (source_stack, migration.source_retained),
+                (migration.source_parameters, &[]),
+                migration.capabilities,
+                None,
+            )
+            .await
+            .and_then(|outcome| outcome.into_result(source_stack))
+            .map_err(|error| Failure::Remove {
+                error,
+                restored: None,
+            })?;
+        }
+        checkpoint(hooks, Phase::Retained).await?;
+    }
+
+    if !done(Phase::Removed) {
+        let removed = update_step(
+            (source, hooks),
+            Step::RemoveSource,
+            (source_stack, migration.source_removed),
+            (migration.source_parameters, &[]),
+            migration.capabilities,
+            Some(migration.removed),
+        )
+        .await;
+        // a stack operation still running in CloudFormation cannot be rolled back yet
+        let timed_out = matches!(removed, Ok(Outcome::TimedOut { .. }));
+        if let Err(error) = removed.and_then(|outcome| outcome.into_result(source_stack)) {
+            if timed_out {
+                return Err(Failure::RemoveTimedOut(error));
+            }
+            if !retains {
+                return Err(Failure::Remove {
+                    error,
+                    restored: None,
+                });
+            }
+            hooks.failed(Step::RemoveSource, error.as_ref());
+            let restored = update_step(
+                (&source.uncancellable(), hooks),
+                Step::RestoreDeletionPolicy,
+                (source_stack, migration.source_original),
+                (migration.source_parameters, &[]),
+                migration.capabilities,
+                None,
+            )
+            .await
+            .and_then(|outcome| outcome.into_result(source_stack));
+            return Err(Failure::Remove {
+                error,
+                restored: Some(restored),
+            });
+        }
+        checkpoint(hooks, Phase::Removed).await?;
+    }
+
+    let mut changeset_id = None;
+    if !done(Phase::Imported) {
+        match import(target, migration, hooks).await {
+            Ok(id) => changeset_id = Some(id),
+            Err((_, error)) if error.is::<DeadlineReached>() => {
+                return Err(Failure::ImportInterrupted(error))
+            }
+            Err((step, error)) => {
+                hooks.failed(step, error.as_ref());
+                let rollback = roll_back_import((source, target), migration, hooks).await;
+                return Err(Failure::Import { error, rollback });
+            }
+        }
+        checkpoint(hooks, Phase::Imported).await?;
+    }
+
+    // The resources are managed by the target stack now, only the DeletionPolicy cleanup is missing
+    if !done(Phase::Completed) {
+        update_step(
+            (target, hooks),
+            Step::UpdateTarget,
+            (migration.target_stack, migration.target_final),
+            (migration.target_parameters, migration.source_parameters),
+            migration.capabilities,
+            None,
+        )
+        .await
+        .and_then(|outcome| outcome.into_result(migration.target_stack))
+        .map_err(Failure::Update)?;
+        checkpoint(hooks, Phase::Completed).await?;
+    }
+    Ok(changeset_id)
+}
+
+/// Updates a stack as a step, passing the Parameters of the template as in [`parameters_for`]
+async fn update_step<A: StackApi>(
+    (waiter, hooks): (&StackWaiter<'_, A>, &mut impl Hooks),
+    step: Step,
+    (stack_name, template): (&str, &Value),
+    (previous, source): (&[Parameter], &[Parameter]),
+    capabilities: &[Capability],
+    total: Option<usize>,
+) -> Result<Outcome, Box<dyn Error>> {
+    let mut progress = hooks.start(step)?;
+    let outcome = update_stack(
+        waiter,
+        stack_name,
+        (template, &parameters_for(template, previous, source)),
+        capabilities,
+        (Some(progress.as_mut()), total),
+    )
+    .await;
+    match outcome {
+        Ok(Outcome::Succeeded) => {
+            progress.complete();
+            hooks.finished(step);
+        }
+        _ => progress.fail(),
+    }
+    outcome
+}
+
+/// Imports the resources into the target stack. Returns the ID of the change set, or the step
+/// which failed.
+async fn import<A: StackApi>(
+    waiter: &StackWaiter<'_, A>,
+    migration: &Migration<'_>,
+    hooks: &mut impl Hooks,
+) -> Result<String, (Step, Box<dyn Error>)> {
+    let stack_name = migration.target_stack;
+    let changeset_name = format!("{}-{}", stack_name, Uuid::new_v4());
+    let template = migration.target_import;
+    let parameters = parameters_for(
+        template,
+        migration.target_parameters,
+        migration.source_parameters,
+    );
+
+    let step = Step::CreateImport;
+    let mut progress = hooks.start(step).map_err(|err| (step, err))?;
+    let created = async {
+        let resources = hooks
+            .resources_to_import(step, (stack_name, &changeset_name), (template, &parameters))
+            .await?;
+        create_import(
+            waiter,
+            (stack_name, &changeset_name),
+            (template, &parameters),
+            &resources,
+            migration.capabilities,
+        )
+        .await
+    }
+    .await;
+    let changeset_id = finish(step, (progress.as_mut(), hooks), created)?;
+
+    let step = Step::ExecuteImport;
+    let mut progress = hooks.start(step).map_err(|err| (step, err))?;
+    let executed = execute_import(
+        waiter,
+        (stack_name, &changeset_name),
+        (Some(progress.as_mut()), Some(migration.imported.len())),
+    )
+    .await;
+    finish(step, (progress.as_mut(), hooks), executed)?;
+    Ok(changeset_id)
+}
+
+/// Ends the progress of a step with its result
+fn finish<T>(
+    step: Step,
+    (progress, hooks): (&mut dyn Progress, &mut impl Hooks),
+    result: Result<T, Box<dyn Error>>,
+) -> Result<T, (Step, Box<dyn Error>)> {
+    match result {
+        Ok(value) => {
+            progress.complete();
+            hooks.finished(step);
+            Ok(value)
+        }
+        Err(err) => {
+            progress.fail();
+            Err((step, err))
+        }
+    }
+}
+
+/// Imports the resources back into the source stack after the import into the target stack failed
+async fn roll_back_import<A: StackApi, B: StackApi>(
+    (source, target): (&StackWaiter<'_, A>, &StackWaiter<'_, B>),
+    migration: &Migration<'_>,
+    hooks: &mut impl Hooks,
+) -> Rollback {
+    if !migration.rollback {
+        return Rollback::Disabled;
+    }
+
+    // a failed rollback of the import can leave resources in the target stack, which cannot be
+    // imported into another stack
+    let mut still_imported = stack_api::list_all_resources(target.client(), migration.target_stack)
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|resource| resource.logical_resource_id())
+        .filter(|id| migration.imported.iter().any(|imported| imported == id))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if !still_imported.is_empty() {
+        still_imported.sort();
+        return Rollback::StillImported(still_imported);
+    }
+
+    let stack_name = migration.source_stack;
+    let changeset_name = format!("{}-{}", stack_name, Uuid::new_v4());
+    let (template_retained, template_original) = migration.source_restore;
+    let parameters = parameters_for(template_retained, migration.source_parameters, &[]);
+    let step = Step::RestoreSource;
+    let mut progress = match hooks.start(step) {
+        Ok(progress) => progress,
+        Err(err) => return Rollback::Failed(err),
+    };
+    let restored = async {
+        let resources = hooks
+            .resources_to_import(
+                step,
+                (stack_name, &changeset_name),
+                (template_retained, &parameters),
+            )
+            .await?;
+        // restoring the resources must not stop at the deadline of the migration
+        restore_resources(
+            &source.uncancellable(),
+            (stack_name, &changeset_name),
+            (template_retained, template_original),
+            &parameters,
+            &resources,
+            migration.capabilities,
+            Some(progress.as_mut()),
+        )
+        .await
+    }
+    .await;
+    match finish(step, (progress.as_mut(), hooks), restored) {
+        Ok(()) => Rollback::Restored,
+        Err((_, err)) => Rollback::Failed(err),
+    }
+}
+
+/// Runs the checkpoint after a phase
+async fn checkpoint(hooks: &mut impl Hooks, phase: Phase) -> Result<(), Failure> {
+    hooks
+        .checkpoint(phase)
+        .await
+        .map_err(|error| Failure::Stopped { error, phase })
+}
+
+/// Updates a stack to a new template and waits until the update has finished. Progress is shown on
+/// the indicator as the number of completed resources, out of `total` if known.
+pub async fn update_stack<A: StackApi>(
+    waiter: &StackWaiter<'_, A>,
+    stack_name: &str,
+    (template, parameters): (&Value, &[Parameter]),
+    capabilities: &[Capability],
+    (indicator, total): (Option<&mut dyn Indicator>, Option<usize>),
+) -> Result<Outcome, Box<dyn Error>> {
+    tracing::info!(
+        stack = stack_name,
+        resources = template["Resources"]
+            .as_object()
+            .map_or(0, |resources| resources.len()),
+        "Updating stack"
+    );
+    waiter
+        .client()
+        .update_stack(stack_name, template, parameters, capabilities)
+        .await
+        .map_err(|err| err as Box<dyn Error>)?;
+    waiter.outcome(stack_name, indicator, total).await
+}
+
+/// Creates a change set importing resources into a stack and waits until it has been created.
+/// Returns the ID of the change set, fails with the reason CloudFormation gives if the change set
+/// could not be created.
+pub async fn create_import<A: StackApi>(
+    waiter: &StackWaiter<'_, A>,
+    (stack_name, changeset_name): (&str, &str),
+    (template, parameters): (&Value, &[Parameter]),
+    resources: &[ResourceToImport],
+    capabilities: &[Capability],
+) -> Result<String, Box<dyn Error>> {
+    tracing::info!(
+        stack = stack_name,
+        changeset = changeset_name,
+        resources = resources.len(),
+        "Creating import changeset"
+    );
+    let changeset_id = waiter
+        .client()
+        .create_import_changeset(
+            stack_name,
+            changeset_name,
+            template,
+            parameters,
+            resources,
+            capabilities,
+        )
+        .await
+        .map_err(|err| err as Box<dyn Error>)?;
+
+    match waiter
+        .wait_for_changeset(stack_name, changeset_name)
+        .await?
+    {
+        Outcome::Succeeded => Ok(changeset_id),
+        Outcome::Failed { status, reason, .. } => Err(format!(
+            "Changeset creation failed {}: {}",
+            status,
+            reason.unwrap_or_default()
+        )
+        .into()),
+        outcome => outcome.into_result(stack_name).map(|_| changeset_id),
+    }
+}
+
+/// Executes an import change set and waits until the resources have been imported
+pub async fn execute_import<A: StackApi>(
+    waiter: &StackWaiter<'_, A>,
+    (stack_name, changeset_name): (&str, &str),
+    (indicator, total): (Option<&mut dyn Indicator>, Option<usize>),
+) -> Result<(), Box<dyn Error>> {
+    tracing::info!(
+        stack = stack_name,
+        changeset = changeset_name,
+        "Executing changeset"
+    );
+    waiter
+        .client()
+        .execute_changeset(stack_name, changeset_name)
+        .await
+        .map_err(|err| err as Box<dyn Error>)?;
+    waiter.wait(stack_name, indicator, total).await
+}
+
+/// Imports resources which have been removed from a stack back into it, after the import into the
+/// target stack failed. The stack is first updated to the template with the DeletionPolicy needed
+/// for the import and then to its original template.
+pub async fn restore_resources<A: StackApi>(
+    waiter: &StackWaiter<'_, A>,
+    (stack_name, changeset_name): (&str, &str),
+    (template_retained, template_original): (&Value, &Value),
+    parameters: &[Parameter],
+    resources: &[ResourceToImport],
+    capabilities: &[Capability],
+    indicator: Option<&mut dyn Indicator>,
+) -> Result<(), Box<dyn Error>> {
+    create_import(
+        waiter,
+        (stack_name, changeset_name),
+        (template_retained, parameters),
+        resources,
+        capabilities,
+    )
+    .await?;
+    execute_import(
+        waiter,
+        (stack_name, changeset_name),
+        (indicator, Some(resources.len())),
+    )
+    .await?;
+
+    if template_original != template_retained {
+        update_stack(
+            waiter,
+            stack_name,
+            (template_original, parameters),
+            capabilities,
+            (None, None),
+        )
+        .await?
+        .into_result(stack_name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancellation::Cancellation;
+    use crate::composer;
+    use crate::stack_api::FakeStackApi;
+    use aws_sdk_cloudformation::types::StackEvent;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    const STALL_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+    fn source_template() -> Value {
+        json!({
+            "Parameters": {"Env": {"Type": "String"}},
+            "Resources": {
+                "Bucket": {
+                    "Type": "AWS::S3::Bucket",
+                    "Properties": {"BucketName": {"Fn::Sub": "data-${Env}"}},
+                },
+                "Queue": {"Type": "AWS::SQS::Queue"},
+            },
+        })
+    }
+
+    fn api() -> FakeStackApi {
+        FakeStackApi::new()
+            .with_stack("Source", source_template())
+            .with_parameter("Source", "Env", "prod")
+            .with_stack(
+                "Target",
+                json!({"Resources": {"Topic": {"Type": "AWS::SNS::Topic"}}}),
+            )
+    }
+
+    /// A bucket to import under the given logical ID
+    fn bucket(logical_id: &str, physical_id: &str) -> ResourceToImport {
+        ResourceToImport::builder()
+            .resource_type("AWS::S3::Bucket")
+            .logical_resource_id(logical_id)
+            .resource_identifier("BucketName", physical_id)
+            .build()
+    }
+
+    async fn retain_and_remove(waiter: &StackWaiter<'_, FakeStackApi>) -> (Value, Value) {
+        let ids = vec!["Bucket".to_string()];
+        let retained = composer::retain_resources(source_template(), ids.clone());
+        let removed = composer::remove_resources(retained.clone(), ids);
+        let previous = waiter.client().stack_parameters("Source").await.unwrap();
+        for template in [&retained, &removed] {
+            let parameters = crate::stack_api::parameters_for(template, &previous, &[]);
+            update_stack(waiter, "Source", (template, &parameters), &[], (None, None))
+                .await
+                .unwrap()
+                .into_result("Source")
+                .unwrap();
+        }
+        (retained, removed)
+    }
+
+    /// The templates and Parameters of moving the bucket to the target stack as `Data`
+    struct Fixture {
+        original: Value,
+        retained: Value,
+        removed: Value,
+        import: Value,
+        target: Value,
+        source_parameters: Vec<Parameter>,
+        target_parameters: Vec<Parameter>,
+        imported: Vec<String>,
+    }
+
+    impl Fixture {
+        async fn new(api: &FakeStackApi) -> Self {
+            let ids = vec!["Bucket".to_string()];
+            let retained = composer::retain_resources(source_template(), ids.clone());
+            let removed = composer::remove_resources(retained.clone(), ids);
+            let (import, target) = composer::add_resources(
+                api.template("Target").unwrap(),
+                source_template(),
+                HashMap::from([("Bucket".to_string(), "Data".to_string())]),
+            )
+            .unwrap();
+            Self {
+                original: source_template(),
+                retained,
+                removed,
+                import,
+                target,
+                source_parameters: api.stack_parameters("Source").await.unwrap(),
+                target_parameters: api.stack_parameters("Target").await.unwrap(),
+                imported: vec!["Data".to_string()],
+            }
+        }
+
+        fn migration(&self) -> Migration<'_> {
+            Migration {
+                source_stack: "Source",
+                target_stack: "Target",
+                source_original: &self.original,
+                source_retained: &self.retained,
+                source_removed: &self.removed,
+                target_import: &self.import,
+                target_final: &self.target,
+                source_restore: (&self.retained, &self.original),
+                source_parameters: &self.source_parameters,
+                target_parameters: &self.target_parameters,
+                capabilities: &[],
+                imported: &self.imported,
+                removed: 1,
+                rollback: true,
+                completed: None,
+            }
+        }
+    }
+
+    struct NoProgress;
+
+    impl Indicator for NoProgress {
+        fn progress(&mut self, _events: &[StackEvent], _details: &str) {}
+    }
+
+    impl Progress for NoProgress {
+        fn complete(&mut self) {}
+        fn fail(&mut self) {}
+    }
+
+    /// Records the steps and checkpoints of a migration. `on_start` is called with every step, to
+    /// let the fake fail; the checkpoint after `stop_after` fails.
+    struct Recorder<'a> {
+        api: &'a FakeStackApi,
+        events: Vec<String>,
+        on_start: fn(Step, &FakeStackApi),
+        stop_after: Option<Phase>,
+    }
+
+    impl<'a> Recorder<'a> {
+        fn new(api: &'a FakeStackApi) -> Self {
+            Self {
+                api,
+                events: Vec::new(),
+                on_start: |_, _| {},
+                stop_after: None,
+            }
+        }
+    }
+
+    impl Hooks for Recorder<'_> {
+        fn start(&mut self, step: Step) -> Result<Box<dyn Progress>, Box<dyn Error>> {
+            (self.on_start)(step, self.api);
+            self.events.push(format!("start {:?}", step));
+            Ok(Box::new(NoProgress))
+        }
+
+        fn finished(&mut self, step: Step) {
+            self.events.push(format!("finished {:?}", step));
+        }
+
+        fn failed(&mut self, step: Step, err: &dyn Error) {
+            self.events.push(format!("failed {:?}: {}", step, err));
+        }
+
+        async fn resources_to_import(
+            &mut self,
+            step: Step,
+            _stack_names: (&str, &str),
+            _template: (&Value, &[Parameter]),
+        ) -> Result<Vec<ResourceToImport>, Box<dyn Error>> {
+            let logical_id = match step {
+                Step::RestoreSource => "Bucket",
+                _ => "Data",
+            };
+            Ok(vec![bucket(logical_id, &physical_id("Bucket"))])
+        }
+
+        async fn checkpoint(&mut self, phase: Phase) -> Result<(), Box<dyn Error>> {
+            self.events.push(format!("checkpoint {}", phase));
+            match self.stop_after == Some(phase) {
+                true => Err("Cancelled".into()),
+                false => Ok(()),
+            }
+        }
+    }
+
+    /// Physical ID the fake generates for a resource of the source stack
+    fn physical_id(logical_id: &str) -> String {
+        format!("source-{}", logical_id.to_lowercase())
+    }
+
+    #[tokio::test]
+    async fn runs_the_steps_with_a_checkpoint_after_every_phase() {
+        let api = api();
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let fixture = Fixture::new(&api).await;
+        let mut hooks = Recorder::new(&api);
+
+        let changeset_id = execute((&waiter, &waiter), &fixture.migration(), &mut hooks)
+            .await
+            .unwrap();
+
+        assert!(changeset_id.is_some());
+        assert_eq!(
+            hooks.events,
+            vec![
+                "start RetainSource",
+                "finished RetainSource",
+                "checkpoint Retained",
+                "start RemoveSource",
+                "finished RemoveSource",
+                "checkpoint Removed",
+                "start CreateImport",
+                "finished CreateImport",
+                "start ExecuteImport",
+                "finished ExecuteImport",
+                "checkpoint Imported",
+                "start UpdateTarget",
+                "finished UpdateTarget",
+                "checkpoint Completed",
+            ]
+        );
+        assert!(!api.resources("Source").contains_key("Bucket"));
+        assert_eq!(api.resources("Target")["Data"], physical_id("Bucket"));
+        assert_eq!(api.parameters("Target")["Env"], "prod");
+        assert_eq!(api.template("Target"), Some(fixture.target));
+    }
+
+    #[tokio::test]
+    async fn restores_the_deletion_policy_if_the_removal_fails() {
+        let api = api();
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let fixture = Fixture::new(&api).await;
+        let mut hooks = Recorder::new(&api);
+        hooks.on_start = |step, api| match step {
+            Step::RemoveSource => api.fail_on("update_stack", "Source", "Resource is in use"),
+            Step::RestoreDeletionPolicy => api.clear_failure("update_stack", "Source"),
+            _ => {}
+        };
+
+        let failure = execute((&waiter, &waiter), &fixture.migration(), &mut hooks)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            failure,
+            Failure::Remove {
+                restored: Some(Ok(())),
+                ..
+            }
+        ));
+        assert!(!failure.is_orphaned());
+        assert_eq!(
+            hooks.events[3..],
+            [
+                "start RemoveSource",
+                "failed RemoveSource: Resource is in use",
+                "start RestoreDeletionPolicy",
+                "finished RestoreDeletionPolicy",
+            ]
+        );
+        assert_eq!(api.template("Source"), Some(source_template()));
+    }
+
+    #[tokio::test]
+    async fn imports_the_resources_back_if_the_import_fails() {
+        let api = api();
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let fixture = Fixture::new(&api).await;
+        let mut hooks = Recorder::new(&api);
+        hooks.on_start = |step, api| {
+            if step == Step::ExecuteImport {
+                api.fail_on("execute_changeset", "Target", "Rate exceeded");
+            }
+        };
+
+        let failure = execute((&waiter, &waiter), &fixture.migration(), &mut hooks)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            failure,
+            Failure::Import {
+                rollback: Rollback::Restored,
+                ..
+            }
+        ));
+        assert!(!failure.is_orphaned());
+        assert_eq!(
+            hooks.events[8..],
+            [
+                "start ExecuteImport",
+                "failed ExecuteImport: Rate exceeded",
+                "start RestoreSource",
+                "finished RestoreSource",
+            ]
+        );
+        assert_eq!(api.resources("Source")["Bucket"], physical_id("Bucket"));
+        assert_eq!(api.template("Source"), Some(source_template()));
+        assert_eq!(api.parameters("Source")["Env"], "prod");
+        assert!(!api.resources("Target").contains_key("Data"));
+    }
+
+    #[tokio::test]
+    async fn leaves_the_resources_orphaned_if_rolling_back_is_disabled() {
+        let api = api();
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let fixture = Fixture::new(&api).await;
+        let mut hooks = Recorder::new(&api);
+        hooks.on_start = |step, api| {
+            if step == Step::CreateImport {
+                api.fail_on("create_import_changeset", "Target", "Access denied");
+            }
+        };
+        let migration = Migration {
+            rollback: false,
+            ..fixture.migration()
+        };
+
+        let failure = execute((&waiter, &waiter), &migration, &mut hooks)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            failure,
+            Failure::Import {
+                rollback: Rollback::Disabled,
+                ..
+            }
+        ));
+        assert!(failure.is_orphaned());
+        assert_eq!(
+            hooks.events.last().unwrap(),
+            "failed CreateImport: Access denied"
+        );
+        assert!(!api.resources("Source").contains_key("Bucket"));
+    }
+
+    #[tokio::test]
+    async fn does_not_roll_back_while_the_target_still_manages_the_resources() {
+        let api = api();
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let fixture = Fixture::new(&api).await;
+        let mut hooks = Recorder::new(&api);
+        // the import goes through, but following it fails
+        hooks.on_start = |step, api| {
+            if step == Step::ExecuteImport {
+                api.fail_on("describe_stack_events", "Target", "Rate exceeded");
+            }
+        };
+
+        let failure = execute((&waiter, &waiter), &fixture.migration(), &mut hooks)
+            .await
+            .unwrap_err();
+
+        match &failure {
+            Failure::Import {
+                rollback: Rollback::StillImported(ids),
+                ..
+            } => assert_eq!(ids, &["Data"]),
+            failure => panic!("unexpected failure: {:?}", failure),
+        }
+        assert!(failure.is_orphaned());
+        assert!(!hooks.events.contains(&"start RestoreSource".to_string()));
+    }
+
+    #[tokio::test]
+    async fn fails_the_final_update_with_the_resources_in_the_target_stack() {
+        let api = api();
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let fixture = Fixture::new(&api).await;
+        let mut hooks = Recorder::new(&api);
+        hooks.on_start = |step, api| {
+            if step == Step::UpdateTarget {
+                api.fail_on(
+                    "update_stack",
+                    "Target",
+                    "Stack is in UPDATE_IN_PROGRESS state",
+                );
+            }
+        };
+
+        let failure = execute((&waiter, &waiter), &fixture.migration(), &mut hooks)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(failure, Failure::Update(_)));
+        assert!(!failure.is_orphaned());
+        assert_eq!(hooks.events.last().unwrap(), "start UpdateTarget");
+        assert_eq!(api.resources("Target")["Data"], physical_id("Bucket"));
+    }
+
+    #[tokio::test]
+    async fn stops_at_a_failed_checkpoint() {
+        let api = api();
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let fixture = Fixture::new(&api).await;
+        let mut hooks = Recorder::new(&api);
+        hooks.stop_after = Some(Phase::Retained);
+
+        let failure = execute((&waiter, &waiter), &fixture.migration(), &mut hooks)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            failure,
+            Failure::Stopped {
+                phase: Phase::Retained,
+                ..
+            }
+        ));
+        assert!(!failure.is_orphaned());
+        assert_eq!(hooks.events.last().unwrap(), "checkpoint Retained");
+        assert_eq!(api.template("Source"), Some(fixture.retained));
+    }
+
+    #[tokio::test]
+    async fn resumes_after_the_last_completed_phase() {
+        let api = api();
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let fixture = Fixture::new(&api).await;
+        let mut hooks = Recorder::new(&api);
+        hooks.stop_after = Some(Phase::Removed);
+        let failure = execute((&waiter, &waiter), &fixture.migration(), &mut hooks)
+            .await
+            .unwrap_err();
+        assert!(failure.is_orphaned());
+
+        let mut hooks = Recorder::new(&api);
+        let migration = Migration {
+            completed: Some(Phase::Removed),
+            ..fixture.migration()
+        };
+        execute((&waiter, &waiter), &migration, &mut hooks)
+            .await
+            .unwrap();
+
+        assert_eq!(hooks.events[0], "start CreateImport");
+        assert_eq!(hooks.events.last().unwrap(), "checkpoint Completed");
+        assert_eq!(api.resources("Target")["Data"], physical_id("Bucket"));
+        assert_eq!(
+            api.calls()
+                .iter()
+                .filter(|call| *call == "update_stack Source")
+                .count(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn update_stack_fails_if_the_update_cannot_be_started() {
+        let api = api();
+        api.fail_on(
+            "update_stack",
+            "Source",
+            "Stack is in UPDATE_IN_PROGRESS state",
+        );
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let removed = composer::remove_resources(source_template(), vec!["Bucket".to_string()]);
+
+        let err = update_stack(&waiter, "Source", (&removed, &[]), &[], (None, None))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "Stack is in UPDATE_IN_PROGRESS state");
+        assert_eq!(api.template("Source"), Some(source_template()));
+    }
+
+    #[tokio::test]
+    async fn create_import_fails_with_the_reason_of_the_changeset() {
+        let api = api();
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let mut import = api.template("Target").unwrap();
+        import["Resources"]["Bucket"] = json!({"Type": "AWS::S3::Bucket"});
+
+        let err = create_import(
+            &waiter,
+            ("Target", "Import"),
+            (&import, &[]),
+            &[bucket("Bucket", "data-prod")],
+            &[],
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Changeset creation failed FAILED: The following resource(s) to import [Bucket] must have DeletionPolicy attribute specified in the template."
+        );
+        assert!(!api.resources("Target").contains_key("Bucket"));
+    }
+
+    #[tokio::test]
+    async fn restores_resources_after_a_failed_import() {
+        let api = api();
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let physical_id = api.resources("Source")["Bucket"].clone();
+        let (retained, _) = retain_and_remove(&waiter).await;
+
+        let (import, _) = composer::add_resources(
+            api.template("Target").unwrap(),
+            source_template(),
+            HashMap::from([("Bucket".to_string(), "Bucket".to_string())]),
+        )
+        .unwrap();
+        api.fail_on("execute_changeset", "Target", "Rate exceeded");
+        let source = api.stack_parameters("Source").await.unwrap();
+        create_import(
+            &waiter,
+            ("Target", "Import"),
+            (
+                &import,
+                &crate::stack_api::parameters_for(&import, &[], &source),
+            ),
+            &[bucket("Bucket", &physical_id)],
+            &[],
+        )
+        .await
+        .unwrap();
+        let err = execute_import(&waiter, ("Target", "Import"), (None, Some(1)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Rate exceeded");
+
+        let parameters = crate::stack_api::parameters_for(&retained, &source, &[]);
+        restore_resources(
+            &waiter,
+            ("Source", "Restore"),
+            (&retained, &source_template()),
+            &parameters,
+            &[bucket("Bucket", &physical_id)],
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(api.resources("Source")["Bucket"], physical_id);
+        assert_eq!(api.template("Source"), Some(source_template()));
+        assert_eq!(api.parameters("Source")["Env"], "prod");
+        assert!(!api.resources("Target").contains_key("Bucket"));
+    }
+}
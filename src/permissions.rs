@@ -0,0 +1,79 @@
+use aws_config::SdkConfig;
+use aws_sdk_iam as iam;
+use aws_sdk_sts as sts;
+use std::error::Error;
+
+/// CloudFormation actions needed to move resources between stacks
+pub const REQUIRED_ACTIONS: [&str; 10] = [
+    "cloudformation:CreateChangeSet",
+    "cloudformation:DescribeChangeSet",
+    "cloudformation:DescribeStacks",
+    "cloudformation:ExecuteChangeSet",
+    "cloudformation:GetTemplate",
+    "cloudformation:GetTemplateSummary",
+    "cloudformation:ListStackResources",
+    "cloudformation:ListStacks",
+    "cloudformation:UpdateStack",
+    "cloudformation:ValidateTemplate",
+];
+
+/// Simulates the policies of the current principal for the given actions on the given resources
+/// and returns the actions which would be denied.
+pub async fn denied_actions(
+    config: &SdkConfig,
+    actions: &[&str],
+    resource_arns: &[String],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let identity = sts::Client::new(config)
+        .get_caller_identity()
+        .send()
+        .await?;
+    let principal = principal_arn(
+        identity
+            .arn()
+            .ok_or("Unable to determine caller identity")?,
+    );
+
+    let iam = iam::Client::new(config);
+    let mut denied = Vec::new();
+    let mut marker = None;
+
+    loop {
+        let resp = iam
+            .simulate_principal_policy()
+            .policy_source_arn(&principal)
+            .set_action_names(Some(actions.iter().map(|a| a.to_string()).collect()))
+            .set_resource_arns(Some(resource_arns.to_vec()))
+            .set_marker(marker)
+            .send()
+            .await?;
+
+        for result in resp.evaluation_results() {
+            if result.eval_decision() != &iam::types::PolicyEvaluationDecisionType::Allowed {
+                denied.push(result.eval_action_name().to_string());
+            }
+        }
+
+        match resp.marker() {
+            Some(next_marker) if resp.is_truncated() => marker = Some(next_marker.to_string()),
+            _ => break,
+        }
+    }
+
+    denied.sort();
+    denied.dedup();
+    Ok(denied)
+}
+
+/// The policy simulator needs the ARN of the role, not of the assumed role session:
+/// `arn:aws:sts::123456789012:assumed-role/Name/session` ► `arn:aws:iam::123456789012:role/Name`
+fn principal_arn(caller_arn: &str) -> String {
+    let parts = caller_arn.split(':').collect::<Vec<_>>();
+    match (parts.as_slice(), caller_arn.contains(":assumed-role/")) {
+        ([_, partition, _, _, account, resource], true) => {
+            let role = resource.split('/').nth(1).unwrap_or_default();
+            format!("arn:{}:iam::{}:role/{}", partition, account, role)
+        }
+        _ => caller_arn.to_string(),
+    }
+}
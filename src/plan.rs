@@ -0,0 +1,238 @@
+//! Plan manifests describe a migration in a file, so it can be reviewed and validated before it is
+//! executed.
+//!
+//! ```yaml
+//! source: Stack1
+//! target: Stack2
+//! resources:
+//!   - Bucket21D68F7E8
+//!   - Bucket182C536A1:NewBucket
+//! physical_ids:
+//!   Bucket21D68F7E8: my-bucket-v2
+//! ```
+//!
+//! A batch manifest lists several plans, which are executed one after another:
+//!
+//! ```yaml
+//! migrations:
+//!   - source: Stack1
+//!     target: Stack2
+//!     resources:
+//!       - Bucket21D68F7E8
+//!   - source: Stack3
+//!     target: Stack4
+//!     resources:
+//!       - Table
+//!       - Queue:OrdersQueue
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Plan {
+    /// Name of the source stack
+    pub source: String,
+
+    /// Name of the target stack
+    pub target: String,
+
+    /// Logical IDs of the resources to move, optionally with a new ID: `ID[:NEW_ID]`
+    pub resources: Vec<String>,
+
+    /// Physical IDs to import resources with, instead of the IDs recorded in the source stack
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub physical_ids: BTreeMap<String, String>,
+}
+
+impl Plan {
+    /// Reads a plan from a YAML or JSON file
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let body = fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read plan {}: {}", path.display(), err))?;
+        serde_yaml::from_str(&body)
+            .map_err(|err| format!("Invalid plan {}: {}", path.display(), err))
+    }
+
+    /// Pairs of source and target logical IDs, in the order of the plan
+    pub fn resource_ids(&self) -> Vec<(String, String)> {
+        self.resources.iter().map(|id| split_ids(id)).collect()
+    }
+}
+
+/// Several migrations, executed in the order of the manifest
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Batch {
+    pub migrations: Vec<Plan>,
+}
+
+impl Batch {
+    /// Reads a batch manifest from a YAML or JSON file
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let body = fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read manifest {}: {}", path.display(), err))?;
+        let batch: Batch = serde_yaml::from_str(&body)
+            .map_err(|err| format!("Invalid manifest {}: {}", path.display(), err))?;
+        if batch.migrations.is_empty() {
+            return Err(format!("Manifest {} has no migrations", path.display()));
+        }
+        if let Some(index) = batch.migrations.iter().position(|m| m.resources.is_empty()) {
+            return Err(format!(
+                "Migration {} of manifest {} has no resources",
+                index + 1,
+                path.display()
+            ));
+        }
+        Ok(batch)
+    }
+}
+
+/// Splits `ID[:NEW_ID]` into the source and target logical ID
+pub fn split_ids(id: &str) -> (String, String) {
+    match id.split_once(':') {
+        Some((source_id, target_id)) => (source_id.to_string(), target_id.to_string()),
+        None => (id.to_string(), id.to_string()),
+    }
+}
+
+/// Splits `ID=PHYSICAL_ID` into the logical and the physical ID
+pub fn split_physical_id(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((logical_id, physical_id)) if !logical_id.is_empty() && !physical_id.is_empty() => {
+            Ok((logical_id.to_string(), physical_id.to_string()))
+        }
+        _ => Err(format!(
+            "Invalid physical ID override '{}', expected ID=PHYSICAL_ID",
+            value
+        )),
+    }
+}
+
+/// Logical IDs which keep their ID in a rename within one stack, sorted. IDs are compared
+/// case-sensitively like CloudFormation does, so `mybucket` renamed to `MyBucket` is a rename.
+pub fn unrenamed_ids(id_map: &HashMap<String, String>) -> Vec<String> {
+    let mut ids = id_map
+        .iter()
+        .filter(|(old_id, new_id)| old_id == new_id)
+        .map(|(old_id, _)| old_id.clone())
+        .collect::<Vec<_>>();
+    ids.sort();
+    ids
+}
+
+/// Logical IDs consist of up to 255 alphanumeric characters
+pub fn is_valid_logical_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 255 && id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Suggests a valid logical ID based on `id`. Characters which are not allowed are removed and a
+/// number is appended if the ID is already taken.
+pub fn suggest_logical_id(id: &str, taken: &HashSet<String>) -> String {
+    let mut base = id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(250)
+        .collect::<String>();
+    if base.is_empty() {
+        base = "Resource".to_string();
+    }
+
+    let mut suggestion = base.clone();
+    let mut counter = 2;
+    while taken.contains(&suggestion) {
+        suggestion = format!("{}{}", base, counter);
+        counter += 1;
+    }
+    suggestion
+}
+
+/// Derives a logical ID from the CDK construct path of a resource, e.g. `Stack/Orders/Table/Resource`
+/// becomes `OrdersTable`
+pub fn logical_id_from_cdk_path(path: &str) -> Option<String> {
+    let mut segments = path.split('/').skip(1).collect::<Vec<_>>();
+    if matches!(segments.last(), Some(&"Resource") | Some(&"Default")) {
+        segments.pop();
+    }
+    let id = segments.iter().map(|s| pascal_case(s)).collect::<String>();
+    (!id.is_empty()).then_some(id)
+}
+
+/// Derives a logical ID from the physical name of a resource, e.g. `orders-table` becomes
+/// `OrdersTable`. ARNs and URLs are reduced to their last segment, random suffixes generated by
+/// CloudFormation are dropped. IDs assigned by AWS, like `sg-0123456789abcdef0`, are no names.
+pub fn logical_id_from_name(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', ':']).next().unwrap_or(name);
+    if is_generated_id(name) {
+        return None;
+    }
+    let id = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !is_generated_suffix(part))
+        .map(pascal_case)
+        .collect::<String>();
+    (!id.is_empty()).then_some(id)
+}
+
+/// Whether a logical ID ends with the hash CDK appends to generated IDs, e.g. `Bucket83908E77`
+pub fn has_generated_suffix(logical_id: &str) -> bool {
+    let suffix = &logical_id[logical_id.len().saturating_sub(8)..];
+    logical_id.len() > 8
+        && suffix
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('A'..='F').contains(&c))
+}
+
+fn pascal_case(value: &str) -> String {
+    let mut chars = value.chars().filter(|c| c.is_ascii_alphanumeric());
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + &chars.collect::<String>(),
+        None => String::new(),
+    }
+}
+
+fn is_generated_id(name: &str) -> bool {
+    match name.split_once('-') {
+        Some((prefix, id)) => {
+            prefix.chars().all(|c| c.is_ascii_lowercase())
+                && id.len() >= 8
+                && id.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => false,
+    }
+}
+
+/// Random suffixes of generated names, e.g. `1A2B3C4D5E6F7`
+fn is_generated_suffix(part: &str) -> bool {
+    part.len() >= 8
+        && part.chars().any(|c| c.is_ascii_digit())
+        && part
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unrenamed_ids_treats_case_only_changes_as_renames() {
+        let id_map = HashMap::from([
+            ("mybucket".to_string(), "MyBucket".to_string()),
+            ("Queue".to_string(), "Queue".to_string()),
+        ]);
+
+        assert_eq!(unrenamed_ids(&id_map), vec!["Queue".to_string()]);
+    }
+
+    #[test]
+    fn suggest_logical_id_distinguishes_case() {
+        let taken = HashSet::from(["mybucket".to_string()]);
+
+        assert_eq!(suggest_logical_id("MyBucket", &taken), "MyBucket");
+        assert_eq!(suggest_logical_id("mybucket", &taken), "mybucket2");
+    }
+}
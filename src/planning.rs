@@ -0,0 +1,700 @@
+//! Planning of a migration, separate from its execution. [`plan`] computes from the templates of
+//! both stacks everything a migration does: its phases with the templates before and after each of
+//! them, the action for every resource, the capabilities the templates require and warnings. The
+//! result is serializable, so it can be persisted and compared with the plan of another run.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::plan::{self, Plan};
+use crate::{analysis, attributes, capabilities, caveats, composer, sanitize};
+
+/// A migration as computed from a plan manifest and the current templates of both stacks
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MigrationPlan {
+    pub source: String,
+    pub target: String,
+
+    /// The stack operations of the migration, in the order they are executed
+    pub phases: Vec<Phase>,
+
+    pub resources: Vec<ResourceAction>,
+
+    /// The templates of the migration by their name, e.g. `target-import`, including the original
+    /// templates as `source-original` and `target-original`
+    pub templates: BTreeMap<String, Value>,
+
+    /// Capabilities required by any of the templates deployed, e.g. `CAPABILITY_IAM`
+    pub capabilities: Vec<String>,
+
+    pub warnings: Vec<String>,
+
+    /// Paths of the entries removed from the moved resources, e.g. metadata of the tool which
+    /// created the source stack
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<String>,
+}
+
+/// A stack operation of a migration
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Phase {
+    /// Name of the phase, as printed with `--plain`, e.g. `import-target`
+    pub name: String,
+    pub stack: String,
+    pub description: String,
+
+    /// Names of the templates before and after the phase
+    pub before: String,
+    pub after: String,
+}
+
+/// What happens to a resource of the source stack
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResourceAction {
+    pub source_id: String,
+    pub target_id: String,
+    pub resource_type: String,
+    pub action: Action,
+
+    /// Physical ID to import the resource with, instead of the ID recorded in the source stack
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub physical_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Moved with its logical ID
+    Move,
+
+    /// Moved and given a new logical ID in the target stack
+    MoveAndRename,
+}
+
+/// Decisions a migration is composed with beyond its plan manifest, as taken in the prompts of an
+/// interactive run. The default composes the templates like `cfn-teleport plan` does.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Resources of the source stack which refer to the same physical resource as a moved one, by
+    /// their logical ID, with the ID of the moved resource. They are only removed from the source
+    /// stack.
+    pub aliases: HashMap<String, String>,
+
+    /// Transforms to declare in the target stack, by default the ones the moved resources rely on
+    pub transforms: Option<Vec<String>>,
+
+    /// Tools whose metadata is removed from the moved resources, by default the detected ones
+    pub profiles: Option<Vec<&'static sanitize::Profile>>,
+
+    /// Attributes which are not copied into the target stack, by the logical ID in the source stack
+    pub stripped_attributes: Vec<(String, &'static str)>,
+
+    /// New names of Outputs moving along, and of their exports, by the original output name
+    pub output_names: BTreeMap<String, String>,
+    pub export_names: BTreeMap<String, String>,
+
+    /// Fixed values for the references of remaining policies to moved resources, by expression,
+    /// see [`composer::hardcode_policy_references`]
+    pub policy_values: HashMap<String, String>,
+
+    /// Definitions of moved resources edited during review, by their logical ID in the target stack
+    pub edited_resources: HashMap<String, Value>,
+
+    /// Whether the resources are renamed within the source stack, which makes the source stack
+    /// without the resources the template they are imported into
+    pub renaming: bool,
+}
+
+/// Computes the migration of a plan manifest from the current templates of both stacks. Transforms
+/// the moved resources rely on are declared in the target templates, which is reported as warning.
+pub fn plan(
+    manifest: &Plan,
+    template_source: &Value,
+    template_target: &Value,
+    options: &Options,
+) -> Result<MigrationPlan, String> {
+    if manifest.source == manifest.target && !options.renaming {
+        return Err("Source and target stack must be different".to_string());
+    }
+    let resource_ids = manifest.resource_ids();
+    if let Some((_, id)) = resource_ids
+        .iter()
+        .find(|(_, id)| !plan::is_valid_logical_id(id))
+    {
+        return Err(format!(
+            "Invalid logical ID {}, only alphanumeric characters are allowed",
+            id
+        ));
+    }
+    let source_ids = resource_ids
+        .iter()
+        .map(|(id, _)| id.clone())
+        .collect::<Vec<_>>();
+    if let Some(id) = source_ids
+        .iter()
+        .find(|id| template_source["Resources"].get(id.as_str()).is_none())
+    {
+        return Err(format!(
+            "Resource {} not found in stack {}",
+            id, manifest.source
+        ));
+    }
+    let removed_ids = source_ids
+        .iter()
+        .chain(options.aliases.keys())
+        .cloned()
+        .collect::<Vec<_>>();
+    let template_retained = composer::hardcode_policy_references(
+        composer::retain_resources(template_source.clone(), removed_ids.clone()),
+        &removed_ids,
+        &options.policy_values,
+    );
+    let template_removed = composer::hardcode_policy_references(
+        composer::remove_resources(template_source.clone(), removed_ids.clone()),
+        &removed_ids,
+        &options.policy_values,
+    );
+
+    // renamed resources are imported into the stack they have just been removed from
+    let template_target = match options.renaming {
+        true => &template_removed,
+        false => template_target,
+    };
+    if let Some((_, id)) = resource_ids
+        .iter()
+        .find(|(_, id)| template_target["Resources"].get(id.as_str()).is_some())
+    {
+        return Err(format!(
+            "Logical ID {} already exists in stack {}",
+            id, manifest.target
+        ));
+    }
+
+    let mut warnings = Vec::new();
+    let transforms = match &options.transforms {
+        Some(transforms) => transforms.clone(),
+        None => analysis::missing_transforms(template_source, template_target, &source_ids)
+            .into_iter()
+            .map(|missing| {
+                warnings.push(format!(
+                    "Declaring transform {} in stack {}, {} rely on it",
+                    missing.transform,
+                    manifest.target,
+                    missing.resources.join(", ")
+                ));
+                missing.transform
+            })
+            .collect(),
+    };
+    let template_target_declared = composer::add_transforms(template_target.clone(), &transforms);
+
+    let profiles = match &options.profiles {
+        Some(profiles) => profiles.clone(),
+        None => sanitize::detect(template_source),
+    };
+    let (template_source_composed, removed) =
+        compose_source(template_source, &source_ids, options, &profiles);
+    let (mut template_import, mut template_final) = composer::add_resources(
+        template_target_declared,
+        template_source_composed,
+        resource_ids.iter().cloned().collect::<HashMap<_, _>>(),
+    )?;
+    if !options.edited_resources.is_empty() {
+        for (logical_id, resource) in &options.edited_resources {
+            template_final["Resources"][logical_id] = resource.clone();
+            template_import["Resources"][logical_id] = resource.clone();
+        }
+        template_import = composer::set_default_deletion_policy(
+            template_import,
+            options.edited_resources.keys().cloned().collect(),
+        );
+    }
+
+    warnings.extend(
+        analysis::dangling_references(&template_retained, &removed_ids)
+            .iter()
+            .map(|reference| format!("Stack {}: {}", manifest.source, reference)),
+    );
+    warnings.extend(
+        analysis::unresolved_references(&template_final)
+            .iter()
+            .map(|reference| format!("Stack {}: {}", manifest.target, reference)),
+    );
+    warnings.extend(
+        caveats::caveats(
+            source_ids
+                .iter()
+                .filter_map(|id| template_source["Resources"][id]["Type"].as_str()),
+        )
+        .iter()
+        .map(|caveat| format!("{}: {}", caveat.resource_type, caveat.warning)),
+    );
+
+    let mut required = Vec::new();
+    for template in [
+        &template_retained,
+        &template_removed,
+        &template_import,
+        &template_final,
+    ] {
+        for requirement in capabilities::required_capabilities(template) {
+            if !required.contains(&requirement.capability) {
+                required.push(requirement.capability);
+            }
+        }
+    }
+    let capabilities = capabilities::CAPABILITIES
+        .iter()
+        .filter(|capability| required.contains(capability))
+        .map(|capability| capability.to_string())
+        .collect();
+
+    let resources = resource_ids
+        .iter()
+        .map(|(source_id, target_id)| ResourceAction {
+            source_id: source_id.clone(),
+            target_id: target_id.clone(),
+            resource_type: template_source["Resources"][source_id]["Type"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            action: match source_id == target_id {
+                true => Action::Move,
+                false => Action::MoveAndRename,
+            },
+            physical_id: manifest.physical_ids.get(source_id).cloned(),
+        })
+        .collect();
+
+    let phase = |name: &str, stack: &str, description: String, before: &str, after: &str| Phase {
+        name: name.to_string(),
+        stack: stack.to_string(),
+        description,
+        before: before.to_string(),
+        after: after.to_string(),
+    };
+    let phases = vec![
+        phase(
+            "retain-source",
+            &manifest.source,
+            format!(
+                "Set the DeletionPolicy in stack {} to Retain",
+                manifest.source
+            ),
+            "source-original",
+            "source-retained",
+        ),
+        phase(
+            "remove-source",
+            &manifest.source,
+            format!("Remove the resources from stack {}", manifest.source),
+            "source-retained",
+            "source-removed",
+        ),
+        phase(
+            "import-target",
+            &manifest.target,
+            format!("Import the resources into stack {}", manifest.target),
+            "target-original",
+            "target-import",
+        ),
+        phase(
+            "update-target",
+            &manifest.target,
+            format!("Update stack {} to its final template", manifest.target),
+            "target-import",
+            "target-final",
+        ),
+    ];
+
+    let templates = [
+        ("source-original", template_source.clone()),
+        ("target-original", template_target.clone()),
+        ("source-retained", template_retained),
+        ("source-removed", template_removed),
+        ("target-import", template_import),
+        ("target-final", template_final),
+    ]
+    .into_iter()
+    .map(|(name, template)| (name.to_string(), template))
+    .collect();
+
+    Ok(MigrationPlan {
+        source: manifest.source.clone(),
+        target: manifest.target.clone(),
+        phases,
+        resources,
+        templates,
+        capabilities,
+        warnings,
+        removed,
+    })
+}
+
+/// The source template as its resources are copied into the target stack: aliases are merged,
+/// colliding Outputs renamed, metadata of the tools which created the source stack and stripped
+/// attributes are removed. Returns the template with the paths of the removed entries.
+fn compose_source(
+    template_source: &Value,
+    moved_ids: &[String],
+    options: &Options,
+    profiles: &[&sanitize::Profile],
+) -> (Value, Vec<String>) {
+    let mut template = composer::merge_aliases(template_source.clone(), &options.aliases);
+    template = composer::rename_outputs(template, &options.output_names, &options.export_names);
+    let mut removed = Vec::new();
+
+    for profile in profiles {
+        for path in profile.sanitize(&mut template, moved_ids) {
+            removed.push(format!("{} ({} profile)", path, profile.name));
+        }
+    }
+
+    for (logical_id, attribute) in &options.stripped_attributes {
+        if attributes::strip(&mut template, logical_id, attribute) {
+            removed.push(format!("Resources.{}.{}", logical_id, attribute));
+        }
+    }
+
+    (template, removed)
+}
+
+impl MigrationPlan {
+    /// The template of the given name, `Null` for unknown names
+    pub fn template(&self, name: &str) -> &Value {
+        self.templates.get(name).unwrap_or(&Value::Null)
+    }
+}
+
+/// Renders the plan without its templates, for humans
+impl fmt::Display for MigrationPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Move {} resources from stack {} to {}:",
+            self.resources.len(),
+            self.source,
+            self.target
+        )?;
+        for resource in &self.resources {
+            match resource.action {
+                Action::Move => write!(f, "  {} ({})", resource.source_id, resource.resource_type)?,
+                Action::MoveAndRename => write!(
+                    f,
+                    "  {} -> {} ({})",
+                    resource.source_id, resource.target_id, resource.resource_type
+                )?,
+            }
+            match &resource.physical_id {
+                Some(physical_id) => writeln!(f, ", imported as {}", physical_id)?,
+                None => writeln!(f)?,
+            }
+        }
+        writeln!(f, "Phases:")?;
+        for (index, phase) in self.phases.iter().enumerate() {
+            writeln!(f, "  {}. {}", index + 1, phase.description)?;
+        }
+        if !self.capabilities.is_empty() {
+            writeln!(f, "Capabilities: {}", self.capabilities.join(", "))?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "WARNING: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn manifest(source: &str, target: &str, resources: &[&str]) -> Plan {
+        Plan {
+            source: source.to_string(),
+            target: target.to_string(),
+            resources: resources.iter().map(|id| id.to_string()).collect(),
+            physical_ids: Default::default(),
+        }
+    }
+
+    fn source_template() -> Value {
+        json!({
+            "Resources": {
+                "Bucket": {
+                    "Type": "AWS::S3::Bucket",
+                    "Metadata": {"aws:cdk:path": "Stack1/Bucket/Resource"}
+                },
+                "Role": {
+                    "Type": "AWS::IAM::Role",
+                    "Properties": {
+                        "Policies": [{
+                            "PolicyDocument": {
+                                "Statement": [{
+                                    "Effect": "Allow",
+                                    "Action": "s3:GetObject",
+                                    "Resource": {"Fn::GetAtt": ["Bucket", "Arn"]}
+                                }]
+                            }
+                        }]
+                    }
+                }
+            },
+            "Outputs": {
+                "BucketName": {"Value": {"Ref": "Bucket"}}
+            }
+        })
+    }
+
+    fn target_template() -> Value {
+        json!({"Resources": {"Queue": {"Type": "AWS::SQS::Queue"}}})
+    }
+
+    #[test]
+    fn plan_computes_the_templates_of_every_phase() {
+        let migration = plan(
+            &manifest("Stack1", "Stack2", &["Bucket:Storage"]),
+            &source_template(),
+            &target_template(),
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            migration.template("source-retained")["Resources"]["Bucket"]["DeletionPolicy"],
+            "Retain"
+        );
+        assert!(migration.template("source-removed")["Resources"]
+            .get("Bucket")
+            .is_none());
+        assert_eq!(
+            migration.template("target-import")["Resources"]["Storage"]["DeletionPolicy"],
+            "Delete"
+        );
+        assert!(migration.template("target-import").get("Outputs").is_none());
+        assert_eq!(
+            migration.template("target-final")["Outputs"]["BucketName"]["Value"],
+            json!({"Ref": "Storage"})
+        );
+        assert_eq!(migration.resources[0].action, Action::MoveAndRename);
+        let phases = migration
+            .phases
+            .iter()
+            .map(|phase| (phase.before.as_str(), phase.after.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            phases,
+            vec![
+                ("source-original", "source-retained"),
+                ("source-retained", "source-removed"),
+                ("target-original", "target-import"),
+                ("target-import", "target-final"),
+            ]
+        );
+        assert!(phases
+            .iter()
+            .all(|(before, after)| migration.templates.contains_key(*before)
+                && migration.templates.contains_key(*after)));
+    }
+
+    #[test]
+    fn plan_removes_the_metadata_of_detected_tools() {
+        let migration = plan(
+            &manifest("Stack1", "Stack2", &["Bucket"]),
+            &source_template(),
+            &target_template(),
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(migration.template("target-final")["Resources"]["Bucket"]
+            .get("Metadata")
+            .is_none());
+        assert_eq!(
+            migration.removed,
+            vec!["Resources.Bucket.Metadata.aws:cdk:path (cdk profile)".to_string()]
+        );
+
+        let options = Options {
+            profiles: Some(Vec::new()),
+            ..Default::default()
+        };
+        let migration = plan(
+            &manifest("Stack1", "Stack2", &["Bucket"]),
+            &source_template(),
+            &target_template(),
+            &options,
+        )
+        .unwrap();
+        assert!(migration.removed.is_empty());
+    }
+
+    #[test]
+    fn plan_applies_the_decisions_of_a_run() {
+        let options = Options {
+            stripped_attributes: vec![("Bucket".to_string(), "Metadata")],
+            profiles: Some(Vec::new()),
+            output_names: BTreeMap::from([("BucketName".to_string(), "MovedBucket".to_string())]),
+            policy_values: HashMap::from([(
+                "Bucket.Arn".to_string(),
+                "arn:aws:s3:::bucket".to_string(),
+            )]),
+            edited_resources: HashMap::from([(
+                "Bucket".to_string(),
+                json!({"Type": "AWS::S3::Bucket", "Properties": {"BucketName": "bucket"}}),
+            )]),
+            ..Default::default()
+        };
+        let migration = plan(
+            &manifest("Stack1", "Stack2", &["Bucket"]),
+            &source_template(),
+            &target_template(),
+            &options,
+        )
+        .unwrap();
+
+        for name in ["source-retained", "source-removed"] {
+            assert_eq!(
+                migration.template(name)["Resources"]["Role"]["Properties"]["Policies"][0]
+                    ["PolicyDocument"]["Statement"][0]["Resource"],
+                "arn:aws:s3:::bucket"
+            );
+        }
+        assert!(migration.template("target-final")["Outputs"]
+            .get("MovedBucket")
+            .is_some());
+        assert_eq!(
+            migration.template("target-final")["Resources"]["Bucket"]["Properties"]["BucketName"],
+            "bucket"
+        );
+        assert_eq!(
+            migration.template("target-import")["Resources"]["Bucket"]["DeletionPolicy"],
+            "Delete"
+        );
+        assert_eq!(
+            migration.removed,
+            vec!["Resources.Bucket.Metadata".to_string()]
+        );
+    }
+
+    #[test]
+    fn plan_removes_aliases_only_from_the_source_stack() {
+        let mut template_source = source_template();
+        template_source["Resources"]["BucketAlias"] = json!({"Type": "AWS::S3::Bucket"});
+        template_source["Resources"]["Role"]["DependsOn"] = json!("BucketAlias");
+        let options = Options {
+            aliases: HashMap::from([("BucketAlias".to_string(), "Bucket".to_string())]),
+            ..Default::default()
+        };
+        let migration = plan(
+            &manifest("Stack1", "Stack2", &["Bucket"]),
+            &template_source,
+            &target_template(),
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(
+            migration.template("source-retained")["Resources"]["BucketAlias"]["DeletionPolicy"],
+            "Retain"
+        );
+        assert!(migration.template("source-removed")["Resources"]
+            .get("BucketAlias")
+            .is_none());
+        assert!(migration.template("target-final")["Resources"]
+            .get("BucketAlias")
+            .is_none());
+    }
+
+    #[test]
+    fn plan_declares_missing_transforms() {
+        let mut template_source = source_template();
+        template_source["Transform"] = json!("AWS::Serverless-2016-10-31");
+        template_source["Resources"]["Function"] = json!({
+            "Type": "AWS::Serverless::Function",
+            "Properties": {"Runtime": "python3.12"}
+        });
+        let migration = plan(
+            &manifest("Stack1", "Stack2", &["Function"]),
+            &template_source,
+            &target_template(),
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            migration.template("target-import")["Transform"],
+            "AWS::Serverless-2016-10-31"
+        );
+        assert!(migration.warnings[0].starts_with("Declaring transform"));
+
+        let options = Options {
+            transforms: Some(Vec::new()),
+            ..Default::default()
+        };
+        let migration = plan(
+            &manifest("Stack1", "Stack2", &["Function"]),
+            &template_source,
+            &target_template(),
+            &options,
+        )
+        .unwrap();
+        assert!(migration
+            .template("target-import")
+            .get("Transform")
+            .is_none());
+    }
+
+    #[test]
+    fn plan_imports_renamed_resources_into_the_source_stack() {
+        let manifest = manifest("Stack1", "Stack1", &["Bucket:Storage"]);
+        let err = plan(
+            &manifest,
+            &source_template(),
+            &source_template(),
+            &Options::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err, "Source and target stack must be different");
+
+        let options = Options {
+            renaming: true,
+            ..Default::default()
+        };
+        let migration = plan(&manifest, &source_template(), &source_template(), &options).unwrap();
+        assert_eq!(
+            migration.template("target-original"),
+            migration.template("source-removed")
+        );
+        let resources = migration.template("target-final")["Resources"]
+            .as_object()
+            .unwrap();
+        assert!(resources.contains_key("Storage"));
+        assert!(!resources.contains_key("Bucket"));
+    }
+
+    #[test]
+    fn plan_refuses_taken_and_missing_logical_ids() {
+        let err = plan(
+            &manifest("Stack1", "Stack2", &["Bucket:Queue"]),
+            &source_template(),
+            &target_template(),
+            &Options::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err, "Logical ID Queue already exists in stack Stack2");
+
+        let err = plan(
+            &manifest("Stack1", "Stack2", &["Table"]),
+            &source_template(),
+            &target_template(),
+            &Options::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err, "Resource Table not found in stack Stack1");
+    }
+}
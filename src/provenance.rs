@@ -0,0 +1,285 @@
+//! Provenance of exported migration artifacts.
+//!
+//! `cfn-teleport plan --output-dir` writes a manifest next to the templates, with the version of
+//! the tool, the plan of the migration and the SHA-256 digest of every file. A migration executed
+//! with `--from-artifacts` verifies the digests first, so the executed templates are provably the
+//! reviewed ones. The original templates of both stacks are exported as well, the migration is
+//! only executed while the stacks still have them.
+//!
+//! ```json
+//! {
+//!   "tool_version": "0.36.0",
+//!   "plan": { "source": "Stack1", "target": "Stack2", "resources": ["Bucket21D68F7E8"] },
+//!   "files": {
+//!     "source-retained.json": "3f1c…",
+//!     "source-removed.json": "9ab0…"
+//!   }
+//! }
+//! ```
+//!
+//! `cfn-teleport plan --plan-file` writes all of this into a single plan file instead, which
+//! `cfn-teleport apply` executes. The plan file is signed with HMAC-SHA256 if a key is set in
+//! `CFN_TELEPORT_SIGNING_KEY`, so only the holders of the key can create plans which are applied
+//! with it. Without a key, it carries the SHA-256 digest of its content.
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::plan::Plan;
+
+/// File name of the manifest within the artifact directory
+pub const MANIFEST: &str = "manifest.json";
+
+/// Environment variable with the key plan files are signed and verified with
+pub const SIGNING_KEY_ENV: &str = "CFN_TELEPORT_SIGNING_KEY";
+
+/// Templates of a migration, as exported and executed
+pub static TEMPLATES: [&str; 4] = [
+    "source-retained",
+    "source-removed",
+    "target-import",
+    "target-final",
+];
+
+/// Templates of both stacks a migration has been computed from, which must still be deployed when
+/// it is executed
+pub static ORIGINALS: [&str; 2] = ["source-original", "target-original"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Version of cfn-teleport which exported the artifacts
+    pub tool_version: String,
+
+    /// The migration the artifacts have been computed for
+    pub plan: Plan,
+
+    /// Hex encoded SHA-256 digests of the artifacts, by file name
+    pub files: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// Creates the manifest of artifacts which have been written to `dir`
+    pub fn create(
+        dir: &Path,
+        tool_version: &str,
+        plan: Plan,
+        files: &[String],
+    ) -> Result<Self, String> {
+        let files = files
+            .iter()
+            .map(|name| Ok((name.clone(), digest_file(&dir.join(name))?)))
+            .collect::<Result<_, String>>()?;
+        Ok(Manifest {
+            tool_version: tool_version.to_string(),
+            plan,
+            files,
+        })
+    }
+
+    /// Reads the manifest of an artifact directory
+    pub fn read(dir: &Path) -> Result<Self, String> {
+        let path = dir.join(MANIFEST);
+        let body = fs::read_to_string(&path)
+            .map_err(|err| format!("Unable to read manifest {}: {}", path.display(), err))?;
+        serde_json::from_str(&body)
+            .map_err(|err| format!("Invalid manifest {}: {}", path.display(), err))
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<(), String> {
+        let path = dir.join(MANIFEST);
+        let body = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(&path, body)
+            .map_err(|err| format!("Unable to write manifest {}: {}", path.display(), err))
+    }
+
+    /// Compares the digests of the artifacts in `dir` with the manifest. All files which are
+    /// missing or have been changed since the export are reported at once.
+    pub fn verify(&self, dir: &Path) -> Result<(), String> {
+        let mismatches = self
+            .files
+            .iter()
+            .filter_map(|(name, expected)| match digest_file(&dir.join(name)) {
+                Ok(actual) if actual == *expected => None,
+                Ok(_) => Some(format!("{} has been changed", name)),
+                Err(err) => Some(err),
+            })
+            .collect::<Vec<_>>();
+        if mismatches.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "The artifacts in {} do not match their manifest:\n - {}",
+            dir.display(),
+            mismatches.join("\n - ")
+        ))
+    }
+}
+
+/// A migration with its computed templates in a single file, signed to be applied later
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlanFile {
+    /// Version of cfn-teleport which computed the plan
+    pub tool_version: String,
+
+    /// The migration the templates have been computed for
+    pub plan: Plan,
+
+    /// The templates of the migration by their name, e.g. `target-import`
+    pub templates: BTreeMap<String, serde_json::Value>,
+
+    /// `hmac-sha256:<hex>` with a signing key, else `sha256:<hex>`
+    pub signature: String,
+}
+
+impl PlanFile {
+    pub fn create(
+        tool_version: &str,
+        plan: Plan,
+        templates: BTreeMap<String, serde_json::Value>,
+        key: Option<&[u8]>,
+    ) -> Result<Self, String> {
+        let mut plan_file = PlanFile {
+            tool_version: tool_version.to_string(),
+            plan,
+            templates,
+            signature: String::new(),
+        };
+        plan_file.signature = plan_file.sign(key)?;
+        Ok(plan_file)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let body = fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read plan file {}: {}", path.display(), err))?;
+        serde_json::from_str(&body)
+            .map_err(|err| format!("Invalid plan file {}: {}", path.display(), err))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let body = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(path, body)
+            .map_err(|err| format!("Unable to write plan file {}: {}", path.display(), err))
+    }
+
+    /// Checks the signature. With a key, the plan file must have been signed with the same key,
+    /// a plan file with only a digest is refused.
+    pub fn verify(&self, key: Option<&[u8]>) -> Result<(), String> {
+        match (self.signature.starts_with("hmac-sha256:"), key) {
+            (true, None) => {
+                return Err(format!(
+                    "The plan file is signed with a key, set it in {} to apply it",
+                    SIGNING_KEY_ENV
+                ))
+            }
+            (false, Some(_)) => {
+                return Err(format!(
+                    "The plan file is not signed with the key in {}",
+                    SIGNING_KEY_ENV
+                ))
+            }
+            _ => {}
+        }
+        if self.sign(key)? != self.signature {
+            return Err(
+                "The plan file has been changed since it was created, or signed with another key"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    fn sign(&self, key: Option<&[u8]>) -> Result<String, String> {
+        let content = serde_json::to_string(&serde_json::json!({
+            "tool_version": self.tool_version,
+            "plan": self.plan,
+            "templates": self.templates,
+        }))
+        .map_err(|err| err.to_string())?;
+        match key {
+            Some(key) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).map_err(|err| err.to_string())?;
+                mac.update(content.as_bytes());
+                Ok(format!("hmac-sha256:{}", hex(&mac.finalize().into_bytes())))
+            }
+            None => Ok(format!("sha256:{}", digest(content.as_bytes()))),
+        }
+    }
+}
+
+/// Hex encoded SHA-256 digest
+pub fn digest(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn digest_file(path: &Path) -> Result<String, String> {
+    fs::read(path)
+        .map(|bytes| digest(&bytes))
+        .map_err(|err| format!("Unable to read {}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan_file(key: Option<&[u8]>) -> PlanFile {
+        let plan = Plan {
+            source: "Stack1".to_string(),
+            target: "Stack2".to_string(),
+            resources: vec!["Bucket".to_string()],
+            physical_ids: Default::default(),
+        };
+        let templates = BTreeMap::from([(
+            "target-import".to_string(),
+            serde_json::json!({"Resources": {"Bucket": {"Type": "AWS::S3::Bucket"}}}),
+        )]);
+        PlanFile::create("0.36.0", plan, templates, key).unwrap()
+    }
+
+    #[test]
+    fn verify_accepts_unchanged_plan_files() {
+        assert!(plan_file(None).verify(None).is_ok());
+        assert!(plan_file(Some(b"key")).verify(Some(b"key")).is_ok());
+    }
+
+    #[test]
+    fn verify_refuses_changed_templates() {
+        for key in [None, Some(b"key".as_slice())] {
+            let mut plan_file = plan_file(key);
+            plan_file.templates.insert(
+                "target-final".to_string(),
+                serde_json::json!({"Resources": {}}),
+            );
+
+            let err = plan_file.verify(key).unwrap_err();
+            assert!(err.contains("has been changed"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn verify_refuses_another_key() {
+        let err = plan_file(Some(b"key")).verify(Some(b"other")).unwrap_err();
+        assert!(err.contains("signed with another key"), "{}", err);
+    }
+
+    #[test]
+    fn verify_refuses_plan_files_without_key_when_a_key_is_set() {
+        let err = plan_file(None).verify(Some(b"key")).unwrap_err();
+        assert!(err.contains("not signed with the key"), "{}", err);
+    }
+
+    #[test]
+    fn verify_refuses_signed_plan_files_without_key() {
+        let err = plan_file(Some(b"key")).verify(None).unwrap_err();
+        assert!(err.contains(SIGNING_KEY_ENV), "{}", err);
+    }
+}
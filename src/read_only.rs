@@ -0,0 +1,78 @@
+use aws_sdk_cloudformation::config::SharedHttpClient;
+use aws_smithy_runtime_api::client::http::{
+    HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpConnector,
+};
+use aws_smithy_runtime_api::client::orchestrator::HttpRequest;
+use aws_smithy_runtime_api::client::result::ConnectorError;
+use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+
+use crate::http_client;
+
+/// Prefixes of the operations which only read, e.g. `DescribeStacks` or `GetTemplate`
+static READ_PREFIXES: [&str; 5] = ["Describe", "List", "Get", "Validate", "Estimate"];
+
+/// Operations which only read, but have none of the prefixes. Assuming a role only creates
+/// temporary credentials.
+static READ_OPERATIONS: [&str; 6] = [
+    "Query",
+    "Scan",
+    "BatchGetItem",
+    "AssumeRole",
+    "AssumeRoleWithWebIdentity",
+    "AssumeRoleWithSAML",
+];
+
+/// Whether an operation of an AWS API only reads
+pub fn is_read_operation(operation: &str) -> bool {
+    READ_PREFIXES
+        .iter()
+        .any(|prefix| operation.starts_with(prefix))
+        || READ_OPERATIONS.contains(&operation)
+}
+
+/// Wraps an HTTP client, so every request of an operation which is not known to only read fails
+/// before it is sent. As all clients of a run share the HTTP client of the SDK config, no code path
+/// can change anything in the account, whatever it does. Requests without an operation, e.g. for
+/// credentials of the instance metadata service, are sent.
+pub fn wrap(client: SharedHttpClient) -> SharedHttpClient {
+    SharedHttpClient::new(ReadOnlyClient { inner: client })
+}
+
+#[derive(Debug)]
+struct ReadOnlyClient {
+    inner: SharedHttpClient,
+}
+
+impl HttpClient for ReadOnlyClient {
+    fn http_connector(
+        &self,
+        settings: &HttpConnectorSettings,
+        components: &RuntimeComponents,
+    ) -> SharedHttpConnector {
+        SharedHttpConnector::new(ReadOnlyConnector {
+            inner: self.inner.http_connector(settings, components),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct ReadOnlyConnector {
+    inner: SharedHttpConnector,
+}
+
+impl HttpConnector for ReadOnlyConnector {
+    fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+        match http_client::operation(&request) {
+            Some(operation) if !is_read_operation(&operation) => {
+                HttpConnectorFuture::ready(Err(ConnectorError::user(
+                    format!(
+                        "Refused to call {}, because cfn-teleport runs with --read-only",
+                        operation
+                    )
+                    .into(),
+                )))
+            }
+            _ => self.inner.call(request),
+        }
+    }
+}
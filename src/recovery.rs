@@ -0,0 +1,127 @@
+//! Report of resources which a failed migration left in neither stack, with everything needed to
+//! import them into either stack again.
+
+use serde_json::{json, Value};
+
+/// A moved resource which might not be managed by any stack
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrphanedResource {
+    /// Logical ID in the source stack
+    pub source_id: String,
+
+    /// Logical ID in the target stack
+    pub target_id: String,
+    pub resource_type: String,
+    pub physical_id: String,
+
+    /// Property identifying the resource on import, e.g. `BucketName`
+    pub identifier: Option<String>,
+
+    /// Definition of the resource in the source template
+    pub definition: Value,
+}
+
+impl OrphanedResource {
+    fn to_import(&self, logical_id: &str) -> Value {
+        json!({
+            "ResourceType": self.resource_type,
+            "LogicalResourceId": logical_id,
+            "ResourceIdentifier": {
+                self.identifier.as_deref().unwrap_or("<IDENTIFIER>"): self.physical_id,
+            },
+        })
+    }
+}
+
+/// The `--resources-to-import` of an import into the source stack, with the logical IDs of the
+/// source stack
+pub fn source_imports(resources: &[OrphanedResource]) -> Value {
+    resources
+        .iter()
+        .map(|resource| resource.to_import(&resource.source_id))
+        .collect()
+}
+
+/// The `--resources-to-import` of an import into the target stack
+pub fn target_imports(resources: &[OrphanedResource]) -> Value {
+    resources
+        .iter()
+        .map(|resource| resource.to_import(&resource.target_id))
+        .collect()
+}
+
+/// Machine-readable report, written next to the templates it refers to
+pub fn report_json(resources: &[OrphanedResource], stacks: (&str, &str)) -> Value {
+    json!({
+        "SourceStack": stacks.0,
+        "TargetStack": stacks.1,
+        "Resources": resources
+            .iter()
+            .map(|resource| json!({
+                "SourceLogicalId": resource.source_id,
+                "TargetLogicalId": resource.target_id,
+                "ResourceType": resource.resource_type,
+                "PhysicalId": resource.physical_id,
+                "ResourceIdentifier": resource.identifier,
+                "Definition": resource.definition,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Human-readable report with the resources and the AWS CLI commands to import them into either
+/// stack. `artifacts` is the directory of the templates and the files with the resources to import.
+pub fn render(resources: &[OrphanedResource], stacks: (&str, &str), artifacts: &str) -> String {
+    let (source_stack, target_stack) = stacks;
+    let mut report = String::from(
+        "The following resources have been removed from the source stack and might not be managed by any stack:\n",
+    );
+
+    for resource in resources {
+        report.push_str(&format!(
+            "\n  {} ({})\n    Physical ID: {}\n    Identifier:  {}\n    Definition:\n",
+            resource.source_id,
+            resource.resource_type,
+            resource.physical_id,
+            resource.identifier.as_deref().unwrap_or("unknown"),
+        ));
+        let definition = serde_json::to_string_pretty(&resource.definition).unwrap_or_default();
+        for line in definition.lines() {
+            report.push_str(&format!("      {}\n", line));
+        }
+    }
+
+    report.push_str(&format!(
+        "\nFirst check which of them are part of a stack already:\n\n  aws cloudformation describe-stack-resources --stack-name {}\n  aws cloudformation describe-stack-resources --stack-name {}\n",
+        source_stack, target_stack
+    ));
+
+    for (stack_name, import_template, final_template, imports) in [
+        (
+            target_stack,
+            "target-import",
+            "target-final",
+            "orphaned-resources-target",
+        ),
+        (
+            source_stack,
+            "source-restore-import",
+            "source-restore-final",
+            "orphaned-resources-source",
+        ),
+    ] {
+        report.push_str(&format!(
+            "\nTo import the resources into stack {stack}, run:\n\n  aws cloudformation create-change-set --stack-name {stack} --change-set-name cfn-teleport-recovery --change-set-type IMPORT --template-body file://{dir}/{template}.json --resources-to-import file://{dir}/{imports}.json\n  aws cloudformation wait change-set-create-complete --stack-name {stack} --change-set-name cfn-teleport-recovery\n  aws cloudformation execute-change-set --stack-name {stack} --change-set-name cfn-teleport-recovery\n  aws cloudformation wait stack-import-complete --stack-name {stack}\n  aws cloudformation update-stack --stack-name {stack} --template-body file://{dir}/{final_template}.json\n",
+            stack = stack_name,
+            dir = artifacts,
+            template = import_template,
+            imports = imports,
+            final_template = final_template,
+        ));
+    }
+
+    report.push_str(
+        "\nAdd --capabilities to the create-change-set and update-stack commands if the templates require them. Templates larger than 51,200 bytes have to be uploaded to S3 and passed with --template-url.\n",
+    );
+    report
+}
@@ -0,0 +1,145 @@
+//! Reductions of the size of a template, for consolidations which approach the quota of
+//! CloudFormation on the template body. Reductions only change the definitions of the given
+//! resources, usually the ones copied into the target stack, so the resources of the target stack
+//! stay untouched by the import.
+
+use serde_json::{Map, Value};
+
+/// Size of a template body from which reductions are suggested, 90% of the maximum size
+pub fn advice_threshold(body_limit: usize) -> usize {
+    body_limit / 10 * 9
+}
+
+/// Keys of resource Metadata which are read at runtime, e.g. by cfn-init, and are never stripped
+static RUNTIME_METADATA: [&str; 2] = [
+    "AWS::CloudFormation::Init",
+    "AWS::CloudFormation::Authentication",
+];
+
+/// A change which shrinks a template without changing the resources it defines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reduction {
+    /// Removes the Metadata of resources, e.g. `aws:cdk:path`, except for keys read at runtime
+    StripMetadata,
+
+    /// Replaces long-form intrinsic functions with compact equivalents, e.g. `Fn::Join` with
+    /// `Fn::Sub` and `Fn::GetAtt` lists with `Resource.Attribute` strings
+    CompactIntrinsics,
+}
+
+/// All reductions, in the order they are applied
+pub static REDUCTIONS: [Reduction; 2] = [Reduction::StripMetadata, Reduction::CompactIntrinsics];
+
+impl Reduction {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Reduction::StripMetadata => "Strip the Metadata of the resources",
+            Reduction::CompactIntrinsics => {
+                "Convert long-form intrinsic functions to compact forms"
+            }
+        }
+    }
+
+    /// Applies the reduction to the given resources of the template
+    pub fn apply(&self, mut template: Value, resource_ids: &[String]) -> Value {
+        for id in resource_ids {
+            let Some(resource) = template["Resources"].get_mut(id.as_str()) else {
+                continue;
+            };
+            match self {
+                Reduction::StripMetadata => strip_metadata(resource),
+                Reduction::CompactIntrinsics => compact(resource),
+            }
+        }
+        template
+    }
+}
+
+/// Size of the template body in bytes, as it is sent to CloudFormation
+pub fn body_size(template: &Value) -> usize {
+    serde_json::to_string(template).map_or(0, |body| body.len())
+}
+
+/// The largest resources of a template by their serialized size, largest first
+pub fn largest_resources(template: &Value, count: usize) -> Vec<(String, usize)> {
+    let mut sizes = template["Resources"]
+        .as_object()
+        .map(|resources| {
+            resources
+                .iter()
+                .map(|(id, resource)| (id.clone(), body_size(resource)))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    sizes.truncate(count);
+    sizes
+}
+
+fn strip_metadata(resource: &mut Value) {
+    let Some(resource) = resource.as_object_mut() else {
+        return;
+    };
+    let Some(Value::Object(metadata)) = resource.get_mut("Metadata") else {
+        return;
+    };
+    metadata.retain(|key, _| RUNTIME_METADATA.contains(&key.as_str()));
+    if metadata.is_empty() {
+        resource.remove("Metadata");
+    }
+}
+
+/// Compacts the intrinsic functions of a value, innermost first
+fn compact(value: &mut Value) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(compact),
+        Value::Object(map) => {
+            map.values_mut().for_each(compact);
+            if let Some(compacted) = compact_function(map) {
+                *value = compacted;
+            }
+        }
+        _ => {}
+    }
+}
+
+fn compact_function(map: &Map<String, Value>) -> Option<Value> {
+    if map.len() != 1 {
+        return None;
+    }
+    let (key, argument) = map.iter().next()?;
+    match (key.as_str(), argument) {
+        // ["Resource", "Attribute"] -> "Resource.Attribute"
+        ("Fn::GetAtt", Value::Array(parts)) => {
+            let [Value::String(resource), Value::String(attribute)] = parts.as_slice() else {
+                return None;
+            };
+            Some(serde_json::json!({ "Fn::GetAtt": format!("{}.{}", resource, attribute) }))
+        }
+        // ["-", ["a", {"Ref": "B"}]] -> "a-${B}"
+        ("Fn::Join", Value::Array(arguments)) => {
+            let [Value::String(separator), Value::Array(parts)] = arguments.as_slice() else {
+                return None;
+            };
+            if separator.contains("${") {
+                return None;
+            }
+            let parts = parts.iter().map(sub_part).collect::<Option<Vec<_>>>()?;
+            Some(serde_json::json!({ "Fn::Sub": parts.join(separator) }))
+        }
+        _ => None,
+    }
+}
+
+/// Part of a `Fn::Join` as text of a `Fn::Sub`, if it has an equivalent
+fn sub_part(part: &Value) -> Option<String> {
+    match part {
+        Value::String(text) if !text.contains("${") => Some(text.clone()),
+        Value::Object(map) if map.len() == 1 => match map.iter().next()? {
+            (key, Value::String(name)) if key == "Ref" => Some(format!("${{{}}}", name)),
+            (key, Value::String(name)) if key == "Fn::GetAtt" => Some(format!("${{{}}}", name)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,606 @@
+//! Finds and rewrites references to logical IDs inside CloudFormation templates.
+//!
+//! References can be expressed through `Ref`, `Fn::GetAtt`, `Fn::Sub` and the `DependsOn` resource
+//! attribute. Templates are expected in their JSON representation, YAML templates need to be
+//! converted with [`crate::cfn_yaml`] first.
+
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// Intrinsic function or attribute a logical ID is referenced with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReferenceKind {
+    Ref,
+    GetAtt,
+    Sub,
+    DependsOn,
+}
+
+impl std::fmt::Display for ReferenceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ReferenceKind::Ref => "Ref",
+            ReferenceKind::GetAtt => "Fn::GetAtt",
+            ReferenceKind::Sub => "Fn::Sub",
+            ReferenceKind::DependsOn => "DependsOn",
+        })
+    }
+}
+
+/// Segment of the path to a value within a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// A reference to a logical ID and where it has been found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    /// Referenced logical ID
+    pub id: String,
+    pub kind: ReferenceKind,
+
+    /// Path of the referencing value, relative to the searched value
+    pub path: Vec<PathSegment>,
+}
+
+impl Reference {
+    /// JSON pointer of the referencing value, e.g. `/Resources/Function/Properties/Tags/0/Value`,
+    /// as used by [`Value::pointer`]
+    pub fn pointer(&self) -> String {
+        self.path
+            .iter()
+            .map(|segment| match segment {
+                PathSegment::Key(key) => format!("/{}", key.replace('~', "~0").replace('/', "~1")),
+                PathSegment::Index(index) => format!("/{}", index),
+            })
+            .collect()
+    }
+
+    /// Readable form of the path, e.g. `Resources.Function.Properties.Tags[0].Value`
+    pub fn display_path(&self) -> String {
+        let mut path = String::new();
+        for segment in &self.path {
+            match segment {
+                PathSegment::Key(key) if path.is_empty() => path.push_str(key),
+                PathSegment::Key(key) => path.push_str(&format!(".{}", key)),
+                PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+
+    /// Logical ID of the resource or name of the output containing the reference, if the
+    /// reference has been found in a template
+    pub fn entry(&self) -> Option<(&str, &str)> {
+        match self.path.as_slice() {
+            [PathSegment::Key(section), PathSegment::Key(id), ..] => Some((section, id)),
+            _ => None,
+        }
+    }
+}
+
+/// Collects the logical IDs referenced anywhere in the given value
+pub fn collect_references(value: &Value) -> HashSet<String> {
+    find_references(value)
+        .into_iter()
+        .map(|reference| reference.id)
+        .collect()
+}
+
+/// Finds all references to logical IDs in the given value, in the order of the value, with the
+/// path and the intrinsic function of each reference
+pub fn find_references(value: &Value) -> Vec<Reference> {
+    let mut references = Vec::new();
+    collect(value, &mut Vec::new(), &mut references);
+    references
+}
+
+fn collect(value: &Value, path: &mut Vec<PathSegment>, references: &mut Vec<Reference>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                path.push(PathSegment::Key(key.clone()));
+                match (key.as_str(), value) {
+                    ("Ref", Value::String(id)) => {
+                        if !is_pseudo_parameter(id) {
+                            found(references, id, ReferenceKind::Ref, parent(path));
+                        }
+                    }
+                    ("Fn::GetAtt", Value::Array(items)) => {
+                        if let Some(Value::String(id)) = items.first() {
+                            found(references, id, ReferenceKind::GetAtt, parent(path));
+                        }
+                        collect_items(items, path, references);
+                    }
+                    ("Fn::GetAtt", Value::String(expression)) => {
+                        if let Some((id, _)) = split_get_att(expression) {
+                            found(references, id, ReferenceKind::GetAtt, parent(path));
+                        }
+                    }
+                    ("Fn::Sub", value) => {
+                        let (template, variables) = sub_parts(value);
+                        if let Some(template) = template {
+                            for expression in sub_expressions(template) {
+                                if let Some(id) = sub_reference(expression, &variables) {
+                                    found(references, id, ReferenceKind::Sub, parent(path));
+                                }
+                            }
+                        }
+                        if let Value::Array(items) = value {
+                            collect_items(items, path, references);
+                        }
+                    }
+                    ("DependsOn", Value::String(id)) => {
+                        found(references, id, ReferenceKind::DependsOn, path);
+                    }
+                    ("DependsOn", Value::Array(ids)) => {
+                        for (index, id) in ids.iter().enumerate() {
+                            if let Some(id) = id.as_str() {
+                                path.push(PathSegment::Index(index));
+                                found(references, id, ReferenceKind::DependsOn, path);
+                                path.pop();
+                            }
+                        }
+                    }
+                    _ => collect(value, path, references),
+                }
+                path.pop();
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                path.push(PathSegment::Index(index));
+                collect(item, path, references);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Path of the object an intrinsic function is the key of
+fn parent(path: &[PathSegment]) -> &[PathSegment] {
+    &path[..path.len().saturating_sub(1)]
+}
+
+fn found(references: &mut Vec<Reference>, id: &str, kind: ReferenceKind, path: &[PathSegment]) {
+    references.push(Reference {
+        id: id.to_string(),
+        kind,
+        path: path.to_vec(),
+    });
+}
+
+/// Collects the references in the arguments of an intrinsic function after the first one
+fn collect_items(items: &[Value], path: &mut Vec<PathSegment>, references: &mut Vec<Reference>) {
+    for (index, item) in items.iter().enumerate().skip(1) {
+        path.push(PathSegment::Index(index));
+        collect(item, path, references);
+        path.pop();
+    }
+}
+
+/// Rewrites all references to logical IDs found in `id_map` keys to the mapped logical ID
+pub fn traverse_and_update(value: &mut Value, id_map: &HashMap<String, String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match (key.as_str(), value) {
+                    ("Ref", Value::String(id)) => {
+                        if let Some(new_id) = id_map.get(id.as_str()) {
+                            *id = new_id.to_string();
+                        }
+                    }
+                    ("Fn::GetAtt", Value::Array(items)) => {
+                        if let Some(Value::String(id)) = items.first_mut() {
+                            if let Some(new_id) = id_map.get(id.as_str()) {
+                                *id = new_id.to_string();
+                            }
+                        }
+                        items
+                            .iter_mut()
+                            .skip(1)
+                            .for_each(|item| traverse_and_update(item, id_map));
+                    }
+                    ("Fn::GetAtt", Value::String(expression)) => {
+                        if let Some((id, attribute)) = split_get_att(expression) {
+                            if let Some(new_id) = id_map.get(id) {
+                                *expression = format!("{}.{}", new_id, attribute);
+                            }
+                        }
+                    }
+                    ("Fn::Sub", value) => update_sub(value, id_map),
+                    (_, value) => traverse_and_update(value, id_map),
+                }
+            }
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| traverse_and_update(item, id_map)),
+        _ => {}
+    }
+}
+
+/// Rewrites the `DependsOn` attribute of a resource definition
+pub fn update_depends_on(resource: &mut Value, id_map: &HashMap<String, String>) {
+    let rename = |id: &mut Value| {
+        if let Some(new_id) = id.as_str().and_then(|id| id_map.get(id)) {
+            *id = Value::String(new_id.to_string());
+        }
+    };
+
+    match resource.get_mut("DependsOn") {
+        Some(Value::Array(ids)) => ids.iter_mut().for_each(rename),
+        Some(id) => rename(id),
+        None => {}
+    }
+}
+
+/// Renames logical IDs in the source text of a template, keeping its formatting and comments.
+/// Every occurrence of an ID as a whole word is replaced, except for parts of resource types like
+/// `AWS::S3::Bucket` and attribute names like `Bucket.Arn`, so the result has to be checked against
+/// the template renamed with `update_template`.
+pub fn rename_in_source(body: &str, id_map: &HashMap<String, String>) -> String {
+    let mut renamed = String::with_capacity(body.len());
+    let mut word = String::new();
+
+    for c in body.chars() {
+        if c.is_ascii_alphanumeric() {
+            word.push(c);
+            continue;
+        }
+        push_word(&mut renamed, &mut word, id_map);
+        renamed.push(c);
+    }
+    push_word(&mut renamed, &mut word, id_map);
+
+    renamed
+}
+
+fn push_word(renamed: &mut String, word: &mut String, id_map: &HashMap<String, String>) {
+    let qualified = renamed.ends_with("::") || renamed.ends_with('.');
+    match id_map.get(word.as_str()) {
+        Some(new_id) if !qualified => renamed.push_str(new_id),
+        _ => renamed.push_str(word),
+    }
+    word.clear();
+}
+
+/// Renames resources of a template and updates all references to them in the `Resources` and
+/// `Outputs` sections. Logical IDs are compared case-sensitively like CloudFormation does, so
+/// case-only renames, e.g. `mybucket` to `MyBucket`, are renames like any other.
+pub fn update_template(
+    template: &mut Value,
+    id_map: &HashMap<String, String>,
+) -> Result<(), String> {
+    let resources = template
+        .get_mut("Resources")
+        .and_then(|resources| resources.as_object_mut())
+        .ok_or("Template has no Resources section")?;
+
+    let mut old_ids = id_map.keys().collect::<Vec<_>>();
+    old_ids.sort();
+    let mut targets: HashMap<&String, &String> = HashMap::new();
+    for old_id in old_ids {
+        let new_id = &id_map[old_id];
+        // two resources renamed to the same logical ID would overwrite each other
+        if let Some(other_id) = targets.insert(new_id, old_id) {
+            return Err(format!(
+                "Unable to rename both {} and {} to {}",
+                other_id, old_id, new_id
+            ));
+        }
+        if old_id == new_id {
+            continue;
+        }
+        let renamed_away = id_map.get(new_id).is_some_and(|renamed| renamed != new_id);
+        if resources.contains_key(new_id) && !renamed_away {
+            return Err(format!(
+                "Unable to rename {} to {}, a resource with that logical ID already exists",
+                old_id, new_id
+            ));
+        }
+    }
+
+    // renamed resources keep their position in the template
+    *resources = std::mem::take(resources)
+        .into_iter()
+        .map(|(id, resource)| (id_map.get(&id).cloned().unwrap_or(id), resource))
+        .collect();
+
+    for resource in resources.values_mut() {
+        update_depends_on(resource, id_map);
+        traverse_and_update(resource, id_map);
+    }
+
+    if let Some(outputs) = template.get_mut("Outputs") {
+        traverse_and_update(outputs, id_map);
+    }
+
+    Ok(())
+}
+
+/// Splits the string form of `Fn::GetAtt` into the logical ID and the attribute path. Attribute
+/// paths can have multiple segments, e.g. `Database.Endpoint.Address`.
+pub fn split_get_att(expression: &str) -> Option<(&str, &str)> {
+    match expression.split_once('.') {
+        Some((id, attribute)) if !id.is_empty() && !attribute.is_empty() => Some((id, attribute)),
+        _ => None,
+    }
+}
+
+/// Pseudo parameters like `AWS::Region` can be referenced but are not logical IDs
+pub fn is_pseudo_parameter(id: &str) -> bool {
+    id.starts_with("AWS::")
+}
+
+fn update_sub(value: &mut Value, id_map: &HashMap<String, String>) {
+    let variables = sub_parts(value).1;
+
+    match value {
+        Value::String(template) => *template = update_sub_string(template, id_map, &variables),
+        Value::Array(items) => {
+            if let Some(Value::String(template)) = items.first_mut() {
+                *template = update_sub_string(template, id_map, &variables);
+            }
+            items
+                .iter_mut()
+                .skip(1)
+                .for_each(|item| traverse_and_update(item, id_map));
+        }
+        _ => traverse_and_update(value, id_map),
+    }
+}
+
+/// Splits the argument of `Fn::Sub` into the template string and the names of local variables
+pub(crate) fn sub_parts(value: &Value) -> (Option<&str>, HashSet<String>) {
+    match value {
+        Value::String(template) => (Some(template), HashSet::new()),
+        Value::Array(items) => {
+            let variables = match items.get(1) {
+                Some(Value::Object(map)) => map.keys().cloned().collect(),
+                _ => HashSet::new(),
+            };
+            (items.first().and_then(|t| t.as_str()), variables)
+        }
+        _ => (None, HashSet::new()),
+    }
+}
+
+/// Returns the content of all `${...}` placeholders of a `Fn::Sub` template string
+pub(crate) fn sub_expressions(template: &str) -> Vec<&str> {
+    let mut expressions = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        match after.find('}') {
+            Some(end) => {
+                expressions.push(&after[..end]);
+                rest = &after[end + 1..];
+            }
+            None => break,
+        }
+    }
+
+    expressions
+}
+
+/// Determines the logical ID referenced by a `Fn::Sub` placeholder, if any
+pub(crate) fn sub_reference<'a>(
+    expression: &'a str,
+    variables: &HashSet<String>,
+) -> Option<&'a str> {
+    if expression.starts_with('!') {
+        // ${!Literal} is an escaped placeholder
+        return None;
+    }
+
+    let id = expression.split('.').next().unwrap_or(expression).trim();
+    if id.is_empty() || variables.contains(id) || is_pseudo_parameter(id) {
+        None
+    } else {
+        Some(id)
+    }
+}
+
+fn update_sub_string(
+    template: &str,
+    id_map: &HashMap<String, String>,
+    variables: &HashSet<String>,
+) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+
+        let expression = &after[..end];
+        let new_id = sub_reference(expression, variables).and_then(|id| id_map.get(id));
+        match new_id {
+            Some(new_id) => {
+                let attribute = expression.split_once('.').map(|(_, attr)| attr);
+                result.push_str("${");
+                result.push_str(new_id);
+                if let Some(attribute) = attribute {
+                    result.push('.');
+                    result.push_str(attribute);
+                }
+                result.push('}');
+            }
+            None => {
+                result.push_str("${");
+                result.push_str(expression);
+                result.push('}');
+            }
+        }
+        rest = &after[end + 1..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn id_map(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(old_id, new_id)| (old_id.to_string(), new_id.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn update_template_rejects_two_resources_renamed_to_the_same_id() {
+        let mut template = json!({
+            "Resources": {
+                "A": {"Type": "AWS::S3::Bucket"},
+                "B": {"Type": "AWS::S3::Bucket"},
+            }
+        });
+        let original = template.clone();
+
+        let result = update_template(&mut template, &id_map(&[("A", "C"), ("B", "C")]));
+
+        assert_eq!(
+            result,
+            Err("Unable to rename both A and B to C".to_string())
+        );
+        assert_eq!(template, original);
+    }
+
+    #[test]
+    fn update_template_rejects_renaming_to_an_existing_id() {
+        let mut template = json!({
+            "Resources": {
+                "A": {"Type": "AWS::S3::Bucket"},
+                "B": {"Type": "AWS::S3::Bucket"},
+            }
+        });
+
+        for map in [id_map(&[("A", "B")]), id_map(&[("A", "B"), ("B", "B")])] {
+            let result = update_template(&mut template, &map);
+
+            assert_eq!(
+                result,
+                Err(
+                    "Unable to rename A to B, a resource with that logical ID already exists"
+                        .to_string()
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn update_template_swaps_ids() {
+        let mut template = json!({
+            "Resources": {
+                "A": {"Type": "AWS::S3::Bucket"},
+                "B": {"Type": "AWS::SQS::Queue"},
+            }
+        });
+
+        update_template(&mut template, &id_map(&[("A", "B"), ("B", "A")])).unwrap();
+
+        assert_eq!(
+            template,
+            json!({
+                "Resources": {
+                    "B": {"Type": "AWS::S3::Bucket"},
+                    "A": {"Type": "AWS::SQS::Queue"},
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn traverse_and_update_renames_string_form_of_get_att_with_nested_attribute() {
+        let mut value = json!({"Fn::GetAtt": "Db.Endpoint.Address"});
+
+        traverse_and_update(&mut value, &id_map(&[("Db", "Database")]));
+
+        assert_eq!(value, json!({"Fn::GetAtt": "Database.Endpoint.Address"}));
+    }
+
+    #[test]
+    fn traverse_and_update_renames_nested_attribute_in_sub() {
+        let mut value = json!({
+            "Fn::Sub": "jdbc:postgresql://${Db.Endpoint.Address}:${Db.Endpoint.Port}/${AWS::Region}"
+        });
+
+        traverse_and_update(&mut value, &id_map(&[("Db", "Database")]));
+
+        assert_eq!(
+            value,
+            json!({
+                "Fn::Sub": "jdbc:postgresql://${Database.Endpoint.Address}:${Database.Endpoint.Port}/${AWS::Region}"
+            })
+        );
+    }
+
+    #[test]
+    fn update_template_renames_case_only() {
+        let mut template = json!({
+            "Resources": {
+                "mybucket": {"Type": "AWS::S3::Bucket"},
+                "Policy": {
+                    "Type": "AWS::S3::BucketPolicy",
+                    "DependsOn": "mybucket",
+                    "Properties": {
+                        "Bucket": {"Ref": "mybucket"},
+                        "PolicyDocument": {"Resource": {"Fn::Sub": "${mybucket.Arn}/*"}},
+                    },
+                },
+            },
+            "Outputs": {
+                "Arn": {"Value": {"Fn::GetAtt": ["mybucket", "Arn"]}},
+            },
+        });
+
+        update_template(&mut template, &id_map(&[("mybucket", "MyBucket")])).unwrap();
+
+        assert_eq!(
+            template,
+            json!({
+                "Resources": {
+                    "MyBucket": {"Type": "AWS::S3::Bucket"},
+                    "Policy": {
+                        "Type": "AWS::S3::BucketPolicy",
+                        "DependsOn": "MyBucket",
+                        "Properties": {
+                            "Bucket": {"Ref": "MyBucket"},
+                            "PolicyDocument": {"Resource": {"Fn::Sub": "${MyBucket.Arn}/*"}},
+                        },
+                    },
+                },
+                "Outputs": {
+                    "Arn": {"Value": {"Fn::GetAtt": ["MyBucket", "Arn"]}},
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn update_template_rejects_case_only_rename_to_an_existing_id() {
+        let mut template = json!({
+            "Resources": {
+                "mybucket": {"Type": "AWS::S3::Bucket"},
+                "MyBucket": {"Type": "AWS::S3::Bucket"},
+            }
+        });
+
+        let result = update_template(&mut template, &id_map(&[("mybucket", "MyBucket")]));
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use aws_sdk_sts as sts;
+use aws_sdk_sts::error::ProvideErrorMetadata;
+use std::error::Error;
+
+use crate::error::{fail, ExitCode};
+
+/// Regions which are disabled by default and need to be enabled per account
+pub static OPT_IN_REGIONS: [&str; 16] = [
+    "af-south-1",
+    "ap-east-1",
+    "ap-east-2",
+    "ap-south-2",
+    "ap-southeast-3",
+    "ap-southeast-4",
+    "ap-southeast-5",
+    "ap-southeast-7",
+    "ca-west-1",
+    "eu-central-2",
+    "eu-south-1",
+    "eu-south-2",
+    "il-central-1",
+    "me-central-1",
+    "me-south-1",
+    "mx-central-1",
+];
+
+/// Partitions in which cfn-teleport is known to work
+pub static PARTITIONS: [&str; 3] = ["aws", "aws-cn", "aws-us-gov"];
+
+pub fn is_opt_in(region: &str) -> bool {
+    OPT_IN_REGIONS.contains(&region)
+}
+
+/// Partition a region belongs to, e.g. `aws-cn` for `cn-north-1`
+pub fn partition_of(region: &str) -> &'static str {
+    let prefixes = [
+        ("cn-", "aws-cn"),
+        ("us-gov-", "aws-us-gov"),
+        ("us-isob-", "aws-iso-b"),
+        ("us-isof-", "aws-iso-f"),
+        ("eu-isoe-", "aws-iso-e"),
+        ("us-iso-", "aws-iso"),
+    ];
+    prefixes
+        .iter()
+        .find(|(prefix, _)| region.starts_with(prefix))
+        .map_or("aws", |(_, partition)| partition)
+}
+
+/// Domain suffix of the endpoints of a partition, the value of `AWS::URLSuffix`
+pub fn url_suffix(partition: &str) -> &'static str {
+    match partition {
+        "aws-cn" => "amazonaws.com.cn",
+        "aws-iso" => "c2s.ic.gov",
+        "aws-iso-b" => "sc2s.sgov.gov",
+        _ => "amazonaws.com",
+    }
+}
+
+/// Makes sure a region is configured, belongs to the expected partition and, for opt-in regions,
+/// is enabled for the account. Calls to a disabled region otherwise fail with errors about invalid
+/// security tokens.
+pub async fn check_region(
+    config: &aws_config::SdkConfig,
+    expected_partition: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let region = match config.region() {
+        Some(region) => region.as_ref(),
+        None => return Err(fail(
+            ExitCode::Validation,
+            "No AWS region configured, set --region, AWS_REGION or a region in your AWS profile",
+        )),
+    };
+
+    let partition = partition_of(region);
+    if let Some(expected_partition) = expected_partition {
+        if expected_partition != partition {
+            return Err(fail(
+                ExitCode::Validation,
+                format!(
+                    "Region {} belongs to partition {}, not {}",
+                    region, partition, expected_partition
+                ),
+            ));
+        }
+    }
+    if !PARTITIONS.contains(&partition) {
+        return Err(fail(
+            ExitCode::Validation,
+            format!(
+                "Partition {} is not supported, only {} are",
+                partition,
+                PARTITIONS.join(", ")
+            ),
+        ));
+    }
+
+    if !is_opt_in(region) {
+        return Ok(());
+    }
+
+    // STS is called in the configured region, credentials are only accepted there if the region
+    // is enabled for the account
+    if let Err(err) = sts::Client::new(config).get_caller_identity().send().await {
+        if matches!(
+            err.code(),
+            Some("InvalidClientTokenId") | Some("UnrecognizedClientException")
+        ) {
+            return Err(fail(
+                ExitCode::Validation,
+                format!(
+                    "Region {} is an opt-in region which is not enabled for this account. Enable it in the account settings or use another region",
+                    region
+                ),
+            ));
+        }
+        return Err(err.into());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,210 @@
+//! Render model for lists of resources, as shown in pickers and confirmations.
+
+use console::style;
+
+/// A resource as displayed to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceRow {
+    pub resource_type: String,
+    pub logical_id: String,
+    pub new_logical_id: Option<String>,
+    pub physical_id: String,
+}
+
+impl ResourceRow {
+    pub fn new(resource_type: &str, logical_id: &str, physical_id: &str) -> Self {
+        Self {
+            resource_type: resource_type.to_string(),
+            logical_id: logical_id.to_string(),
+            new_logical_id: None,
+            physical_id: physical_id.to_string(),
+        }
+    }
+
+    /// Sets the logical ID the resource will have in the target stack. Setting the current logical
+    /// ID means the resource is not renamed.
+    pub fn renamed_to(mut self, new_logical_id: &str) -> Self {
+        self.new_logical_id = if new_logical_id == self.logical_id {
+            None
+        } else {
+            Some(new_logical_id.to_string())
+        };
+        self
+    }
+
+    pub fn is_renamed(&self) -> bool {
+        self.new_logical_id.is_some()
+    }
+}
+
+/// Column widths shared by all rows of a table, so renamed and unchanged resources line up
+#[derive(Debug, Default)]
+struct Widths {
+    resource_type: usize,
+    logical_id: usize,
+    new_logical_id: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ResourceTable {
+    rows: Vec<ResourceRow>,
+}
+
+impl ResourceTable {
+    pub fn new(rows: Vec<ResourceRow>) -> Self {
+        Self { rows }
+    }
+
+    pub fn rows(&self) -> &[ResourceRow] {
+        &self.rows
+    }
+
+    fn has_renames(&self) -> bool {
+        self.rows.iter().any(ResourceRow::is_renamed)
+    }
+
+    fn widths(&self) -> Widths {
+        let mut widths = Widths::default();
+        for row in &self.rows {
+            widths.resource_type = widths.resource_type.max(row.resource_type.len());
+            widths.logical_id = widths.logical_id.max(row.logical_id.len());
+            if let Some(new_logical_id) = &row.new_logical_id {
+                widths.new_logical_id = widths.new_logical_id.max(new_logical_id.len());
+            }
+        }
+        widths
+    }
+
+    fn line(&self, row: &ResourceRow, widths: &Widths) -> String {
+        let resource_type = format!(
+            "{:<width$}",
+            row.resource_type,
+            width = widths.resource_type + 2
+        );
+
+        if !self.has_renames() {
+            return format!(
+                "{}  {:<width$}  {}",
+                resource_type,
+                row.logical_id,
+                row.physical_id,
+                width = widths.logical_id + 2
+            );
+        }
+
+        // padding is applied before styling, escape sequences would break the alignment
+        let logical_ids = match &row.new_logical_id {
+            Some(new_logical_id) => format!(
+                "{:<width1$} → {}",
+                row.logical_id,
+                style(format!(
+                    "{:<width2$}",
+                    new_logical_id,
+                    width2 = widths.new_logical_id
+                ))
+                .italic(),
+                width1 = widths.logical_id
+            ),
+            None => format!(
+                "{:<width$}",
+                row.logical_id,
+                width = widths.logical_id + widths.new_logical_id + 3
+            ),
+        };
+
+        format!("{}  {}    {}", resource_type, logical_ids, row.physical_id)
+    }
+
+    /// One aligned line per resource, in the order of the rows
+    pub fn lines(&self) -> Vec<String> {
+        let widths = self.widths();
+        self.rows
+            .iter()
+            .map(|row| self.line(row, &widths))
+            .collect()
+    }
+
+    /// Lines grouped into renamed and unchanged resources, each group with a heading. Without
+    /// renamed resources this is the same as `lines()`.
+    pub fn grouped_lines(&self) -> Vec<String> {
+        if !self.has_renames() {
+            return self.lines();
+        }
+
+        let widths = self.widths();
+        let mut lines = Vec::new();
+        for (heading, renamed) in [("Renamed:", true), ("Unchanged:", false)] {
+            let rows = self
+                .rows
+                .iter()
+                .filter(|row| row.is_renamed() == renamed)
+                .collect::<Vec<_>>();
+            if rows.is_empty() {
+                continue;
+            }
+
+            lines.push(heading.to_string());
+            lines.extend(
+                rows.iter()
+                    .map(|row| format!("  {}", self.line(row, &widths))),
+            );
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> ResourceTable {
+        ResourceTable::new(vec![
+            ResourceRow::new("AWS::S3::Bucket", "Bucket", "my-bucket").renamed_to("Logs"),
+            ResourceRow::new("AWS::SQS::Queue", "Queue", "my-queue").renamed_to("Queue"),
+            ResourceRow::new("AWS::DynamoDB::Table", "Table", "my-table").renamed_to("Orders"),
+        ])
+    }
+
+    #[test]
+    fn grouped_lines_separate_renamed_and_unchanged_resources() {
+        console::set_colors_enabled(false);
+
+        assert_eq!(
+            table().grouped_lines(),
+            vec![
+                "Renamed:",
+                "  AWS::S3::Bucket         Bucket → Logs      my-bucket",
+                "  AWS::DynamoDB::Table    Table  → Orders    my-table",
+                "Unchanged:",
+                "  AWS::SQS::Queue         Queue              my-queue",
+            ]
+        );
+    }
+
+    #[test]
+    fn grouped_lines_without_renames_are_plain_lines() {
+        let table = ResourceTable::new(vec![
+            ResourceRow::new("AWS::S3::Bucket", "Bucket", "my-bucket"),
+            ResourceRow::new("AWS::SQS::Queue", "Queue", "my-queue").renamed_to("Queue"),
+        ]);
+
+        assert_eq!(table.grouped_lines(), table.lines());
+        assert_eq!(
+            table.lines(),
+            vec![
+                "AWS::S3::Bucket    Bucket    my-bucket",
+                "AWS::SQS::Queue    Queue     my-queue",
+            ]
+        );
+    }
+
+    #[test]
+    fn renaming_to_the_current_id_is_no_rename() {
+        let row = ResourceRow::new("AWS::S3::Bucket", "Bucket", "my-bucket").renamed_to("Bucket");
+
+        assert!(!row.is_renamed());
+        assert!(ResourceRow::new("AWS::S3::Bucket", "bucket", "my-bucket")
+            .renamed_to("Bucket")
+            .is_renamed());
+    }
+}
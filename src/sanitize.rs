@@ -0,0 +1,149 @@
+//! Sanitization profiles for stacks created by third-party tools.
+//!
+//! Tools like the CDK, SAM, the Serverless Framework or Amplify generate the templates of their
+//! stacks and annotate resources with tool-specific metadata, which is meaningless or misleading
+//! once a resource has been copied into another stack. Each profile recognizes the templates of one
+//! tool, cleans these annotations from copied resources and tells what has to change on the side of
+//! the tool, which would otherwise recreate or delete the moved resources on its next deployment.
+
+use serde_json::Value;
+
+/// Rules for the templates of one tool
+#[derive(Debug)]
+pub struct Profile {
+    pub name: &'static str,
+    pub tool: &'static str,
+
+    /// Prefixes of `Metadata` keys which are removed from copied resources
+    pub metadata_prefixes: &'static [&'static str],
+
+    /// Changes needed in the sources of the tool after the move
+    pub advice: &'static str,
+
+    detect: fn(&Value) -> bool,
+}
+
+pub static PROFILES: [Profile; 4] = [
+    Profile {
+        name: "cdk",
+        tool: "AWS CDK",
+        metadata_prefixes: &["aws:cdk:", "aws:asset:"],
+        advice: "Move the constructs of the resources from the CDK app of the source stack to the app of the target stack and keep their logical IDs, e.g. with overrideLogicalId(), before the next cdk deploy.",
+        detect: is_cdk,
+    },
+    Profile {
+        name: "sam",
+        tool: "AWS SAM",
+        metadata_prefixes: &["SamResourceId", "aws:asset:"],
+        advice: "Move the resources from the SAM template of the source stack to the template of the target stack before the next sam deploy.",
+        detect: is_sam,
+    },
+    Profile {
+        name: "serverless",
+        tool: "Serverless Framework",
+        metadata_prefixes: &[],
+        advice: "Remove the resources from the resources section of the serverless.yml of the source stack before the next sls deploy. Resources the framework generates from functions and events cannot stay in another stack, it recreates them on every deployment.",
+        detect: is_serverless,
+    },
+    Profile {
+        name: "amplify",
+        tool: "AWS Amplify",
+        metadata_prefixes: &["aws:cdk:", "aws:asset:"],
+        advice: "Remove the resources from the Amplify backend before the next amplify push, Amplify regenerates the templates of its stacks on every deployment and manages the resources it defines itself.",
+        detect: is_amplify,
+    },
+];
+
+/// Finds a profile by name
+pub fn find(name: &str) -> Option<&'static Profile> {
+    PROFILES.iter().find(|profile| profile.name == name)
+}
+
+/// Finds the profiles of the tools which generated a template
+pub fn detect(template: &Value) -> Vec<&'static Profile> {
+    PROFILES
+        .iter()
+        .filter(|profile| (profile.detect)(template))
+        .collect()
+}
+
+impl Profile {
+    /// Removes the metadata of the tool from the given resources of a template. Returns the paths
+    /// of the removed entries, e.g. `Resources.Bucket.Metadata.aws:cdk:path`.
+    pub fn sanitize(&self, template: &mut Value, logical_ids: &[String]) -> Vec<String> {
+        let mut removed = Vec::new();
+
+        for logical_id in logical_ids {
+            let Some(resource) = template
+                .get_mut("Resources")
+                .and_then(|resources| resources.get_mut(logical_id))
+                .and_then(|resource| resource.as_object_mut())
+            else {
+                continue;
+            };
+            let Some(metadata) = resource
+                .get_mut("Metadata")
+                .and_then(|metadata| metadata.as_object_mut())
+            else {
+                continue;
+            };
+
+            let keys = metadata
+                .keys()
+                .filter(|key| {
+                    self.metadata_prefixes
+                        .iter()
+                        .any(|prefix| key.starts_with(prefix))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            for key in keys {
+                metadata.shift_remove(&key);
+                removed.push(format!("Resources.{}.Metadata.{}", logical_id, key));
+            }
+
+            if metadata.is_empty() {
+                resource.shift_remove("Metadata");
+            }
+        }
+
+        removed
+    }
+}
+
+fn resources(template: &Value) -> impl Iterator<Item = (&String, &Value)> {
+    template["Resources"].as_object().into_iter().flatten()
+}
+
+fn is_cdk(template: &Value) -> bool {
+    resources(template).any(|(_, resource)| {
+        resource["Type"] == "AWS::CDK::Metadata"
+            || resource["Metadata"].get("aws:cdk:path").is_some()
+    })
+}
+
+fn is_sam(template: &Value) -> bool {
+    let transforms = match &template["Transform"] {
+        Value::Array(transforms) => transforms.iter().collect(),
+        transform => vec![transform],
+    };
+    transforms
+        .iter()
+        .any(|transform| transform.as_str() == Some("AWS::Serverless-2016-10-31"))
+        || resources(template)
+            .any(|(_, resource)| resource["Metadata"].get("SamResourceId").is_some())
+}
+
+fn is_serverless(template: &Value) -> bool {
+    template["Outputs"]
+        .get("ServerlessDeploymentBucketName")
+        .is_some()
+        || template["Resources"]
+            .get("ServerlessDeploymentBucket")
+            .is_some()
+}
+
+fn is_amplify(template: &Value) -> bool {
+    let description = template["Description"].as_str().unwrap_or_default();
+    description.contains("\"createdBy\":\"Amplify\"")
+}
@@ -0,0 +1,88 @@
+use std::error::Error;
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use crate::error::{fail, ExitCode};
+use crate::{spinner, timing};
+
+/// Environment variables passed to the notification command
+pub const STATUS_ENV: &str = "CFN_TELEPORT_STATUS";
+pub const MESSAGE_ENV: &str = "CFN_TELEPORT_MESSAGE";
+
+/// Waits until `time` has been reached. Ctrl+C aborts the wait before anything is changed.
+pub async fn wait_until(time: SystemTime) -> Result<(), Box<dyn Error>> {
+    let Ok(remaining) = time.duration_since(SystemTime::now()) else {
+        return Ok(());
+    };
+
+    let mut spinner = spinner::Spin::new(
+        "wait-schedule",
+        &format!(
+            "Waiting until {} to start the migration, press Ctrl+C to abort",
+            timing::format_timestamp(time)
+        ),
+    );
+    let deadline = std::time::Instant::now() + remaining;
+    while let Some(remaining) = deadline.checked_duration_since(std::time::Instant::now()) {
+        spinner.update(&format!("{} remaining", timing::format_duration(remaining)));
+        tokio::select! {
+            _ = tokio::time::sleep(remaining.min(Duration::from_secs(1))) => {}
+            _ = tokio::signal::ctrl_c() => {
+                spinner.fail();
+                return Err(fail(ExitCode::Aborted, "The migration has been aborted while waiting for its scheduled start"));
+            }
+        }
+    }
+    spinner.complete();
+    Ok(())
+}
+
+/// Counts down before the migration starts without confirmation, so an operator watching the run
+/// can still stop it with Ctrl+C before anything is changed
+pub async fn countdown(seconds: u64) -> Result<(), Box<dyn Error>> {
+    let mut spinner = spinner::Spin::new(
+        "countdown",
+        &format!(
+            "Starting the migration in {} seconds, press Ctrl+C to abort",
+            seconds
+        ),
+    );
+    for remaining in (1..=seconds).rev() {
+        spinner.update(&format!("{}s remaining", remaining));
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+            _ = tokio::signal::ctrl_c() => {
+                spinner.fail();
+                return Err(fail(ExitCode::Aborted, "The migration has been aborted during the countdown"));
+            }
+        }
+    }
+    spinner.complete();
+    Ok(())
+}
+
+/// Runs the notification command through the shell, with the outcome of the migration in
+/// `CFN_TELEPORT_STATUS` (`success` or `failure`) and `CFN_TELEPORT_MESSAGE`
+pub fn notify(command: &str, result: &Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+    let (status, message) = match result {
+        Ok(()) => ("success", "Migration completed".to_string()),
+        Err(err) => ("failure", err.to_string()),
+    };
+
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+
+    let exit_status = Command::new(shell)
+        .arg(flag)
+        .arg(command)
+        .env(STATUS_ENV, status)
+        .env(MESSAGE_ENV, message)
+        .status()?;
+    if !exit_status.success() {
+        return Err(format!("Notification command failed with {}", exit_status).into());
+    }
+    Ok(())
+}
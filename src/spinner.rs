@@ -1,48 +1,324 @@
 use atty::Stream;
-use console::style;
+use aws_sdk_cloudformation::types::StackEvent;
+use cfn_teleport::migration::Progress;
+use cfn_teleport::waiter::Indicator;
+use console::{style, Term};
 use spinach::{Color, Spinach, Spinner};
-use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::timing;
+
+/// Interval of the status lines printed instead of a spinner when stdout is not a terminal, e.g. in
+/// CI logs
+const STATUS_INTERVAL: Duration = Duration::from_secs(30);
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Switches to plain output: instead of spinners and status lines, one line is printed per phase
+/// transition, `PHASE <phase> START|OK|FAIL <details>`
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// Suppresses spinners, status lines and informational output, so only errors and warnings are
+/// printed, to stderr
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Transition of a phase in plain output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transition {
+    Start,
+    Ok,
+    Fail,
+}
+
+/// Prints the line of a phase transition in plain output
+fn print_phase(phase: &str, transition: Transition, details: &str) {
+    let transition = match transition {
+        Transition::Start => "START",
+        Transition::Ok => "OK",
+        Transition::Fail => "FAIL",
+    };
+    println!("PHASE {} {} {}", phase, transition, details);
+}
 
 pub struct Spin {
+    /// Stable name of the phase in plain output, e.g. `import-target`
+    phase: &'static str,
     spinner: Option<Spinach>,
+    /// The message prefixed with the start time
     message: String,
+    label: String,
+    details: Option<String>,
+    started: Instant,
+    last_status: Instant,
+    finished: bool,
 }
 
 impl Spin {
-    pub fn new(message: &str) -> Self {
-        if atty::is(Stream::Stdout) {
-            let sp = Spinach::new_with(
+    pub fn new(phase: &'static str, label: &str) -> Self {
+        let message = format!("[{}] {}", timing::timestamp(), label);
+        let started = Instant::now();
+
+        let spinner = if is_quiet() {
+            None
+        } else if is_plain() {
+            print_phase(phase, Transition::Start, label);
+            None
+        } else if atty::is(Stream::Stdout) {
+            Some(Spinach::new_with(
                 Spinner::new(vec!["-", "=", "≡"], 100),
-                String::from(message),
+                message.clone(),
                 Color::Ignore,
-            );
-            Self {
-                spinner: Some(sp),
-                message: String::from(message),
-            }
+            ))
         } else {
-            print!("{}", message);
-            flush();
-            Self {
-                spinner: None,
-                message: String::from(message),
-            }
+            println!("{}", message);
+            None
+        };
+
+        Self {
+            phase,
+            spinner,
+            message,
+            label: label.to_string(),
+            details: None,
+            started,
+            last_status: started,
+            finished: false,
+        }
+    }
+
+    /// Shows details about the progress next to the message
+    pub fn update(&mut self, details: &str) {
+        self.details = Some(details.to_string());
+        if let Some(spinner) = self.spinner.as_ref() {
+            spinner.text(format!("{} ({})", self.message, details));
+        }
+        self.tick();
+    }
+
+    /// Prints a status line with the latest details when stdout is not a terminal and the last
+    /// line is older than the status interval
+    pub fn tick(&mut self) {
+        if self.spinner.is_some()
+            || self.finished
+            || is_plain()
+            || is_quiet()
+            || self.last_status.elapsed() < STATUS_INTERVAL
+        {
+            return;
+        }
+        self.last_status = Instant::now();
+
+        let elapsed = timing::format_duration(self.started.elapsed());
+        match &self.details {
+            Some(details) => println!(
+                "[{}] {} ({}, {} elapsed)",
+                timing::timestamp(),
+                self.label,
+                details,
+                elapsed
+            ),
+            None => println!(
+                "[{}] {} ({} elapsed)",
+                timing::timestamp(),
+                self.label,
+                elapsed
+            ),
         }
     }
 
     pub fn complete(&mut self) {
+        if std::mem::replace(&mut self.finished, true) || is_quiet() {
+            return;
+        }
         let success_prefix = style("✔".to_string()).green();
+        let duration = timing::format_duration(self.started.elapsed());
+
+        if is_plain() {
+            print_phase(self.phase, Transition::Ok, &duration);
+        } else if let Some(spinner) = self.spinner.take() {
+            spinner.stop_with(
+                "✔",
+                format!("{} ({})", self.message, duration),
+                Color::Green,
+            );
+        } else {
+            println!(
+                "[{}] {}: {} ({})",
+                timing::timestamp(),
+                self.label,
+                success_prefix,
+                duration
+            );
+        }
+    }
+
+    /// Marks the task as failed, unless it has been completed already
+    pub fn fail(&mut self) {
+        if std::mem::replace(&mut self.finished, true) || is_quiet() {
+            return;
+        }
+        let failure_prefix = style("✖".to_string()).red();
+        let duration = timing::format_duration(self.started.elapsed());
 
-        if let Some(spinner) = self.spinner.take() {
-            spinner.stop_with("✔", self.message.clone(), Color::Green);
+        if is_plain() {
+            print_phase(self.phase, Transition::Fail, &duration);
+        } else if let Some(spinner) = self.spinner.take() {
+            spinner.fail(format!("{} ({})", self.message, duration));
         } else {
-            println!(": {}", success_prefix);
+            println!(
+                "[{}] {}: {} ({})",
+                timing::timestamp(),
+                self.label,
+                failure_prefix,
+                duration
+            );
+        }
+    }
+}
+
+impl Indicator for Spin {
+    fn progress(&mut self, _events: &[StackEvent], details: &str) {
+        self.update(details);
+    }
+
+    fn tick(&mut self) {
+        Spin::tick(self);
+    }
+}
+
+impl Progress for Spin {
+    fn complete(&mut self) {
+        Spin::complete(self);
+    }
+
+    fn fail(&mut self) {
+        Spin::fail(self);
+    }
+}
+
+impl Drop for Spin {
+    /// A task abandoned because of an error still ends its phase in plain output
+    fn drop(&mut self) {
+        if is_plain() && !self.finished {
+            self.fail();
+        }
+    }
+}
+
+/// Live status of individual resources during a stack operation
+pub struct StatusTable {
+    rows: Vec<(String, String, String)>,
+    rendered: usize,
+    term: Term,
+}
+
+impl StatusTable {
+    /// Shows the resources as pending until their first event
+    pub fn new(logical_ids: &[String]) -> Self {
+        let mut table = Self {
+            rows: logical_ids
+                .iter()
+                .map(|id| (id.to_string(), "PENDING".to_string(), String::new()))
+                .collect(),
+            rendered: 0,
+            term: Term::stdout(),
+        };
+        table.render(&[]);
+        table
+    }
+
+    fn update(&mut self, event: &StackEvent) -> bool {
+        let logical_id = event.logical_resource_id().unwrap_or_default();
+        let Some(row) = self.rows.iter_mut().find(|(id, _, _)| id == logical_id) else {
+            return false;
+        };
+
+        let status = event
+            .resource_status()
+            .map(|status| status.as_str().to_string())
+            .unwrap_or_default();
+        row.2 = event
+            .resource_status_reason()
+            .unwrap_or_default()
+            .to_string();
+        row.1 = status;
+        true
+    }
+
+    /// Redraws the table in a terminal, otherwise prints the given rows
+    fn render(&mut self, changed: &[String]) {
+        if is_plain() || is_quiet() {
+            return;
+        }
+        let width = self
+            .rows
+            .iter()
+            .map(|(id, _, _)| id.len())
+            .max()
+            .unwrap_or(0);
+        let line = |(id, status, reason): &(String, String, String)| {
+            let status_styled = if status.ends_with("_FAILED") {
+                style(format!("{:<24}", status)).red()
+            } else if status.ends_with("_COMPLETE") {
+                style(format!("{:<24}", status)).green()
+            } else {
+                style(format!("{:<24}", status)).yellow()
+            };
+            format!(
+                "  {:<width$}  {}  {}",
+                id,
+                status_styled,
+                reason,
+                width = width
+            )
+            .trim_end()
+            .to_string()
+        };
+
+        if !self.term.is_term() {
+            for row in self.rows.iter().filter(|(id, _, _)| changed.contains(id)) {
+                println!("{}", line(row));
+            }
+            return;
+        }
+
+        let _ = self.term.clear_last_lines(self.rendered);
+        for row in &self.rows {
+            let _ = self.term.write_line(&line(row));
+        }
+        self.rendered = self.rows.len();
+    }
+}
+
+impl Indicator for StatusTable {
+    fn progress(&mut self, events: &[StackEvent], _details: &str) {
+        let changed = events
+            .iter()
+            .filter(|event| self.update(event))
+            .filter_map(|event| event.logical_resource_id().map(str::to_string))
+            .collect::<Vec<_>>();
+        if !changed.is_empty() {
+            self.render(&changed);
         }
     }
 }
 
-fn flush() {
-    std::io::stdout()
-        .flush()
-        .expect("Unable to flush to stdout")
+/// The rows show how the operation ended for every resource
+impl Progress for StatusTable {
+    fn complete(&mut self) {}
+    fn fail(&mut self) {}
 }
@@ -0,0 +1,197 @@
+use aws_config::SdkConfig;
+use aws_sdk_cloudcontrol as cloudcontrol;
+use console::style;
+use serde_json::Value;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use cfn_teleport::cancellation::Cancellation;
+
+use crate::error::{fail, ExitCode};
+use crate::spinner;
+
+/// Default number of minutes to wait for moved resources to stabilize
+pub const DEFAULT_STABILIZATION_TIMEOUT: u64 = 30;
+
+/// Seconds between two rounds of status checks
+const POLL_INTERVAL: u64 = 15;
+
+/// Properties holding the state of a resource, in the order they are looked up
+static STATUS_PROPERTIES: [&str; 5] = [
+    "Status",
+    "State",
+    "DistributionStatus",
+    "DBInstanceStatus",
+    "DBClusterStatus",
+];
+
+/// States in which a resource is settled
+static STABLE_STATES: [&str; 11] = [
+    "ACTIVE",
+    "AVAILABLE",
+    "DEPLOYED",
+    "ENABLED",
+    "ISSUED",
+    "READY",
+    "RUNNING",
+    "IN_SERVICE",
+    "INSERVICE",
+    "SUCCEEDED",
+    "STOPPED",
+];
+
+/// States from which a resource does not stabilize by itself
+static FAILED_STATES: [&str; 3] = ["FAILED", "ERROR", "INCOMPATIBLE"];
+
+/// A moved resource to wait for
+pub struct MovedResource {
+    pub logical_id: String,
+    pub resource_type: String,
+    pub physical_id: String,
+}
+
+/// State of a resource as reported by Cloud Control
+enum Stability {
+    Stable,
+    Pending(String),
+    Failed(String),
+    Unknown(String),
+}
+
+/// Polls the state of the moved resources through Cloud Control until all of them are stable.
+/// Resources without a state property are stable as soon as Cloud Control can describe them,
+/// resource types Cloud Control does not support are skipped with a warning.
+pub async fn wait(
+    config: &SdkConfig,
+    resources: Vec<MovedResource>,
+    timeout: Duration,
+    cancellation: &Cancellation,
+    spinner: &mut spinner::Spin,
+) -> Result<(), Box<dyn Error>> {
+    let client = cloudcontrol::Client::new(config);
+    let started = Instant::now();
+    let total = resources.len();
+    let mut pending = resources;
+    let mut skipped = Vec::new();
+
+    loop {
+        let mut still_pending = Vec::new();
+        let mut states = Vec::new();
+        for resource in pending {
+            match stability(&client, &resource).await {
+                Stability::Stable => {}
+                Stability::Pending(state) => {
+                    states.push(format!("{} {}", resource.logical_id, state));
+                    still_pending.push(resource);
+                }
+                Stability::Failed(state) => {
+                    return Err(format!(
+                        "Resource {} is in state {} and does not stabilize",
+                        resource.logical_id, state
+                    )
+                    .into())
+                }
+                Stability::Unknown(reason) => {
+                    skipped.push(format!("{}: {}", resource.logical_id, reason))
+                }
+            }
+        }
+        pending = still_pending;
+
+        if pending.is_empty() {
+            for reason in &skipped {
+                println!(
+                    "{} Stabilization of {} not checked",
+                    style("WARNING:").yellow().bold(),
+                    reason
+                );
+            }
+            return Ok(());
+        }
+
+        tracing::debug!(
+            pending = pending.len(),
+            skipped = skipped.len(),
+            states = states.join(", "),
+            "Waiting for resources to stabilize"
+        );
+        spinner.update(&format!(
+            "{} of {} resources stable, waiting for {}",
+            total - pending.len() - skipped.len(),
+            total,
+            states.join(", ")
+        ));
+
+        if started.elapsed() > timeout {
+            return Err(format!(
+                "Resources did not stabilize within {} minutes: {}",
+                timeout.as_secs() / 60,
+                states.join(", ")
+            )
+            .into());
+        }
+        if cancellation.is_cancelled() || cancellation.is_expired() {
+            return Err(fail(
+                ExitCode::Aborted,
+                format!(
+                    "Stopped waiting for the resources to stabilize: {}",
+                    states.join(", ")
+                ),
+            ));
+        }
+
+        let next = Instant::now() + Duration::from_secs(POLL_INTERVAL);
+        while Instant::now() < next {
+            spinner.tick();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+}
+
+async fn stability(client: &cloudcontrol::Client, resource: &MovedResource) -> Stability {
+    let output = match client
+        .get_resource()
+        .type_name(&resource.resource_type)
+        .identifier(&resource.physical_id)
+        .send()
+        .await
+    {
+        Ok(output) => output,
+        Err(err) => {
+            let err = cloudcontrol::Error::from(err);
+            return match err {
+                cloudcontrol::Error::UnsupportedActionException(_)
+                | cloudcontrol::Error::TypeNotFoundException(_) => Stability::Unknown(format!(
+                    "{} is not supported by Cloud Control",
+                    resource.resource_type
+                )),
+                // throttling and transient errors are retried in the next round
+                err => Stability::Pending(format!("(not described: {})", err)),
+            };
+        }
+    };
+
+    let properties = output
+        .resource_description()
+        .and_then(|description| description.properties())
+        .and_then(|properties| serde_json::from_str::<Value>(properties).ok())
+        .unwrap_or_default();
+    let Some(state) = STATUS_PROPERTIES
+        .iter()
+        .find_map(|property| properties[*property].as_str())
+    else {
+        return Stability::Stable;
+    };
+
+    let normalized = state.to_uppercase().replace([' ', '-'], "_");
+    if STABLE_STATES.contains(&normalized.as_str()) {
+        Stability::Stable
+    } else if FAILED_STATES
+        .iter()
+        .any(|failed| normalized.contains(failed))
+    {
+        Stability::Failed(state.to_string())
+    } else {
+        Stability::Pending(state.to_string())
+    }
+}
@@ -0,0 +1,507 @@
+//! The CloudFormation operations a migration is made of, behind a trait.
+//!
+//! The engine talks to CloudFormation through `StackApi`, which is implemented by the SDK client and
+//! by `FakeStackApi`, an in-memory CloudFormation which is only built for tests and with the `fake`
+//! feature. The fake keeps stacks with their templates and resources, executes change sets and
+//! stack updates instantly and fails operations on request, so the phases of a migration and its
+//! error paths can be exercised without AWS.
+
+use aws_sdk_cloudformation as cloudformation;
+use aws_sdk_cloudformation::types::{
+    Capability, ChangeSetStatus, ChangeSetType, Parameter, ResourceToImport, StackEvent,
+    StackResourceSummary, StackStatus, StackSummary,
+};
+use serde_json::Value;
+use std::error::Error;
+use std::future::Future;
+
+use crate::template_body;
+
+#[cfg(any(test, feature = "fake"))]
+mod fake;
+#[cfg(any(test, feature = "fake"))]
+pub use fake::FakeStackApi;
+
+pub type ApiResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Operations on stacks, change sets and templates
+pub trait StackApi {
+    /// All stacks which have neither been deleted nor are being deleted
+    fn list_stacks(&self) -> impl Future<Output = ApiResult<Vec<StackSummary>>> + Send;
+
+    /// A page of the resources of a stack, with the token of the next page
+    fn list_stack_resources(
+        &self,
+        stack_name: &str,
+        next_token: Option<String>,
+    ) -> impl Future<Output = ApiResult<(Vec<StackResourceSummary>, Option<String>)>> + Send;
+
+    /// The current template of a stack
+    fn get_template(&self, stack_name: &str) -> impl Future<Output = ApiResult<Value>> + Send;
+
+    /// Values of the Parameters of a stack, as they have been passed to it
+    fn stack_parameters(
+        &self,
+        stack_name: &str,
+    ) -> impl Future<Output = ApiResult<Vec<Parameter>>> + Send;
+
+    /// Starts an update of a stack to a new template
+    fn update_stack(
+        &self,
+        stack_name: &str,
+        template: &Value,
+        parameters: &[Parameter],
+        capabilities: &[Capability],
+    ) -> impl Future<Output = ApiResult<()>> + Send;
+
+    /// Creates a change set importing resources into a stack and returns its ID
+    fn create_import_changeset(
+        &self,
+        stack_name: &str,
+        changeset_name: &str,
+        template: &Value,
+        parameters: &[Parameter],
+        resources: &[ResourceToImport],
+        capabilities: &[Capability],
+    ) -> impl Future<Output = ApiResult<String>> + Send;
+
+    /// Status of a change set with the reason CloudFormation gives for it
+    fn changeset_status(
+        &self,
+        stack_name: &str,
+        changeset_name: &str,
+    ) -> impl Future<Output = ApiResult<(ChangeSetStatus, Option<String>)>> + Send;
+
+    fn execute_changeset(
+        &self,
+        stack_name: &str,
+        changeset_name: &str,
+    ) -> impl Future<Output = ApiResult<()>> + Send;
+
+    /// Status of a stack with the reason CloudFormation gives for it
+    fn stack_status(
+        &self,
+        stack_name: &str,
+    ) -> impl Future<Output = ApiResult<(StackStatus, Option<String>)>> + Send;
+
+    /// A page of the events of a stack, newest first, with the token of the next page
+    fn describe_stack_events(
+        &self,
+        stack_name: &str,
+        next_token: Option<String>,
+    ) -> impl Future<Output = ApiResult<(Vec<StackEvent>, Option<String>)>> + Send;
+}
+
+impl StackApi for cloudformation::Client {
+    async fn list_stacks(&self) -> ApiResult<Vec<StackSummary>> {
+        // stacks being deleted are as good as gone
+        let status_filter = StackStatus::values()
+            .iter()
+            .filter(|status| !status.starts_with("DELETE"))
+            .map(|status| StackStatus::from(*status))
+            .collect::<Vec<_>>();
+        let mut stacks = Vec::new();
+        let mut token = None;
+        // a page holds up to 100 stacks, every page is read until there is no next token
+        loop {
+            let output = self
+                .list_stacks()
+                .set_stack_status_filter(Some(status_filter.clone()))
+                .set_next_token(token)
+                .send()
+                .await
+                .map_err(cloudformation::Error::from)?;
+            stacks.extend(output.stack_summaries().iter().cloned());
+            token = output.next_token().map(str::to_string);
+            if token.is_none() {
+                return Ok(stacks);
+            }
+        }
+    }
+
+    async fn list_stack_resources(
+        &self,
+        stack_name: &str,
+        next_token: Option<String>,
+    ) -> ApiResult<(Vec<StackResourceSummary>, Option<String>)> {
+        let output = self
+            .list_stack_resources()
+            .stack_name(stack_name)
+            .set_next_token(next_token)
+            .send()
+            .await
+            .map_err(cloudformation::Error::from)?;
+        Ok((
+            output.stack_resource_summaries().to_vec(),
+            output.next_token().map(str::to_string),
+        ))
+    }
+
+    async fn get_template(&self, stack_name: &str) -> ApiResult<Value> {
+        let output = self
+            .get_template()
+            .stack_name(stack_name)
+            .send()
+            .await
+            .map_err(cloudformation::Error::from)?;
+        let body = output.template_body().ok_or("No template found")?;
+        Ok(serde_json::from_str(body)?)
+    }
+
+    async fn stack_parameters(&self, stack_name: &str) -> ApiResult<Vec<Parameter>> {
+        let output = self
+            .describe_stacks()
+            .stack_name(stack_name)
+            .send()
+            .await
+            .map_err(cloudformation::Error::from)?;
+        let stack = output.stacks().first().ok_or("Stack not found")?;
+        Ok(stack.parameters().to_vec())
+    }
+
+    async fn update_stack(
+        &self,
+        stack_name: &str,
+        template: &Value,
+        parameters: &[Parameter],
+        capabilities: &[Capability],
+    ) -> ApiResult<()> {
+        let body = template_body::prepare(template).await?;
+        self.update_stack()
+            .stack_name(stack_name)
+            .set_template_body(body.body())
+            .set_template_url(body.url())
+            .set_parameters(Some(parameters.to_vec()))
+            .set_capabilities(Some(capabilities.to_vec()))
+            .send()
+            .await
+            .map_err(cloudformation::Error::from)?;
+        Ok(())
+    }
+
+    async fn create_import_changeset(
+        &self,
+        stack_name: &str,
+        changeset_name: &str,
+        template: &Value,
+        parameters: &[Parameter],
+        resources: &[ResourceToImport],
+        capabilities: &[Capability],
+    ) -> ApiResult<String> {
+        let body = template_body::prepare(template).await?;
+        let output = self
+            .create_change_set()
+            .stack_name(stack_name)
+            .change_set_name(changeset_name)
+            .set_template_body(body.body())
+            .set_template_url(body.url())
+            .change_set_type(ChangeSetType::Import)
+            .set_parameters(Some(parameters.to_vec()))
+            .set_resources_to_import(Some(resources.to_vec()))
+            .set_capabilities(Some(capabilities.to_vec()))
+            .send()
+            .await
+            .map_err(cloudformation::Error::from)?;
+        Ok(output.id().unwrap_or(changeset_name).to_string())
+    }
+
+    async fn changeset_status(
+        &self,
+        stack_name: &str,
+        changeset_name: &str,
+    ) -> ApiResult<(ChangeSetStatus, Option<String>)> {
+        let output = self
+            .describe_change_set()
+            .stack_name(stack_name)
+            .change_set_name(changeset_name)
+            .send()
+            .await
+            .map_err(cloudformation::Error::from)?;
+        let status = output
+            .status()
+            .cloned()
+            .ok_or("Failed to determine change set status")?;
+        Ok((status, output.status_reason().map(str::to_string)))
+    }
+
+    async fn execute_changeset(&self, stack_name: &str, changeset_name: &str) -> ApiResult<()> {
+        self.execute_change_set()
+            .stack_name(stack_name)
+            .change_set_name(changeset_name)
+            .send()
+            .await
+            .map_err(cloudformation::Error::from)?;
+        Ok(())
+    }
+
+    async fn stack_status(&self, stack_name: &str) -> ApiResult<(StackStatus, Option<String>)> {
+        let output = self
+            .describe_stacks()
+            .stack_name(stack_name)
+            .send()
+            .await
+            .map_err(cloudformation::Error::from)?;
+        let stack = output
+            .stacks()
+            .first()
+            .ok_or("Failed to determine stack status")?;
+        let status = stack
+            .stack_status()
+            .cloned()
+            .ok_or("Failed to determine stack status")?;
+        Ok((status, stack.stack_status_reason().map(str::to_string)))
+    }
+
+    async fn describe_stack_events(
+        &self,
+        stack_name: &str,
+        next_token: Option<String>,
+    ) -> ApiResult<(Vec<StackEvent>, Option<String>)> {
+        let output = self
+            .describe_stack_events()
+            .stack_name(stack_name)
+            .set_next_token(next_token)
+            .send()
+            .await
+            .map_err(cloudformation::Error::from)?;
+        Ok((
+            output.stack_events().to_vec(),
+            output.next_token().map(str::to_string),
+        ))
+    }
+}
+
+/// All resources of a stack, read page by page and sorted by type, logical ID and physical ID
+pub async fn list_all_resources(
+    api: &impl StackApi,
+    stack_name: &str,
+) -> ApiResult<Vec<StackResourceSummary>> {
+    let mut resources = Vec::new();
+    let mut token = None;
+    loop {
+        let (page, next_token) = api.list_stack_resources(stack_name, token).await?;
+        resources.extend(page);
+        token = next_token;
+        if token.is_none() {
+            break;
+        }
+    }
+
+    resources.sort_by_key(|resource| {
+        (
+            resource.resource_type().unwrap_or_default().to_string(),
+            resource
+                .logical_resource_id()
+                .unwrap_or_default()
+                .to_string(),
+            resource
+                .physical_resource_id()
+                .unwrap_or_default()
+                .to_string(),
+        )
+    });
+    Ok(resources)
+}
+
+/// The Parameters to pass along with a template to a stack. Parameters the stack has already keep
+/// their previous value, Parameters copied from the source stack get the value they have there.
+/// Parameters which are neither fall back to their Default.
+pub fn parameters_for(
+    template: &Value,
+    previous: &[Parameter],
+    source: &[Parameter],
+) -> Vec<Parameter> {
+    let Some(declared) = template["Parameters"].as_object() else {
+        return Vec::new();
+    };
+    declared
+        .keys()
+        .filter_map(|name| {
+            let is_named = |parameter: &&Parameter| parameter.parameter_key() == Some(name);
+            if previous.iter().any(|parameter| is_named(&parameter)) {
+                return Some(
+                    Parameter::builder()
+                        .parameter_key(name)
+                        .use_previous_value(true)
+                        .build(),
+                );
+            }
+            let value = source.iter().find(is_named)?.parameter_value()?;
+            Some(
+                Parameter::builder()
+                    .parameter_key(name)
+                    .parameter_value(value)
+                    .build(),
+            )
+        })
+        .collect()
+}
+
+/// Parameters of a template which would be copied from the source stack, but are declared with
+/// `NoEcho`. CloudFormation only returns their value masked, so it cannot be passed on.
+pub fn masked_parameters(
+    template: &Value,
+    previous: &[Parameter],
+    source_template: &Value,
+) -> Vec<String> {
+    let Some(declared) = template["Parameters"].as_object() else {
+        return Vec::new();
+    };
+    declared
+        .keys()
+        .filter(|name| {
+            !previous
+                .iter()
+                .any(|parameter| parameter.parameter_key() == Some(name.as_str()))
+        })
+        .filter(|name| {
+            let no_echo = &source_template["Parameters"][name.as_str()]["NoEcho"];
+            *no_echo == true || *no_echo == "true"
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn parameter(name: &str, value: &str) -> Parameter {
+        Parameter::builder()
+            .parameter_key(name)
+            .parameter_value(value)
+            .build()
+    }
+
+    fn template() -> Value {
+        json!({
+            "Parameters": {
+                "Env": {"Type": "String"},
+                "VpcId": {"Type": "AWS::EC2::VPC::Id"},
+                "Size": {"Type": "Number", "Default": 10},
+            },
+            "Resources": {
+                "Bucket": {"Type": "AWS::S3::Bucket"},
+            },
+        })
+    }
+
+    #[test]
+    fn parameters_for_keeps_previous_and_copies_source_values() {
+        let parameters = parameters_for(
+            &template(),
+            &[parameter("Env", "prod")],
+            &[parameter("Env", "dev"), parameter("VpcId", "vpc-1")],
+        );
+
+        assert_eq!(
+            parameters,
+            vec![
+                Parameter::builder()
+                    .parameter_key("Env")
+                    .use_previous_value(true)
+                    .build(),
+                parameter("VpcId", "vpc-1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn masked_parameters_are_copied_no_echo_parameters() {
+        let source_template = json!({
+            "Parameters": {
+                "Env": {"Type": "String", "NoEcho": true},
+                "VpcId": {"Type": "String", "NoEcho": "true"},
+                "Size": {"Type": "Number", "NoEcho": "false"},
+            },
+        });
+
+        assert_eq!(
+            masked_parameters(&template(), &[parameter("Env", "****")], &source_template),
+            vec!["VpcId".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn update_stack_requires_values_for_parameters_without_default() {
+        let api = FakeStackApi::new()
+            .with_stack("Target", json!({"Resources": {}}))
+            .with_stack("Source", template())
+            .with_parameter("Source", "Env", "dev")
+            .with_parameter("Source", "VpcId", "vpc-1");
+
+        let err = api
+            .update_stack("Target", &template(), &[], &[])
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Parameters: [Env] must have values");
+
+        let source = api.stack_parameters("Source").await.unwrap();
+        let parameters = parameters_for(&template(), &[], &source);
+        api.update_stack("Target", &template(), &parameters, &[])
+            .await
+            .unwrap();
+        assert_eq!(
+            api.parameters("Target"),
+            BTreeMap::from([
+                ("Env".to_string(), "dev".to_string()),
+                ("Size".to_string(), "10".to_string()),
+                ("VpcId".to_string(), "vpc-1".to_string()),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn update_stack_keeps_previous_values() {
+        let api = FakeStackApi::new()
+            .with_stack("Source", template())
+            .with_parameter("Source", "Env", "dev")
+            .with_parameter("Source", "VpcId", "vpc-1")
+            .with_parameter("Source", "Size", "20");
+        let mut removed = template();
+        removed["Resources"] = json!({});
+
+        let previous = api.stack_parameters("Source").await.unwrap();
+        api.update_stack(
+            "Source",
+            &removed,
+            &parameters_for(&removed, &previous, &[]),
+            &[],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(api.parameters("Source")["Size"], "20");
+        assert!(api.resources("Source").is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_all_resources_reads_every_page() {
+        let resources = (1..=5)
+            .map(|n| (format!("Queue{}", n), json!({"Type": "AWS::SQS::Queue"})))
+            .chain([("Bucket".to_string(), json!({"Type": "AWS::S3::Bucket"}))])
+            .collect::<serde_json::Map<_, _>>();
+        let api = FakeStackApi::new()
+            .with_stack("Stack", json!({ "Resources": resources }))
+            .with_page_size(2);
+
+        let resources = list_all_resources(&api, "Stack").await.unwrap();
+
+        assert_eq!(
+            resources
+                .iter()
+                .map(|resource| resource.logical_resource_id().unwrap_or_default())
+                .collect::<Vec<_>>(),
+            vec!["Bucket", "Queue1", "Queue2", "Queue3", "Queue4", "Queue5"]
+        );
+        assert_eq!(
+            api.calls()
+                .iter()
+                .filter(|call| *call == "list_stack_resources Stack")
+                .count(),
+            3
+        );
+    }
+}
@@ -0,0 +1,577 @@
+//! In-memory CloudFormation, see [`FakeStackApi`]
+
+use aws_sdk_cloudformation::primitives::DateTime;
+use aws_sdk_cloudformation::types::{
+    Capability, ChangeSetStatus, Parameter, ResourceStatus, ResourceToImport, StackEvent,
+    StackResourceSummary, StackStatus, StackSummary,
+};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use super::{ApiResult, StackApi};
+
+/// A stack of the fake
+#[derive(Debug, Clone)]
+struct FakeStack {
+    template: Value,
+    status: StackStatus,
+    status_reason: Option<String>,
+
+    /// Values of the Parameters of the template, by name
+    parameters: BTreeMap<String, String>,
+
+    /// Physical IDs by logical ID
+    resources: BTreeMap<String, String>,
+
+    /// Events of the stack, newest first
+    events: Vec<StackEvent>,
+}
+
+/// An import change set of the fake, waiting to be executed
+#[derive(Debug, Clone)]
+struct FakeChangeSet {
+    stack_name: String,
+    template: Value,
+    parameters: BTreeMap<String, String>,
+    resources: BTreeMap<String, String>,
+
+    /// Why the change set failed, it cannot be executed then
+    failure: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    stacks: BTreeMap<String, FakeStack>,
+    changesets: BTreeMap<String, FakeChangeSet>,
+    failures: BTreeMap<(String, String), String>,
+    calls: Vec<String>,
+
+    /// Number of items per page of paginated operations, all at once if not set
+    page_size: Option<usize>,
+
+    /// Number of events emitted so far, the fake clock of the event timestamps
+    clock: i64,
+}
+
+/// In-memory CloudFormation for tests of the engine. Stack operations complete as soon as they are
+/// started and emit the events CloudFormation would; resources added by a stack update get a
+/// generated physical ID, resources removed from a template are gone. Resources whose DeletionPolicy
+/// is not `Retain` are not tracked beyond that, the fake knows no physical resources outside of
+/// stacks.
+#[derive(Debug, Default)]
+pub struct FakeStackApi {
+    state: Mutex<FakeState>,
+}
+
+impl FakeStackApi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a stack with the given template, every resource of it gets a generated physical ID
+    pub fn with_stack(self, stack_name: &str, template: Value) -> Self {
+        let resources = logical_ids(&template)
+            .into_iter()
+            .map(|id| {
+                let physical_id = physical_id(stack_name, &id);
+                (id, physical_id)
+            })
+            .collect();
+        self.state.lock().unwrap().stacks.insert(
+            stack_name.to_string(),
+            FakeStack {
+                template,
+                status: StackStatus::CreateComplete,
+                status_reason: None,
+                parameters: BTreeMap::new(),
+                resources,
+                events: Vec::new(),
+            },
+        );
+        self
+    }
+
+    /// Returns the results of paginated operations in pages of the given size
+    pub fn with_page_size(self, page_size: usize) -> Self {
+        self.state.lock().unwrap().page_size = Some(page_size);
+        self
+    }
+
+    /// Sets the status of a stack, e.g. to leave an operation in progress or let it fail
+    pub fn set_status(&self, stack_name: &str, status: StackStatus, reason: Option<&str>) {
+        if let Some(stack) = self.state.lock().unwrap().stacks.get_mut(stack_name) {
+            stack.status = status;
+            stack.status_reason = reason.map(str::to_string);
+        }
+    }
+
+    /// Emits an event of a resource of a stack, or of the stack itself if `logical_id` is the
+    /// name of the stack
+    pub fn add_event(
+        &self,
+        stack_name: &str,
+        logical_id: &str,
+        status: ResourceStatus,
+        reason: Option<&str>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let resource_type = match logical_id == stack_name {
+            true => "AWS::CloudFormation::Stack".to_string(),
+            false => state.stacks.get(stack_name).map_or(String::new(), |stack| {
+                stack.template["Resources"][logical_id]["Type"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string()
+            }),
+        };
+        state.emit(stack_name, (logical_id, &resource_type), status, reason);
+    }
+
+    /// Sets the value of a Parameter of a stack added before
+    pub fn with_parameter(self, stack_name: &str, name: &str, value: &str) -> Self {
+        if let Some(stack) = self.state.lock().unwrap().stacks.get_mut(stack_name) {
+            stack.parameters.insert(name.to_string(), value.to_string());
+        }
+        self
+    }
+
+    /// Makes an operation on a stack fail with the given message. Operations are named like the
+    /// methods of `StackApi`, e.g. `update_stack`.
+    pub fn fail_on(&self, operation: &str, stack_name: &str, message: &str) {
+        self.state.lock().unwrap().failures.insert(
+            (operation.to_string(), stack_name.to_string()),
+            message.to_string(),
+        );
+    }
+
+    /// Lets an operation made to fail with `fail_on` succeed again
+    pub fn clear_failure(&self, operation: &str, stack_name: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .remove(&(operation.to_string(), stack_name.to_string()));
+    }
+
+    /// Operations called so far, as `OPERATION STACK`
+    pub fn calls(&self) -> Vec<String> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// The current template of a stack
+    pub fn template(&self, stack_name: &str) -> Option<Value> {
+        let state = self.state.lock().unwrap();
+        state
+            .stacks
+            .get(stack_name)
+            .map(|stack| stack.template.clone())
+    }
+
+    /// Values of the Parameters of a stack by name
+    pub fn parameters(&self, stack_name: &str) -> BTreeMap<String, String> {
+        let state = self.state.lock().unwrap();
+        state
+            .stacks
+            .get(stack_name)
+            .map(|stack| stack.parameters.clone())
+            .unwrap_or_default()
+    }
+
+    /// Physical IDs of the resources of a stack by logical ID
+    pub fn resources(&self, stack_name: &str) -> BTreeMap<String, String> {
+        let state = self.state.lock().unwrap();
+        state
+            .stacks
+            .get(stack_name)
+            .map(|stack| stack.resources.clone())
+            .unwrap_or_default()
+    }
+
+    /// Records a call and returns the state of the fake, unless the operation should fail
+    fn call(
+        &self,
+        operation: &str,
+        stack_name: &str,
+    ) -> ApiResult<std::sync::MutexGuard<'_, FakeState>> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(format!("{} {}", operation, stack_name));
+        if let Some(message) = state
+            .failures
+            .get(&(operation.to_string(), stack_name.to_string()))
+        {
+            return Err(message.clone().into());
+        }
+        if operation != "list_stacks" && !state.stacks.contains_key(stack_name) {
+            return Err(format!("Stack with id {} does not exist", stack_name).into());
+        }
+        Ok(state)
+    }
+}
+
+impl FakeState {
+    /// Adds an event to a stack, with the next timestamp of the fake clock
+    fn emit(
+        &mut self,
+        stack_name: &str,
+        (logical_id, resource_type): (&str, &str),
+        status: ResourceStatus,
+        reason: Option<&str>,
+    ) {
+        self.clock += 1;
+        let event = StackEvent::builder()
+            .stack_name(stack_name)
+            .event_id(format!("{}-{}", stack_name, self.clock))
+            .logical_resource_id(logical_id)
+            .resource_type(resource_type)
+            .resource_status(status)
+            .set_resource_status_reason(reason.map(str::to_string))
+            .timestamp(DateTime::from_secs(1_700_000_000 + self.clock))
+            .build();
+        if let Some(stack) = self.stacks.get_mut(stack_name) {
+            stack.events.insert(0, event);
+        }
+    }
+
+    /// Emits the events of a stack operation which changed the given resources
+    fn emit_operation(
+        &mut self,
+        stack_name: &str,
+        (in_progress, complete): (ResourceStatus, ResourceStatus),
+        resources: &[(String, String, ResourceStatus)],
+    ) {
+        let stack = (stack_name, "AWS::CloudFormation::Stack");
+        self.emit(stack_name, stack, in_progress, None);
+        for (logical_id, resource_type, status) in resources {
+            self.emit(
+                stack_name,
+                (logical_id, resource_type),
+                status.clone(),
+                None,
+            );
+        }
+        self.emit(stack_name, stack, complete, None);
+    }
+
+    /// The page of `items` starting at `next_token`, with the token of the next page
+    fn page<T: Clone>(
+        &self,
+        items: &[T],
+        next_token: Option<String>,
+    ) -> ApiResult<(Vec<T>, Option<String>)> {
+        let start = match next_token {
+            Some(token) => token
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid token {}", token))?,
+            None => 0,
+        };
+        let end = self
+            .page_size
+            .map_or(items.len(), |size| (start + size).min(items.len()));
+        let next_token = (end < items.len()).then(|| end.to_string());
+        Ok((items[start.min(end)..end].to_vec(), next_token))
+    }
+}
+
+impl StackApi for FakeStackApi {
+    async fn list_stacks(&self) -> ApiResult<Vec<StackSummary>> {
+        let state = self.call("list_stacks", "")?;
+        Ok(state
+            .stacks
+            .iter()
+            .filter(|(_, stack)| !stack.status.as_str().starts_with("DELETE"))
+            .map(|(name, stack)| {
+                StackSummary::builder()
+                    .stack_name(name)
+                    .stack_id(format!(
+                        "arn:aws:cloudformation:us-east-1:123456789012:stack/{}/fake",
+                        name
+                    ))
+                    .stack_status(stack.status.clone())
+                    .build()
+            })
+            .collect())
+    }
+
+    async fn list_stack_resources(
+        &self,
+        stack_name: &str,
+        next_token: Option<String>,
+    ) -> ApiResult<(Vec<StackResourceSummary>, Option<String>)> {
+        let state = self.call("list_stack_resources", stack_name)?;
+        let stack = &state.stacks[stack_name];
+        let resources = stack
+            .resources
+            .iter()
+            .map(|(logical_id, physical_id)| {
+                StackResourceSummary::builder()
+                    .logical_resource_id(logical_id)
+                    .physical_resource_id(physical_id)
+                    .resource_type(resource_type(&stack.template["Resources"][logical_id]))
+                    .resource_status(ResourceStatus::CreateComplete)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        state.page(&resources, next_token)
+    }
+
+    async fn get_template(&self, stack_name: &str) -> ApiResult<Value> {
+        let state = self.call("get_template", stack_name)?;
+        Ok(state.stacks[stack_name].template.clone())
+    }
+
+    async fn stack_parameters(&self, stack_name: &str) -> ApiResult<Vec<Parameter>> {
+        let state = self.call("stack_parameters", stack_name)?;
+        Ok(state.stacks[stack_name]
+            .parameters
+            .iter()
+            .map(|(name, value)| {
+                Parameter::builder()
+                    .parameter_key(name)
+                    .parameter_value(value)
+                    .build()
+            })
+            .collect())
+    }
+
+    async fn update_stack(
+        &self,
+        stack_name: &str,
+        template: &Value,
+        parameters: &[Parameter],
+        _capabilities: &[Capability],
+    ) -> ApiResult<()> {
+        let mut state = self.call("update_stack", stack_name)?;
+        let stack = state.stacks.get_mut(stack_name).unwrap();
+        let parameters = resolve_parameters(template, parameters, &stack.parameters)?;
+        if *template == stack.template && parameters == stack.parameters {
+            return Err("No updates are to be performed.".into());
+        }
+
+        let ids = logical_ids(template);
+        let mut changes = Vec::new();
+        for id in logical_ids(&stack.template) {
+            let resource = &stack.template["Resources"][&id];
+            let status = match (ids.contains(&id), &resource["DeletionPolicy"]) {
+                (true, _) if template["Resources"][&id] == *resource => continue,
+                (true, _) => ResourceStatus::UpdateComplete,
+                (false, policy) if policy == "Retain" => ResourceStatus::DeleteSkipped,
+                (false, _) => ResourceStatus::DeleteComplete,
+            };
+            changes.push((id, resource_type(resource), status));
+        }
+        for id in &ids {
+            if !stack.resources.contains_key(id) {
+                let resource_type = resource_type(&template["Resources"][id]);
+                changes.push((id.clone(), resource_type, ResourceStatus::CreateComplete));
+            }
+        }
+
+        stack.resources.retain(|id, _| ids.contains(id));
+        for id in ids {
+            stack
+                .resources
+                .entry(id.clone())
+                .or_insert_with(|| physical_id(stack_name, &id));
+        }
+        stack.template = template.clone();
+        stack.parameters = parameters;
+        stack.status = StackStatus::UpdateComplete;
+        stack.status_reason = None;
+        state.emit_operation(
+            stack_name,
+            (
+                ResourceStatus::UpdateInProgress,
+                ResourceStatus::UpdateComplete,
+            ),
+            &changes,
+        );
+        Ok(())
+    }
+
+    async fn create_import_changeset(
+        &self,
+        stack_name: &str,
+        changeset_name: &str,
+        template: &Value,
+        parameters: &[Parameter],
+        resources: &[ResourceToImport],
+        _capabilities: &[Capability],
+    ) -> ApiResult<String> {
+        let mut state = self.call("create_import_changeset", stack_name)?;
+        let parameters =
+            resolve_parameters(template, parameters, &state.stacks[stack_name].parameters)?;
+        let mut imported = BTreeMap::new();
+        for resource in resources {
+            let logical_id = resource
+                .logical_resource_id()
+                .unwrap_or_default()
+                .to_string();
+            if state.stacks[stack_name].resources.contains_key(&logical_id) {
+                return Err(format!("Resource {} exists in the stack already", logical_id).into());
+            }
+            let physical_id = resource
+                .resource_identifier()
+                .and_then(|identifier| identifier.values().next())
+                .ok_or_else(|| format!("No resource identifier for {}", logical_id))?;
+            imported.insert(logical_id, physical_id.to_string());
+        }
+
+        // like CloudFormation, the change set is created, but fails
+        let without_policy = imported
+            .keys()
+            .filter(|id| template["Resources"][id.as_str()]["DeletionPolicy"].is_null())
+            .cloned()
+            .collect::<Vec<_>>();
+        let failure = (!without_policy.is_empty()).then(|| {
+            format!(
+                "The following resource(s) to import [{}] must have DeletionPolicy attribute specified in the template.",
+                without_policy.join(", ")
+            )
+        });
+
+        state.changesets.insert(
+            changeset_name.to_string(),
+            FakeChangeSet {
+                stack_name: stack_name.to_string(),
+                template: template.clone(),
+                parameters,
+                resources: imported,
+                failure,
+            },
+        );
+        Ok(changeset_name.to_string())
+    }
+
+    async fn changeset_status(
+        &self,
+        stack_name: &str,
+        changeset_name: &str,
+    ) -> ApiResult<(ChangeSetStatus, Option<String>)> {
+        let state = self.call("changeset_status", stack_name)?;
+        match state.changesets.get(changeset_name) {
+            Some(changeset) if changeset.stack_name == stack_name => match &changeset.failure {
+                Some(failure) => Ok((ChangeSetStatus::Failed, Some(failure.clone()))),
+                None => Ok((ChangeSetStatus::CreateComplete, None)),
+            },
+            _ => Err(format!("ChangeSet [{}] does not exist", changeset_name).into()),
+        }
+    }
+
+    async fn execute_changeset(&self, stack_name: &str, changeset_name: &str) -> ApiResult<()> {
+        let mut state = self.call("execute_changeset", stack_name)?;
+        let changeset = state
+            .changesets
+            .remove(changeset_name)
+            .filter(|changeset| changeset.stack_name == stack_name)
+            .ok_or_else(|| format!("ChangeSet [{}] does not exist", changeset_name))?;
+        if changeset.failure.is_some() {
+            return Err(format!(
+                "ChangeSet [{}] cannot be executed in its current status of [FAILED]",
+                changeset_name
+            )
+            .into());
+        }
+
+        let imported = changeset
+            .resources
+            .keys()
+            .map(|id| {
+                let resource_type = resource_type(&changeset.template["Resources"][id]);
+                (id.clone(), resource_type, ResourceStatus::ImportComplete)
+            })
+            .collect::<Vec<_>>();
+        let stack = state.stacks.get_mut(stack_name).unwrap();
+        stack.resources.extend(changeset.resources);
+        stack.template = changeset.template;
+        stack.parameters = changeset.parameters;
+        stack.status = StackStatus::ImportComplete;
+        stack.status_reason = None;
+        state.emit_operation(
+            stack_name,
+            (
+                ResourceStatus::ImportInProgress,
+                ResourceStatus::ImportComplete,
+            ),
+            &imported,
+        );
+        Ok(())
+    }
+
+    async fn stack_status(&self, stack_name: &str) -> ApiResult<(StackStatus, Option<String>)> {
+        let state = self.call("stack_status", stack_name)?;
+        let stack = &state.stacks[stack_name];
+        Ok((stack.status.clone(), stack.status_reason.clone()))
+    }
+
+    async fn describe_stack_events(
+        &self,
+        stack_name: &str,
+        next_token: Option<String>,
+    ) -> ApiResult<(Vec<StackEvent>, Option<String>)> {
+        let state = self.call("describe_stack_events", stack_name)?;
+        state.page(&state.stacks[stack_name].events, next_token)
+    }
+}
+
+/// Values of the Parameters of a template as CloudFormation determines them from the passed
+/// Parameters, the previous values and the Defaults
+fn resolve_parameters(
+    template: &Value,
+    parameters: &[Parameter],
+    previous: &BTreeMap<String, String>,
+) -> ApiResult<BTreeMap<String, String>> {
+    let declared = template["Parameters"]
+        .as_object()
+        .cloned()
+        .unwrap_or_default();
+    if let Some(unknown) = parameters
+        .iter()
+        .filter_map(|parameter| parameter.parameter_key())
+        .find(|name| !declared.contains_key(*name))
+    {
+        return Err(format!("Parameters: [{}] do not exist in the template", unknown).into());
+    }
+
+    let mut values = BTreeMap::new();
+    for (name, declaration) in declared {
+        let passed = parameters
+            .iter()
+            .find(|parameter| parameter.parameter_key() == Some(name.as_str()));
+        let value = match passed {
+            Some(parameter) if parameter.use_previous_value() == Some(true) => {
+                previous.get(&name).cloned().ok_or_else(|| {
+                    format!(
+                        "Invalid input for parameter key {}. Cannot specify usePreviousValue as true for a parameter key not in the previous template",
+                        name
+                    )
+                })?
+            }
+            Some(parameter) => parameter.parameter_value().unwrap_or_default().to_string(),
+            None => match &declaration["Default"] {
+                Value::String(default) => default.to_string(),
+                Value::Null => {
+                    return Err(format!("Parameters: [{}] must have values", name).into())
+                }
+                default => default.to_string(),
+            },
+        };
+        values.insert(name, value);
+    }
+    Ok(values)
+}
+
+fn logical_ids(template: &Value) -> Vec<String> {
+    template["Resources"]
+        .as_object()
+        .map(|resources| resources.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn resource_type(resource: &Value) -> String {
+    resource["Type"].as_str().unwrap_or_default().to_string()
+}
+
+fn physical_id(stack_name: &str, logical_id: &str) -> String {
+    format!("{}-{}", stack_name, logical_id).to_lowercase()
+}
@@ -0,0 +1,271 @@
+use aws_config::SdkConfig;
+use aws_sdk_dynamodb as dynamodb;
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_sts as sts;
+use std::collections::HashMap;
+use std::error::Error;
+
+use cfn_teleport::cancellation::Phase;
+
+use crate::error::{fail, ExitCode};
+
+/// Environment variable to set the DynamoDB table of the shared state
+pub const STATE_TABLE_ENV: &str = "CFN_TELEPORT_STATE_TABLE";
+
+/// Partition key of the state table. Every stack of a running migration has its own item, so a
+/// stack can only be part of one migration at a time.
+pub const KEY: &str = "StackName";
+
+/// Registration of a stack in a running migration
+#[derive(Debug, Clone)]
+pub struct Registration {
+    pub stack_name: String,
+    pub migration_id: String,
+
+    /// `Source` or `Target`
+    pub role: String,
+    pub counterpart: String,
+    pub user: String,
+    pub started_at: String,
+    pub phase: String,
+    pub resources: Vec<String>,
+}
+
+impl Registration {
+    fn to_item(&self) -> HashMap<String, AttributeValue> {
+        let string = |value: &str| AttributeValue::S(value.to_string());
+        let mut item = HashMap::from([
+            (KEY.to_string(), string(&self.stack_name)),
+            ("MigrationId".to_string(), string(&self.migration_id)),
+            ("Role".to_string(), string(&self.role)),
+            ("Counterpart".to_string(), string(&self.counterpart)),
+            ("User".to_string(), string(&self.user)),
+            ("StartedAt".to_string(), string(&self.started_at)),
+            ("Phase".to_string(), string(&self.phase)),
+        ]);
+        // DynamoDB does not allow empty sets
+        if !self.resources.is_empty() {
+            item.insert(
+                "Resources".to_string(),
+                AttributeValue::Ss(self.resources.clone()),
+            );
+        }
+        item
+    }
+
+    fn from_item(item: &HashMap<String, AttributeValue>) -> Self {
+        let string = |name: &str| {
+            item.get(name)
+                .and_then(|value| value.as_s().ok())
+                .cloned()
+                .unwrap_or_default()
+        };
+        let mut resources = item
+            .get("Resources")
+            .and_then(|value| value.as_ss().ok())
+            .cloned()
+            .unwrap_or_default();
+        resources.sort();
+
+        Self {
+            stack_name: string(KEY),
+            migration_id: string("MigrationId"),
+            role: string("Role"),
+            counterpart: string("Counterpart"),
+            user: string("User"),
+            started_at: string("StartedAt"),
+            phase: string("Phase"),
+            resources,
+        }
+    }
+}
+
+/// A migration registered in the state table
+pub struct StateTable {
+    client: dynamodb::Client,
+    table: String,
+    migration_id: String,
+    stacks: Vec<String>,
+}
+
+impl StateTable {
+    /// Registers a migration for its source and target stack. Fails if another migration of either
+    /// stack is in progress.
+    pub async fn register(
+        config: &SdkConfig,
+        table: &str,
+        registrations: Vec<Registration>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut state = Self {
+            client: dynamodb::Client::new(config),
+            table: table.to_string(),
+            migration_id: registrations
+                .first()
+                .map(|registration| registration.migration_id.clone())
+                .unwrap_or_default(),
+            stacks: Vec::new(),
+        };
+
+        for registration in registrations {
+            let result = state
+                .client
+                .put_item()
+                .table_name(table)
+                .set_item(Some(registration.to_item()))
+                .condition_expression("attribute_not_exists(#key)")
+                .expression_attribute_names("#key", KEY)
+                .send()
+                .await;
+
+            match result {
+                Ok(_) => state.stacks.push(registration.stack_name),
+                Err(err) => {
+                    // the stacks registered so far must not stay locked
+                    state.release().await?;
+                    let conflict = err
+                        .as_service_error()
+                        .is_some_and(|err| err.is_conditional_check_failed_exception());
+                    if !conflict {
+                        return Err(dynamodb::Error::from(err).into());
+                    }
+
+                    let other = state.get(&registration.stack_name).await?;
+                    return Err(fail(
+                        ExitCode::Validation,
+                        match other {
+                            Some(other) => format!(
+                                "Stack {} is part of migration {} by {} since {} (phase {}), run cfn-teleport status for details",
+                                other.stack_name, other.migration_id, other.user, other.started_at, other.phase
+                            ),
+                            None => format!(
+                                "Stack {} is part of another migration",
+                                registration.stack_name
+                            ),
+                        },
+                    ));
+                }
+            }
+        }
+
+        Ok(state)
+    }
+
+    pub fn migration_id(&self) -> &str {
+        &self.migration_id
+    }
+
+    /// Records the last completed phase of the migration
+    pub async fn update_phase(&self, phase: Phase) -> Result<(), Box<dyn Error>> {
+        for stack_name in &self.stacks {
+            self.client
+                .update_item()
+                .table_name(&self.table)
+                .key(KEY, AttributeValue::S(stack_name.clone()))
+                .update_expression("SET #phase = :phase")
+                .condition_expression("#id = :id")
+                .expression_attribute_names("#phase", "Phase")
+                .expression_attribute_names("#id", "MigrationId")
+                .expression_attribute_values(":phase", AttributeValue::S(phase.to_string()))
+                .expression_attribute_values(":id", AttributeValue::S(self.migration_id.clone()))
+                .send()
+                .await
+                .map_err(dynamodb::Error::from)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the registration of the migration
+    pub async fn release(&self) -> Result<(), Box<dyn Error>> {
+        release(&self.client, &self.table, &self.migration_id, &self.stacks).await
+    }
+
+    async fn get(&self, stack_name: &str) -> Result<Option<Registration>, Box<dyn Error>> {
+        let output = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key(KEY, AttributeValue::S(stack_name.to_string()))
+            .consistent_read(true)
+            .send()
+            .await
+            .map_err(dynamodb::Error::from)?;
+        Ok(output.item().map(Registration::from_item))
+    }
+}
+
+/// Lists all registered migrations, ordered by their start
+pub async fn list(config: &SdkConfig, table: &str) -> Result<Vec<Registration>, Box<dyn Error>> {
+    let client = dynamodb::Client::new(config);
+    let mut registrations = Vec::new();
+    let mut start_key = None;
+
+    loop {
+        let output = client
+            .scan()
+            .table_name(table)
+            .consistent_read(true)
+            .set_exclusive_start_key(start_key)
+            .send()
+            .await
+            .map_err(dynamodb::Error::from)?;
+        registrations.extend(output.items().iter().map(Registration::from_item));
+
+        start_key = output.last_evaluated_key().cloned();
+        if start_key.is_none() {
+            break;
+        }
+    }
+
+    // the source stack of a migration comes first
+    registrations.sort_by(|a, b| {
+        (&a.started_at, &a.migration_id, &a.role).cmp(&(&b.started_at, &b.migration_id, &b.role))
+    });
+    Ok(registrations)
+}
+
+/// Removes the registration of a migration which did not release it, e.g. after it has been
+/// completed manually. Returns the released stacks.
+pub async fn release_migration(
+    config: &SdkConfig,
+    table: &str,
+    migration_id: &str,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let stacks = list(config, table)
+        .await?
+        .into_iter()
+        .filter(|registration| registration.migration_id == migration_id)
+        .map(|registration| registration.stack_name)
+        .collect::<Vec<_>>();
+    release(&dynamodb::Client::new(config), table, migration_id, &stacks).await?;
+    Ok(stacks)
+}
+
+/// ARN of the caller, to tell the team who runs a migration
+pub async fn caller(config: &SdkConfig) -> Result<String, Box<dyn Error>> {
+    let identity = sts::Client::new(config)
+        .get_caller_identity()
+        .send()
+        .await?;
+    Ok(identity.arn().unwrap_or_default().to_string())
+}
+
+async fn release(
+    client: &dynamodb::Client,
+    table: &str,
+    migration_id: &str,
+    stacks: &[String],
+) -> Result<(), Box<dyn Error>> {
+    for stack_name in stacks {
+        client
+            .delete_item()
+            .table_name(table)
+            .key(KEY, AttributeValue::S(stack_name.clone()))
+            .condition_expression("#id = :id")
+            .expression_attribute_names("#id", "MigrationId")
+            .expression_attribute_values(":id", AttributeValue::S(migration_id.to_string()))
+            .send()
+            .await
+            .map_err(dynamodb::Error::from)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,157 @@
+//! Template bodies for the CloudFormation API. CloudFormation accepts up to 51,200 bytes in the
+//! request body, larger templates of up to 1 MB only as URL of an object in S3. With a bucket
+//! installed, every call which sends a template uploads the larger ones and passes a presigned URL
+//! instead, so the callers never need to know how large a template is. The uploaded objects are
+//! deleted by [`cleanup`] at the end of the run.
+//!
+//! `GetTemplate` always returns the body inline, reading templates needs no store.
+
+use aws_sdk_s3 as s3;
+use aws_sdk_s3::presigning::PresigningConfig;
+use serde_json::Value;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::analysis::TEMPLATE_BODY_LIMIT;
+use crate::stack_api::ApiResult;
+
+/// Maximum size in bytes of a template passed as URL of an object in S3
+pub const TEMPLATE_URL_LIMIT: usize = 1_048_576;
+
+/// Validity of the presigned URLs. CloudFormation reads the template while handling the call, not
+/// when the update or change set is executed.
+pub const URL_EXPIRY: Duration = Duration::from_secs(3600);
+
+static STORE: OnceLock<Store> = OnceLock::new();
+
+/// A template as it is passed to CloudFormation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateBody {
+    Inline(String),
+
+    /// Presigned URL of the uploaded template
+    Url(String),
+}
+
+impl TemplateBody {
+    /// Value of the `TemplateBody` parameter
+    pub fn body(&self) -> Option<String> {
+        match self {
+            TemplateBody::Inline(body) => Some(body.clone()),
+            TemplateBody::Url(_) => None,
+        }
+    }
+
+    /// Value of the `TemplateURL` parameter
+    pub fn url(&self) -> Option<String> {
+        match self {
+            TemplateBody::Inline(_) => None,
+            TemplateBody::Url(url) => Some(url.clone()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Store {
+    client: s3::Client,
+    bucket: String,
+
+    /// Prefix of the keys of this run, so concurrent runs never overwrite each other's templates
+    prefix: String,
+    keys: Mutex<Vec<String>>,
+}
+
+/// Uploads templates which exceed the inline quota into a bucket for the rest of the run. Only the
+/// first call has an effect.
+pub fn install(client: s3::Client, bucket: &str) {
+    let _ = STORE.set(Store {
+        client,
+        bucket: bucket.to_string(),
+        prefix: format!("cfn-teleport/{}", uuid::Uuid::new_v4()),
+        keys: Mutex::new(Vec::new()),
+    });
+}
+
+/// Maximum size in bytes of a template which can be passed to CloudFormation in this run
+pub fn body_limit() -> usize {
+    match STORE.get() {
+        Some(_) => TEMPLATE_URL_LIMIT,
+        None => TEMPLATE_BODY_LIMIT,
+    }
+}
+
+/// The body of a template, uploaded to the bucket if it is too large to be passed inline
+pub async fn prepare(template: &Value) -> ApiResult<TemplateBody> {
+    let body = serde_json::to_string(template)?;
+    if body.len() <= TEMPLATE_BODY_LIMIT {
+        return Ok(TemplateBody::Inline(body));
+    }
+    let Some(store) = STORE.get() else {
+        return Err(format!(
+            "The template has {} bytes, CloudFormation accepts up to {} bytes without a bucket to upload it to",
+            body.len(),
+            TEMPLATE_BODY_LIMIT
+        )
+        .into());
+    };
+
+    let key = {
+        let mut keys = store.keys.lock().unwrap();
+        let key = format!("{}/template-{}.json", store.prefix, keys.len() + 1);
+        keys.push(key.clone());
+        key
+    };
+    store
+        .client
+        .put_object()
+        .bucket(&store.bucket)
+        .key(&key)
+        .content_type("application/json")
+        .body(body.into_bytes().into())
+        .send()
+        .await
+        .map_err(s3::Error::from)?;
+    let request = store
+        .client
+        .get_object()
+        .bucket(&store.bucket)
+        .key(&key)
+        .presigned(PresigningConfig::expires_in(URL_EXPIRY)?)
+        .await
+        .map_err(s3::Error::from)?;
+    Ok(TemplateBody::Url(request.uri().to_string()))
+}
+
+/// Keeps an uploaded template beyond the run, for a change set created by another tool. Returns
+/// the location of the object, `None` for an inline template.
+pub fn keep(body: &TemplateBody) -> Option<String> {
+    let url = body.url()?;
+    let store = STORE.get()?;
+    let mut keys = store.keys.lock().unwrap();
+    let index = keys
+        .iter()
+        .position(|key| url.contains(&format!("/{}?", key)))?;
+    Some(format!("s3://{}/{}", store.bucket, keys.remove(index)))
+}
+
+/// Deletes the templates uploaded in this run. Returns the keys which could not be deleted.
+pub async fn cleanup() -> Vec<String> {
+    let Some(store) = STORE.get() else {
+        return Vec::new();
+    };
+    let keys = std::mem::take(&mut *store.keys.lock().unwrap());
+    let mut remaining = Vec::new();
+    for key in keys {
+        let deleted = store
+            .client
+            .delete_object()
+            .bucket(&store.bucket)
+            .key(&key)
+            .send()
+            .await;
+        if deleted.is_err() {
+            remaining.push(format!("s3://{}/{}", store.bucket, key));
+        }
+    }
+    remaining
+}
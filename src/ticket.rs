@@ -0,0 +1,215 @@
+//! Change tickets rendered from a migration, for change-management systems like ServiceNow or Jira.
+//!
+//! The payload is a JSON template with `{{name}}` placeholders in its strings. A string consisting
+//! of nothing but a placeholder is replaced by the value itself, which keeps lists like `{{arns}}`
+//! a JSON array. Placeholders within a longer string are replaced by their text, lists joined
+//! with commas.
+//!
+//! ```json
+//! {
+//!   "short_description": "{{summary}}",
+//!   "description": "{{description}}",
+//!   "cmdb_ci": "{{arns}}",
+//!   "start_date": "{{change_window_start}}",
+//!   "end_date": "{{change_window_end}}",
+//!   "backout_plan": "{{rollback_plan}}"
+//! }
+//! ```
+
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::Path;
+
+/// A resource moved by the migration
+#[derive(Debug, Clone, PartialEq)]
+pub struct TicketResource {
+    /// Logical ID in the source stack
+    pub source_id: String,
+
+    /// Logical ID in the target stack
+    pub target_id: String,
+    pub resource_type: String,
+    pub physical_id: String,
+}
+
+/// Everything a change ticket of a migration is rendered from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeTicket {
+    pub source_stack: String,
+    pub target_stack: String,
+
+    /// ARNs of the source and the target stack
+    pub stack_arns: Vec<String>,
+    pub resources: Vec<TicketResource>,
+
+    /// Free text, or an ISO 8601 interval like `2024-05-01T22:00Z/2024-05-02T02:00Z`
+    pub change_window: Option<String>,
+}
+
+impl ChangeTicket {
+    /// One-line summary of the migration
+    pub fn summary(&self) -> String {
+        format!(
+            "Move {} resource{} from CloudFormation stack {} to {}",
+            self.resources.len(),
+            if self.resources.len() == 1 { "" } else { "s" },
+            self.source_stack,
+            self.target_stack
+        )
+    }
+
+    /// Summary followed by one line per moved resource
+    pub fn description(&self) -> String {
+        let mut lines = vec![format!("{}:", self.summary())];
+        for resource in &self.resources {
+            let rename = if resource.source_id == resource.target_id {
+                String::new()
+            } else {
+                format!(" as {}", resource.target_id)
+            };
+            lines.push(format!(
+                "- {} ({}, {}){}",
+                resource.source_id, resource.resource_type, resource.physical_id, rename
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// ARNs of the stacks and of all moved resources whose physical ID is an ARN
+    pub fn arns(&self) -> Vec<String> {
+        let resource_arns = self
+            .resources
+            .iter()
+            .map(|resource| &resource.physical_id)
+            .filter(|id| id.starts_with("arn:"));
+        self.stack_arns
+            .iter()
+            .chain(resource_arns)
+            .cloned()
+            .collect()
+    }
+
+    /// Moving the resources back is a migration in the opposite direction, with the original
+    /// logical IDs
+    pub fn rollback_plan(&self) -> String {
+        let resources = self
+            .resources
+            .iter()
+            .map(|resource| {
+                if resource.source_id == resource.target_id {
+                    format!(" --resource {}", resource.target_id)
+                } else {
+                    format!(" --resource {}:{}", resource.target_id, resource.source_id)
+                }
+            })
+            .collect::<String>();
+        format!(
+            "The resources are retained during the migration and never deleted. To roll back, move them back into stack {}: cfn-teleport --source {} --target {}{}",
+            self.source_stack, self.target_stack, self.source_stack, resources
+        )
+    }
+
+    /// Values of all placeholders
+    pub fn variables(&self) -> Map<String, Value> {
+        let (start, end) = match self.change_window.as_deref().map(|w| w.split_once('/')) {
+            Some(Some((start, end))) => (start.trim(), end.trim()),
+            _ => ("", ""),
+        };
+        let resources = self
+            .resources
+            .iter()
+            .map(|resource| {
+                json!({
+                    "LogicalId": resource.source_id,
+                    "NewLogicalId": resource.target_id,
+                    "Type": resource.resource_type,
+                    "PhysicalId": resource.physical_id,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut variables = Map::new();
+        variables.insert("summary".into(), json!(self.summary()));
+        variables.insert("description".into(), json!(self.description()));
+        variables.insert("source_stack".into(), json!(self.source_stack));
+        variables.insert("target_stack".into(), json!(self.target_stack));
+        variables.insert("arns".into(), json!(self.arns()));
+        variables.insert("resources".into(), json!(resources));
+        variables.insert(
+            "change_window".into(),
+            json!(self.change_window.clone().unwrap_or_default()),
+        );
+        variables.insert("change_window_start".into(), json!(start));
+        variables.insert("change_window_end".into(), json!(end));
+        variables.insert("rollback_plan".into(), json!(self.rollback_plan()));
+        variables
+    }
+}
+
+/// Reads a ticket template from a JSON or YAML file
+pub fn read_template(path: &Path) -> Result<Value, String> {
+    let body = fs::read_to_string(path)
+        .map_err(|err| format!("Unable to read ticket template {}: {}", path.display(), err))?;
+    serde_yaml::from_str(&body)
+        .map_err(|err| format!("Invalid ticket template {}: {}", path.display(), err))
+}
+
+/// Renders a ticket template. Unknown placeholders are an error, so typos do not end up in a
+/// ticket.
+pub fn render(template: &Value, ticket: &ChangeTicket) -> Result<Value, String> {
+    render_value(template, &ticket.variables())
+}
+
+fn render_value(value: &Value, variables: &Map<String, Value>) -> Result<Value, String> {
+    match value {
+        Value::String(text) => render_string(text, variables),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| render_value(item, variables))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, item)| Ok((key.clone(), render_value(item, variables)?)))
+            .collect::<Result<Map<_, _>, String>>()
+            .map(Value::Object),
+        other => Ok(other.clone()),
+    }
+}
+
+fn render_string(text: &str, variables: &Map<String, Value>) -> Result<Value, String> {
+    let lookup = |name: &str| {
+        variables
+            .get(name.trim())
+            .ok_or_else(|| format!("Unknown placeholder {{{{{}}}}} in ticket template", name))
+    };
+
+    if let Some(name) = text
+        .strip_prefix("{{")
+        .and_then(|rest| rest.strip_suffix("}}"))
+        .filter(|name| !name.contains("{{") && !name.contains("}}"))
+    {
+        return lookup(name).cloned();
+    }
+
+    let mut rendered = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        rendered.push_str(&as_text(lookup(&rest[start + 2..start + end])?));
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(Value::String(rendered))
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(as_text).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
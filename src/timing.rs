@@ -0,0 +1,44 @@
+use aws_sdk_cloudformation::primitives::{DateTime, DateTimeFormat};
+use std::time::{Duration, SystemTime};
+
+/// Current time in UTC, formatted as RFC3339 with second precision, e.g. `2024-05-01T12:34:56Z`
+pub fn timestamp() -> String {
+    format_timestamp(SystemTime::now())
+}
+
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    DateTime::from_secs(secs as i64)
+        .fmt(DateTimeFormat::DateTime)
+        .unwrap_or_default()
+}
+
+/// Parses an RFC3339 timestamp, e.g. `2024-05-01T22:00:00Z`
+pub fn parse_timestamp(timestamp: &str) -> Result<SystemTime, String> {
+    DateTime::from_str(timestamp, DateTimeFormat::DateTime)
+        .ok()
+        .and_then(|time| SystemTime::try_from(time).ok())
+        .ok_or_else(|| {
+            format!(
+                "Invalid timestamp '{}', expected RFC 3339, e.g. 2024-05-01T22:00:00Z",
+                timestamp
+            )
+        })
+}
+
+/// Formats a duration for humans, e.g. `42s`, `3m 07s` or `1h 02m 03s`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
@@ -0,0 +1,72 @@
+//! Explanations and fixes for common reasons of failed import changesets.
+
+/// A known failure, recognized by fragments of the CloudFormation status reason
+#[derive(Debug, PartialEq, Eq)]
+pub struct KnownFailure {
+    /// Lowercase fragments of which any must be contained in the status reason
+    pub patterns: &'static [&'static str],
+    pub explanation: &'static str,
+    pub fix: &'static str,
+}
+
+pub static KNOWN_FAILURES: [KnownFailure; 9] = [
+    KnownFailure {
+        patterns: &["already exists in stack", "belongs to another stack"],
+        explanation: "The resource is still managed by another stack. A resource can only belong to one stack at a time.",
+        fix: "Make sure the resource has been removed from the source stack with a DeletionPolicy of Retain before importing it, and that no other stack manages it.",
+    },
+    KnownFailure {
+        patterns: &["must have deletionpolicy"],
+        explanation: "Every resource to import needs a DeletionPolicy attribute in the template.",
+        fix: "Add a DeletionPolicy (e.g. Retain) to the resource in the target template.",
+    },
+    KnownFailure {
+        patterns: &["missing required propert", "required key", "is required"],
+        explanation: "The template of the target stack lacks a property the resource type requires for import.",
+        fix: "Add the missing property with the value of the existing resource to its definition in the source stack, deploy it, and run the migration again.",
+    },
+    KnownFailure {
+        patterns: &["drift", "does not match the actual", "do not match the actual"],
+        explanation: "The properties in the template differ from the actual configuration of the resource.",
+        fix: "Run drift detection on the source stack and update its template to the actual configuration before moving the resource.",
+    },
+    KnownFailure {
+        patterns: &["identifiers specified for resources to import", "resource identifier"],
+        explanation: "The identifier used to import the resource does not match its definition in the template.",
+        fix: "Check the identifier properties (e.g. BucketName, TableName) of the resource in the template against the physical resource.",
+    },
+    KnownFailure {
+        patterns: &["does not exist", "not found", "could not be found"],
+        explanation: "The physical resource could not be found in this account and region.",
+        fix: "Check that the resource has not been deleted and that the region and credentials are those of the stacks.",
+    },
+    KnownFailure {
+        patterns: &["does not support import", "not supported for import", "unsupported resource type"],
+        explanation: "The resource type cannot be imported into a stack.",
+        fix: "Recreate the resource in the target stack instead of moving it.",
+    },
+    KnownFailure {
+        patterns: &["update, create or delete operations cannot be executed during import", "not being imported"],
+        explanation: "An import changeset must not change anything besides importing the resources.",
+        fix: "Deploy pending changes of the target stack first, so its template matches the deployed state, and run the migration again.",
+    },
+    KnownFailure {
+        patterns: &["not authorized", "accessdenied", "access denied"],
+        explanation: "The credentials lack permissions for the import.",
+        fix: "Run `cfn-teleport validate --plan <FILE>` to see which actions are denied.",
+    },
+];
+
+/// Finds the known failures matching a status reason
+pub fn diagnose(status_reason: &str) -> Vec<&'static KnownFailure> {
+    let status_reason = status_reason.to_lowercase();
+    KNOWN_FAILURES
+        .iter()
+        .filter(|failure| {
+            failure
+                .patterns
+                .iter()
+                .any(|pattern| status_reason.contains(pattern))
+        })
+        .collect()
+}
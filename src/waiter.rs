@@ -0,0 +1,641 @@
+//! Waiting for stack operations and change sets, following the events of the stack.
+
+use aws_sdk_cloudformation::primitives::DateTime;
+use aws_sdk_cloudformation::types::{ChangeSetStatus, ResourceStatus, StackEvent, StackStatus};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::cancellation::Cancellation;
+use crate::stack_api::{ApiResult, StackApi};
+
+/// Default number of minutes without any stack event after which a stack operation is considered stuck
+pub const DEFAULT_STALL_TIMEOUT: u64 = 30;
+
+/// How waiting for a stack operation or a change set ended. Errors of the AWS API while waiting
+/// are returned as errors instead, as they say nothing about the operation itself.
+#[derive(Debug)]
+pub enum Outcome {
+    Succeeded,
+
+    /// The operation ended in a status other than the expected one
+    Failed {
+        status: String,
+        reason: Option<String>,
+
+        /// Failures of individual resources, as `LOGICAL_ID: REASON`
+        events: Vec<String>,
+    },
+
+    /// Waiting stopped before the operation ended, the operation itself might still succeed
+    TimedOut {
+        last_status: String,
+        cause: Timeout,
+
+        /// Progress of the operation so far, e.g. `3 of 5 resources`
+        progress: String,
+    },
+}
+
+/// Why waiting stopped before the operation ended
+#[derive(Debug)]
+pub enum Timeout {
+    /// No stack events for the given time
+    Stalled(Duration),
+
+    /// The deadline of the run has been reached
+    Deadline,
+}
+
+/// Waiting stopped at the deadline of the run while the stack operation continues in CloudFormation,
+/// so the migration is left partially completed
+#[derive(Debug)]
+pub struct DeadlineReached(String);
+
+impl fmt::Display for DeadlineReached {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for DeadlineReached {}
+
+/// Shows the progress of a stack operation while it is being waited for
+pub trait Indicator {
+    /// Called with the new events of the stack, oldest first, and the progress so far, e.g.
+    /// `1 of 3 resources, MyTable: IMPORT_IN_PROGRESS`
+    fn progress(&mut self, events: &[StackEvent], details: &str);
+
+    /// Called on every poll while the operation is in progress
+    fn tick(&mut self) {}
+}
+
+impl Outcome {
+    /// Turns the outcome of waiting for a stack into an error. Reaching the deadline is a
+    /// [`DeadlineReached`] error.
+    pub fn into_result(self, stack_name: &str) -> Result<(), Box<dyn Error>> {
+        match self {
+            Outcome::Succeeded => Ok(()),
+            Outcome::Failed {
+                status,
+                reason,
+                events,
+            } => {
+                let mut message = format!("Stack update failed {}", status);
+                if let Some(reason) = reason.filter(|_| events.is_empty()) {
+                    message.push_str(&format!(": {}", reason));
+                }
+                for failure in &events {
+                    message.push_str(&format!("\n - {}", failure));
+                }
+                Err(message.into())
+            }
+            Outcome::TimedOut {
+                last_status,
+                cause: Timeout::Stalled(timeout),
+                progress,
+            } => Err(format!(
+                "Stack {} did not make any progress for {} minutes in {}, the operation might be stuck ({} completed)",
+                stack_name,
+                timeout.as_secs() / 60,
+                last_status,
+                progress
+            )
+            .into()),
+            Outcome::TimedOut {
+                last_status,
+                cause: Timeout::Deadline,
+                progress,
+            } => Err(Box::new(DeadlineReached(format!(
+                "Stopped waiting for stack {} in {} at the deadline, the stack operation continues in CloudFormation ({} completed)",
+                stack_name, last_status, progress
+            )))),
+        }
+    }
+}
+
+/// Waits for stack operations by following the events of the stack. Waiting stops at the deadline
+/// of the cancellation, cancelling it alone does not stop waiting: the stack operation keeps running
+/// in CloudFormation and the next safe point is after it.
+pub struct StackWaiter<'a, A: StackApi> {
+    client: &'a A,
+    stall_timeout: Duration,
+    cancellation: Cancellation,
+}
+
+/// Events of the current stack operation seen so far
+#[derive(Default)]
+struct Progress {
+    latest: Option<DateTime>,
+    seen: HashSet<String>,
+    completed: HashSet<String>,
+    failures: Vec<String>,
+
+    /// Most recent event of a resource, as `LOGICAL_ID: STATUS`
+    last_event: Option<String>,
+}
+
+impl Progress {
+    /// Records new events, oldest first
+    fn record(&mut self, stack_name: &str, events: &[StackEvent]) {
+        for event in events {
+            if let Some(timestamp) = event.timestamp() {
+                if self.latest.is_none_or(|latest| *timestamp > latest) {
+                    self.latest = Some(*timestamp);
+                }
+            }
+
+            let logical_id = event.logical_resource_id().unwrap_or_default();
+            let status = event.resource_status().map(ResourceStatus::as_str);
+            let status = status.unwrap_or_default();
+            if logical_id != stack_name {
+                self.last_event = Some(format!("{}: {}", logical_id, status));
+            }
+
+            if status.ends_with("_FAILED") {
+                if let Some(reason) = event.resource_status_reason() {
+                    self.failures.push(format!("{}: {}", logical_id, reason));
+                }
+            } else if logical_id != stack_name
+                && (status.ends_with("_COMPLETE") || status == "DELETE_SKIPPED")
+                && !status.contains("ROLLBACK")
+            {
+                self.completed.insert(logical_id.to_string());
+            }
+        }
+    }
+
+    fn describe(&self, total: Option<usize>) -> String {
+        match total {
+            Some(total) => format!("{} of {} resources", self.completed.len().min(total), total),
+            None => format!("{} resources", self.completed.len()),
+        }
+    }
+
+    /// Progress followed by the most recent resource event, shown on the spinner and in the status
+    /// lines, e.g. `1 of 3 resources, MyTable: IMPORT_IN_PROGRESS`
+    fn details(&self, total: Option<usize>) -> String {
+        match &self.last_event {
+            Some(event) => format!("{}, {}", self.describe(total), event),
+            None => self.describe(total),
+        }
+    }
+}
+
+impl<'a, A: StackApi> StackWaiter<'a, A> {
+    pub fn new(client: &'a A, stall_timeout: Duration, cancellation: Cancellation) -> Self {
+        Self {
+            client,
+            stall_timeout,
+            cancellation,
+        }
+    }
+
+    /// The API the waiter follows the stacks with, to start the operations to wait for
+    pub fn client(&self) -> &'a A {
+        self.client
+    }
+
+    /// A waiter following the same API which does not stop when the migration is cancelled or
+    /// reaches its deadline, for operations which must not be abandoned halfway, e.g. a rollback
+    pub fn uncancellable(&self) -> Self {
+        Self::new(self.client, self.stall_timeout, Cancellation::new())
+    }
+
+    /// Waits until the current update or import of a stack has finished. Progress is shown on the
+    /// indicator as the number of completed resources, out of `total` if known. Fails if the stack
+    /// does not emit any events for longer than the stall timeout.
+    pub async fn wait(
+        &self,
+        stack_name: &str,
+        indicator: Option<&mut dyn Indicator>,
+        total: Option<usize>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.outcome(stack_name, indicator, total)
+            .await?
+            .into_result(stack_name)
+    }
+
+    /// Waits like `wait`, but returns how the operation ended instead of an error
+    pub async fn outcome(
+        &self,
+        stack_name: &str,
+        mut indicator: Option<&mut dyn Indicator>,
+        total: Option<usize>,
+    ) -> Result<Outcome, Box<dyn Error>> {
+        let mut progress = Progress::default();
+        let mut last_activity = Instant::now();
+
+        loop {
+            let (status, reason) = self.stack_status(stack_name).await?;
+
+            let events = self
+                .new_events(stack_name, &mut progress, is_in_progress(&status))
+                .await?;
+            tracing::debug!(
+                stack = stack_name,
+                status = status.as_str(),
+                new_events = events.len(),
+                progress = progress.describe(total),
+                "Waiting for stack"
+            );
+            if !events.is_empty() {
+                last_activity = Instant::now();
+                // events are returned newest first
+                let events = events.into_iter().rev().collect::<Vec<_>>();
+                progress.record(stack_name, &events);
+                if let Some(indicator) = indicator.as_mut() {
+                    indicator.progress(&events, &progress.details(total));
+                }
+            }
+
+            if !is_in_progress(&status) {
+                if status == StackStatus::UpdateComplete || status == StackStatus::ImportComplete {
+                    return Ok(Outcome::Succeeded);
+                }
+
+                return Ok(Outcome::Failed {
+                    status: status.as_str().to_string(),
+                    reason,
+                    events: progress.failures,
+                });
+            }
+
+            if last_activity.elapsed() > self.stall_timeout {
+                return Ok(Outcome::TimedOut {
+                    last_status: status.as_str().to_string(),
+                    cause: Timeout::Stalled(self.stall_timeout),
+                    progress: progress.describe(total),
+                });
+            }
+
+            if let Some(indicator) = indicator.as_mut() {
+                indicator.tick();
+            }
+
+            if self.cancellation.is_expired() {
+                return Ok(Outcome::TimedOut {
+                    last_status: status.as_str().to_string(),
+                    cause: Timeout::Deadline,
+                    progress: progress.describe(total),
+                });
+            }
+
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn stack_status(
+        &self,
+        stack_name: &str,
+    ) -> Result<(StackStatus, Option<String>), Box<dyn Error>> {
+        into_error(self.client.stack_status(stack_name).await)
+    }
+
+    /// Waits until a change set has been created
+    pub async fn wait_for_changeset(
+        &self,
+        stack_name: &str,
+        changeset_name: &str,
+    ) -> Result<Outcome, Box<dyn Error>> {
+        loop {
+            let (status, reason) = into_error(
+                self.client
+                    .changeset_status(stack_name, changeset_name)
+                    .await,
+            )?;
+            tracing::debug!(
+                stack = stack_name,
+                changeset = changeset_name,
+                status = status.as_str(),
+                "Waiting for changeset"
+            );
+
+            match status {
+                ChangeSetStatus::CreateInProgress | ChangeSetStatus::CreatePending => {}
+                ChangeSetStatus::CreateComplete => return Ok(Outcome::Succeeded),
+                status => {
+                    return Ok(Outcome::Failed {
+                        status: status.as_str().to_string(),
+                        reason,
+                        events: Vec::new(),
+                    })
+                }
+            }
+
+            if self.cancellation.is_expired() {
+                return Ok(Outcome::TimedOut {
+                    last_status: status.as_str().to_string(),
+                    cause: Timeout::Deadline,
+                    progress: "0 resources".to_string(),
+                });
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Fetches the events which have not been seen yet. Without a previous event, this reads back
+    /// to the start of the current operation, or the end of the previous one while the current
+    /// operation has not emitted any events yet.
+    async fn new_events(
+        &self,
+        stack_name: &str,
+        progress: &mut Progress,
+        in_progress: bool,
+    ) -> Result<Vec<StackEvent>, Box<dyn Error>> {
+        let mut events = Vec::new();
+        let mut token = None;
+
+        loop {
+            let (page, next_token) =
+                into_error(self.client.describe_stack_events(stack_name, token).await)?;
+
+            for event in &page {
+                let timestamp = event.timestamp();
+                match progress.latest {
+                    Some(latest) if timestamp.is_some_and(|t| *t < latest) => return Ok(events),
+                    Some(_) => {}
+                    None if in_progress
+                        && is_stack_event(event, stack_name)
+                        && is_terminal(event) =>
+                    {
+                        return Ok(events)
+                    }
+                    None => {}
+                }
+
+                let event_id = event.event_id().unwrap_or_default().to_string();
+                if !progress.seen.insert(event_id) {
+                    continue;
+                }
+                events.push(event.clone());
+
+                if progress.latest.is_none()
+                    && is_stack_event(event, stack_name)
+                    && is_operation_start(event)
+                {
+                    return Ok(events);
+                }
+            }
+
+            token = next_token;
+            if token.is_none() {
+                return Ok(events);
+            }
+        }
+    }
+}
+
+fn into_error<T>(result: ApiResult<T>) -> Result<T, Box<dyn Error>> {
+    result.map_err(|err| err as Box<dyn Error>)
+}
+
+fn is_in_progress(status: &StackStatus) -> bool {
+    status.as_str().ends_with("_IN_PROGRESS")
+}
+
+fn is_stack_event(event: &StackEvent, stack_name: &str) -> bool {
+    event.resource_type() == Some("AWS::CloudFormation::Stack")
+        && event.logical_resource_id() == Some(stack_name)
+}
+
+fn is_operation_start(event: &StackEvent) -> bool {
+    matches!(
+        event.resource_status(),
+        Some(ResourceStatus::UpdateInProgress) | Some(ResourceStatus::ImportInProgress)
+    )
+}
+
+fn is_terminal(event: &StackEvent) -> bool {
+    event
+        .resource_status()
+        .is_some_and(|status| !status.as_str().ends_with("_IN_PROGRESS"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stack_api::FakeStackApi;
+    use aws_sdk_cloudformation::types::ResourceToImport;
+    use serde_json::json;
+
+    const STALL_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+    /// Records the progress shown while waiting
+    #[derive(Default)]
+    struct Recorder {
+        details: Vec<String>,
+        events: Vec<String>,
+    }
+
+    impl Indicator for Recorder {
+        fn progress(&mut self, events: &[StackEvent], details: &str) {
+            self.details.push(details.to_string());
+            self.events.extend(events.iter().map(|event| {
+                format!(
+                    "{} {}",
+                    event.logical_resource_id().unwrap_or_default(),
+                    event.resource_status().map_or("", ResourceStatus::as_str)
+                )
+            }));
+        }
+    }
+
+    fn api() -> FakeStackApi {
+        FakeStackApi::new()
+            .with_stack(
+                "Stack",
+                json!({"Resources": {"Bucket": {"Type": "AWS::S3::Bucket"}}}),
+            )
+            .with_page_size(2)
+    }
+
+    #[tokio::test]
+    async fn outcome_of_a_successful_update() {
+        let api = api();
+        api.add_event("Stack", "Stack", ResourceStatus::CreateComplete, None);
+        let template = json!({"Resources": {
+            "Bucket": {"Type": "AWS::S3::Bucket"},
+            "Queue": {"Type": "AWS::SQS::Queue"},
+            "Topic": {"Type": "AWS::SNS::Topic"},
+        }});
+        api.update_stack("Stack", &template, &[], &[])
+            .await
+            .unwrap();
+
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let mut recorder = Recorder::default();
+        let outcome = waiter
+            .outcome("Stack", Some(&mut recorder), Some(2))
+            .await
+            .unwrap();
+
+        assert!(matches!(outcome, Outcome::Succeeded));
+        // the events of the update are read across pages, up to the start of the update
+        assert_eq!(
+            recorder.events,
+            vec![
+                "Stack UPDATE_IN_PROGRESS",
+                "Queue CREATE_COMPLETE",
+                "Topic CREATE_COMPLETE",
+                "Stack UPDATE_COMPLETE",
+            ]
+        );
+        assert_eq!(
+            recorder.details,
+            vec!["2 of 2 resources, Topic: CREATE_COMPLETE"]
+        );
+    }
+
+    #[tokio::test]
+    async fn outcome_of_a_failed_update() {
+        let api = api();
+        api.add_event("Stack", "Stack", ResourceStatus::UpdateInProgress, None);
+        api.add_event(
+            "Stack",
+            "Bucket",
+            ResourceStatus::UpdateFailed,
+            Some("Bucket name already exists"),
+        );
+        api.add_event(
+            "Stack",
+            "Stack",
+            ResourceStatus::UpdateRollbackComplete,
+            None,
+        );
+        api.set_status(
+            "Stack",
+            StackStatus::UpdateRollbackComplete,
+            Some("The following resource(s) failed to update: [Bucket]."),
+        );
+
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let outcome = waiter.outcome("Stack", None, None).await.unwrap();
+
+        let Outcome::Failed {
+            status,
+            reason,
+            events,
+        } = &outcome
+        else {
+            panic!("Unexpected outcome {:?}", outcome);
+        };
+        assert_eq!(status, "UPDATE_ROLLBACK_COMPLETE");
+        assert_eq!(
+            reason.as_deref(),
+            Some("The following resource(s) failed to update: [Bucket].")
+        );
+        assert_eq!(events, &vec!["Bucket: Bucket name already exists"]);
+        assert_eq!(
+            outcome.into_result("Stack").unwrap_err().to_string(),
+            "Stack update failed UPDATE_ROLLBACK_COMPLETE\n - Bucket: Bucket name already exists"
+        );
+    }
+
+    #[tokio::test]
+    async fn outcome_of_a_stalled_update() {
+        let api = api();
+        api.set_status("Stack", StackStatus::UpdateInProgress, None);
+
+        let waiter = StackWaiter::new(&api, Duration::ZERO, Cancellation::new());
+        let outcome = waiter.outcome("Stack", None, None).await.unwrap();
+
+        assert!(matches!(
+            &outcome,
+            Outcome::TimedOut {
+                cause: Timeout::Stalled(_),
+                last_status,
+                ..
+            } if last_status == "UPDATE_IN_PROGRESS"
+        ));
+        assert!(!outcome
+            .into_result("Stack")
+            .unwrap_err()
+            .is::<DeadlineReached>());
+    }
+
+    #[tokio::test]
+    async fn outcome_at_the_deadline() {
+        let api = api();
+        api.set_status("Stack", StackStatus::UpdateInProgress, None);
+
+        let cancellation = Cancellation::new().with_timeout(Duration::ZERO);
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, cancellation);
+        let outcome = waiter.outcome("Stack", None, Some(1)).await.unwrap();
+
+        assert!(matches!(
+            &outcome,
+            Outcome::TimedOut {
+                cause: Timeout::Deadline,
+                progress,
+                ..
+            } if progress == "0 of 1 resources"
+        ));
+        assert!(outcome
+            .into_result("Stack")
+            .unwrap_err()
+            .is::<DeadlineReached>());
+    }
+
+    #[tokio::test]
+    async fn errors_of_the_api_are_errors() {
+        let api = api();
+        api.fail_on("stack_status", "Stack", "Rate exceeded");
+
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let err = waiter.wait("Stack", None, None).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "Rate exceeded");
+    }
+
+    fn import(logical_id: &str) -> ResourceToImport {
+        ResourceToImport::builder()
+            .resource_type("AWS::SQS::Queue")
+            .logical_resource_id(logical_id)
+            .resource_identifier("QueueUrl", "https://queue")
+            .build()
+    }
+
+    #[tokio::test]
+    async fn outcome_of_changesets() {
+        let api = api();
+        let waiter = StackWaiter::new(&api, STALL_TIMEOUT, Cancellation::new());
+        let template = json!({"Resources": {
+            "Bucket": {"Type": "AWS::S3::Bucket"},
+            "Queue": {"Type": "AWS::SQS::Queue", "DeletionPolicy": "Retain"},
+        }});
+        api.create_import_changeset("Stack", "Retained", &template, &[], &[import("Queue")], &[])
+            .await
+            .unwrap();
+        let mut without_policy = template.clone();
+        without_policy["Resources"]["Queue"]
+            .as_object_mut()
+            .unwrap()
+            .remove("DeletionPolicy");
+        api.create_import_changeset(
+            "Stack",
+            "Deleted",
+            &without_policy,
+            &[],
+            &[import("Queue")],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let outcome = waiter
+            .wait_for_changeset("Stack", "Retained")
+            .await
+            .unwrap();
+        assert!(matches!(outcome, Outcome::Succeeded));
+
+        let outcome = waiter.wait_for_changeset("Stack", "Deleted").await.unwrap();
+        assert!(matches!(
+            outcome,
+            Outcome::Failed { status, reason: Some(reason), .. }
+                if status == "FAILED" && reason.contains("[Queue] must have DeletionPolicy")
+        ));
+    }
+}
@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+use crate::timing;
+
+/// Environment variable to override the default workspace root
+pub const WORKSPACE_ENV: &str = "CFN_TELEPORT_WORKSPACE";
+
+/// Number of days workspaces of previous runs are kept by default
+pub const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+/// Directory holding all artifacts of a single run: templates, changeset payloads and the log
+pub struct Workspace {
+    path: PathBuf,
+}
+
+impl Workspace {
+    /// Creates a new workspace below `root`
+    pub fn create(root: &Path) -> Result<Self, Box<dyn Error>> {
+        let name = format!(
+            "{}-{}",
+            timing::timestamp().replace(':', "-"),
+            &Uuid::new_v4().simple().to_string()[..8]
+        );
+        let path = root.join(name);
+        fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    /// Opens the workspace of a previous run
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.is_dir() {
+            return Err(format!("Workspace {} does not exist", path.display()).into());
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Name of the workspace directory, which identifies the run
+    pub fn id(&self) -> String {
+        self.path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Writes a JSON artifact, e.g. a template, into the workspace
+    pub fn write_json(&self, name: &str, value: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        fs::write(
+            self.path.join(format!("{}.json", name)),
+            serde_json::to_string_pretty(value)?,
+        )?;
+        Ok(())
+    }
+
+    /// Reads a JSON artifact written by `write_json`
+    pub fn read_json(&self, name: &str) -> Result<serde_json::Value, Box<dyn Error>> {
+        let path = self.path.join(format!("{}.json", name));
+        let body = fs::read_to_string(&path)
+            .map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Appends a timestamped line to the run log, which is logged with `--verbose` as well
+    pub fn log(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        tracing::info!("{}", message);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path.join("run.log"))?;
+        writeln!(file, "[{}] {}", timing::timestamp(), message)?;
+        Ok(())
+    }
+}
+
+/// Root directory of all workspaces: `--workspace`, `$CFN_TELEPORT_WORKSPACE` or
+/// `~/.cfn-teleport/runs`
+pub fn root(workspace: Option<PathBuf>) -> PathBuf {
+    workspace
+        .or_else(|| std::env::var_os(WORKSPACE_ENV).map(PathBuf::from))
+        .unwrap_or_else(|| {
+            let home = std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(PathBuf::from)
+                .unwrap_or_default();
+            home.join(".cfn-teleport").join("runs")
+        })
+}
+
+/// Deletes all workspaces below `root` which have not been modified for `max_age`. Returns the
+/// deleted directories.
+pub fn purge(root: &Path, max_age: Duration) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut purged = Vec::new();
+    if !root.is_dir() {
+        return Ok(purged);
+    }
+
+    let now = SystemTime::now();
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let modified = entry.metadata()?.modified()?;
+        if now.duration_since(modified).unwrap_or_default() >= max_age {
+            fs::remove_dir_all(entry.path())?;
+            purged.push(entry.path());
+        }
+    }
+
+    purged.sort();
+    Ok(purged)
+}
+
+pub fn days(days: u64) -> Duration {
+    Duration::from_secs(days * 24 * 60 * 60)
+}
@@ -1,7 +1,7 @@
 #!/usr/bin/env node
 import cdk = require('aws-cdk-lib');
 
-import { TestStack } from '../lib';
+import { LargeTestStack, TestStack } from '../lib';
 
 const app = new cdk.App();
 new TestStack(app, 'CfnTeleportTest1', {
@@ -19,3 +19,19 @@ new TestStack(app, 'CfnTeleportTest2', {
   },
   resources: false,
 });
+
+new LargeTestStack(app, 'CfnTeleportTestLarge1', {
+  env: {
+    account: process.env.CDK_DEFAULT_ACCOUNT,
+    region: process.env.CDK_DEFAULT_REGION,
+  },
+  logGroups: 150,
+});
+
+new LargeTestStack(app, 'CfnTeleportTestLarge2', {
+  env: {
+    account: process.env.CDK_DEFAULT_ACCOUNT,
+    region: process.env.CDK_DEFAULT_REGION,
+  },
+  logGroups: 0,
+});
@@ -2,19 +2,35 @@ import {
   aws_dynamodb,
   aws_ec2,
   aws_iam,
+  aws_logs,
   aws_s3,
+  aws_sqs,
+  CfnOutput,
+  CfnResource,
+  Fn,
   RemovalPolicy,
   Stack,
   StackProps,
   Tags,
 } from 'aws-cdk-lib';
 import { CfnInstanceProfile } from 'aws-cdk-lib/aws-iam';
-import { Construct } from 'constructs';
+import { Construct, IConstruct } from 'constructs';
 
 type TestStackProps = StackProps & {
   resources: boolean;
 };
 
+type LargeTestStackProps = StackProps & {
+  logGroups: number;
+};
+
+/**
+ * Fixes the logical ID of a construct, so the end-to-end tests can refer to it
+ */
+function logicalId(construct: IConstruct, id: string) {
+  (construct.node.defaultChild as CfnResource).overrideLogicalId(id);
+}
+
 export class TestStack extends Stack {
   constructor(scope: Construct, id: string, props: TestStackProps) {
     super(scope, id, props);
@@ -26,11 +42,19 @@ export class TestStack extends Stack {
         isDefault: true,
       });
 
-      new aws_s3.Bucket(this, 'Bucket-1', {
+      const bucket1 = new aws_s3.Bucket(this, 'Bucket-1', {
         bucketName: `${this.account}-cfn-teleport-test-1`,
         removalPolicy: RemovalPolicy.DESTROY,
       });
 
+      // moves along with the bucket, the export name is based on a Fn::Sub expression
+      new CfnOutput(this, 'Bucket1Arn', {
+        value: bucket1.bucketArn,
+        exportName: Fn.sub('${AWS::StackName}-${BucketName}-arn', {
+          BucketName: bucket1.bucketName,
+        }),
+      });
+
       new aws_s3.Bucket(this, 'Bucket-2', {
         bucketName: `${this.account}-cfn-teleport-test-2`,
         removalPolicy: RemovalPolicy.DESTROY,
@@ -66,6 +90,44 @@ export class TestStack extends Stack {
 
       CfnInstanceProfile;
 
+      // moves along with the queue policy, which references it
+      const queue = new aws_sqs.Queue(this, 'Queue');
+      logicalId(queue, 'Queue');
+      queue.addToResourcePolicy(
+        new aws_iam.PolicyStatement({
+          actions: ['sqs:SendMessage'],
+          principals: [new aws_iam.ServicePrincipal('sns.amazonaws.com')],
+          resources: [queue.queueArn],
+        })
+      );
+      logicalId(queue.node.findChild('Policy'), 'QueuePolicy');
+      new CfnOutput(this, 'QueueUrl', {
+        value: queue.queueUrl,
+      });
+
+      // stays when the queue moves, its reference to the queue is replaced by the ARN
+      const consumerPolicy = new aws_iam.ManagedPolicy(this, 'QueueConsumerPolicy', {
+        statements: [
+          new aws_iam.PolicyStatement({
+            actions: ['sqs:ReceiveMessage'],
+            resources: [queue.queueArn],
+          }),
+        ],
+      });
+      logicalId(consumerPolicy, 'QueueConsumerPolicy');
+
+      // imported with its ARN and requires CAPABILITY_NAMED_IAM
+      const managedPolicy = new aws_iam.ManagedPolicy(this, 'ManagedPolicy', {
+        managedPolicyName: 'cfn-teleport-test',
+        statements: [
+          new aws_iam.PolicyStatement({
+            actions: ['sqs:ListQueues'],
+            resources: ['*'],
+          }),
+        ],
+      });
+      logicalId(managedPolicy, 'ManagedPolicy');
+
       new aws_ec2.Instance(this, 'Instance', {
         vpc,
         machineImage,
@@ -80,3 +142,23 @@ export class TestStack extends Stack {
     }
   }
 }
+
+/**
+ * Stack with more resources than a single page of ListStackResources returns
+ */
+export class LargeTestStack extends Stack {
+  constructor(scope: Construct, id: string, props: LargeTestStackProps) {
+    super(scope, id, props);
+
+    Tags.of(this).add('ApplicationName', 'cfn-teleport-test');
+
+    for (let i = 1; i <= props.logGroups; i++) {
+      const logGroup = new aws_logs.LogGroup(this, `LogGroup${i}`, {
+        logGroupName: `/cfn-teleport-test/large-${i}`,
+        removalPolicy: RemovalPolicy.DESTROY,
+        retention: aws_logs.RetentionDays.ONE_DAY,
+      });
+      logicalId(logGroup, `LogGroup${i}`);
+    }
+  }
+}
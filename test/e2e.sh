@@ -0,0 +1,196 @@
+#!/usr/bin/env bash
+#
+# End-to-end tests of cfn-teleport against real stacks, which `make test` deploys. Every scenario
+# moves resources in non-interactive mode and back again, so the stacks end up as deployed.
+
+set -euo pipefail
+
+cd "$(dirname "$0")/cdk"
+
+STACK1=CfnTeleportTest1
+STACK2=CfnTeleportTest2
+STACK_YAML=CfnTeleportTestYaml
+STACK_LARGE1=CfnTeleportTestLarge1
+STACK_LARGE2=CfnTeleportTestLarge2
+
+BUCKET1=Bucket182C536A1
+BUCKET2=Bucket21D68F7E8
+INSTANCE=InstanceC1063A87
+SECURITY_GROUP=SecurityGroupDD263621
+KEY_PAIR=KeyPair
+INSTANCE_PROFILE=InstanceInstanceProfileAB5AEF02
+ROLE=Role1ABCC5F0
+DYNAMO_TABLE=DynamoDbTable6316879D
+
+LARGE_RESOURCES=150
+
+# the test stacks contain named IAM resources
+teleport() {
+  echo "######### cfn-teleport $* #########"
+  cfn-teleport --yes --plain --capability CAPABILITY_IAM,CAPABILITY_NAMED_IAM "$@"
+}
+
+fail() {
+  echo "FAILED: $*"
+  exit 1
+}
+
+assert_resource() {
+  aws cloudformation describe-stack-resource \
+    --stack-name "$1" --logical-resource-id "$2" > /dev/null \
+    || fail "Expected resource $2 in stack $1"
+}
+
+assert_no_resource() {
+  if aws cloudformation describe-stack-resource \
+    --stack-name "$1" --logical-resource-id "$2" > /dev/null 2>&1; then
+    fail "Expected no resource $2 in stack $1"
+  fi
+}
+
+assert_output() {
+  [ -n "$(aws cloudformation describe-stacks --stack-name "$1" \
+    --query "Stacks[0].Outputs[?OutputKey=='$2'].OutputValue" --output text)" ] \
+    || fail "Expected output $2 in stack $1"
+}
+
+assert_resource_count() {
+  local count
+  count=$(aws cloudformation list-stack-resources --stack-name "$1" \
+    --query "length(StackResourceSummaries[?ResourceType=='$2'])" --output text)
+  [ "$count" -eq "$3" ] || fail "Expected $3 resources of type $2 in stack $1, found $count"
+}
+
+assert_changes() {
+  if npx cdk diff --fail "$@"; then
+    fail "cdk diff did not show changes"
+  fi
+}
+
+assert_no_changes() {
+  npx cdk diff --fail "$@" || fail "cdk diff did show changes"
+}
+
+assert_exit_code() {
+  local expected=$1
+  shift
+  local code=0
+  "$@" || code=$?
+  [ "$code" -eq "$expected" ] || fail "Expected exit code $expected, got $code"
+}
+
+scenario_move() {
+  teleport --source "$STACK1" --target "$STACK2" \
+    --resource "$BUCKET1" \
+    --resource "$BUCKET2" \
+    --resource "$INSTANCE" \
+    --resource "$SECURITY_GROUP" \
+    --resource "$KEY_PAIR" \
+    --resource "$INSTANCE_PROFILE" \
+    --resource "$ROLE" \
+    --resource "$DYNAMO_TABLE"
+  assert_changes "$STACK1" "$STACK2"
+
+  teleport --source "$STACK2" --target "$STACK1" \
+    --resource "$BUCKET1" \
+    --resource "$BUCKET2" \
+    --resource "$INSTANCE" \
+    --resource "$SECURITY_GROUP" \
+    --resource "$KEY_PAIR" \
+    --resource "$INSTANCE_PROFILE" \
+    --resource "$ROLE" \
+    --resource "$DYNAMO_TABLE"
+  assert_no_changes "$STACK1" "$STACK2"
+}
+
+scenario_rename() {
+  teleport --source "$STACK1" --target "$STACK2" \
+    --resource "$BUCKET2:RenamedBucket" \
+    --resource "$DYNAMO_TABLE:RenamedTable"
+  assert_resource "$STACK2" RenamedBucket
+  assert_resource "$STACK2" RenamedTable
+  assert_no_resource "$STACK1" "$BUCKET2"
+
+  teleport --source "$STACK2" --target "$STACK1" \
+    --resource "RenamedBucket:$BUCKET2" \
+    --resource "RenamedTable:$DYNAMO_TABLE"
+  assert_no_changes "$STACK1" "$STACK2"
+}
+
+scenario_cross_references() {
+  # the queue policy and the output of the queue URL move along, the consumer policy stays and
+  # refers to the queue by its ARN afterwards
+  teleport --source "$STACK1" --target "$STACK2" \
+    --resource Queue \
+    --resource QueuePolicy
+  assert_resource "$STACK2" Queue
+  assert_resource "$STACK2" QueuePolicy
+  assert_output "$STACK2" QueueUrl
+
+  teleport --source "$STACK2" --target "$STACK1" \
+    --resource Queue \
+    --resource QueuePolicy
+  assert_output "$STACK1" QueueUrl
+
+  # the consumer policy refers to the queue with Fn::GetAtt again
+  npx cdk deploy --require-approval never "$STACK1"
+  assert_no_changes "$STACK1" "$STACK2"
+}
+
+scenario_capabilities() {
+  # a named IAM resource without the acknowledged capability aborts before anything is changed
+  assert_exit_code 2 cfn-teleport --yes --plain \
+    --source "$STACK1" --target "$STACK2" --resource ManagedPolicy
+  assert_resource "$STACK1" ManagedPolicy
+
+  teleport --source "$STACK1" --target "$STACK2" \
+    --resource ManagedPolicy
+  assert_resource "$STACK2" ManagedPolicy
+
+  teleport --source "$STACK2" --target "$STACK1" \
+    --resource ManagedPolicy
+  assert_no_changes "$STACK1" "$STACK2"
+}
+
+scenario_yaml() {
+  teleport --source "$STACK_YAML" --target "$STACK2" \
+    --resource YamlQueue \
+    --resource YamlQueuePolicy \
+    --resource YamlTopic
+  assert_resource "$STACK2" YamlQueue
+  assert_output "$STACK2" YamlQueueArn
+  assert_no_resource "$STACK_YAML" YamlTopic
+
+  teleport --source "$STACK2" --target "$STACK_YAML" \
+    --resource YamlQueue \
+    --resource YamlQueuePolicy \
+    --resource YamlTopic
+  assert_resource "$STACK_YAML" YamlQueue
+  assert_resource "$STACK_YAML" YamlTopic
+  assert_no_changes "$STACK2"
+}
+
+scenario_large() {
+  # more resources than a single page of ListStackResources
+  assert_exit_code 0 teleport --source "$STACK_LARGE1" --target "$STACK_LARGE2" \
+    --all-resources --feasibility
+
+  teleport --source "$STACK_LARGE1" --target "$STACK_LARGE2" --all-resources
+  assert_resource_count "$STACK_LARGE2" AWS::Logs::LogGroup "$LARGE_RESOURCES"
+
+  teleport --source "$STACK_LARGE2" --target "$STACK_LARGE1" --all-resources
+  assert_resource_count "$STACK_LARGE1" AWS::Logs::LogGroup "$LARGE_RESOURCES"
+  assert_no_changes "$STACK_LARGE1" "$STACK_LARGE2"
+}
+
+SCENARIOS=("$@")
+if [ ${#SCENARIOS[@]} -eq 0 ]; then
+  SCENARIOS=(move rename cross_references capabilities yaml large)
+fi
+
+for scenario in "${SCENARIOS[@]}"; do
+  echo "================= Scenario ${scenario} ================="
+  "scenario_${scenario}"
+done
+
+echo "All scenarios passed: ${SCENARIOS[*]}"